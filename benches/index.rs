@@ -0,0 +1,168 @@
+//! Benchmarks for index maintenance on large documents, comparing
+//! incremental index updates to a full re-index (and derive) after each edit.
+//! Incremental updates should scale logarithmically with document size,
+//! also for edits in the middle of a tier.
+//!
+//! Run with `cargo bench --bench index`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use eaf_rs::{Annotation, Eaf, LinguisticType, StereoType, Tier};
+
+/// Number of edits per benchmark.
+const EDITS: usize = 1000;
+/// Number of edits per benchmark, when re-indexing in full after each edit.
+const FULL: usize = 5;
+
+/// Generates a document with `tiers` main tiers, each with `len` annotations,
+/// and a symbolic association tier for each main tier.
+fn document(tiers: usize, len: usize) -> Eaf {
+    let values: Vec<(String, Vec<_>)> = (0..tiers)
+        .map(|t| {
+            let values = (0..len as i64)
+                .map(|i| (format!("value {i}"), i * 1000, i * 1000 + 800))
+                .collect();
+            (format!("tier{t}"), values)
+        })
+        .collect();
+
+    let mut eaf = Eaf::from_tier_values(&values).expect("Failed to generate document");
+
+    let stereotype = StereoType::SymbolicAssociation;
+    let mut a_ids = eaf.generate_a_id_multi(tiers * len).into_iter();
+    for t in 0..tiers {
+        let parent_id = format!("tier{t}");
+        let annotations: Vec<Annotation> = eaf.get_tier(&parent_id)
+            .expect("Missing tier")
+            .annotations.iter()
+            .map(|a| Annotation::referred("ref", &a_ids.next().expect("Missing ID"), a.id(), None))
+            .collect();
+        let lt = LinguisticType::new("ref", Some(&stereotype));
+        let tier = Tier::new(&format!("ref{t}"), Some(&annotations), Some(&lt.linguistic_type_id), Some(&parent_id));
+        eaf.add_tier(Some(tier), Some(&stereotype)).expect("Failed to add tier");
+    }
+
+    eaf
+}
+
+/// Returns average duration for `f`, run `n` times.
+fn time<F: FnMut(usize)>(n: usize, mut f: F) -> Duration {
+    let start = Instant::now();
+    for i in 0..n {
+        f(i)
+    }
+    start.elapsed() / n as u32
+}
+
+fn report(label: &str, incremental: Duration, full: Duration) {
+    println!(
+        "  {label:<24}{:>14.1?}{:>14.1?}{:>10.0}x",
+        incremental,
+        full,
+        full.as_secs_f64() / incremental.as_secs_f64().max(f64::EPSILON)
+    );
+}
+
+fn bench(tiers: usize, len: usize) {
+    let eaf = document(tiers, len);
+    println!("\n{} annotations, {} time slots", eaf.a_len(), eaf.time_order.len());
+    println!("  {:<24}{:>14}{:>14}{:>11}", "", "incremental", "full", "gain");
+
+    // `derive()` used to clone the document first.
+    let mut doc = eaf.clone();
+    let derive = time(FULL, |_| doc.derive().expect("Failed to derive"));
+    let derive_clone = time(FULL, |_| {
+        let copy = black_box(doc.clone());
+        doc.derive().expect("Failed to derive");
+        drop(copy)
+    });
+    report("derive", derive, derive_clone);
+
+    // Edits, with incremental index updates, and with a full re-index
+    // and derive after each edit, as previously required.
+    let reindex = |doc: &mut Eaf| {
+        doc.index();
+        doc.derive().expect("Failed to derive");
+    };
+
+    // Append annotations at the end of each tier.
+    let end = len as i64 * 1000;
+    let add = |doc: &mut Eaf, i: usize| {
+        let start = end + i as i64 * 1000;
+        let annotation = Annotation::alignable("new", &format!("new{i}"), &format!("new_ts{i}a"), &format!("new_ts{i}b"))
+            .with_ts_val(start, start + 800);
+        doc.add_annotation(&annotation, &format!("tier{}", i % tiers)).expect("Failed to add annotation");
+    };
+    doc = eaf.clone();
+    let incremental = time(EDITS, |i| add(&mut doc, i));
+    doc = eaf.clone();
+    let full = time(FULL, |i| {
+        add(&mut doc, i);
+        reindex(&mut doc)
+    });
+    report("add_annotation, end", incremental, full);
+
+    // Insert annotations in the gaps in the middle of each tier,
+    // which shifts the position of all following annotations.
+    let insert = |doc: &mut Eaf, i: usize| {
+        let start = (len / 2 + i / tiers) as i64 * 1000 + 850;
+        let annotation = Annotation::alignable("mid", &format!("mid{i}"), &format!("mid_ts{i}a"), &format!("mid_ts{i}b"))
+            .with_ts_val(start, start + 100);
+        doc.add_annotation(&annotation, &format!("tier{}", i % tiers)).expect("Failed to add annotation");
+    };
+    doc = eaf.clone();
+    let incremental = time(EDITS, |i| insert(&mut doc, i));
+    doc = eaf.clone();
+    let full = time(FULL, |i| {
+        insert(&mut doc, i);
+        reindex(&mut doc)
+    });
+    report("add_annotation, middle", incremental, full);
+
+    // Move time slots, which also moves referred annotations.
+    let ts_ids: Vec<String> = eaf.time_order.time_slots.iter()
+        .step_by(eaf.time_order.len() / EDITS)
+        .map(|ts| ts.time_slot_id.to_owned())
+        .collect();
+    let set = |doc: &mut Eaf, i: usize| {
+        let val = doc.ts_val(&ts_ids[i]).expect("Missing time value");
+        doc.set_ts_val(&ts_ids[i], Some(val + 100)).expect("Failed to set time value");
+    };
+    doc = eaf.clone();
+    let incremental = time(ts_ids.len(), |i| set(&mut doc, i));
+    doc = eaf.clone();
+    let full = time(FULL, |i| {
+        set(&mut doc, i);
+        reindex(&mut doc)
+    });
+    report("set_ts_val", incremental, full);
+
+    // Remove annotations in the middle of tiers, including referred annotations.
+    let a_ids: Vec<String> = (0..EDITS)
+        .map(|i| eaf.tiers[i % tiers].annotations[len / 2 + i / tiers].id().to_owned())
+        .collect();
+    let remove = |doc: &mut Eaf, i: usize| {
+        doc.remove_annotation(&a_ids[i]).expect("Failed to remove annotation");
+    };
+    doc = eaf.clone();
+    let incremental = time(a_ids.len(), |i| remove(&mut doc, i));
+    doc = eaf.clone();
+    let full = time(FULL, |i| {
+        remove(&mut doc, i);
+        reindex(&mut doc)
+    });
+    report("remove_annotation", incremental, full);
+
+    // Time lookups using the maintained time index.
+    let lookup = time(EDITS, |i| {
+        black_box(doc.annotations_at(i as i64 * 997 % end, None));
+    });
+    println!("  {:<24}{:>14.1?}", "annotations_at", lookup);
+}
+
+fn main() {
+    bench(10, 1_000);
+    bench(20, 5_000);
+    bench(40, 5_000);
+}
@@ -0,0 +1,638 @@
+//! Inter-annotator agreement between two tiers, in the same or in different EAF-files,
+//! similar to ELAN's "Calculate Inter-Annotator Reliability".
+//!
+//! Annotations are matched on time span: two annotations match if their overlap
+//! is at least `AgreementOptions::min_overlap` of their combined extent,
+//! where each annotation can match at most one other, largest overlap first
+//! (Holle & Rein 2015). Matched and unmatched annotations make up the
+//! segments that the following measures are computed from:
+//! - Cohen's kappa for annotation values, with unmatched annotations as a separate
+//!   category, as in ELAN's modified kappa. Also without unmatched annotations.
+//! - Krippendorff's alpha for interval data, for the start and end times
+//!   of matched annotations.
+//! - Staccato (Lücking et al. 2011) for segmentation, i.e. how well annotations
+//!   correspond in time compared to random segmentations, regardless of value.
+//! - Overlap percentage, i.e. the time both tiers have annotations,
+//!   relative to the time either tier has annotations.
+//!
+//! Annotations without time values are ignored.
+
+use crate::{CvType, Eaf, EafError, Overlap, Tier};
+
+use super::computed::intersection;
+
+/// Options for `Eaf::agreement()`.
+#[derive(Debug, Clone)]
+pub struct AgreementOptions {
+    /// Minimum overlap for two annotations to match,
+    /// as a fraction of their combined extent (`0.0`-`1.0`).
+    pub min_overlap: f64,
+    /// Number of random segmentations for Staccato.
+    pub simulations: usize,
+    /// Significance level for Staccato.
+    pub significance: f64,
+    /// Seed for the random segmentations for Staccato.
+    /// The same seed gives the same result.
+    pub seed: u64,
+}
+
+impl Default for AgreementOptions {
+    fn default() -> Self {
+        Self {
+            min_overlap: 0.6,
+            simulations: 1000,
+            significance: 0.05,
+            seed: 1,
+        }
+    }
+}
+
+/// Annotation in a segment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentAnnotation {
+    pub id: String,
+    pub value: String,
+    pub start: i64,
+    pub end: i64,
+}
+
+/// Segment, i.e. a matched annotation pair,
+/// or an annotation without a match in the other tier.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentMatch {
+    /// Annotation in the first tier.
+    pub first: Option<SegmentAnnotation>,
+    /// Annotation in the second tier.
+    pub second: Option<SegmentAnnotation>,
+    /// Overlap of first annotation relative to the second annotation,
+    /// if matched.
+    pub overlap: Option<Overlap>,
+    /// Overlap as a fraction of the combined extent, if matched.
+    pub ratio: Option<f64>,
+}
+
+impl SegmentMatch {
+    /// Returns `true` if the segment is a matched annotation pair.
+    pub fn is_match(&self) -> bool {
+        self.first.is_some() && self.second.is_some()
+    }
+
+    /// Returns `true` if both annotations exist and have the same value.
+    pub fn is_agreement(&self) -> bool {
+        match (&self.first, &self.second) {
+            (Some(first), Some(second)) => first.value == second.value,
+            _ => false
+        }
+    }
+
+    fn start(&self) -> Option<i64> {
+        [&self.first, &self.second].iter()
+            .filter_map(|a| a.as_ref().map(|a| a.start))
+            .min()
+    }
+}
+
+/// Confusion matrix over annotation values, for segments.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfusionMatrix {
+    /// Annotation values. Values in the controlled vocabularies
+    /// of the tiers come first, in vocabulary order.
+    /// The last category, `None`, is for unmatched annotations.
+    pub categories: Vec<Option<String>>,
+    /// Segment counts, with rows for values in the first tier,
+    /// and columns for values in the second tier.
+    pub counts: Vec<Vec<usize>>,
+}
+
+impl ConfusionMatrix {
+    fn new(categories: Vec<Option<String>>, segments: &[SegmentMatch]) -> Self {
+        let mut counts = vec![vec![0; categories.len()]; categories.len()];
+        let idx = |a: &Option<SegmentAnnotation>| {
+            let value = a.as_ref().map(|a| a.value.to_owned());
+            categories.iter().position(|c| *c == value).unwrap_or_default()
+        };
+        for segment in segments.iter() {
+            counts[idx(&segment.first)][idx(&segment.second)] += 1;
+        }
+        Self { categories, counts }
+    }
+
+    /// Returns segment count for the specified values,
+    /// where `None` is an unmatched annotation.
+    pub fn count(&self, first: Option<&str>, second: Option<&str>) -> usize {
+        let idx = |value: Option<&str>| self.categories.iter()
+            .position(|c| c.as_deref() == value);
+        match (idx(first), idx(second)) {
+            (Some(row), Some(col)) => self.counts[row][col],
+            _ => 0
+        }
+    }
+
+    /// Returns the total number of segments.
+    pub fn total(&self) -> usize {
+        self.counts.iter().flatten().sum()
+    }
+
+    /// Cohen's kappa. If `unmatched` is `false`, only matched segments are included.
+    /// Returns `None` if there are no segments, or if agreement by chance is 1.
+    pub fn kappa(&self, unmatched: bool) -> Option<f64> {
+        let idx: Vec<usize> = self.categories.iter()
+            .enumerate()
+            .filter(|(_, c)| unmatched || c.is_some())
+            .map(|(i, _)| i)
+            .collect();
+        let total: usize = idx.iter()
+            .flat_map(|r| idx.iter().map(|c| self.counts[*r][*c]))
+            .sum();
+        if total == 0 {
+            return None
+        }
+        let total = total as f64;
+
+        let observed = idx.iter().map(|i| self.counts[*i][*i]).sum::<usize>() as f64 / total;
+        let expected = idx.iter()
+            .map(|i| {
+                let row: usize = idx.iter().map(|c| self.counts[*i][*c]).sum();
+                let col: usize = idx.iter().map(|r| self.counts[*r][*i]).sum();
+                row as f64 * col as f64
+            })
+            .sum::<f64>() / (total * total);
+
+        match expected < 1.0 {
+            true => Some((observed - expected) / (1.0 - expected)),
+            false => None,
+        }
+    }
+}
+
+/// Inter-annotator agreement between two tiers, see `Eaf::agreement()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Agreement {
+    /// Matched and unmatched annotations, sorted on time.
+    pub matches: Vec<SegmentMatch>,
+    /// Confusion matrix for segment values.
+    pub confusion: ConfusionMatrix,
+    /// Cohen's kappa for segment values, with unmatched annotations
+    /// as a separate category (ELAN's modified kappa).
+    pub kappa: Option<f64>,
+    /// Cohen's kappa for matched segments only.
+    pub kappa_matched: Option<f64>,
+    /// Krippendorff's alpha for interval data,
+    /// for the start and end times of matched segments.
+    pub alpha: Option<f64>,
+    /// Staccato segmentation agreement, i.e. the fraction of annotations that
+    /// correspond to an annotation in the other tier better than by chance.
+    pub staccato: Option<f64>,
+    /// Time both tiers have annotations in percent
+    /// of the time either tier has annotations.
+    pub overlap_percentage: Option<f64>,
+}
+
+/// Random number generator (xorshift64*), for reproducible
+/// random segmentations.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Zero state only generates zeros
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    /// Returns a float in the range `0.0..1.0`.
+    fn float(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns an integer in the range `0..max`.
+    fn below(&mut self, max: usize) -> usize {
+        (self.float() * max as f64) as usize
+    }
+}
+
+/// Returns annotations with time values in `tier`, sorted on time.
+fn annotations(tier: &Tier) -> Vec<SegmentAnnotation> {
+    let mut annotations: Vec<SegmentAnnotation> = tier.iter()
+        .filter_map(|a| match a.ts_val() {
+            (Some(start), Some(end)) => Some(SegmentAnnotation {
+                id: a.id().to_owned(),
+                value: a.to_str().to_owned(),
+                start,
+                end,
+            }),
+            _ => None
+        })
+        .collect();
+    annotations.sort_by_key(|a| (a.start, a.end));
+    annotations
+}
+
+/// Returns the values in the controlled vocabulary for `tier`, if any.
+fn cv_values(eaf: &Eaf, tier: &Tier) -> Vec<String> {
+    eaf.linguistic_types.iter()
+        .find(|lt| lt.linguistic_type_id == tier.linguistic_type_ref)
+        .and_then(|lt| lt.controlled_vocabulary.as_deref())
+        .and_then(|cv_id| eaf.controlled_vocabularies.iter().find(|cv| cv.cv_id == cv_id))
+        .map(|cv| cv.iter()
+            .filter_map(|entry| match entry {
+                CvType::CvEntry(e) => Some(e.value.to_owned()),
+                CvType::CvEntryMl(e) => e.cve_values.first().map(|v| v.value.to_owned()),
+                CvType::Description(_) => None,
+            })
+            .collect())
+        .unwrap_or_default()
+}
+
+/// Overlap of two time spans as a fraction of their combined extent.
+fn ratio(span1: (i64, i64), span2: (i64, i64)) -> Option<f64> {
+    let (start, end) = intersection(span1, span2)?;
+    let extent = span1.1.max(span2.1) - span1.0.min(span2.0);
+    Some((end - start) as f64 / extent as f64)
+}
+
+/// Matches annotations, largest overlap first.
+fn segments(first: &[SegmentAnnotation], second: &[SegmentAnnotation], min_overlap: f64) -> Vec<SegmentMatch> {
+    // (ratio, first index, second index)
+    let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+    for (i, a) in first.iter().enumerate() {
+        for (j, b) in second.iter().enumerate().take_while(|(_, b)| b.start < a.end) {
+            if let Some(r) = ratio((a.start, a.end), (b.start, b.end))
+                && r >= min_overlap
+            {
+                candidates.push((r, i, j));
+            }
+        }
+    }
+    candidates.sort_by(|x, y| y.0.total_cmp(&x.0).then((x.1, x.2).cmp(&(y.1, y.2))));
+
+    let mut first_matched = vec![false; first.len()];
+    let mut second_matched = vec![false; second.len()];
+    let mut segments: Vec<SegmentMatch> = Vec::new();
+    for (r, i, j) in candidates.into_iter() {
+        if first_matched[i] || second_matched[j] {
+            continue
+        }
+        first_matched[i] = true;
+        second_matched[j] = true;
+        let (a, b) = (&first[i], &second[j]);
+        segments.push(SegmentMatch {
+            first: Some(a.to_owned()),
+            second: Some(b.to_owned()),
+            overlap: Overlap::from_spans((a.start, a.end), (b.start, b.end)),
+            ratio: Some(r),
+        });
+    }
+    segments.extend(first.iter().zip(first_matched.iter())
+        .filter(|(_, m)| !**m)
+        .map(|(a, _)| SegmentMatch { first: Some(a.to_owned()), second: None, overlap: None, ratio: None }));
+    segments.extend(second.iter().zip(second_matched.iter())
+        .filter(|(_, m)| !**m)
+        .map(|(b, _)| SegmentMatch { first: None, second: Some(b.to_owned()), overlap: None, ratio: None }));
+
+    segments.sort_by_key(|s| (s.start(), s.first.is_none()));
+    segments
+}
+
+/// Krippendorff's alpha for interval data, for two coders without missing values.
+/// Each unit is a pair of values.
+fn alpha(units: &[(f64, f64)]) -> Option<f64> {
+    let values: Vec<f64> = units.iter()
+        .flat_map(|(a, b)| [*a, *b])
+        .collect();
+    let n = values.len() as f64;
+    if values.len() < 4 {
+        return None
+    }
+
+    // Each unit contributes both ordered pairs, divided by (values in unit - 1) = 1.
+    let observed = units.iter()
+        .map(|(a, b)| 2.0 * (a - b).powi(2))
+        .sum::<f64>() / n;
+    // Sum of squared differences over all ordered pairs, via the variance:
+    // sum_i sum_j (x_i - x_j)^2 = 2n * sum_i (x_i - mean)^2
+    let mean = values.iter().sum::<f64>() / n;
+    let expected = 2.0 * n * values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n * (n - 1.0));
+
+    match expected > 0.0 {
+        true => Some(1.0 - observed / expected),
+        false => None,
+    }
+}
+
+/// Degree of organisation for each span in `spans`, i.e. the largest overlap
+/// with a span in `other` as a fraction of their combined extent.
+/// Both must be sorted on start time.
+fn organisation(spans: &[(f64, f64)], other: &[(f64, f64)]) -> Vec<f64> {
+    // Largest end time so far, for finding the first span that may overlap.
+    let max_ends: Vec<f64> = other.iter()
+        .scan(f64::MIN, |max, (_, end)| {
+            *max = max.max(*end);
+            Some(*max)
+        })
+        .collect();
+
+    spans.iter()
+        .map(|(start, end)| {
+            let first = max_ends.partition_point(|max| max <= start);
+            other[first..].iter()
+                .take_while(|(s, _)| s < end)
+                .map(|(s, e)| {
+                    let overlap = end.min(*e) - start.max(*s);
+                    let extent = end.max(*e) - start.min(*s);
+                    if overlap > 0.0 { overlap / extent } else { 0.0 }
+                })
+                .fold(0.0, f64::max)
+        })
+        .collect()
+}
+
+/// Random segmentation within `start`-`end`, with the same annotation lengths
+/// as `spans` in random order, separated by random gaps.
+fn random_segmentation(spans: &[(f64, f64)], start: f64, end: f64, rng: &mut Rng) -> Vec<(f64, f64)> {
+    let mut lengths: Vec<f64> = spans.iter().map(|(s, e)| e - s).collect();
+    for i in (1..lengths.len()).rev() {
+        lengths.swap(i, rng.below(i + 1));
+    }
+    let free = (end - start - lengths.iter().sum::<f64>()).max(0.0);
+    let mut cuts: Vec<f64> = (0..lengths.len()).map(|_| rng.float() * free).collect();
+    cuts.sort_by(f64::total_cmp);
+
+    let mut previous_cut = 0.0;
+    let mut time = start;
+    lengths.iter().zip(cuts.iter())
+        .map(|(len, cut)| {
+            time += cut - previous_cut;
+            previous_cut = *cut;
+            let span = (time, time + len);
+            time += len;
+            span
+        })
+        .collect()
+}
+
+/// Staccato segmentation agreement: compares the degree of organisation
+/// of each annotation with that of random segmentations. Annotations with
+/// a degree of organisation above the critical value at the specified
+/// significance level agree.
+fn staccato(first: &[SegmentAnnotation], second: &[SegmentAnnotation], options: &AgreementOptions) -> Option<f64> {
+    let spans = |annotations: &[SegmentAnnotation]| -> Vec<(f64, f64)> {
+        annotations.iter().map(|a| (a.start as f64, a.end as f64)).collect()
+    };
+    let (first, second) = (spans(first), spans(second));
+    if first.is_empty() || second.is_empty() {
+        return None
+    }
+    let start = first[0].0.min(second[0].0);
+    let end = first.iter().chain(second.iter())
+        .map(|(_, e)| *e)
+        .fold(f64::MIN, f64::max);
+
+    let mut rng = Rng::new(options.seed);
+    let mut random: Vec<f64> = Vec::new();
+    for _ in 0..options.simulations {
+        let random_first = random_segmentation(&first, start, end, &mut rng);
+        let random_second = random_segmentation(&second, start, end, &mut rng);
+        random.extend(organisation(&random_first, &random_second));
+        random.extend(organisation(&random_second, &random_first));
+    }
+    random.sort_by(f64::total_cmp);
+    let critical = match random.is_empty() {
+        true => 0.0,
+        false => {
+            let idx = ((1.0 - options.significance) * random.len() as f64).ceil() as usize;
+            random[idx.clamp(1, random.len()) - 1]
+        }
+    };
+
+    let observed: Vec<f64> = organisation(&first, &second).into_iter()
+        .chain(organisation(&second, &first))
+        .collect();
+    let agreeing = observed.iter()
+        .filter(|doo| **doo > 0.0 && **doo > critical)
+        .count();
+
+    Some(agreeing as f64 / observed.len() as f64)
+}
+
+/// Merges overlapping annotations into time spans.
+fn covered(annotations: &[SegmentAnnotation]) -> Vec<(i64, i64)> {
+    let mut spans: Vec<(i64, i64)> = Vec::new();
+    for a in annotations.iter() {
+        match spans.last_mut() {
+            Some(last) if a.start <= last.1 => last.1 = last.1.max(a.end),
+            _ => spans.push((a.start, a.end)),
+        }
+    }
+    spans
+}
+
+/// Time both tiers have annotations, relative to the time either has annotations.
+fn overlap_percentage(first: &[SegmentAnnotation], second: &[SegmentAnnotation]) -> Option<f64> {
+    let (first, second) = (covered(first), covered(second));
+    let duration = |spans: &[(i64, i64)]| spans.iter().map(|(s, e)| e - s).sum::<i64>();
+
+    let both: i64 = first.iter()
+        .flat_map(|a| second.iter().filter_map(|b| intersection(*a, *b)))
+        .map(|(s, e)| e - s)
+        .sum();
+    let either = duration(&first) + duration(&second) - both;
+
+    match either > 0 {
+        true => Some(100.0 * both as f64 / either as f64),
+        false => None,
+    }
+}
+
+/// See `Eaf::agreement()`.
+pub(crate) fn agreement(
+    eaf: &Eaf,
+    tier_id: &str,
+    other: &Eaf,
+    other_tier_id: &str,
+    options: &AgreementOptions
+) -> Result<Agreement, EafError> {
+    let tier = eaf.get_tier(tier_id)
+        .ok_or_else(|| EafError::TierIdInvalid(tier_id.to_owned()))?;
+    let other_tier = other.get_tier(other_tier_id)
+        .ok_or_else(|| EafError::TierIdInvalid(other_tier_id.to_owned()))?;
+
+    let first = annotations(tier);
+    let second = annotations(other_tier);
+    let segments = segments(&first, &second, options.min_overlap);
+
+    // Controlled vocabulary values first, then the remaining values sorted.
+    let mut categories: Vec<Option<String>> = Vec::new();
+    for value in cv_values(eaf, tier).into_iter().chain(cv_values(other, other_tier)) {
+        if !categories.contains(&Some(value.to_owned())) {
+            categories.push(Some(value));
+        }
+    }
+    let mut values: Vec<&str> = first.iter().chain(second.iter())
+        .map(|a| a.value.as_str())
+        .filter(|v| !categories.iter().any(|c| c.as_deref() == Some(*v)))
+        .collect();
+    values.sort();
+    values.dedup();
+    categories.extend(values.into_iter().map(|v| Some(v.to_owned())));
+    categories.push(None);
+
+    let confusion = ConfusionMatrix::new(categories, &segments);
+
+    let boundaries: Vec<(f64, f64)> = segments.iter()
+        .filter_map(|s| Some((s.first.as_ref()?, s.second.as_ref()?)))
+        .flat_map(|(a, b)| [(a.start as f64, b.start as f64), (a.end as f64, b.end as f64)])
+        .collect();
+
+    Ok(Agreement {
+        kappa: confusion.kappa(true),
+        kappa_matched: confusion.kappa(false),
+        alpha: alpha(&boundaries),
+        staccato: staccato(&first, &second, options),
+        overlap_percentage: overlap_percentage(&first, &second),
+        matches: segments,
+        confusion,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eaf::test_util::{TestEaf, aligned, assert_approx};
+
+    fn eaf() -> Eaf {
+        TestEaf::new()
+            .time_slots(&[0, 1000, 1100, 2000, 2400, 3000, 4000, 5000])
+            .tier("R1", "default-lt", None, &[
+                aligned("a1", "ts1", "ts2", "A"),
+                aligned("a2", "ts2", "ts4", "B"),
+                aligned("a3", "ts4", "ts6", "A"),
+                aligned("a4", "ts7", "ts8", "B"),
+            ])
+            .tier("R2", "default-lt", None, &[
+                aligned("a5", "ts1", "ts2", "A"),
+                aligned("a6", "ts3", "ts4", "B"),
+                aligned("a7", "ts4", "ts5", "B"),
+                aligned("a8", "ts7", "ts8", "A"),
+            ])
+            .eaf()
+    }
+
+    fn segment(start: i64, end: i64) -> SegmentAnnotation {
+        SegmentAnnotation { id: String::new(), value: String::new(), start, end }
+    }
+
+    #[test]
+    fn kappa() {
+        // po = 0.7, pe = 0.5
+        let confusion = ConfusionMatrix {
+            categories: vec![Some("yes".into()), Some("no".into()), None],
+            counts: vec![vec![20, 5, 0], vec![10, 15, 0], vec![0, 0, 0]],
+        };
+        assert_approx(confusion.kappa(true), 0.4);
+        assert_approx(confusion.kappa(false), 0.4);
+        assert_eq!(confusion.total(), 50);
+        assert_eq!(confusion.count(Some("no"), Some("yes")), 10);
+
+        // po = 4/6, pe = 26/36
+        let confusion = ConfusionMatrix {
+            categories: vec![Some("yes".into()), None],
+            counts: vec![vec![4, 1], vec![1, 0]],
+        };
+        assert_approx(confusion.kappa(true), -0.2);
+        // Agreement by chance is 1.
+        assert_eq!(confusion.kappa(false), None);
+
+        assert_eq!(ConfusionMatrix::default().kappa(true), None);
+    }
+
+    #[test]
+    fn krippendorff_alpha() {
+        assert_approx(alpha(&[(1.0, 2.0), (3.0, 3.0), (5.0, 6.0)]), 47.0 / 52.0);
+        assert_approx(alpha(&[(1.0, 1.0), (4.0, 4.0)]), 1.0);
+        // Systematic disagreement.
+        assert_approx(alpha(&[(1.0, 2.0), (2.0, 1.0)]), -0.5);
+        assert_eq!(alpha(&[(1.0, 2.0)]), None);
+        assert_eq!(alpha(&[(1.0, 1.0), (1.0, 1.0)]), None);
+    }
+
+    #[test]
+    fn staccato_agreement() {
+        let options = AgreementOptions::default();
+        let first = [segment(0, 100), segment(500, 600)];
+
+        assert_approx(staccato(&first, &first, &options), 1.0);
+        let disjoint = [segment(200, 300), segment(700, 800)];
+        assert_approx(staccato(&first, &disjoint, &options), 0.0);
+        // One annotation in each tier corresponds.
+        let partial = [segment(0, 100), segment(800, 900)];
+        assert_approx(staccato(&first, &partial, &options), 0.5);
+        assert_eq!(staccato(&first, &[], &options), None);
+
+        // Same seed, same random segmentations.
+        let mut rng1 = Rng::new(7);
+        let mut rng2 = Rng::new(7);
+        let spans = [(0.0, 100.0), (500.0, 600.0)];
+        assert_eq!(
+            random_segmentation(&spans, 0.0, 1000.0, &mut rng1),
+            random_segmentation(&spans, 0.0, 1000.0, &mut rng2)
+        );
+        let random = random_segmentation(&spans, 0.0, 1000.0, &mut rng1);
+        assert!(random.iter().all(|(s, e)| *s >= 0.0 && *e <= 1000.0 && (e - s - 100.0).abs() < 1e-9));
+        assert!(random[0].1 <= random[1].0);
+    }
+
+    #[test]
+    fn overlap() {
+        assert_approx(overlap_percentage(&[segment(0, 100)], &[segment(50, 150)]), 100.0 / 3.0);
+        assert_approx(overlap_percentage(&[segment(0, 100)], &[segment(0, 100)]), 100.0);
+        assert_approx(overlap_percentage(&[segment(0, 100)], &[segment(100, 200)]), 0.0);
+        assert_eq!(overlap_percentage(&[], &[]), None);
+        assert_eq!(organisation(&[(0.0, 100.0), (200.0, 300.0)], &[(50.0, 150.0)]), [1.0 / 3.0, 0.0]);
+    }
+
+    #[test]
+    fn tiers() {
+        let eaf = eaf();
+        let agreement = eaf.agreement("R1", &eaf, "R2", &AgreementOptions::default()).unwrap();
+
+        let ids: Vec<(Option<&str>, Option<&str>)> = agreement.matches.iter()
+            .map(|m| (m.first.as_ref().map(|a| a.id.as_str()), m.second.as_ref().map(|a| a.id.as_str())))
+            .collect();
+        // a7 overlaps a3 by less than `min_overlap`.
+        assert_eq!(ids, [
+            (Some("a1"), Some("a5")),
+            (Some("a2"), Some("a6")),
+            (Some("a3"), None),
+            (None, Some("a7")),
+            (Some("a4"), Some("a8")),
+        ]);
+        assert_eq!(agreement.matches[0].overlap, Some(Overlap::Equal));
+        assert_approx(agreement.matches[1].ratio, 0.9);
+        assert!(agreement.matches[0].is_agreement());
+        assert!(!agreement.matches[4].is_agreement() && agreement.matches[4].is_match());
+
+        let confusion = &agreement.confusion;
+        assert_eq!(confusion.categories, [Some("A".to_owned()), Some("B".to_owned()), None]);
+        assert_eq!(confusion.counts, [[1, 0, 1], [1, 1, 0], [0, 1, 0]]);
+
+        // po = 2/5, pe = 9/25
+        assert_approx(agreement.kappa, 0.0625);
+        // po = 2/3, pe = 4/9
+        assert_approx(agreement.kappa_matched, 0.4);
+        // Boundaries differ only for a2/a6 start.
+        assert_approx(agreement.alpha, 0.9997551801651421);
+        // 3300ms of both, 4000ms of either.
+        assert_approx(agreement.overlap_percentage, 82.5);
+
+        assert!(matches!(
+            eaf.agreement("R1", &eaf, "R3", &AgreementOptions::default()),
+            Err(EafError::TierIdInvalid(_))
+        ));
+    }
+}
@@ -345,6 +345,14 @@ impl Annotation {
         };
     }
 
+    /// Removes previous annotation ID if annotation is a ref annotation,
+    /// e.g. for the first annotation in a symbolic subdivision.
+    pub fn unset_previous(&mut self) {
+        if let AnnotationType::RefAnnotation(a) = &mut self.annotation_type {
+            a.previous_annotation = None
+        }
+    }
+
     /// Generates start, end timeslots for annotation
     /// and sets generated time slot references.
     /// Time values must have been be derived and set.
@@ -622,11 +630,7 @@ impl Annotation {
     pub(crate) fn overlaps(&self, annotation: &Self) -> bool {
         if let (Some(t_self1), Some(t_self2)) = self.ts_val() {
             if let (Some(t_other1), Some(t_other2)) = annotation.ts_val() {
-                let range = t_self1 .. t_self2;
-                println!("{range:?} t other 1: {t_other1} t other 2: {t_other2}");
-                if range.contains(&t_other1) || range.contains(&t_other2) {
-                    return true
-                }
+                return t_self1 < t_other2 && t_other1 < t_self2
             }
         }
 
@@ -17,3 +17,4 @@ pub use ref_annotation::RefAnnotation;
 pub use annotation_type::AnnotationType;
 pub use annotation_value::AnnotationValue;
 pub use builder::{AnnotationBuilder, AnnotationAlignable, AnnotationReferred};
+pub use overlap::Overlap;
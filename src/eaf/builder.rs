@@ -208,6 +208,7 @@ impl EafBuilder {
 
         Ok(Eaf {
             // path: self.path,
+            source: None,
             xmlns_xsi: self.xmlns_xsi.ok_or_else(|| EafError::XmlNameSpaceMissing)?,
             xsi_nonamespaceschemalocation: self.xsi_nonamespaceschemalocation
                 .ok_or_else(|| EafError::XmlNoNameSpaceMissing)?,
@@ -0,0 +1,712 @@
+//! CHAT, the transcription format used by [CHILDES](https://childes.talkbank.org)
+//! and [TalkBank](https://talkbank.org), for exchanging transcriptions with CLAN.
+//!
+//! Mapping between EAF and CHAT:
+//! - Each main tier becomes a speaker, with one `*SPK:` line per annotation.
+//!   The speaker code is the tier ID if this is a valid CHAT code (e.g. `CHI`, `MOT`),
+//!   otherwise it is derived from the tier participant or tier ID.
+//! - Tiers that depend on a main tier, at any depth, become dependent tiers
+//!   (`%mor`, `%gra`, `%com`, ...) on the utterance their annotations belong to.
+//!   Tier IDs in the form `mor@CHI`, as generated by ELAN's CHAT import, map to `%mor`.
+//!   Tier IDs that are not standard CHAT dependent tiers are prefixed with `x`, e.g. `%xgloss`.
+//! - Annotation time spans are written as bullets at the end of each utterance.
+//! - `@Participants` and `@ID` are generated from tier participants, and
+//!   `@Languages` from the EAF languages, or the locales if there are none.
+//! - On import, each speaker becomes a main tier with the speaker code as tier ID,
+//!   and each dependent tier a `Symbolic_Association` tier, e.g. `mor@CHI`.
+//!   All utterances must have time bullets. Headers other than
+//!   `@Languages` and `@Participants` are not imported.
+
+use std::{collections::HashMap, fmt::Display, fs::File, io::Write, path::Path};
+
+use crate::{Annotation, Eaf, EafError, Language, LinguisticType, StereoType, Tier};
+
+/// Delimits time bullets, e.g. `\u{15}1200_3400\u{15}`.
+const BULLET: char = '\u{15}';
+
+/// Standard CHAT dependent tiers.
+const DEPENDENT_TIERS: [&str; 22] = [
+    "act", "add", "alt", "cod", "coh", "com", "def", "eng", "err", "exp", "fac",
+    "flo", "gls", "gpx", "gra", "int", "mod", "mor", "ort", "pho", "sit", "spa",
+];
+
+/// Role used for participants, since EAF has no corresponding attribute.
+const DEFAULT_ROLE: &str = "Unidentified";
+
+/// CHAT participant, as listed in `@Participants`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatParticipant {
+    /// Speaker code, e.g. `CHI`.
+    pub code: String,
+    pub name: Option<String>,
+    /// Role, e.g. `Target_Child`.
+    pub role: String,
+}
+
+/// CHAT dependent tier line, e.g. `%mor:\tpro|I v|want .`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatDependent {
+    /// Dependent tier name without `%`, e.g. `mor`.
+    pub name: String,
+    pub value: String,
+}
+
+/// CHAT utterance (main line), with its dependent tiers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatUtterance {
+    /// Speaker code, e.g. `CHI`.
+    pub speaker: String,
+    pub value: String,
+    /// Start time in milliseconds, from the time bullet.
+    pub start: Option<i64>,
+    /// End time in milliseconds, from the time bullet.
+    pub end: Option<i64>,
+    pub dependents: Vec<ChatDependent>,
+}
+
+/// CHAT transcription.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chat {
+    /// ISO 639-3 language codes, as listed in `@Languages`.
+    pub languages: Vec<String>,
+    pub participants: Vec<ChatParticipant>,
+    /// Corpus name, used in `@ID` headers.
+    pub corpus: String,
+    /// Media file name without extension, and media type
+    /// (`audio` or `video`), as listed in `@Media`.
+    pub media: Option<(String, String)>,
+    pub utterances: Vec<ChatUtterance>,
+}
+
+impl Default for Chat {
+    fn default() -> Self {
+        Self {
+            languages: Vec::new(),
+            participants: Vec::new(),
+            corpus: "elan".to_owned(),
+            media: None,
+            utterances: Vec::new(),
+        }
+    }
+}
+
+impl Display for Chat {
+    /// CHAT content, with bullets for utterances that have time values.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let languages = match self.languages.is_empty() {
+            true => vec!["und".to_owned()],
+            false => self.languages.to_owned(),
+        };
+
+        writeln!(f, "@UTF8")?;
+        writeln!(f, "@Begin")?;
+        writeln!(f, "@Languages:\t{}", languages.join(", "))?;
+        let participants: Vec<String> = self.participants.iter()
+            .map(|p| match &p.name {
+                Some(name) => format!("{} {} {}", p.code, name.split_whitespace().collect::<Vec<_>>().join("_"), p.role),
+                None => format!("{} {}", p.code, p.role),
+            })
+            .collect();
+        writeln!(f, "@Participants:\t{}", participants.join(", "))?;
+        for p in self.participants.iter() {
+            writeln!(f, "@ID:\t{}|{}|{}|||||{}|||", languages[0], self.corpus, p.code, p.role)?;
+        }
+        if let Some((name, media_type)) = &self.media {
+            writeln!(f, "@Media:\t{name}, {media_type}")?;
+        }
+        for u in self.utterances.iter() {
+            write!(f, "*{}:\t{}", u.speaker, u.value)?;
+            if let (Some(start), Some(end)) = (u.start, u.end) {
+                write!(f, " {BULLET}{start}_{end}{BULLET}")?;
+            }
+            writeln!(f)?;
+            for d in u.dependents.iter() {
+                writeln!(f, "%{}:\t{}", d.name, d.value)?;
+            }
+        }
+        writeln!(f, "@End")
+    }
+}
+
+/// Returns `true` if `code` can be used as a CHAT speaker code as is.
+fn is_speaker_code(code: &str) -> bool {
+    (1..=7).contains(&code.len())
+        && code.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+/// Derives a unique speaker code for a main tier,
+/// e.g. `ANN` for participant "Anna".
+fn speaker_code(tier: &Tier, existing: &[ChatParticipant]) -> String {
+    let code = match is_speaker_code(&tier.tier_id) {
+        true => tier.tier_id.to_owned(),
+        false => {
+            let code: String = tier.participant.as_deref()
+                .filter(|p| p.chars().any(|c| c.is_ascii_alphanumeric()))
+                .unwrap_or(&tier.tier_id)
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .take(3)
+                .collect::<String>()
+                .to_uppercase();
+            match code.is_empty() {
+                true => "SPK".to_owned(),
+                false => code,
+            }
+        }
+    };
+
+    let mut unique = code.to_owned();
+    let mut n = 1;
+    while existing.iter().any(|p| p.code == unique) {
+        n += 1;
+        unique = format!("{code}{n}");
+    }
+    unique
+}
+
+/// Derives dependent tier name from a tier ID, e.g.
+/// `mor` for `mor@CHI`, or `xgloss` for `gloss`.
+fn dependent_name(tier_id: &str) -> String {
+    let name: String = tier_id.split('@').next()
+        .unwrap_or(tier_id)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    if DEPENDENT_TIERS.contains(&name.as_str()) || (name.starts_with('x') && name.len() > 1) {
+        name
+    } else {
+        format!("x{name}")
+    }
+}
+
+/// Returns all tiers that depend on `tier_id`, at any depth, in tier order.
+fn dependent_tiers<'a>(eaf: &'a Eaf, tier_id: &str) -> Vec<&'a Tier> {
+    let mut ids: Vec<&str> = vec![tier_id];
+    let mut i = 0;
+    while i < ids.len() {
+        let parent = ids[i];
+        ids.extend(eaf.tiers.iter()
+            .filter(|t| t.parent_ref.as_deref() == Some(parent))
+            .map(|t| t.tier_id.as_str()));
+        i += 1;
+    }
+    eaf.tiers.iter()
+        .filter(|t| t.tier_id != tier_id && ids.contains(&t.tier_id.as_str()))
+        .collect()
+}
+
+/// Returns `true` if the utterance ends with a CHAT terminator, e.g. `.`, `?`, `+...`.
+fn has_terminator(value: &str) -> bool {
+    value.trim_end().ends_with(['.', '?', '!'])
+}
+
+/// Parses a time bullet, e.g. `1200_3400`.
+fn parse_bullet(bullet: &str) -> Result<(i64, i64), EafError> {
+    let (start, end) = bullet.split_once('_')
+        .ok_or_else(|| EafError::ChatInvalid(format!("Invalid time bullet '{bullet}'")))?;
+    Ok((start.trim().parse()?, end.trim().parse()?))
+}
+
+/// Splits utterance into text and the time span of the final bullet,
+/// removing all bullets from the text.
+fn split_bullets(value: &str) -> Result<(String, Option<(i64, i64)>), EafError> {
+    let mut text = String::new();
+    let mut span = None;
+    for (i, part) in value.split(BULLET).enumerate() {
+        match i % 2 {
+            0 => text.push_str(part),
+            _ => span = Some(parse_bullet(part)?),
+        }
+    }
+    Ok((text.split_whitespace().collect::<Vec<_>>().join(" "), span))
+}
+
+impl Chat {
+    /// Reads and parses a CHAT file.
+    pub fn read(path: &Path) -> Result<Self, EafError> {
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(&content)
+    }
+
+    /// Parses CHAT content. Speakers not listed in
+    /// `@Participants` are added with an unidentified role.
+    pub fn parse(content: &str) -> Result<Self, EafError> {
+        let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+
+        // Join continuation lines, which start with a tab.
+        let mut lines: Vec<String> = Vec::new();
+        for line in content.lines() {
+            match (line.starts_with('\t'), lines.last_mut()) {
+                (true, Some(last)) => {
+                    last.push(' ');
+                    last.push_str(line.trim());
+                },
+                _ => lines.push(line.to_owned()),
+            }
+        }
+
+        let mut chat = Self::default();
+        for line in lines.iter().filter(|l| !l.trim().is_empty()) {
+            let (label, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.trim();
+            match line.chars().next() {
+                Some('@') => match label {
+                    "@Languages" => chat.languages = value.split(',')
+                        .map(|l| l.trim().to_owned())
+                        .filter(|l| !l.is_empty())
+                        .collect(),
+                    "@Participants" => {
+                        for p in value.split(',') {
+                            let words: Vec<&str> = p.split_whitespace().collect();
+                            let participant = match words.as_slice() {
+                                [] => continue,
+                                [code] => ChatParticipant {
+                                    code: code.to_string(),
+                                    name: None,
+                                    role: DEFAULT_ROLE.to_owned(),
+                                },
+                                [code, role] => ChatParticipant {
+                                    code: code.to_string(),
+                                    name: None,
+                                    role: role.to_string(),
+                                },
+                                [code, name @ .., role] => ChatParticipant {
+                                    code: code.to_string(),
+                                    name: Some(name.join(" ").replace('_', " ")),
+                                    role: role.to_string(),
+                                },
+                            };
+                            chat.participants.push(participant);
+                        }
+                    },
+                    "@Media" => chat.media = value.split_once(',')
+                        .map(|(name, media_type)| (name.trim().to_owned(), media_type.trim().to_owned())),
+                    _ => (),
+                },
+                Some('*') => {
+                    let speaker = label[1..].trim().to_owned();
+                    if !chat.participants.iter().any(|p| p.code == speaker) {
+                        chat.participants.push(ChatParticipant {
+                            code: speaker.to_owned(),
+                            name: None,
+                            role: DEFAULT_ROLE.to_owned(),
+                        });
+                    }
+                    let (value, span) = split_bullets(value)?;
+                    chat.utterances.push(ChatUtterance {
+                        speaker,
+                        value,
+                        start: span.map(|s| s.0),
+                        end: span.map(|s| s.1),
+                        dependents: Vec::new(),
+                    });
+                },
+                Some('%') => {
+                    let Some(utterance) = chat.utterances.last_mut() else {
+                        return Err(EafError::ChatInvalid(format!("Dependent tier before first utterance: '{line}'")))
+                    };
+                    utterance.dependents.push(ChatDependent {
+                        name: label[1..].trim().to_owned(),
+                        value: split_bullets(value)?.0,
+                    });
+                },
+                _ => return Err(EafError::ChatInvalid(format!("Unexpected line '{line}'"))),
+            }
+        }
+
+        Ok(chat)
+    }
+
+    /// Serializes and writes CHAT to disk.
+    pub fn write(&self, path: &Path) -> Result<(), EafError> {
+        let mut outfile = File::create(&path)?;
+        outfile.write_all(self.to_string().as_bytes()).map_err(|e| EafError::IOError(e))
+    }
+
+    /// Generates CHAT from the main tiers in an `Eaf`, and the tiers depending on these.
+    ///
+    /// Utterances are sorted on start time. Utterances without a terminator
+    /// are terminated with ` .`, and empty ones are written as `0 .`,
+    /// as required by CHAT. A dependent tier line contains the values
+    /// of all annotations in that tier that belong to the utterance,
+    /// separated by a space.
+    pub fn from_eaf(eaf: &Eaf) -> Result<Self, EafError> {
+        let mut languages: Vec<String> = match eaf.languages.is_empty() {
+            false => eaf.languages.iter().map(|l| l.lang_id.to_owned()).collect(),
+            true => eaf.locales.iter().map(|l| l.language_code.to_owned()).collect(),
+        };
+        languages.dedup();
+
+        let media = eaf.header.media_descriptor.first()
+            .and_then(|m| m.file_name().map(|f| (f, m.mime_type.starts_with("video"))))
+            .and_then(|(f, is_video)| Path::new(&f).file_stem()
+                .map(|s| (s.to_string_lossy().to_string(), match is_video {
+                    true => "video".to_owned(),
+                    false => "audio".to_owned(),
+                }))
+            );
+
+        let mut chat = Self {
+            languages,
+            media,
+            ..Self::default()
+        };
+
+        for tier in eaf.main_tiers() {
+            let code = speaker_code(tier, &chat.participants);
+            chat.participants.push(ChatParticipant {
+                code: code.to_owned(),
+                name: tier.participant.to_owned().filter(|p| !p.trim().is_empty() && p != &code),
+                role: DEFAULT_ROLE.to_owned(),
+            });
+
+            let mut utterances: Vec<ChatUtterance> = tier.iter()
+                .map(|a| {
+                    let (start, end) = a.ts_val();
+                    let value = match a.to_str().trim() {
+                        "" => "0 .".to_owned(),
+                        v if has_terminator(v) => v.to_owned(),
+                        v => format!("{v} ."),
+                    };
+                    ChatUtterance { speaker: code.to_owned(), value, start, end, dependents: Vec::new() }
+                })
+                .collect();
+
+            let index = UtteranceIndex::new(tier);
+            for dependent in dependent_tiers(eaf, &tier.tier_id) {
+                let name = dependent_name(&dependent.tier_id);
+                let mut values: Vec<Vec<&str>> = vec![Vec::new(); utterances.len()];
+                for annotation in dependent.iter() {
+                    if let Some(i) = index.get(annotation) {
+                        values[i].push(annotation.to_str());
+                    }
+                }
+                for (utterance, values) in utterances.iter_mut().zip(values) {
+                    let value = values.join(" ");
+                    if !value.trim().is_empty() {
+                        utterance.dependents.push(ChatDependent { name: name.to_owned(), value });
+                    }
+                }
+            }
+
+            chat.utterances.extend(utterances);
+        }
+
+        chat.utterances.sort_by_key(|u| u.start.unwrap_or(i64::MAX));
+
+        Ok(chat)
+    }
+
+    /// Generates an `Eaf` with one main tier per speaker, with the speaker code
+    /// as tier ID and the participant name (or code) as participant.
+    /// Each dependent tier becomes a referred tier with stereotype `Symbolic_Association`,
+    /// with the tier ID `<DEPENDENT_TIER>@<SPEAKER_CODE>`, e.g. `mor@CHI`.
+    ///
+    /// Returns error if an utterance has no time bullet, or if its speaker
+    /// is not listed in `participants`.
+    /// Since annotations in a tier may not overlap, an utterance
+    /// that overlaps the next one by the same speaker is truncated.
+    pub fn to_eaf(&self) -> Result<Eaf, EafError> {
+        // (speaker code, utterances with time spans)
+        let mut speakers: Vec<(String, Vec<(&ChatUtterance, i64, i64)>)> = self.participants.iter()
+            .map(|p| (p.code.to_owned(), Vec::new()))
+            .collect();
+        // speaker code -> index in speakers
+        let codes: HashMap<String, usize> = speakers.iter()
+            .enumerate()
+            .map(|(i, (code, _))| (code.to_owned(), i))
+            .collect();
+        for u in self.utterances.iter() {
+            let (Some(start), Some(end)) = (u.start, u.end) else {
+                return Err(EafError::ChatInvalid(format!("No time bullet for utterance '*{}: {}'", u.speaker, u.value)))
+            };
+            let Some(i) = codes.get(&u.speaker) else {
+                return Err(EafError::ChatInvalid(format!("Unknown speaker for utterance '*{}: {}'", u.speaker, u.value)))
+            };
+            speakers[*i].1.push((u, start, end));
+        }
+        speakers.retain(|(_, utterances)| !utterances.is_empty());
+        for (_, utterances) in speakers.iter_mut() {
+            utterances.sort_by_key(|(_, start, end)| (*start, *end));
+            for i in 1..utterances.len() {
+                let next_start = utterances[i].1;
+                if utterances[i-1].2 > next_start {
+                    utterances[i-1].2 = next_start;
+                }
+            }
+        }
+
+        let tier_values: Vec<(String, Vec<(String, i64, i64)>)> = speakers.iter()
+            .map(|(code, utterances)| (
+                code.to_owned(),
+                utterances.iter()
+                    .map(|(u, start, end)| (u.value.to_owned(), *start, *end))
+                    .collect()
+            ))
+            .collect();
+
+        let mut eaf = Eaf::from_tier_values(&tier_values)?;
+
+        eaf.languages = self.languages.iter()
+            .map(|l| Language { lang_id: l.to_owned(), ..Language::default() })
+            .collect();
+
+        let mut a_id = eaf.generate_a_id_num().unwrap_or(1);
+        for (code, utterances) in speakers.iter() {
+            let participant = self.participants.iter()
+                .find(|p| &p.code == code)
+                .and_then(|p| p.name.to_owned())
+                .unwrap_or(code.to_owned());
+            let Some(main_tier) = eaf.get_tier_mut(code) else {
+                continue;
+            };
+            main_tier.participant = Some(participant.to_owned());
+            let main_ids: Vec<String> = main_tier.iter().map(|a| a.id().to_owned()).collect();
+
+            // Dependent tier names in the order these first appear.
+            let mut names: Vec<&str> = Vec::new();
+            for (u, ..) in utterances.iter() {
+                for d in u.dependents.iter() {
+                    if !names.contains(&d.name.as_str()) {
+                        names.push(&d.name);
+                    }
+                }
+            }
+
+            for name in names {
+                let mut annotations = Vec::new();
+                for ((u, start, end), main_id) in utterances.iter().zip(main_ids.iter()) {
+                    // A repeated dependent tier is joined with the first.
+                    let value = u.dependents.iter()
+                        .filter(|d| d.name == name)
+                        .map(|d| d.value.as_str())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    if value.is_empty() {
+                        continue;
+                    }
+                    annotations.push(Annotation::builder()
+                        .annotation_value(value)
+                        .annotation_id(format!("a{a_id}"))
+                        .time_values(*start, *end)
+                        .annotation_ref(main_id)
+                        .build_referred()?);
+                    a_id += 1;
+                }
+
+                let tier = Tier::builder()
+                    .tier_id(&format!("{name}@{code}"))
+                    .participant(&participant)
+                    .linguistic_type_ref(name)
+                    .parent_ref(code)
+                    .annotations(&annotations)
+                    .build()?;
+                eaf.tiers.push(tier);
+
+                let lt = LinguisticType::new(name, Some(&StereoType::SymbolicAssociation));
+                if !eaf.linguistic_types.contains(&lt) {
+                    eaf.add_linguistic_type(&lt, true);
+                }
+            }
+        }
+
+        eaf.index();
+        eaf.derive()?;
+
+        Ok(eaf)
+    }
+}
+
+/// Finds the annotation in a main tier that an annotation in
+/// a dependent tier belongs to.
+struct UtteranceIndex<'a> {
+    /// Main annotation ID -> annotation index
+    ids: HashMap<&'a str, usize>,
+    /// Main annotation time spans with annotation index, sorted on start time.
+    spans: Vec<(i64, i64, usize)>,
+}
+
+impl<'a> UtteranceIndex<'a> {
+    fn new(tier: &'a Tier) -> Self {
+        let ids = tier.iter()
+            .enumerate()
+            .map(|(i, a)| (a.id(), i))
+            .collect();
+        let mut spans: Vec<(i64, i64, usize)> = tier.iter()
+            .enumerate()
+            .filter_map(|(i, a)| match a.ts_val() {
+                (Some(s), Some(e)) => Some((s, e, i)),
+                _ => None
+            })
+            .collect();
+        spans.sort();
+        Self { ids, spans }
+    }
+
+    /// Returns the index of the main annotation that `annotation` belongs to,
+    /// either via its main annotation (referred annotations),
+    /// or its time span (time aligned annotations).
+    fn get(&self, annotation: &Annotation) -> Option<usize> {
+        if let Some(main) = annotation.main() {
+            return self.ids.get(main).copied()
+        }
+        let (Some(start), Some(end)) = annotation.ts_val() else {
+            return None
+        };
+        // Main annotations do not overlap, so only the last one
+        // starting at or before the annotation can contain it.
+        let i = self.spans.partition_point(|(s, ..)| *s <= start).checked_sub(1)?;
+        let (_, e, idx) = self.spans[i];
+        (end <= e).then_some(idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eaf::test_util::{TestEaf, aligned, referred};
+
+    const CHAT: &str = "@UTF8
+@Begin
+@Languages:\teng
+@Participants:\tCHI Target_Child, MOT Mary_Smith Mother
+@ID:\teng|elan|CHI|||||Target_Child|||
+@ID:\teng|elan|MOT|||||Mother|||
+*CHI:\tI want cookie . \u{15}0_1200\u{15}
+%mor:\tpro|I v|want n|cookie .
+*MOT:\tno
+\tcookies now ? \u{15}1000_2500\u{15}
+%com:\tlaughs
+*CHI:\tplease ! \u{15}2500_3000\u{15}
+@End
+";
+
+    /// Main tier `CHI` with a referred tier `mor@CHI`
+    /// and a time aligned, dependent tier `words`.
+    fn eaf() -> Eaf {
+        TestEaf::new()
+            .time_slots(&[0, 500, 1000, 2000, 3000])
+            .tier("CHI", "default-lt", None, &[
+                aligned("a1", "ts1", "ts3", "hi there"),
+                aligned("a2", "ts4", "ts5", "bye ?"),
+            ])
+            .participant("Anna")
+            .linguistic_type("mor", Some("Symbolic_Association"))
+            .tier("mor@CHI", "mor", Some("CHI"), &[referred("a3", "a2", "co|bye ?")])
+            .linguistic_type("words", Some("Included_In"))
+            .tier("words", "words", Some("CHI"), &[
+                aligned("a4", "ts1", "ts2", "hi"),
+                aligned("a5", "ts2", "ts3", "there"),
+            ])
+            .eaf()
+    }
+
+    #[test]
+    fn parse() {
+        let chat = Chat::parse(CHAT).unwrap();
+        assert_eq!(chat.languages, vec!["eng"]);
+        assert_eq!(chat.participants, vec![
+            ChatParticipant { code: "CHI".to_owned(), name: None, role: "Target_Child".to_owned() },
+            ChatParticipant { code: "MOT".to_owned(), name: Some("Mary Smith".to_owned()), role: "Mother".to_owned() },
+        ]);
+        assert_eq!(chat.utterances.len(), 3);
+        // Continuation line joined, bullet removed
+        assert_eq!(chat.utterances[1].value, "no cookies now ?");
+        assert_eq!((chat.utterances[1].start, chat.utterances[1].end), (Some(1000), Some(2500)));
+        assert_eq!(chat.utterances[0].dependents, vec![
+            ChatDependent { name: "mor".to_owned(), value: "pro|I v|want n|cookie .".to_owned() }
+        ]);
+    }
+
+    #[test]
+    fn to_eaf() {
+        let eaf = Chat::parse(CHAT).unwrap().to_eaf().unwrap();
+        assert_eq!(eaf.main_tier_ids(), vec!["CHI", "MOT"]);
+        assert_eq!(eaf.get_tier("MOT").unwrap().participant.as_deref(), Some("Mary Smith"));
+        assert_eq!(eaf.languages[0].lang_id, "eng");
+
+        let chi: Vec<(String, Option<i64>, Option<i64>)> = eaf.get_tier("CHI").unwrap().iter()
+            .map(|a| (a.to_str().to_owned(), a.ts_val().0, a.ts_val().1))
+            .collect();
+        assert_eq!(chi, vec![
+            ("I want cookie .".to_owned(), Some(0), Some(1200)),
+            ("please !".to_owned(), Some(2500), Some(3000)),
+        ]);
+
+        let mor = eaf.get_tier("mor@CHI").unwrap();
+        assert_eq!(mor.parent_ref.as_deref(), Some("CHI"));
+        assert_eq!(mor.values(), vec!["pro|I v|want n|cookie ."]);
+        assert_eq!(mor.annotations[0].ref_id(), Some(eaf.get_tier("CHI").unwrap().annotations[0].id()));
+        assert_eq!(eaf.get_tier("com@MOT").unwrap().values(), vec!["laughs"]);
+        assert!(eaf.validate().is_empty());
+    }
+
+    #[test]
+    fn round_trip() {
+        let chat = Chat::parse(CHAT).unwrap();
+        let eaf = chat.to_eaf().unwrap();
+        let chat2 = Chat::from_eaf(&eaf).unwrap();
+        assert_eq!(chat2.languages, chat.languages);
+        assert_eq!(chat2.utterances, chat.utterances);
+        assert_eq!(Chat::parse(&chat2.to_string()).unwrap().utterances, chat.utterances);
+    }
+
+    #[test]
+    fn from_eaf() {
+        let eaf = eaf();
+        let chat = Chat::from_eaf(&eaf).unwrap();
+        assert_eq!(chat.participants, vec![
+            ChatParticipant { code: "CHI".to_owned(), name: Some("Anna".to_owned()), role: DEFAULT_ROLE.to_owned() }
+        ]);
+        let utterances: Vec<(&str, Vec<(&str, &str)>)> = chat.utterances.iter()
+            .map(|u| (
+                u.value.as_str(),
+                u.dependents.iter().map(|d| (d.name.as_str(), d.value.as_str())).collect()
+            ))
+            .collect();
+        assert_eq!(utterances, vec![
+            // terminator added, time aligned dependent tier matched via time span
+            ("hi there .", vec![("xwords", "hi there")]),
+            ("bye ?", vec![("mor", "co|bye ?")]),
+        ]);
+        assert!(chat.to_string().contains("*CHI:\thi there . \u{15}0_1000\u{15}\n%xwords:\thi there\n"));
+    }
+
+    #[test]
+    fn speaker_codes() {
+        let eaf = Eaf::from_tier_values(&[
+            ("Speaker A".to_owned(), vec![("a".to_owned(), 0, 1000)]),
+            ("speaker b".to_owned(), vec![("b".to_owned(), 1000, 2000)]),
+        ]).unwrap();
+        let codes: Vec<String> = Chat::from_eaf(&eaf).unwrap().participants.into_iter()
+            .map(|p| p.code)
+            .collect();
+        assert_eq!(codes, vec!["SPE", "SPE2"]);
+    }
+
+    #[test]
+    fn overlap() {
+        let chat = Chat::parse("@Begin\n*CHI:\ta . \u{15}0_2000\u{15}\n*CHI:\tb . \u{15}1500_3000\u{15}\n@End\n").unwrap();
+        let eaf = chat.to_eaf().unwrap();
+        let spans: Vec<(Option<i64>, Option<i64>)> = eaf.get_tier("CHI").unwrap().iter()
+            .map(|a| a.ts_val())
+            .collect();
+        assert_eq!(spans, vec![(Some(0), Some(1500)), (Some(1500), Some(3000))]);
+    }
+
+    #[test]
+    fn invalid() {
+        // No time bullet
+        let chat = Chat::parse("@Begin\n*CHI:\thello .\n@End\n").unwrap();
+        assert!(matches!(chat.to_eaf(), Err(EafError::ChatInvalid(_))));
+
+        // Speaker not listed in participants
+        let mut chat = Chat::parse(CHAT).unwrap();
+        chat.participants.retain(|p| p.code != "MOT");
+        assert!(matches!(chat.to_eaf(), Err(EafError::ChatInvalid(msg)) if msg.contains("MOT")));
+
+        assert!(matches!(Chat::parse("%mor:\tn|cookie\n"), Err(EafError::ChatInvalid(_))));
+        assert!(Chat::parse("*CHI:\thi \u{15}0_x\u{15}\n").is_err());
+    }
+}
@@ -0,0 +1,444 @@
+//! Main tiers computed from the annotation time spans of other tiers,
+//! similar to ELAN's "Create Annotations from Overlaps/Gaps/Subtraction" and "Merge Tiers".
+//! E.g. overlaps between two speaker tiers give co-speech segments,
+//! gaps between all speaker tiers give silence segments.
+//!
+//! Time spans are half-open, see `Overlap`, so annotations that only
+//! share a boundary neither overlap, nor leave a gap between them.
+//! Annotation values in the computed tier are set according to `ValueRule`.
+
+use crate::{Annotation, Eaf, EafError, LinguisticType, Overlap, RangeMode, Tier, TimeSlot};
+
+use super::merge::join_values;
+
+/// Value for annotations in a computed tier.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ValueRule {
+    /// The same value for all annotations.
+    Label(String),
+    /// Values of the source annotations a time span was computed from,
+    /// in the order the source tiers were specified, separated by a space.
+    #[default]
+    Concatenate,
+    /// Values of the source annotations from the specified tier,
+    /// separated by a space.
+    Tier(String),
+}
+
+/// Computed time span, with the annotations it was computed from.
+struct Segment<'a> {
+    start: i64,
+    end: i64,
+    /// Source annotations as `(tier ID, annotation)`.
+    sources: Vec<(&'a str, &'a Annotation)>,
+}
+
+impl<'a> Segment<'a> {
+    fn span(&self) -> (i64, i64) {
+        (self.start, self.end)
+    }
+
+    /// Returns annotation value according to `rule`.
+    fn value(&self, rule: &ValueRule) -> String {
+        match rule {
+            ValueRule::Label(label) => label.to_owned(),
+            ValueRule::Concatenate => self.sources.iter()
+                .fold(String::new(), |value, (_, a)| join_values(&value, a.to_str())),
+            ValueRule::Tier(tier_id) => self.sources.iter()
+                .filter(|(t, _)| t == tier_id)
+                .fold(String::new(), |value, (_, a)| join_values(&value, a.to_str())),
+        }
+    }
+}
+
+/// Intersection of two time spans.
+pub(crate) fn intersection(span1: (i64, i64), span2: (i64, i64)) -> Option<(i64, i64)> {
+    let span = match Overlap::from_spans(span1, span2)? {
+        Overlap::Surrounds(..) => span2,
+        Overlap::Contained(..) | Overlap::Equal => span1,
+        Overlap::Start(_) => (span1.0, span2.1),
+        Overlap::End(_) => (span2.0, span1.1),
+    };
+    Some(span)
+}
+
+/// Time span `span1` minus time span `span2`.
+fn difference(span1: (i64, i64), span2: (i64, i64)) -> Vec<(i64, i64)> {
+    let spans = match Overlap::from_spans(span1, span2) {
+        None => vec![span1],
+        Some(Overlap::Surrounds(..)) => vec![(span1.0, span2.0), (span2.1, span1.1)],
+        Some(Overlap::Contained(..) | Overlap::Equal) => Vec::new(),
+        Some(Overlap::Start(_)) => vec![(span2.1, span1.1)],
+        Some(Overlap::End(_)) => vec![(span1.0, span2.0)],
+    };
+    spans.into_iter()
+        .filter(|(start, end)| start < end)
+        .collect()
+}
+
+/// Returns annotations with time values in the specified tiers,
+/// as `(tier ID, annotation)`, sorted on time.
+fn sources<'a>(eaf: &'a Eaf, tier_ids: &[&str]) -> Result<Vec<Vec<(&'a str, &'a Annotation)>>, EafError> {
+    tier_ids.iter()
+        .map(|id| {
+            let tier = eaf.get_tier(id)
+                .ok_or_else(|| EafError::TierIdInvalid(id.to_string()))?;
+            let mut annotations: Vec<(&str, &Annotation)> = tier.annotations.iter()
+                .filter(|a| matches!(a.ts_val(), (Some(_), Some(_))))
+                .map(|a| (tier.tier_id.as_str(), a))
+                .collect();
+            annotations.sort_by_key(|(_, a)| a.ts_val());
+            Ok(annotations)
+        })
+        .collect()
+}
+
+/// Merges overlapping segments, sorted on time.
+fn coalesce(mut segments: Vec<Segment>) -> Vec<Segment> {
+    segments.sort_by_key(|s| s.span());
+
+    let mut coalesced: Vec<Segment> = Vec::new();
+    for segment in segments.into_iter() {
+        match coalesced.last_mut() {
+            Some(last) if Overlap::from_spans(last.span(), segment.span()).is_some() => {
+                last.end = last.end.max(segment.end);
+                for source in segment.sources.into_iter() {
+                    if !last.sources.iter().any(|(t, a)| *t == source.0 && a.id() == source.1.id()) {
+                        last.sources.push(source);
+                    }
+                }
+            },
+            _ => coalesced.push(segment),
+        }
+    }
+
+    coalesced
+}
+
+/// Merges overlapping annotations into a single segment each.
+fn union<'a>(sources: &[Vec<(&'a str, &'a Annotation)>]) -> Vec<Segment<'a>> {
+    let segments: Vec<Segment> = sources.iter()
+        .flatten()
+        .filter_map(|(t, a)| match a.ts_val() {
+            (Some(start), Some(end)) => Some(Segment { start, end, sources: vec![(*t, *a)] }),
+            _ => None,
+        })
+        .collect();
+
+    coalesce(segments)
+}
+
+/// Time spans where all specified tiers have annotations.
+fn overlaps<'a>(eaf: &'a Eaf, sources: &[Vec<(&'a str, &'a Annotation)>]) -> Vec<Segment<'a>> {
+    let Some((first, rest)) = sources.split_first() else {
+        return Vec::new()
+    };
+
+    let mut segments: Vec<Segment> = first.iter()
+        .filter_map(|(t, a)| match a.ts_val() {
+            (Some(start), Some(end)) => Some(Segment { start, end, sources: vec![(*t, *a)] }),
+            _ => None,
+        })
+        .collect();
+
+    for annotations in rest.iter() {
+        let Some((tier_id, _)) = annotations.first() else {
+            return Vec::new()
+        };
+        let mut intersected: Vec<Segment> = Vec::new();
+        for segment in segments.iter() {
+            for annotation in eaf.annotations_in(segment.start, segment.end, RangeMode::Overlapping, Some(tier_id)) {
+                let (Some(start), Some(end)) = annotation.ts_val() else {
+                    continue
+                };
+                if let Some((start, end)) = intersection(segment.span(), (start, end)) {
+                    let mut sources = segment.sources.to_owned();
+                    sources.push((tier_id, annotation));
+                    intersected.push(Segment { start, end, sources });
+                }
+            }
+        }
+        segments = intersected;
+    }
+
+    segments
+}
+
+/// Adds a main tier with specified time spans and values.
+/// Adjacent annotations share time slot.
+fn add_tier(eaf: &mut Eaf, tier_id: &str, spans: &[(i64, i64, String)]) -> Result<(), EafError> {
+    let a_ids = match eaf.generate_a_id_multi(spans.len()) {
+        // No numerical annotation ID:s in use
+        ids if ids.len() < spans.len() => (1..=spans.len()).map(|n| format!("a{n}")).collect(),
+        ids => ids,
+    };
+    let mut ts_ids = eaf.time_order.gen_id_multi(2 * spans.len()).into_iter();
+
+    let mut time_slots: Vec<TimeSlot> = Vec::new();
+    let mut annotations: Vec<Annotation> = Vec::new();
+    for ((start, end, value), a_id) in spans.iter().zip(a_ids.iter()) {
+        let ts1 = match time_slots.last() {
+            Some(ts) if ts.time_value == Some(*start) => ts.time_slot_id.to_owned(),
+            _ => {
+                let ts = TimeSlot::new(&ts_ids.next().unwrap_or_default(), Some(*start));
+                time_slots.push(ts.to_owned());
+                ts.time_slot_id
+            }
+        };
+        let ts2 = TimeSlot::new(&ts_ids.next().unwrap_or_default(), Some(*end));
+        time_slots.push(ts2.to_owned());
+        annotations.push(Annotation::alignable(value, a_id, &ts1, &ts2.time_slot_id));
+    }
+
+    for ts in time_slots.iter() {
+        eaf.time_order.insert(ts);
+        eaf.index.insert_timeslot(&ts.time_slot_id, ts.time_value);
+    }
+
+    let tier = Tier::new(tier_id, Some(&annotations), None, None);
+    let lt = LinguisticType::default();
+    if !eaf.linguistic_types.iter().any(|l| l.linguistic_type_id == tier.linguistic_type_ref) {
+        eaf.add_linguistic_type(&lt, true)
+    }
+
+    let t_idx = eaf.tiers.len();
+    eaf.index.insert_tier(&tier, t_idx);
+    eaf.tiers.push(tier);
+    let tier = &mut eaf.tiers[t_idx];
+    for annotation in tier.annotations.iter_mut() {
+        eaf.index.derive(annotation, &tier.tier_id)?;
+    }
+
+    Ok(())
+}
+
+/// Computes time spans from the annotations in the specified tiers
+/// with `segments`, and adds these as a new main tier.
+fn compute<F>(
+    eaf: &mut Eaf,
+    tier_ids: &[&str],
+    new_id: &str,
+    rule: &ValueRule,
+    segments: F
+) -> Result<(), EafError>
+where
+    F: for<'a> Fn(&'a Eaf, &[Vec<(&'a str, &'a Annotation)>]) -> Vec<Segment<'a>>
+{
+    if tier_ids.is_empty() {
+        return Err(EafError::NoData)
+    }
+    if !eaf.indexed {
+        eaf.index()
+    }
+    if eaf.index.t2idx.contains_key(new_id) {
+        return Err(EafError::TierIdExists(new_id.to_owned()))
+    }
+    if let ValueRule::Tier(tier_id) = rule
+        && !tier_ids.contains(&tier_id.as_str())
+    {
+        return Err(EafError::TierIdInvalid(tier_id.to_owned()))
+    }
+
+    if !eaf.derived {
+        eaf.derive()?
+    }
+
+    let sources = sources(eaf, tier_ids)?;
+    let mut segments = coalesce(segments(eaf, &sources));
+    for segment in segments.iter_mut() {
+        segment.sources.sort_by_key(|(t, a)| (tier_ids.iter().position(|id| id == t), a.ts_val()));
+    }
+    let spans: Vec<(i64, i64, String)> = segments.iter()
+        .map(|s| (s.start, s.end, s.value(rule)))
+        .collect();
+
+    add_tier(eaf, new_id, &spans)
+}
+
+/// See `Eaf::tier_from_overlaps()`.
+pub(crate) fn tier_from_overlaps(eaf: &mut Eaf, tier_ids: &[&str], new_id: &str, rule: &ValueRule) -> Result<(), EafError> {
+    compute(eaf, tier_ids, new_id, rule, overlaps)
+}
+
+/// See `Eaf::tier_from_gaps()`.
+pub(crate) fn tier_from_gaps(
+    eaf: &mut Eaf,
+    tier_ids: &[&str],
+    new_id: &str,
+    end: Option<i64>,
+    rule: &ValueRule
+) -> Result<(), EafError> {
+    compute(eaf, tier_ids, new_id, rule, |_, sources| {
+        // Boundaries, from 0 to `end` or the end of the last annotation.
+        let mut boundaries: Vec<(i64, i64)> = vec![(0, 0)];
+        boundaries.extend(union(sources).iter().map(|s| s.span()));
+        if let Some(end) = end {
+            boundaries.push((end, end))
+        }
+
+        boundaries.windows(2)
+            .filter(|w| w[0].1 < w[1].0)
+            .map(|w| Segment { start: w[0].1, end: w[1].0, sources: Vec::new() })
+            .collect()
+    })
+}
+
+/// See `Eaf::tier_from_subtraction()`.
+pub(crate) fn tier_from_subtraction(
+    eaf: &mut Eaf,
+    tier_id: &str,
+    subtract: &[&str],
+    new_id: &str,
+    rule: &ValueRule
+) -> Result<(), EafError> {
+    let mut tier_ids = vec![tier_id];
+    tier_ids.extend(subtract.iter().filter(|t| **t != tier_id));
+
+    compute(eaf, &tier_ids, new_id, rule, |_, sources| {
+        let Some((minuend, subtrahends)) = sources.split_first() else {
+            return Vec::new()
+        };
+        let subtrahends = union(subtrahends);
+
+        let mut segments: Vec<Segment> = Vec::new();
+        for (t, annotation) in minuend.iter() {
+            let (Some(start), Some(end)) = annotation.ts_val() else {
+                continue
+            };
+            // Subtrahends are sorted and do not overlap,
+            // so only those from the first overlapping one are relevant.
+            let first = subtrahends.partition_point(|s| s.end <= start);
+            let mut spans = vec![(start, end)];
+            for subtrahend in subtrahends[first..].iter().take_while(|s| s.start < end) {
+                spans = spans.into_iter()
+                    .flat_map(|span| difference(span, subtrahend.span()))
+                    .collect();
+            }
+            segments.extend(spans.into_iter()
+                .map(|(start, end)| Segment { start, end, sources: vec![(*t, *annotation)] }));
+        }
+
+        segments
+    })
+}
+
+/// See `Eaf::tier_from_merge()`.
+pub(crate) fn tier_from_merge(eaf: &mut Eaf, tier_ids: &[&str], new_id: &str, rule: &ValueRule) -> Result<(), EafError> {
+    compute(eaf, tier_ids, new_id, rule, |_, sources| union(sources))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eaf::test_util::{TestEaf, aligned, assert_valid};
+
+    fn eaf() -> Eaf {
+        TestEaf::new()
+            .time_slots(&[100, 500, 1000, 1500, 2000, 2500, 3000, 4000, 5000])
+            .tier("S1", "default-lt", None, &[
+                aligned("a1", "ts1", "ts3", "hello"),
+                aligned("a2", "ts5", "ts7", "there"),
+            ])
+            .tier("S2", "default-lt", None, &[
+                aligned("a3", "ts2", "ts4", "yes"),
+                aligned("a4", "ts4", "ts6", "no"),
+                aligned("a5", "ts8", "ts9", "bye"),
+            ])
+            .eaf()
+    }
+
+    /// Time spans and values for the annotations in a tier.
+    fn spans(eaf: &Eaf, tier_id: &str) -> Vec<(i64, i64, String)> {
+        eaf.get_tier(tier_id).unwrap().annotations.iter()
+            .map(|a| match a.ts_val() {
+                (Some(start), Some(end)) => (start, end, a.to_str().to_owned()),
+                _ => panic!("No time values for '{}'", a.id()),
+            })
+            .collect()
+    }
+
+    fn expected(spans: &[(i64, i64, &str)]) -> Vec<(i64, i64, String)> {
+        spans.iter()
+            .map(|(start, end, value)| (*start, *end, value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn span_operations() {
+        assert_eq!(intersection((0, 10), (5, 15)), Some((5, 10)));
+        assert_eq!(intersection((0, 10), (2, 5)), Some((2, 5)));
+        assert_eq!(intersection((0, 10), (10, 15)), None);
+        assert_eq!(difference((0, 10), (2, 5)), [(0, 2), (5, 10)]);
+        assert_eq!(difference((0, 10), (5, 15)), [(0, 5)]);
+        assert_eq!(difference((0, 10), (0, 10)), []);
+        assert_eq!(difference((0, 10), (10, 15)), [(0, 10)]);
+    }
+
+    #[test]
+    fn overlaps() {
+        let mut eaf = eaf();
+        eaf.tier_from_overlaps(&["S1", "S2"], "overlaps", &ValueRule::Concatenate).unwrap();
+        assert_eq!(spans(&eaf, "overlaps"), expected(&[(500, 1000, "hello yes"), (2000, 2500, "there no")]));
+
+        eaf.tier_from_overlaps(&["S1", "S2"], "S2 overlaps", &ValueRule::Tier("S2".into())).unwrap();
+        assert_eq!(spans(&eaf, "S2 overlaps"), expected(&[(500, 1000, "yes"), (2000, 2500, "no")]));
+        assert!(eaf.get_tier("overlaps").unwrap().parent_ref.is_none());
+        assert_eq!(eaf.annotations_at(750, Some("overlaps")).len(), 1);
+        assert_valid(&eaf);
+    }
+
+    #[test]
+    fn gaps() {
+        let mut eaf = eaf();
+        let silence = ValueRule::Label("silence".into());
+        // Annotations sharing a boundary leave no gap.
+        eaf.tier_from_gaps(&["S1", "S2"], "gaps", None, &silence).unwrap();
+        assert_eq!(spans(&eaf, "gaps"), expected(&[(0, 100, "silence"), (3000, 4000, "silence")]));
+
+        eaf.tier_from_gaps(&["S1", "S2"], "gaps to end", Some(6000), &silence).unwrap();
+        assert_eq!(spans(&eaf, "gaps to end"), expected(&[(0, 100, "silence"), (3000, 4000, "silence"), (5000, 6000, "silence")]));
+
+        eaf.tier_from_gaps(&["S1"], "S1 gaps", Some(3000), &ValueRule::Concatenate).unwrap();
+        assert_eq!(spans(&eaf, "S1 gaps"), expected(&[(0, 100, ""), (1000, 2000, "")]));
+        assert_valid(&eaf);
+    }
+
+    #[test]
+    fn subtraction() {
+        let mut eaf = eaf();
+        eaf.tier_from_subtraction("S1", &["S2"], "S1 only", &ValueRule::Concatenate).unwrap();
+        assert_eq!(spans(&eaf, "S1 only"), expected(&[(100, 500, "hello"), (2500, 3000, "there")]));
+
+        eaf.tier_from_subtraction("S2", &["S1"], "S2 only", &ValueRule::Label("x".into())).unwrap();
+        assert_eq!(spans(&eaf, "S2 only"), expected(&[(1000, 1500, "x"), (1500, 2000, "x"), (4000, 5000, "x")]));
+        assert_valid(&eaf);
+    }
+
+    #[test]
+    fn merge() {
+        let mut eaf = eaf();
+        eaf.tier_from_merge(&["S1", "S2"], "merged", &ValueRule::Concatenate).unwrap();
+        assert_eq!(spans(&eaf, "merged"), expected(&[(100, 1500, "hello yes"), (1500, 3000, "there no"), (4000, 5000, "bye")]));
+
+        // Adjacent annotations share time slot.
+        let tier = eaf.get_tier("merged").unwrap();
+        let (_, ts2) = tier.annotations[0].ts_ref().unwrap();
+        let (ts1, _) = tier.annotations[1].ts_ref().unwrap();
+        assert_eq!(ts2, ts1);
+        assert_valid(&eaf);
+    }
+
+    #[test]
+    fn invalid() {
+        let mut eaf = eaf();
+        let rule = ValueRule::Concatenate;
+        assert!(matches!(eaf.tier_from_merge(&[], "merged", &rule), Err(EafError::NoData)));
+        assert!(matches!(eaf.tier_from_merge(&["S1", "S2"], "S1", &rule), Err(EafError::TierIdExists(_))));
+        assert!(matches!(eaf.tier_from_merge(&["S1", "S3"], "merged", &rule), Err(EafError::TierIdInvalid(_))));
+        assert!(matches!(
+            eaf.tier_from_overlaps(&["S1"], "merged", &ValueRule::Tier("S2".into())),
+            Err(EafError::TierIdInvalid(_))
+        ));
+        assert_eq!(eaf.tiers.len(), 2);
+    }
+}
@@ -99,8 +99,6 @@ impl CvResource {
 
 // using enum for annotation type
 // see: https://users.rust-lang.org/t/serde-deserializing-a-vector-of-enums/51647
-// TODO errors on optional <DESCRIPTION ...> child element in v2.8 eaf, parallel to entry:
-// /Users/jens/dev/TESTDATA/eaf/2014-10-13_1800_US_CNN_Newsroom_12-493.eaf
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "UPPERCASE")]
 #[serde(rename = "CONTROLLED_VOCABULARY")]
@@ -121,12 +119,12 @@ pub struct ControlledVocabulary {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description_attr: Option<String>,
 
-    /// Description.
+    /// Descriptions, one per language.
     /// Invalid element in EAF <v2.8,
     /// can instead be an attributes in CV header.
-    #[serde(rename="DESCRIPTION")]
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub description_value: Option<Description>,
+    #[serde(rename="DESCRIPTION", default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub descriptions: Vec<Description>,
 
     // Child nodes
     #[serde(rename = "$value")]
@@ -140,7 +138,7 @@ impl Default for ControlledVocabulary {
             cv_id: String::default(),
             ext_ref: None,
             description_attr: None,
-            description_value: None,
+            descriptions: Vec::new(),
             // entry: vec!(CvType::CvEntryMl(CvEntryMl::default()))
             entries: None,
         }
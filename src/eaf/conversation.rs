@@ -0,0 +1,545 @@
+//! Conversation analysis statistics for turn-taking between speakers.
+//!
+//! Each speaker is a participant, with speech in one or more main tiers.
+//! Main tiers without a participant are treated as separate speakers,
+//! named after the tier ID. Annotations by the same speaker that overlap
+//! or are adjacent are merged into continuous speech.
+//!
+//! Speech is then ordered on time and divided into turns:
+//! - A speaker keeps the floor until another speaker starts speaking
+//!   and continues beyond the end of the current turn. Silence between
+//!   speech by the same speaker within a turn is a pause.
+//! - Speech by another speaker that starts and ends within the current turn,
+//!   e.g. backchannels, is an overlap, but not a new turn.
+//! - The floor transfer offset (FTO) is the time between the end of a turn
+//!   and the start of the next, by another speaker. Positive offsets are gaps,
+//!   negative offsets are overlaps.
+//!
+//! Annotations without time values are ignored.
+
+use std::{collections::BTreeMap, fs::File, io::Write, path::Path};
+
+use serde::Serialize;
+
+use crate::{Eaf, EafError};
+
+/// Options for `Eaf::conversation_stats()`.
+#[derive(Debug, Clone)]
+pub struct ConversationOptions {
+    /// IDs for the main tiers to include. All main tiers if empty.
+    pub tier_ids: Vec<String>,
+    /// Ignore annotations with an empty value, or only whitespace.
+    pub ignore_empty: bool,
+    /// Bin size in milliseconds for the floor transfer offset histogram.
+    pub bin_size: i64,
+}
+
+impl Default for ConversationOptions {
+    fn default() -> Self {
+        Self {
+            tier_ids: Vec::new(),
+            ignore_empty: true,
+            bin_size: 100,
+        }
+    }
+}
+
+/// Statistics for a single speaker. Times are in milliseconds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpeakerStats {
+    /// Participant, or tier ID for tiers without a participant.
+    pub speaker: String,
+    /// Main tiers with speech by the speaker, separated by `;`.
+    pub tier_ids: String,
+    /// Number of annotations.
+    pub annotations: usize,
+    /// Total speaking time, with overlapping annotations merged.
+    pub speaking_time: i64,
+    /// Speaking time as a fraction of the total speaking time for all speakers.
+    pub speaking_proportion: f64,
+    /// Number of turns.
+    pub turns: usize,
+    /// Number of pauses within turns.
+    pub pauses: usize,
+    /// Total pause time within turns.
+    pub pause_time: i64,
+    /// Average pause length.
+    pub pause_avr: Option<f64>,
+    /// Number of whitespace-delimited tokens.
+    pub tokens: usize,
+    /// Speech rate as tokens per minute of speaking time.
+    pub tokens_per_minute: Option<f64>,
+    /// Number of times the speaker started speaking while
+    /// another speaker had the floor.
+    pub overlap_initiations: usize,
+}
+
+/// Floor transfer from one speaker to another.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FloorTransfer {
+    /// Speaker ending a turn.
+    pub from: String,
+    /// Speaker starting the next turn.
+    pub to: String,
+    /// Start of the next turn, in milliseconds.
+    pub time: i64,
+    /// Floor transfer offset in milliseconds, i.e. start of the next turn
+    /// minus end of the previous turn.
+    pub offset: i64,
+}
+
+/// Summary of a distribution of time values in milliseconds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Distribution {
+    pub count: usize,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub mean: Option<f64>,
+    pub median: Option<f64>,
+    /// Sample standard deviation.
+    pub std_dev: Option<f64>,
+    /// Histogram as (bin start, count), for non-empty bins in ascending order.
+    pub histogram: Vec<(i64, usize)>,
+}
+
+impl Distribution {
+    fn new(values: &[i64], bin_size: i64) -> Self {
+        let mut sorted = values.to_vec();
+        sorted.sort();
+        let n = sorted.len();
+        if n == 0 {
+            return Self::default()
+        }
+
+        let mean = sorted.iter().sum::<i64>() as f64 / n as f64;
+        let median = match n % 2 {
+            0 => (sorted[n / 2 - 1] + sorted[n / 2]) as f64 / 2.0,
+            _ => sorted[n / 2] as f64,
+        };
+        let std_dev = match n > 1 {
+            true => Some((sorted.iter()
+                .map(|v| (*v as f64 - mean).powi(2))
+                .sum::<f64>() / (n - 1) as f64).sqrt()),
+            false => None,
+        };
+
+        let bin_size = bin_size.max(1);
+        let mut bins: BTreeMap<i64, usize> = BTreeMap::new();
+        for value in sorted.iter() {
+            *bins.entry(value.div_euclid(bin_size) * bin_size).or_default() += 1;
+        }
+
+        Self {
+            count: n,
+            min: sorted.first().copied(),
+            max: sorted.last().copied(),
+            mean: Some(mean),
+            median: Some(median),
+            std_dev,
+            histogram: bins.into_iter().collect(),
+        }
+    }
+}
+
+/// Conversation analysis statistics, see `Eaf::conversation_stats()`.
+/// Times are in milliseconds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConversationStats {
+    /// Time from the start of the first annotation
+    /// to the end of the last annotation.
+    pub duration: i64,
+    /// Time with at least one speaker speaking.
+    pub speech_time: i64,
+    /// Time with two or more speakers speaking simultaneously.
+    pub overlap_time: i64,
+    /// Time without speech, within `duration`.
+    pub silence_time: i64,
+    /// Statistics per speaker.
+    pub speakers: Vec<SpeakerStats>,
+    /// Floor transfers between consecutive speakers, in time order.
+    pub transfers: Vec<FloorTransfer>,
+    /// Number of floor transfers with a gap (positive offset).
+    pub gaps: usize,
+    /// Number of floor transfers with an overlap (negative offset).
+    pub overlaps: usize,
+    /// Distribution of floor transfer offsets.
+    pub offsets: Distribution,
+    /// Distribution of gaps between speakers.
+    pub gap_lengths: Distribution,
+    /// Distribution of overlaps between speakers, as positive values.
+    pub overlap_lengths: Distribution,
+}
+
+impl ConversationStats {
+    /// Serializes statistics to JSON.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&self)
+    }
+
+    /// Returns speaker statistics as CSV, one row per speaker.
+    pub fn speakers_to_csv(&self) -> Result<String, EafError> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for speaker in self.speakers.iter() {
+            writer.serialize(speaker)?;
+        }
+        let bytes = writer.into_inner().map_err(|e| EafError::IOError(e.into_error()))?;
+        Ok(String::from_utf8_lossy(&bytes).to_string())
+    }
+
+    /// Returns floor transfers as CSV, one row per transfer.
+    pub fn transfers_to_csv(&self) -> Result<String, EafError> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for transfer in self.transfers.iter() {
+            writer.serialize(transfer)?;
+        }
+        let bytes = writer.into_inner().map_err(|e| EafError::IOError(e.into_error()))?;
+        Ok(String::from_utf8_lossy(&bytes).to_string())
+    }
+
+    /// Writes speaker statistics as CSV to disk.
+    /// See `ConversationStats::speakers_to_csv()`.
+    pub fn write_speakers_csv(&self, path: &Path) -> Result<(), EafError> {
+        let mut outfile = File::create(path)?;
+        outfile.write_all(self.speakers_to_csv()?.as_bytes()).map_err(EafError::IOError)
+    }
+
+    /// Writes floor transfers as CSV to disk.
+    /// See `ConversationStats::transfers_to_csv()`.
+    pub fn write_transfers_csv(&self, path: &Path) -> Result<(), EafError> {
+        let mut outfile = File::create(path)?;
+        outfile.write_all(self.transfers_to_csv()?.as_bytes()).map_err(EafError::IOError)
+    }
+}
+
+/// Speech by a single speaker.
+struct Speaker {
+    name: String,
+    tier_ids: Vec<String>,
+    annotations: usize,
+    tokens: usize,
+    /// Merged time spans, sorted on time.
+    spans: Vec<(i64, i64)>,
+}
+
+/// Returns speakers for the main tiers in `tier_ids`, grouped on participant.
+fn speakers(eaf: &Eaf, tier_ids: &[String], ignore_empty: bool) -> Result<Vec<Speaker>, EafError> {
+    let tiers = match tier_ids.is_empty() {
+        true => eaf.main_tiers().collect::<Vec<_>>(),
+        false => tier_ids.iter()
+            .map(|id| eaf.get_tier(id)
+                .filter(|t| t.parent_ref.is_none())
+                .ok_or_else(|| EafError::TierIdInvalid(id.to_owned())))
+            .collect::<Result<Vec<_>, EafError>>()?,
+    };
+
+    let mut speakers: Vec<Speaker> = Vec::new();
+    for tier in tiers.iter() {
+        let name = tier.participant.as_deref()
+            .filter(|p| !p.trim().is_empty())
+            .unwrap_or(&tier.tier_id);
+        let idx = match speakers.iter().position(|s| s.name == name) {
+            Some(idx) => idx,
+            None => {
+                speakers.push(Speaker {
+                    name: name.to_owned(),
+                    tier_ids: Vec::new(),
+                    annotations: 0,
+                    tokens: 0,
+                    spans: Vec::new(),
+                });
+                speakers.len() - 1
+            }
+        };
+        let speaker = &mut speakers[idx];
+        speaker.tier_ids.push(tier.tier_id.to_owned());
+
+        for annotation in tier.iter() {
+            if ignore_empty && annotation.to_str().trim().is_empty() {
+                continue
+            }
+            if let (Some(start), Some(end)) = annotation.ts_val()
+                && start < end
+            {
+                speaker.annotations += 1;
+                speaker.tokens += annotation.len();
+                speaker.spans.push((start, end));
+            }
+        }
+    }
+
+    for speaker in speakers.iter_mut() {
+        speaker.spans.sort();
+        let mut merged: Vec<(i64, i64)> = Vec::new();
+        for (start, end) in speaker.spans.iter() {
+            match merged.last_mut() {
+                Some(last) if *start <= last.1 => last.1 = last.1.max(*end),
+                _ => merged.push((*start, *end)),
+            }
+        }
+        speaker.spans = merged;
+    }
+
+    Ok(speakers)
+}
+
+/// Returns (speech time, overlap time) for `spans`, i.e. the time
+/// with at least one, and at least two speakers speaking.
+fn coverage(spans: &[(i64, i64, usize)]) -> (i64, i64) {
+    // (time, +1 for start, -1 for end), ends first at the same time
+    let mut events: Vec<(i64, i32)> = spans.iter()
+        .flat_map(|(start, end, _)| [(*start, 1), (*end, -1)])
+        .collect();
+    events.sort();
+
+    let (mut speech, mut overlap) = (0, 0);
+    let mut active = 0;
+    let mut previous = i64::MIN;
+    for (time, change) in events.into_iter() {
+        if active >= 1 {
+            speech += time - previous;
+        }
+        if active >= 2 {
+            overlap += time - previous;
+        }
+        active += change;
+        previous = time;
+    }
+
+    (speech, overlap)
+}
+
+/// See `Eaf::conversation_stats()`.
+pub(crate) fn conversation_stats(eaf: &Eaf, options: &ConversationOptions) -> Result<ConversationStats, EafError> {
+    let speakers = speakers(eaf, &options.tier_ids, options.ignore_empty)?;
+
+    // (start, end, speaker index) for all speakers, sorted on time.
+    let mut spans: Vec<(i64, i64, usize)> = speakers.iter()
+        .enumerate()
+        .flat_map(|(i, s)| s.spans.iter().map(move |(start, end)| (*start, *end, i)))
+        .collect();
+    spans.sort();
+
+    let mut turns = vec![0; speakers.len()];
+    let mut pauses: Vec<Vec<i64>> = vec![Vec::new(); speakers.len()];
+    let mut initiations = vec![0; speakers.len()];
+    let mut transfers: Vec<FloorTransfer> = Vec::new();
+
+    // (speaker index, end of turn) for the speaker with the floor.
+    let mut floor: Option<(usize, i64)> = None;
+    for (start, end, speaker) in spans.iter().copied() {
+        match floor {
+            Some((current, turn_end)) if current == speaker => {
+                if start > turn_end {
+                    pauses[speaker].push(start - turn_end);
+                }
+                floor = Some((current, turn_end.max(end)));
+            },
+            // Overlap within the current turn, e.g. a backchannel
+            Some((_, turn_end)) if end <= turn_end => initiations[speaker] += 1,
+            Some((current, turn_end)) => {
+                if start < turn_end {
+                    initiations[speaker] += 1;
+                }
+                transfers.push(FloorTransfer {
+                    from: speakers[current].name.to_owned(),
+                    to: speakers[speaker].name.to_owned(),
+                    time: start,
+                    offset: start - turn_end,
+                });
+                turns[speaker] += 1;
+                floor = Some((speaker, end));
+            },
+            None => {
+                turns[speaker] += 1;
+                floor = Some((speaker, end));
+            }
+        }
+    }
+
+    let speaking_times: Vec<i64> = speakers.iter()
+        .map(|s| s.spans.iter().map(|(start, end)| end - start).sum())
+        .collect();
+    let total: i64 = speaking_times.iter().sum();
+
+    let speaker_stats: Vec<SpeakerStats> = speakers.iter()
+        .enumerate()
+        .map(|(i, speaker)| {
+            let pause_time: i64 = pauses[i].iter().sum();
+            SpeakerStats {
+                speaker: speaker.name.to_owned(),
+                tier_ids: speaker.tier_ids.join(";"),
+                annotations: speaker.annotations,
+                speaking_time: speaking_times[i],
+                speaking_proportion: match total > 0 {
+                    true => speaking_times[i] as f64 / total as f64,
+                    false => 0.0,
+                },
+                turns: turns[i],
+                pauses: pauses[i].len(),
+                pause_time,
+                pause_avr: match pauses[i].is_empty() {
+                    true => None,
+                    false => Some(pause_time as f64 / pauses[i].len() as f64),
+                },
+                tokens: speaker.tokens,
+                tokens_per_minute: match speaking_times[i] > 0 {
+                    true => Some(speaker.tokens as f64 * 60_000.0 / speaking_times[i] as f64),
+                    false => None,
+                },
+                overlap_initiations: initiations[i],
+            }
+        })
+        .collect();
+
+    let duration = match (spans.first(), spans.iter().map(|(_, end, _)| *end).max()) {
+        (Some((start, _, _)), Some(end)) => end - start,
+        _ => 0,
+    };
+    let (speech_time, overlap_time) = coverage(&spans);
+
+    let offsets: Vec<i64> = transfers.iter().map(|t| t.offset).collect();
+    let gaps: Vec<i64> = offsets.iter().copied().filter(|o| *o > 0).collect();
+    let overlaps: Vec<i64> = offsets.iter().filter(|o| **o < 0).map(|o| -o).collect();
+
+    Ok(ConversationStats {
+        duration,
+        speech_time,
+        overlap_time,
+        silence_time: duration - speech_time,
+        speakers: speaker_stats,
+        gaps: gaps.len(),
+        overlaps: overlaps.len(),
+        offsets: Distribution::new(&offsets, options.bin_size),
+        gap_lengths: Distribution::new(&gaps, options.bin_size),
+        overlap_lengths: Distribution::new(&overlaps, options.bin_size),
+        transfers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eaf::test_util::{TestEaf, aligned, assert_approx, referred};
+
+    fn eaf() -> Eaf {
+        TestEaf::new()
+            .time_slots(&[0, 1000, 1500, 2500, 2700, 3500, 3300, 4500, 3600, 3800, 5000, 6000])
+            .tier("A1", "default-lt", None, &[
+                aligned("a1", "ts1", "ts2", "hello there"),
+                aligned("a2", "ts3", "ts4", "how are you"),
+            ])
+            .participant("Ann")
+            .tier("B1", "default-lt", None, &[aligned("a3", "ts5", "ts6", "fine")])
+            .participant("Bob")
+            .tier("A2", "default-lt", None, &[aligned("a4", "ts7", "ts8", "good")])
+            .participant("Ann")
+            .tier("C", "default-lt", None, &[
+                aligned("a5", "ts9", "ts10", "mm"),
+                aligned("a6", "ts11", "ts12", " "),
+            ])
+            .linguistic_type("gloss", Some("Symbolic_Association"))
+            .tier("G", "gloss", Some("C"), &[referred("a7", "a5", "gloss")])
+            .eaf()
+    }
+
+    #[test]
+    fn distribution() {
+        let distribution = Distribution::new(&[4, 1, 3, 2], 2);
+        assert_eq!(distribution.count, 4);
+        assert_eq!((distribution.min, distribution.max), (Some(1), Some(4)));
+        assert_approx(distribution.mean, 2.5);
+        assert_approx(distribution.median, 2.5);
+        assert_approx(distribution.std_dev, (5.0f64 / 3.0).sqrt());
+        assert_eq!(distribution.histogram, [(0, 1), (2, 2), (4, 1)]);
+
+        let distribution = Distribution::new(&[-150, 50], 100);
+        assert_eq!(distribution.histogram, [(-200, 1), (0, 1)]);
+        assert_eq!(Distribution::new(&[5], 100).std_dev, None);
+        assert_eq!(Distribution::new(&[], 100), Distribution::default());
+    }
+
+    #[test]
+    fn coverage_overlaps() {
+        assert_eq!(coverage(&[(0, 100, 0), (50, 150, 1), (150, 200, 0)]), (200, 50));
+        assert_eq!(coverage(&[(0, 100, 0), (200, 300, 1)]), (200, 0));
+        assert_eq!(coverage(&[]), (0, 0));
+    }
+
+    #[test]
+    fn turns() {
+        let stats = eaf().conversation_stats(&ConversationOptions::default()).unwrap();
+
+        let speakers: Vec<(&str, &str)> = stats.speakers.iter()
+            .map(|s| (s.speaker.as_str(), s.tier_ids.as_str()))
+            .collect();
+        assert_eq!(speakers, [("Ann", "A1;A2"), ("Bob", "B1"), ("C", "C")]);
+
+        let ann = &stats.speakers[0];
+        assert_eq!((ann.annotations, ann.speaking_time, ann.turns), (3, 3200, 2));
+        assert_eq!((ann.pauses, ann.pause_time, ann.pause_avr), (1, 500, Some(500.0)));
+        assert_eq!((ann.tokens, ann.overlap_initiations), (6, 1));
+        assert_approx(ann.tokens_per_minute, 112.5);
+        assert_approx(Some(ann.speaking_proportion), 3200.0 / 4200.0);
+
+        let bob = &stats.speakers[1];
+        assert_eq!((bob.turns, bob.pauses, bob.overlap_initiations), (1, 0, 0));
+        // Backchannel within Ann's turn, empty annotation ignored.
+        let c = &stats.speakers[2];
+        assert_eq!((c.annotations, c.speaking_time, c.turns, c.overlap_initiations), (1, 200, 0, 1));
+
+        assert_eq!(stats.transfers, [
+            FloorTransfer { from: "Ann".into(), to: "Bob".into(), time: 2700, offset: 200 },
+            FloorTransfer { from: "Bob".into(), to: "Ann".into(), time: 3300, offset: -200 },
+        ]);
+        assert_eq!((stats.gaps, stats.overlaps), (1, 1));
+        assert_eq!(stats.duration, 4500);
+        assert_eq!(stats.speech_time, 3800);
+        assert_eq!(stats.overlap_time, 400);
+        assert_eq!(stats.silence_time, 700);
+        assert_approx(stats.offsets.mean, 0.0);
+        assert_approx(stats.offsets.std_dev, 80_000f64.sqrt());
+        assert_eq!(stats.offsets.histogram, [(-200, 1), (200, 1)]);
+        assert_eq!(stats.gap_lengths.max, Some(200));
+        assert_eq!(stats.overlap_lengths.min, Some(200));
+    }
+
+    #[test]
+    fn options() {
+        let eaf = eaf();
+        let options = ConversationOptions { ignore_empty: false, ..ConversationOptions::default() };
+        let stats = eaf.conversation_stats(&options).unwrap();
+        assert_eq!(stats.speakers[2].annotations, 2);
+        assert_eq!(stats.duration, 6000);
+        assert_eq!(stats.silence_time, 1200);
+
+        let options = ConversationOptions { tier_ids: vec!["A1".into(), "B1".into()], ..ConversationOptions::default() };
+        let stats = eaf.conversation_stats(&options).unwrap();
+        assert_eq!(stats.speakers.len(), 2);
+        assert_eq!(stats.transfers.len(), 1);
+        assert_eq!(stats.speakers[0].speaking_time, 2000);
+
+        for tier_id in ["X", "G"] {
+            let options = ConversationOptions { tier_ids: vec![tier_id.into()], ..ConversationOptions::default() };
+            assert!(matches!(eaf.conversation_stats(&options), Err(EafError::TierIdInvalid(_))));
+        }
+    }
+
+    #[test]
+    fn serialize() {
+        let stats = eaf().conversation_stats(&ConversationOptions::default()).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json().unwrap()).unwrap();
+        assert_eq!(json["duration"], 4500);
+        assert_eq!(json["speakers"][1]["speaker"], "Bob");
+        assert_eq!(json["transfers"][1]["offset"], -200);
+
+        let csv = stats.speakers_to_csv().unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("speaker,tier_ids,annotations,speaking_time,"));
+        assert!(lines[1].starts_with("Ann,A1;A2,3,3200,"));
+
+        let csv = stats.transfers_to_csv().unwrap();
+        assert_eq!(csv, "from,to,time,offset\nAnn,Bob,2700,200\nBob,Ann,3300,-200\n");
+    }
+}
@@ -0,0 +1,364 @@
+//! A corpus of ELAN-files, loaded in parallel from a directory.
+//!
+//! Files that fail to parse are kept together with the error,
+//! so that a corpus can be loaded and inspected even if some files are broken.
+//! Query results are tagged with the path to the source file.
+//!
+//! Example:
+//! ```ignore
+//! use eaf_rs::Corpus;
+//! let corpus = Corpus::read(Path::new("my_corpus"))?;
+//! for (path, err) in corpus.failed.iter() {
+//!     println!("Failed to read {}: {err}", path.display());
+//! }
+//! for hit in corpus.query("hello", true) {
+//!     let (index, tier_id, annotation) = hit.hit;
+//!     println!("{} {tier_id}:{index} {}", hit.path.display(), annotation.to_str());
+//! }
+//! ```
+
+use std::{collections::{BTreeMap, BTreeSet, HashMap, HashSet}, path::{Path, PathBuf}};
+
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use regex::Regex;
+
+use crate::{Annotation, Eaf, EafError, QueryResult, Scope, Search, SearchHit};
+
+/// Query result tagged with the path to the source file.
+#[derive(Debug, Clone)]
+pub struct CorpusHit<'a, T> {
+    pub path: &'a Path,
+    /// Result for a single file, e.g. a `QueryResult`.
+    pub hit: T,
+}
+
+/// Annotation and tier counts for a single file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileStats<'a> {
+    pub path: &'a Path,
+    /// Number of tiers.
+    pub t_len: usize,
+    /// Number of annotations.
+    pub a_len: usize,
+}
+
+/// Summary for all tiers in the corpus with the same tier ID.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TierSummary<'a> {
+    /// Files that contain the tier.
+    pub files: Vec<&'a Path>,
+    /// Total number of annotations.
+    pub a_len: usize,
+    /// Linguistic types used for the tier.
+    pub linguistic_types: BTreeSet<&'a str>,
+    /// Participants set for the tier.
+    pub participants: BTreeSet<&'a str>,
+}
+
+/// A corpus of ELAN-files.
+#[derive(Debug, Default)]
+pub struct Corpus {
+    /// Successfully parsed files, sorted on path.
+    pub files: Vec<(PathBuf, Eaf)>,
+    /// Files that failed to parse, sorted on path.
+    pub failed: Vec<(PathBuf, EafError)>,
+}
+
+/// Recursively finds all files with the extension `.eaf` (case insensitive) in `dir`.
+/// Symbolic links are followed, but each directory is only visited once,
+/// so that symbolic link cycles do not cause infinite recursion.
+fn eaf_paths(dir: &Path) -> Result<Vec<PathBuf>, EafError> {
+    let mut visited: HashSet<PathBuf> = HashSet::new();
+    eaf_paths_visited(dir, &mut visited)
+}
+
+/// See `eaf_paths()`. `visited` contains canonical paths
+/// for directories already visited.
+fn eaf_paths_visited(dir: &Path, visited: &mut HashSet<PathBuf>) -> Result<Vec<PathBuf>, EafError> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    if !visited.insert(dir.canonicalize()?) {
+        return Ok(paths)
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            paths.extend(eaf_paths_visited(&path, visited)?);
+        } else if path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase() == "eaf")
+            .unwrap_or(false)
+        {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+impl Corpus {
+    /// Recursively reads all ELAN-files in `dir` in parallel.
+    /// Files that fail to parse are stored in `Corpus::failed`.
+    ///
+    /// Returns error if `dir` is not a directory or can not be read.
+    pub fn read(dir: &Path) -> Result<Self, EafError> {
+        if !dir.is_dir() {
+            return Err(EafError::PathInvalid(dir.display().to_string()))
+        }
+        Ok(Self::from_paths(&eaf_paths(dir)?))
+    }
+
+    /// Reads the specified ELAN-files in parallel.
+    /// Files that fail to parse are stored in `Corpus::failed`.
+    pub fn from_paths(paths: &[PathBuf]) -> Self {
+        let (mut files, mut failed): (Vec<_>, Vec<_>) = paths.par_iter()
+            .map(|p| (p.to_owned(), Eaf::read(p)))
+            .partition_map(|(p, result)| match result {
+                Ok(eaf) => rayon::iter::Either::Left((p, eaf)),
+                Err(err) => rayon::iter::Either::Right((p, err)),
+            });
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        failed.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Self { files, failed }
+    }
+
+    /// Number of successfully parsed files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Iterates over successfully parsed files.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &Eaf)> {
+        self.files.iter().map(|(p, e)| (p.as_path(), e))
+    }
+
+    /// Returns parsed file with specified path.
+    pub fn get(&self, path: &Path) -> Option<&Eaf> {
+        self.files.iter()
+            .find(|(p, _)| p == path)
+            .map(|(_, e)| e)
+    }
+
+    /// Runs `f` on all files in parallel, and tags each result
+    /// with the path to the file.
+    fn collect<'a, T, F>(&'a self, f: F) -> Vec<CorpusHit<'a, T>>
+    where
+        T: Send,
+        F: Fn(&'a Eaf) -> Vec<T> + Sync,
+    {
+        self.files.par_iter()
+            .flat_map(|(path, eaf)| f(eaf).into_iter()
+                .map(|hit| CorpusHit { path: path.as_path(), hit })
+                .collect::<Vec<_>>()
+            )
+            .collect()
+    }
+
+    /// Matches annotation values against a pattern in all files.
+    /// See `Eaf::query()`.
+    pub fn query(
+        &self,
+        pattern: &str,
+        ignore_case: bool
+    ) -> Vec<CorpusHit<'_, (usize, &str, &Annotation)>> {
+        self.collect(|eaf| eaf.query(pattern, ignore_case))
+    }
+
+    /// Matches annotation values against a regular expression in all files.
+    /// See `Eaf::query_rx()`.
+    pub fn query_rx(&self, regex: &Regex) -> Vec<CorpusHit<'_, QueryResult<'_>>> {
+        self.collect(|eaf| eaf.query_rx(regex))
+    }
+
+    /// Structured, multi-tier search in all files.
+    /// See `Eaf::search()`.
+    pub fn search(&self, search: &Search) -> Result<Vec<CorpusHit<'_, SearchHit<'_>>>, EafError> {
+        self.files.iter()
+            .map(|(path, eaf)| Ok(eaf.search(search)?
+                .into_iter()
+                .map(|hit| CorpusHit { path: path.as_path(), hit })
+                .collect::<Vec<_>>()
+            ))
+            .collect::<Result<Vec<_>, EafError>>()
+            .map(|hits| hits.into_iter().flatten().collect())
+    }
+
+    /// Returns all words/tokens in the corpus, sorted.
+    /// See `Eaf::tokens()`.
+    pub fn tokens(
+        &self,
+        strip_prefix: Option<&str>,
+        strip_suffix: Option<&str>,
+        unique: bool,
+        ignore_case: bool,
+    ) -> Vec<String> {
+        let mut tokens: Vec<String> = self.files.par_iter()
+            .flat_map(|(_, eaf)| eaf.tokens(strip_prefix, strip_suffix, unique, ignore_case))
+            .collect();
+
+        tokens.sort();
+
+        if unique {
+            tokens.dedup();
+        }
+
+        tokens
+    }
+
+    /// Compiles ngrams for each file and sums the counts.
+    /// Ngrams never cross file boundaries.
+    /// See `Eaf::ngram()`.
+    pub fn ngram(&self, size: usize, regex_remove: Option<&Regex>, scope: Scope) -> HashMap<String, usize> {
+        self.files.par_iter()
+            .map(|(_, eaf)| eaf.ngram(size, regex_remove, scope.to_owned()))
+            .reduce(HashMap::new, |mut a, b| {
+                for (ngram, count) in b {
+                    *a.entry(ngram).or_insert(0) += count;
+                }
+                a
+            })
+    }
+
+    /// Total number of annotations in the corpus.
+    pub fn a_len(&self) -> usize {
+        self.files.iter().map(|(_, e)| e.a_len()).sum()
+    }
+
+    /// Total number of tiers in the corpus.
+    pub fn t_len(&self) -> usize {
+        self.files.iter().map(|(_, e)| e.t_len()).sum()
+    }
+
+    /// Average number of annotations per file.
+    pub fn a_avr_len(&self) -> f64 {
+        match self.len() {
+            0 => 0.,
+            n => self.a_len() as f64 / n as f64
+        }
+    }
+
+    /// Average number of tiers per file.
+    pub fn t_avr_len(&self) -> f64 {
+        match self.len() {
+            0 => 0.,
+            n => self.t_len() as f64 / n as f64
+        }
+    }
+
+    /// Returns annotation and tier counts for each file.
+    pub fn stats(&self) -> Vec<FileStats<'_>> {
+        self.files.iter()
+            .map(|(path, eaf)| FileStats {
+                path,
+                t_len: eaf.t_len(),
+                a_len: eaf.a_len(),
+            })
+            .collect()
+    }
+
+    /// Returns tier inventory for the corpus, i.e. a summary for each
+    /// unique tier ID, with the files it occurs in, total number of
+    /// annotations, and the linguistic types and participants used.
+    pub fn tiers(&self) -> BTreeMap<&str, TierSummary<'_>> {
+        let mut inventory: BTreeMap<&str, TierSummary> = BTreeMap::new();
+        for (path, eaf) in self.files.iter() {
+            for tier in eaf.tiers.iter() {
+                let summary = inventory.entry(tier.tier_id.as_str()).or_default();
+                summary.files.push(path);
+                summary.a_len += tier.len();
+                summary.linguistic_types.insert(&tier.linguistic_type_ref);
+                if let Some(p) = tier.participant.as_deref().filter(|p| !p.is_empty()) {
+                    summary.participants.insert(p);
+                }
+            }
+        }
+        inventory
+    }
+
+    /// Returns all unique tier IDs in the corpus, sorted.
+    pub fn tier_ids(&self) -> Vec<&str> {
+        self.tiers().into_keys().collect()
+    }
+
+    /// Returns all unique participants in the corpus, sorted.
+    pub fn participants(&self) -> Vec<&str> {
+        self.files.iter()
+            .flat_map(|(_, e)| e.tiers.iter().filter_map(|t| t.participant.as_deref()))
+            .filter(|p| !p.is_empty())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eaf::test_util::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_cycle() {
+        let tmp = TempDir::new();
+        let dir = tmp.path();
+        let sub = dir.join("sub");
+        std::fs::create_dir(&sub).unwrap();
+        std::os::unix::fs::symlink(dir, sub.join("loop")).unwrap();
+        Eaf::from_values(&[("a".to_owned(), 0, 100)], None).unwrap()
+            .write(&sub.join("one.eaf"), None).unwrap();
+
+        let paths = eaf_paths(dir).unwrap();
+        assert_eq!(paths, vec![sub.join("one.eaf")]);
+    }
+
+    #[test]
+    fn read_and_query() {
+        let tmp = TempDir::new();
+        let dir = tmp.path();
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        let mut eaf1 = Eaf::from_values(&[
+            ("hello world".to_owned(), 0, 100),
+            ("bye".to_owned(), 100, 200),
+        ], Some("tx")).unwrap();
+        eaf1.get_tier_mut("tx").unwrap().participant = Some("Anna".to_owned());
+        eaf1.write(&dir.join("one.eaf"), None).unwrap();
+        let eaf2 = Eaf::from_values_multi(&[
+            ("Hello".to_owned(), 0, 100, "tx".to_owned()),
+            ("x".to_owned(), 0, 100, "other".to_owned()),
+        ]).unwrap();
+        eaf2.write(&dir.join("sub").join("two.EAF"), None).unwrap();
+        std::fs::write(dir.join("broken.eaf"), "<ANNOTATION_DOCUMENT>").unwrap();
+        std::fs::write(dir.join("notes.txt"), "hello").unwrap();
+
+        let corpus = Corpus::read(dir).unwrap();
+        assert_eq!(corpus.len(), 2);
+        assert_eq!(corpus.failed.len(), 1);
+        assert_eq!(corpus.failed[0].0, dir.join("broken.eaf"));
+        assert!(corpus.get(&dir.join("one.eaf")).is_some());
+
+        let mut hits: Vec<(&Path, usize, &str)> = corpus.query("hello", true).into_iter()
+            .map(|h| (h.path, h.hit.0, h.hit.2.to_str()))
+            .collect();
+        hits.sort();
+        assert_eq!(hits, vec![
+            (dir.join("one.eaf").as_path(), 1, "hello world"),
+            (dir.join("sub").join("two.EAF").as_path(), 1, "Hello"),
+        ]);
+        assert_eq!(corpus.query_rx(&Regex::new("^b").unwrap()).len(), 1);
+
+        assert_eq!(corpus.tokens(None, None, true, true), vec!["bye", "hello", "world", "x"]);
+        assert_eq!((corpus.a_len(), corpus.t_len()), (4, 3));
+        assert_eq!(corpus.a_avr_len(), 2.);
+
+        let tiers = corpus.tiers();
+        assert_eq!(corpus.tier_ids(), vec!["other", "tx"]);
+        assert_eq!(tiers["tx"].files.len(), 2);
+        assert_eq!(tiers["tx"].a_len, 3);
+        assert_eq!(corpus.participants(), vec!["Anna"]);
+
+        assert!(Corpus::read(&dir.join("notes.txt")).is_err());
+    }
+}
@@ -0,0 +1,644 @@
+//! Semantic diff between two EAF documents.
+//!
+//! Annotation IDs are often renumbered when an EAF is edited and saved,
+//! so a textual XML diff is of little use for reviewing annotation changes.
+//! Instead, tiers are matched on tier ID, and annotations on time span and value:
+//! 1. Identical annotations, i.e. same value and time span (`Annotation::is_identical()`),
+//!    are unchanged.
+//! 2. Annotations with the same time span but a different value are relabelled.
+//! 3. Overlapping annotations (`Annotation::overlap()`) with the same value are retimed,
+//!    largest overlap first.
+//! 4. Overlapping annotations with a different value, where the overlap is at least
+//!    half of their combined extent, are both retimed and relabelled.
+//!
+//! Remaining annotations are added or removed. Annotations without any time values
+//! are only matched if identical, and partially aligned ones only on time span.
+//!
+//! Changes to tier attributes, linguistic types and controlled vocabularies
+//! are listed per attribute, with controlled vocabulary entries compared on value.
+
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{Annotation, ControlledVocabulary, CvType, Eaf, EafError, LinguisticType, Overlap, Tier};
+
+use super::computed::intersection;
+
+/// Whether an item was added, removed or changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiffStatus {
+    /// Only in the other `Eaf`.
+    Added,
+    /// Only in the original `Eaf`.
+    Removed,
+    /// In both, with changes.
+    Changed,
+}
+
+impl Display for DiffStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiffStatus::Added => write!(f, "added"),
+            DiffStatus::Removed => write!(f, "removed"),
+            DiffStatus::Changed => write!(f, "changed"),
+        }
+    }
+}
+
+/// Changed attribute, with `None` for unset attributes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AttributeChange {
+    /// Attribute name as in the EAF, e.g. `PARTICIPANT`.
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl AttributeChange {
+    fn new(name: &str, old: Option<&str>, new: Option<&str>) -> Option<Self> {
+        (old != new).then(|| Self {
+            name: name.to_owned(),
+            old: old.map(String::from),
+            new: new.map(String::from),
+        })
+    }
+}
+
+impl Display for AttributeChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = |v: &Option<String>| match v {
+            Some(v) => format!("'{v}'"),
+            None => "(none)".to_owned(),
+        };
+        write!(f, "{}: {} -> {}", self.name, value(&self.old), value(&self.new))
+    }
+}
+
+/// Annotation in a diff.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DiffAnnotation {
+    pub id: String,
+    pub value: String,
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+}
+
+impl From<&Annotation> for DiffAnnotation {
+    fn from(annotation: &Annotation) -> Self {
+        let (start, end) = annotation.ts_val();
+        Self {
+            id: annotation.id().to_owned(),
+            value: annotation.to_str().to_owned(),
+            start,
+            end,
+        }
+    }
+}
+
+impl Display for DiffAnnotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = |t: Option<i64>| t.map(|t| t.to_string()).unwrap_or_else(|| "?".to_owned());
+        write!(f, "{} [{}-{}] '{}'", self.id, time(self.start), time(self.end), self.value)
+    }
+}
+
+/// Annotation change between the original and the other `Eaf`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnnotationDiff {
+    /// Annotation only in the other `Eaf`.
+    Added(DiffAnnotation),
+    /// Annotation only in the original `Eaf`.
+    Removed(DiffAnnotation),
+    /// Same value, different time span. `overlap` is that of
+    /// the original annotation relative to the new one.
+    Retimed {
+        old: DiffAnnotation,
+        new: DiffAnnotation,
+        overlap: Option<Overlap>,
+    },
+    /// Same time span, different value.
+    Relabelled {
+        old: DiffAnnotation,
+        new: DiffAnnotation,
+    },
+    /// Different time span and value.
+    RetimedRelabelled {
+        old: DiffAnnotation,
+        new: DiffAnnotation,
+        overlap: Option<Overlap>,
+    },
+}
+
+impl AnnotationDiff {
+    /// Returns the original annotation, if any.
+    pub fn old_annotation(&self) -> Option<&DiffAnnotation> {
+        match self {
+            Self::Added(_) => None,
+            Self::Removed(old)
+            | Self::Retimed { old, .. }
+            | Self::Relabelled { old, .. }
+            | Self::RetimedRelabelled { old, .. } => Some(old),
+        }
+    }
+
+    /// Returns the annotation in the other `Eaf`, if any.
+    pub fn new_annotation(&self) -> Option<&DiffAnnotation> {
+        match self {
+            Self::Removed(_) => None,
+            Self::Added(new)
+            | Self::Retimed { new, .. }
+            | Self::Relabelled { new, .. }
+            | Self::RetimedRelabelled { new, .. } => Some(new),
+        }
+    }
+
+    fn start(&self) -> Option<i64> {
+        self.old_annotation().and_then(|a| a.start)
+            .or_else(|| self.new_annotation().and_then(|a| a.start))
+    }
+}
+
+impl Display for AnnotationDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Added(new) => write!(f, "+ {new}"),
+            Self::Removed(old) => write!(f, "- {old}"),
+            Self::Retimed { old, new, .. } => write!(f, "~ retimed: {old} -> {new}"),
+            Self::Relabelled { old, new } => write!(f, "~ relabelled: {old} -> {new}"),
+            Self::RetimedRelabelled { old, new, .. } => write!(f, "~ retimed, relabelled: {old} -> {new}"),
+        }
+    }
+}
+
+/// Differences for a tier.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TierDiff {
+    pub tier_id: String,
+    pub status: DiffStatus,
+    /// Changed tier attributes.
+    pub attributes: Vec<AttributeChange>,
+    /// Changed annotations, sorted on time. For added or removed tiers,
+    /// all annotations are listed as added or removed.
+    pub annotations: Vec<AnnotationDiff>,
+}
+
+impl Display for TierDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Tier '{}' ({})", self.tier_id, self.status)?;
+        for attribute in self.attributes.iter() {
+            writeln!(f, "  {attribute}")?;
+        }
+        for annotation in self.annotations.iter() {
+            writeln!(f, "  {annotation}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Differences for a linguistic type or a controlled vocabulary.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ItemDiff {
+    pub id: String,
+    pub status: DiffStatus,
+    /// Changed attributes. Controlled vocabulary entries are listed
+    /// with name `CV_ENTRY`, without an old value if added,
+    /// and without a new value if removed.
+    pub attributes: Vec<AttributeChange>,
+}
+
+/// Semantic diff between two EAF documents, see `Eaf::diff()`.
+/// Only items with changes are included.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EafDiff {
+    pub tiers: Vec<TierDiff>,
+    pub linguistic_types: Vec<ItemDiff>,
+    pub controlled_vocabularies: Vec<ItemDiff>,
+}
+
+impl EafDiff {
+    /// Returns `true` if no differences were found.
+    pub fn is_empty(&self) -> bool {
+        self.tiers.is_empty()
+            && self.linguistic_types.is_empty()
+            && self.controlled_vocabularies.is_empty()
+    }
+
+    /// Returns all annotation changes as (tier ID, change).
+    pub fn annotations(&self) -> impl Iterator<Item = (&str, &AnnotationDiff)> {
+        self.tiers.iter()
+            .flat_map(|t| t.annotations.iter().map(|a| (t.tier_id.as_str(), a)))
+    }
+}
+
+impl Display for EafDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for tier in self.tiers.iter() {
+            write!(f, "{tier}")?;
+        }
+        for (kind, items) in [("Linguistic type", &self.linguistic_types), ("Controlled vocabulary", &self.controlled_vocabularies)] {
+            for item in items.iter() {
+                writeln!(f, "{kind} '{}' ({})", item.id, item.status)?;
+                for attribute in item.attributes.iter() {
+                    writeln!(f, "  {attribute}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns changed tier attributes.
+fn tier_attributes(old: &Tier, new: &Tier) -> Vec<AttributeChange> {
+    [
+        ("PARTICIPANT", old.participant.as_deref(), new.participant.as_deref()),
+        ("ANNOTATOR", old.annotator.as_deref(), new.annotator.as_deref()),
+        ("LINGUISTIC_TYPE_REF", Some(old.linguistic_type_ref.as_str()), Some(new.linguistic_type_ref.as_str())),
+        ("DEFAULT_LOCALE", old.default_locale.as_deref(), new.default_locale.as_deref()),
+        ("PARENT_REF", old.parent_ref.as_deref(), new.parent_ref.as_deref()),
+        ("EXT_REF", old.ext_ref.as_deref(), new.ext_ref.as_deref()),
+        ("LANG_REF", old.lang_ref.as_deref(), new.lang_ref.as_deref()),
+    ].into_iter()
+        .filter_map(|(name, old, new)| AttributeChange::new(name, old, new))
+        .collect()
+}
+
+/// Matches annotations on time span and value, see module documentation.
+fn annotation_diff(old: &[Annotation], new: &[Annotation]) -> Vec<AnnotationDiff> {
+    let mut old_matched = vec![false; old.len()];
+    let mut new_matched = vec![false; new.len()];
+    let mut diffs: Vec<AnnotationDiff> = Vec::new();
+
+    // 1. Identical annotations, 2. same time span
+    let mut spans: HashMap<(Option<i64>, Option<i64>), Vec<usize>> = HashMap::new();
+    for (j, annotation) in new.iter().enumerate() {
+        spans.entry(annotation.ts_val()).or_default().push(j);
+    }
+    for (i, annotation) in old.iter().enumerate() {
+        if let Some(j) = spans.get(&annotation.ts_val())
+            .and_then(|js| js.iter().copied().find(|j| !new_matched[*j] && annotation.is_identical(&new[*j], true)))
+        {
+            old_matched[i] = true;
+            new_matched[j] = true;
+        }
+    }
+    for (i, annotation) in old.iter().enumerate() {
+        if old_matched[i] || annotation.ts_val() == (None, None) {
+            continue
+        }
+        if let Some(j) = spans.get(&annotation.ts_val())
+            .and_then(|js| js.iter().copied().find(|j| !new_matched[*j]))
+        {
+            old_matched[i] = true;
+            new_matched[j] = true;
+            diffs.push(AnnotationDiff::Relabelled {
+                old: annotation.into(),
+                new: (&new[j]).into(),
+            });
+        }
+    }
+
+    // 3. and 4. Overlapping annotations, same value first, then largest overlap.
+    let span = |a: &Annotation| match a.ts_val() {
+        (Some(start), Some(end)) => Some((start, end)),
+        _ => None,
+    };
+    let mut candidates: Vec<(bool, f64, usize, usize)> = Vec::new();
+    for (i, a) in old.iter().enumerate().filter(|(i, _)| !old_matched[*i]) {
+        let Some(span1) = span(a) else { continue };
+        for (j, b) in new.iter().enumerate().filter(|(j, _)| !new_matched[*j]) {
+            let Some(span2) = span(b) else { continue };
+            let Some((start, end)) = intersection(span1, span2) else { continue };
+            let ratio = (end - start) as f64 / (span1.1.max(span2.1) - span1.0.min(span2.0)) as f64;
+            let same_value = a.is_identical(b, false);
+            if same_value || ratio >= 0.5 {
+                candidates.push((same_value, ratio, i, j));
+            }
+        }
+    }
+    candidates.sort_by(|x, y| y.0.cmp(&x.0)
+        .then(y.1.total_cmp(&x.1))
+        .then((x.2, x.3).cmp(&(y.2, y.3))));
+    for (same_value, _, i, j) in candidates.into_iter() {
+        if old_matched[i] || new_matched[j] {
+            continue
+        }
+        old_matched[i] = true;
+        new_matched[j] = true;
+        let (a, b) = (&old[i], &new[j]);
+        let (old, new, overlap) = (a.into(), b.into(), a.overlap(b));
+        diffs.push(match same_value {
+            true => AnnotationDiff::Retimed { old, new, overlap },
+            false => AnnotationDiff::RetimedRelabelled { old, new, overlap },
+        });
+    }
+
+    diffs.extend(old.iter().zip(old_matched.iter())
+        .filter(|(_, m)| !**m)
+        .map(|(a, _)| AnnotationDiff::Removed(a.into())));
+    diffs.extend(new.iter().zip(new_matched.iter())
+        .filter(|(_, m)| !**m)
+        .map(|(a, _)| AnnotationDiff::Added(a.into())));
+
+    // Annotations without time values last
+    diffs.sort_by_key(|d| (d.start().is_none(), d.start()));
+    diffs
+}
+
+/// Returns changed linguistic type attributes.
+fn lt_attributes(old: &LinguisticType, new: &LinguisticType) -> Vec<AttributeChange> {
+    let bool_str = |b: Option<bool>| b.map(|b| b.to_string());
+    [
+        ("TIME_ALIGNABLE", bool_str(old.time_alignable), bool_str(new.time_alignable)),
+        ("CONSTRAINTS", old.constraints.to_owned(), new.constraints.to_owned()),
+        ("GRAPHIC_REFERENCES", bool_str(old.graphic_references), bool_str(new.graphic_references)),
+        ("CONTROLLED_VOCABULARY_REF", old.controlled_vocabulary.to_owned(), new.controlled_vocabulary.to_owned()),
+        ("EXT_REF", old.ext_ref.to_owned(), new.ext_ref.to_owned()),
+        ("LEXICON_REF", old.lexicon_ref.to_owned(), new.lexicon_ref.to_owned()),
+    ].iter()
+        .filter_map(|(name, old, new)| AttributeChange::new(name, old.as_deref(), new.as_deref()))
+        .collect()
+}
+
+/// Returns entry values for a controlled vocabulary. Multilingual entries
+/// are listed per language as `value (language)`.
+fn cv_entries(cv: &ControlledVocabulary) -> Vec<String> {
+    cv.iter()
+        .flat_map(|entry| match entry {
+            CvType::CvEntry(e) => vec![e.value.to_owned()],
+            CvType::CvEntryMl(e) => e.cve_values.iter()
+                .map(|v| format!("{} ({})", v.value, v.lang_ref))
+                .collect(),
+            CvType::Description(_) => Vec::new(),
+        })
+        .collect()
+}
+
+/// Returns changed controlled vocabulary attributes and entries.
+fn cv_attributes(old: &ControlledVocabulary, new: &ControlledVocabulary) -> Vec<AttributeChange> {
+    let mut attributes: Vec<AttributeChange> = [
+        ("EXT_REF", old.ext_ref.as_deref(), new.ext_ref.as_deref()),
+        ("DESCRIPTION", old.description_attr.as_deref(), new.description_attr.as_deref()),
+    ].into_iter()
+        .filter_map(|(name, old, new)| AttributeChange::new(name, old, new))
+        .collect();
+
+    let (old_entries, new_entries) = (cv_entries(old), cv_entries(new));
+    attributes.extend(old_entries.iter()
+        .filter(|e| !new_entries.contains(e))
+        .filter_map(|e| AttributeChange::new("CV_ENTRY", Some(e), None)));
+    attributes.extend(new_entries.iter()
+        .filter(|e| !old_entries.contains(e))
+        .filter_map(|e| AttributeChange::new("CV_ENTRY", None, Some(e))));
+    attributes
+}
+
+/// Compares items with ID, in the order of `old` followed by items only in `new`.
+fn item_diff<T>(
+    old: &[T],
+    new: &[T],
+    id: impl Fn(&T) -> &str,
+    attributes: impl Fn(&T, &T) -> Vec<AttributeChange>,
+) -> Vec<ItemDiff> {
+    let mut diffs: Vec<ItemDiff> = Vec::new();
+    for item in old.iter() {
+        match new.iter().find(|n| id(n) == id(item)) {
+            Some(other) => {
+                let attributes = attributes(item, other);
+                if !attributes.is_empty() {
+                    diffs.push(ItemDiff { id: id(item).to_owned(), status: DiffStatus::Changed, attributes });
+                }
+            },
+            None => diffs.push(ItemDiff { id: id(item).to_owned(), status: DiffStatus::Removed, attributes: Vec::new() }),
+        }
+    }
+    diffs.extend(new.iter()
+        .filter(|n| !old.iter().any(|o| id(o) == id(n)))
+        .map(|n| ItemDiff { id: id(n).to_owned(), status: DiffStatus::Added, attributes: Vec::new() }));
+    diffs
+}
+
+/// See `Eaf::diff()`.
+pub(crate) fn diff(eaf: &Eaf, other: &Eaf) -> Result<EafDiff, EafError> {
+    // Referred annotations require derived time values for matching.
+    let derived = |eaf: &Eaf| -> Result<Option<Eaf>, EafError> {
+        match eaf.derived {
+            true => Ok(None),
+            false => {
+                let mut eaf = eaf.to_owned();
+                eaf.derive()?;
+                Ok(Some(eaf))
+            }
+        }
+    };
+    let (eaf_derived, other_derived) = (derived(eaf)?, derived(other)?);
+    let eaf = eaf_derived.as_ref().unwrap_or(eaf);
+    let other = other_derived.as_ref().unwrap_or(other);
+
+    let mut tiers: Vec<TierDiff> = Vec::new();
+    for tier in eaf.tiers.iter() {
+        match other.get_tier(&tier.tier_id) {
+            Some(other_tier) => {
+                let attributes = tier_attributes(tier, other_tier);
+                let annotations = annotation_diff(&tier.annotations, &other_tier.annotations);
+                if !attributes.is_empty() || !annotations.is_empty() {
+                    tiers.push(TierDiff {
+                        tier_id: tier.tier_id.to_owned(),
+                        status: DiffStatus::Changed,
+                        attributes,
+                        annotations,
+                    });
+                }
+            },
+            None => tiers.push(TierDiff {
+                tier_id: tier.tier_id.to_owned(),
+                status: DiffStatus::Removed,
+                attributes: Vec::new(),
+                annotations: annotation_diff(&tier.annotations, &[]),
+            }),
+        }
+    }
+    tiers.extend(other.tiers.iter()
+        .filter(|t| eaf.get_tier(&t.tier_id).is_none())
+        .map(|t| TierDiff {
+            tier_id: t.tier_id.to_owned(),
+            status: DiffStatus::Added,
+            attributes: Vec::new(),
+            annotations: annotation_diff(&[], &t.annotations),
+        }));
+
+    Ok(EafDiff {
+        tiers,
+        linguistic_types: item_diff(
+            &eaf.linguistic_types,
+            &other.linguistic_types,
+            |lt| lt.linguistic_type_id.as_str(),
+            lt_attributes
+        ),
+        controlled_vocabularies: item_diff(
+            &eaf.controlled_vocabularies,
+            &other.controlled_vocabularies,
+            |cv| cv.cv_id.as_str(),
+            cv_attributes
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eaf::test_util::{TestEaf, aligned, referred};
+
+    /// Controlled vocabulary `cv` with the specified entries.
+    fn cv(entries: &[&str]) -> String {
+        let entries: Vec<String> = entries.iter()
+            .map(|e| format!("<CV_ENTRY>{e}</CV_ENTRY>"))
+            .collect();
+        format!(r#"<CONTROLLED_VOCABULARY CV_ID="cv" DESCRIPTION="Letters">{}</CONTROLLED_VOCABULARY>"#, entries.concat())
+    }
+
+    /// Old document, and a new document with renumbered IDs and edits.
+    fn eafs() -> (Eaf, Eaf) {
+        let old = TestEaf::new()
+            .version("2.7")
+            .time_slots(&[0, 500, 1000, 2000, 3000, 4000, 5000, 6000])
+            .tier("A", "default-lt", None, &[
+                aligned("a1", "ts1", "ts3", "one"),
+                aligned("a2", "ts3", "ts4", "two"),
+                aligned("a3", "ts4", "ts5", "three"),
+                aligned("a4", "ts5", "ts6", "four"),
+                aligned("a5", "ts7", "ts8", "five"),
+            ])
+            .participant("Ann")
+            .linguistic_type("gloss", Some("Symbolic_Association"))
+            .tier("G", "gloss", Some("A"), &[referred("a6", "a1", "g1")])
+            .tier("R", "default-lt", None, &[aligned("a7", "ts1", "ts2", "r")])
+            .xml(&cv(&["x", "y"]))
+            .eaf();
+
+        let new = TestEaf::new()
+            .version("2.7")
+            .time_slots(&[0, 500, 1000, 2000, 2100, 3000, 3900, 7000, 8000])
+            .tier("A", "default-lt", None, &[
+                aligned("a11", "ts1", "ts3", "one"),
+                aligned("a12", "ts3", "ts4", "TWO"),
+                aligned("a13", "ts5", "ts6", "three"),
+                aligned("a14", "ts6", "ts7", "vier"),
+                aligned("a15", "ts8", "ts9", "new"),
+            ])
+            .participant("Bob")
+            .tier("G", "gloss", Some("A"), &[referred("a16", "a11", "g1")])
+            .tier("N", "default-lt", None, &[aligned("a17", "ts1", "ts2", "n")])
+            .xml(r#"<LINGUISTIC_TYPE CONSTRAINTS="Symbolic_Association" GRAPHIC_REFERENCES="true" LINGUISTIC_TYPE_ID="gloss" TIME_ALIGNABLE="false"/>"#)
+            .xml(r#"<LINGUISTIC_TYPE GRAPHIC_REFERENCES="false" LINGUISTIC_TYPE_ID="new-lt" TIME_ALIGNABLE="true"/>"#)
+            .xml(r#"<CONSTRAINT DESCRIPTION="" STEREOTYPE="Symbolic_Association"/>"#)
+            .xml(&cv(&["x", "z"]))
+            .eaf();
+
+        (old, new)
+    }
+
+    fn annotation(id: &str, value: &str, start: i64, end: i64) -> DiffAnnotation {
+        DiffAnnotation { id: id.to_owned(), value: value.to_owned(), start: Some(start), end: Some(end) }
+    }
+
+    #[test]
+    fn unchanged() {
+        let (old, new) = eafs();
+        assert!(old.diff(&old).unwrap().is_empty());
+        assert!(new.diff(&new).unwrap().is_empty());
+        assert_eq!(old.diff(&old).unwrap().to_string(), "");
+    }
+
+    #[test]
+    fn annotations() {
+        let (old, new) = eafs();
+        let diff = old.diff(&new).unwrap();
+
+        let a = &diff.tiers[0];
+        assert_eq!((a.tier_id.as_str(), a.status), ("A", DiffStatus::Changed));
+        assert_eq!(a.attributes, [AttributeChange {
+            name: "PARTICIPANT".into(),
+            old: Some("Ann".into()),
+            new: Some("Bob".into()),
+        }]);
+        // Identical annotations with new IDs are unchanged, sorted on time.
+        assert_eq!(a.annotations, [
+            AnnotationDiff::Relabelled {
+                old: annotation("a2", "two", 1000, 2000),
+                new: annotation("a12", "TWO", 1000, 2000),
+            },
+            AnnotationDiff::Retimed {
+                old: annotation("a3", "three", 2000, 3000),
+                new: annotation("a13", "three", 2100, 3000),
+                overlap: Overlap::from_spans((2000, 3000), (2100, 3000)),
+            },
+            AnnotationDiff::RetimedRelabelled {
+                old: annotation("a4", "four", 3000, 4000),
+                new: annotation("a14", "vier", 3000, 3900),
+                overlap: Overlap::from_spans((3000, 4000), (3000, 3900)),
+            },
+            AnnotationDiff::Removed(annotation("a5", "five", 5000, 6000)),
+            AnnotationDiff::Added(annotation("a15", "new", 7000, 8000)),
+        ]);
+
+        // Referred annotations are matched on derived time values.
+        assert!(diff.tiers.iter().all(|t| t.tier_id != "G"));
+        assert_eq!(diff.annotations().count(), 7);
+    }
+
+    #[test]
+    fn tiers_and_items() {
+        let (old, new) = eafs();
+        let diff = old.diff(&new).unwrap();
+
+        let tiers: Vec<(&str, DiffStatus, usize)> = diff.tiers.iter()
+            .map(|t| (t.tier_id.as_str(), t.status, t.annotations.len()))
+            .collect();
+        assert_eq!(tiers, [("A", DiffStatus::Changed, 5), ("R", DiffStatus::Removed, 1), ("N", DiffStatus::Added, 1)]);
+        assert_eq!(diff.tiers[1].annotations, [AnnotationDiff::Removed(annotation("a7", "r", 0, 500))]);
+
+        assert_eq!(diff.linguistic_types, [
+            ItemDiff {
+                id: "gloss".into(),
+                status: DiffStatus::Changed,
+                attributes: vec![AttributeChange {
+                    name: "GRAPHIC_REFERENCES".into(),
+                    old: Some("false".into()),
+                    new: Some("true".into()),
+                }],
+            },
+            ItemDiff { id: "new-lt".into(), status: DiffStatus::Added, attributes: Vec::new() },
+        ]);
+        assert_eq!(diff.controlled_vocabularies, [ItemDiff {
+            id: "cv".into(),
+            status: DiffStatus::Changed,
+            attributes: vec![
+                AttributeChange { name: "CV_ENTRY".into(), old: Some("y".into()), new: None },
+                AttributeChange { name: "CV_ENTRY".into(), old: None, new: Some("z".into()) },
+            ],
+        }]);
+
+        // Reversed diff, with removed items before added ones.
+        let reversed = new.diff(&old).unwrap();
+        let tiers: Vec<(&str, DiffStatus)> = reversed.tiers.iter()
+            .map(|t| (t.tier_id.as_str(), t.status))
+            .collect();
+        assert_eq!(tiers, [("A", DiffStatus::Changed), ("N", DiffStatus::Removed), ("R", DiffStatus::Added)]);
+        assert_eq!(reversed.linguistic_types[1].status, DiffStatus::Removed);
+    }
+
+    #[test]
+    fn display() {
+        let (old, new) = eafs();
+        let text = old.diff(&new).unwrap().to_string();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines[0], "Tier 'A' (changed)");
+        assert_eq!(lines[1], "  PARTICIPANT: 'Ann' -> 'Bob'");
+        assert_eq!(lines[2], "  ~ relabelled: a2 [1000-2000] 'two' -> a12 [1000-2000] 'TWO'");
+        assert_eq!(lines[3], "  ~ retimed: a3 [2000-3000] 'three' -> a13 [2100-3000] 'three'");
+        assert_eq!(lines[5], "  - a5 [5000-6000] 'five'");
+        assert_eq!(lines[6], "  + a15 [7000-8000] 'new'");
+        assert!(lines.contains(&"Linguistic type 'new-lt' (added)"));
+        assert!(lines.contains(&"  CV_ENTRY: (none) -> 'z'"));
+    }
+}
@@ -27,9 +27,11 @@ use rayon::prelude::{IntoParallelRefIterator, ParallelIterator, IndexedParallelI
 use regex::Regex;
 use time::format_description;
 use std::collections::HashMap;
+use std::borrow::Cow;
 use std::fs::File;
 use std::io::Write;
 use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
 
 use crate::eaf::ExternalRef;
 use crate::support::affix_file_name;
@@ -37,6 +39,14 @@ use crate::TimeSlot;
 use crate::EafError;
 
 use super::merge::{merge_eafs, OverlapStrategy};
+use super::lossless::Source;
+use super::version::convert;
+use super::edit;
+use super::computed::{self, ValueRule};
+use super::tokenize::{self, Tokenizer, AlignOptions};
+use super::agreement::{self, Agreement, AgreementOptions};
+use super::conversation::{self, ConversationStats, ConversationOptions};
+use super::diff::{self, EafDiff};
 use super::{
     Annotation,
     Constraint,
@@ -52,8 +62,29 @@ use super::{
     Locale,
     Tier,
     TimeOrder,
+    TextGrid,
+    Subtitles,
+    SubtitleFormat,
+    SubtitleOptions,
+    Chat,
+    FlexText,
+    Exmaralda,
+    Table,
+    TabOptions,
+    CsvMapping,
+    RowError,
+    ValidationReport,
+    ValidationIssue,
+    Search,
+    SearchHit,
+    QueryResult,
+    EafVersion,
+    ConversionLoss,
+    TimeIndex,
+    RangeMode,
     EafBuilder
 };
+use super::validate::{repair_stereotypes, stereotype_violations};
 
 /// Returns "unspecified" as `String`
 /// To get around quick-xml not adding attributes with
@@ -118,6 +149,7 @@ pub fn get_path_prefix(path: &Path) -> Option<std::path::Prefix<'_>> {
 /// Used for methods and function where
 /// scope is important, e.g. token
 /// or ngram stats.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Scope {
     /// Scope is a single annotation.
     /// Depending on usage,
@@ -139,7 +171,7 @@ pub enum Scope {
 /// De/Serializable. Make sure to validate output, since breaking changes
 /// were introduced in EAF v2.8. E.g. valid EAF v2.7 documents with
 /// controlled vocabularies do not validate against EAF v2.8+
-/// schemas.
+/// schemas. Use `Eaf::convert_version()` to convert between versions.
 ///
 /// Example:
 /// ```
@@ -256,6 +288,12 @@ pub struct Eaf {
     /// State to check whether `Eaf` is indexed.
     #[serde(skip)]
     pub(crate) indexed: bool,
+
+    /// Not part of EAF specification.
+    /// Original XML for lossless serialization,
+    /// set if read via `Eaf::read_lossless()`.
+    #[serde(skip)]
+    pub(crate) source: Option<Arc<Source>>,
 }
 
 impl Default for Eaf {
@@ -282,6 +320,7 @@ impl Default for Eaf {
             derived: false,
             index: Index::default(),
             indexed: false,
+            source: None,
         }
     }
 }
@@ -300,7 +339,7 @@ impl AsMut<Eaf> for Eaf {
 
 impl Eaf {
     /// Set EAF XML namespaces.
-    fn set_ns(&mut self) {
+    pub(crate) fn set_ns(&mut self) {
         self.xmlns_xsi = "http://www.w3.org/2001/XMLSchema-instance".to_owned();
         self.xsi_nonamespaceschemalocation =
             format!("http://www.mpi.nl/tools/elan/EAFv{}.xsd", self.version);
@@ -316,8 +355,13 @@ impl Eaf {
     /// While `derive` is convenient if working on a single file,
     /// parsing will take slightly longer.
     fn de(path: &Path, derive: bool) -> Result<Self, EafError> {
+        Self::de_str(&std::fs::read_to_string(path)?, derive)
+    }
+
+    /// Deserialize ELAN-file from string. See `Eaf::de()`.
+    pub(crate) fn de_str(xml: &str, derive: bool) -> Result<Self, EafError> {
         // Let Quick XML use serde to deserialize
-        let mut eaf: Eaf = quick_xml::de::from_str(&std::fs::read_to_string(path)?)
+        let mut eaf: Eaf = quick_xml::de::from_str(xml)
             .map_err(|e| EafError::QuickXMLDeError(e))?;
 
         // eaf.path = Some(path.to_owned());
@@ -407,11 +451,199 @@ impl Eaf {
         }
     }
 
+    /// Serializes to a Praat TextGrid string in either the long
+    /// or the short (`short = true`) TextGrid format.
+    ///
+    /// Main tiers are exported as interval tiers,
+    /// zero-length annotations as point tiers.
+    /// See `TextGrid::from_eaf()`.
+    pub fn to_textgrid(&self, short: bool) -> Result<String, EafError> {
+        Ok(TextGrid::from_eaf(self)?.to_string(short))
+    }
+
+    /// Serializes and writes a Praat TextGrid to disk.
+    /// See `Eaf::to_textgrid()`.
+    pub fn write_textgrid(&self, path: &Path, short: bool) -> Result<(), EafError> {
+        TextGrid::from_eaf(self)?.write(path, short)
+    }
+
+    /// Generates a new `Eaf` from a Praat TextGrid file,
+    /// in either the long or short TextGrid format,
+    /// encoded as UTF-8 or UTF-16.
+    ///
+    /// Each TextGrid tier becomes a main tier. Time order,
+    /// annotation IDs and linguistic types are generated,
+    /// the same way as for `Eaf::from_values_multi()`.
+    pub fn from_textgrid(path: &Path) -> Result<Self, EafError> {
+        TextGrid::read(path)?.to_eaf()
+    }
+
+    /// Serializes the specified tier as SRT or WebVTT subtitles.
+    ///
+    /// Annotations are merged, split and wrapped into cues according
+    /// to `options`. Cues are optionally labelled with the tier participant.
+    /// See `Subtitles::from_tier()`.
+    pub fn to_subtitles(
+        &self,
+        tier_id: &str,
+        format: SubtitleFormat,
+        options: &SubtitleOptions
+    ) -> Result<String, EafError> {
+        Ok(Subtitles::from_tier(self, tier_id, options)?.to_string(format))
+    }
+
+    /// Serializes the specified tier as SRT or WebVTT subtitles
+    /// and writes these to disk. See `Eaf::to_subtitles()`.
+    pub fn write_subtitles(
+        &self,
+        path: &Path,
+        tier_id: &str,
+        format: SubtitleFormat,
+        options: &SubtitleOptions
+    ) -> Result<(), EafError> {
+        Subtitles::from_tier(self, tier_id, options)?.write(path, format)
+    }
+
+    /// Generates a new `Eaf` from an SRT or WebVTT file,
+    /// with a single main tier containing one annotation per cue.
+    /// Tier ID defaults to `default`.
+    pub fn from_subtitles(path: &Path, tier_id: Option<&str>) -> Result<Self, EafError> {
+        Subtitles::read(path)?.to_eaf(tier_id.unwrap_or("default"))
+    }
+
+    /// Serializes main tiers and their dependent tiers as CHAT (CHILDES/TalkBank),
+    /// with one utterance per main tier annotation. See `Chat::from_eaf()`.
+    pub fn to_chat(&self) -> Result<String, EafError> {
+        Ok(Chat::from_eaf(self)?.to_string())
+    }
+
+    /// Serializes and writes CHAT to disk. See `Eaf::to_chat()`.
+    pub fn write_chat(&self, path: &Path) -> Result<(), EafError> {
+        Chat::from_eaf(self)?.write(path)
+    }
+
+    /// Generates a new `Eaf` from a CHAT file, with one main tier per speaker
+    /// and a `Symbolic_Association` tier for each dependent tier, e.g. `%mor`.
+    /// Utterances must have time bullets. See `Chat::to_eaf()`.
+    pub fn from_chat(path: &Path) -> Result<Self, EafError> {
+        Chat::read(path)?.to_eaf()
+    }
+
+    /// Serializes main tiers as a FLEx interlinear text (`.flextext`),
+    /// with words, morphemes and glosses from symbolic referred tiers.
+    /// See `FlexText::from_eaf()`.
+    pub fn to_flextext(&self) -> Result<String, EafError> {
+        FlexText::from_eaf(self)?.to_string()
+    }
+
+    /// Serializes and writes a FLEx interlinear text to disk.
+    /// See `Eaf::to_flextext()`.
+    pub fn write_flextext(&self, path: &Path) -> Result<(), EafError> {
+        FlexText::from_eaf(self)?.write(path)
+    }
+
+    /// Generates a new `Eaf` from a FLEx interlinear text (`.flextext`),
+    /// with phrases in a main tier, words and morphemes in `Symbolic_Subdivision` tiers,
+    /// and other items in `Symbolic_Association` tiers. See `FlexText::to_eaf()`.
+    pub fn from_flextext(path: &Path) -> Result<Self, EafError> {
+        FlexText::read(path)?.to_eaf()
+    }
+
+    /// Serializes as an EXMARaLDA basic transcription (`.exb`), and returns it
+    /// together with information that EXMARaLDA can not express, e.g. the tier hierarchy.
+    /// See `Exmaralda::from_eaf()`.
+    pub fn to_exmaralda(&self) -> Result<(String, Vec<ConversionLoss>), EafError> {
+        let (exb, losses) = Exmaralda::from_eaf(self)?;
+        Ok((exb.to_string()?, losses))
+    }
+
+    /// Serializes and writes an EXMARaLDA basic transcription to disk.
+    /// See `Eaf::to_exmaralda()`.
+    pub fn write_exmaralda(&self, path: &Path) -> Result<Vec<ConversionLoss>, EafError> {
+        let (exb, losses) = Exmaralda::from_eaf(self)?;
+        exb.write(path)?;
+        Ok(losses)
+    }
+
+    /// Generates a new `Eaf` from an EXMARaLDA basic transcription (`.exb`),
+    /// with one main tier per EXMARaLDA tier. See `Exmaralda::to_eaf()`.
+    pub fn from_exmaralda(path: &Path) -> Result<Self, EafError> {
+        Exmaralda::read(path)?.to_eaf()
+    }
+
+    /// Serializes annotations as tab-delimited text, similar to ELAN's
+    /// "Export as Tab-delimited Text", with columns, tiers and time range
+    /// according to `options`. See `Table::from_eaf()`.
+    pub fn to_tab_delimited(&self, options: &TabOptions) -> Result<String, EafError> {
+        Table::from_eaf(self, options)?.to_string(options.delimiter)
+    }
+
+    /// Serializes and writes annotations as tab-delimited text to disk.
+    /// See `Eaf::to_tab_delimited()`.
+    pub fn write_tab_delimited(&self, path: &Path, options: &TabOptions) -> Result<(), EafError> {
+        Table::from_eaf(self, options)?.write(path, options.delimiter)
+    }
+
+    /// Generates a new `Eaf` from a CSV/TSV file, with columns mapped
+    /// according to `mapping`. Rows with a parent tier become referred tiers.
+    /// Returns rows that could not be imported, with line numbers.
+    /// See `CsvMapping::to_eaf()`.
+    pub fn from_csv(path: &Path, mapping: &CsvMapping) -> Result<(Self, Vec<RowError>), EafError> {
+        mapping.read(path)
+    }
+
     /// Read an ELAN-file from disk.
     pub fn read(path: &Path) -> Result<Eaf, EafError> {
         Self::de(path, true)
     }
 
+    /// Deserialize ELAN-file in lossless mode.
+    ///
+    /// The original XML is kept, so that `Eaf::to_string_lossless()`
+    /// and `Eaf::write_lossless()` only apply the edits made to the `Eaf`,
+    /// and preserve elements and attributes not in the EAF specification,
+    /// XML comments, attribute and element order, and indentation.
+    /// Unchanged input results in identical output.
+    pub fn read_lossless(path: &Path) -> Result<Eaf, EafError> {
+        Self::de_str_lossless(std::fs::read_to_string(path)?)
+    }
+
+    /// Deserialize ELAN-file from string in lossless mode.
+    /// See `Eaf::read_lossless()`.
+    pub(crate) fn de_str_lossless(xml: String) -> Result<Eaf, EafError> {
+        let mut eaf = Self::de_str(&xml, true)?;
+        let baseline = eaf.se(None)?;
+        eaf.source = Some(Arc::new(Source::new(xml, baseline)));
+        Ok(eaf)
+    }
+
+    /// Returns `true` if the `Eaf` was read in lossless mode
+    /// via `Eaf::read_lossless()`.
+    pub fn is_lossless(&self) -> bool {
+        self.source.is_some()
+    }
+
+    /// Serialize to an XML-string, applying edits to the original XML
+    /// so that everything else is preserved. See `Eaf::read_lossless()`.
+    ///
+    /// Added elements are indented as the original XML.
+    /// Returns error if the `Eaf` was not read in lossless mode.
+    pub fn to_string_lossless(&self) -> Result<String, EafError> {
+        let source = self.source.as_ref()
+            .ok_or(EafError::LosslessSourceMissing)?;
+        source.apply(&self.se(None)?)
+    }
+
+    /// Serialize and write file to disk in lossless mode.
+    /// See `Eaf::to_string_lossless()`.
+    pub fn write_lossless(&self, path: &Path) -> Result<(), EafError> {
+        let content = self.to_string_lossless()?;
+
+        let mut outfile = File::create(&path)?;
+
+        outfile.write_all(content.as_bytes()).map_err(|e| EafError::IOError(e))
+    }
+
     /// Serialize to an XML-string (single line),
     /// and optionally specify indentation (multi-line).
     pub fn to_string(&self, indent: Option<usize>) -> Result<String, EafError> {
@@ -459,18 +691,35 @@ impl Eaf {
     pub fn from_values_multi(
         values: &[(String, i64, i64, String)]
     ) -> Result<Eaf, EafError> {
-        let mut groups: HashMap<String, Vec<(String, i64, i64)>> = HashMap::new();
+        // Group on tier ID, preserving the order tier IDs first appear in.
+        let mut groups: Vec<(String, Vec<(String, i64, i64)>)> = Vec::new();
+        let mut group_idx: HashMap<String, usize> = HashMap::new();
         values.iter()
-            .for_each(|(a, t1, t2, t_id)| groups
-                .entry(t_id.to_string())
-                .or_insert(Vec::new())
-                .push((a.to_owned(), *t1, *t2))
-            );
+            .for_each(|(a, t1, t2, t_id)| {
+                let idx = *group_idx.entry(t_id.to_string())
+                    .or_insert_with(|| {
+                        groups.push((t_id.to_string(), Vec::new()));
+                        groups.len() - 1
+                    });
+                groups[idx].1.push((a.to_owned(), *t1, *t2))
+            });
 
+        Self::from_tier_values(&groups)
+    }
+
+    /// Generate new `Eaf` with one main tier for each item
+    /// in a list of tuples in the form `(tier_id, values)`, where `values`
+    /// are tuples in the form `(annotation_value, start_time_ms, end_time_ms)`.
+    ///
+    /// Tier order is preserved. Time order, annotation IDs and linguistic types
+    /// are generated. Note that all generated tiers will be main tiers.
+    pub fn from_tier_values(
+        tier_values: &[(String, Vec<(String, i64, i64)>)]
+    ) -> Result<Eaf, EafError> {
         // timeslot and annotation references need re-indexing
         let mut start_index: usize = 1;
         let mut time_order = TimeOrder::new();
-        let tiers: Vec<Tier> = groups.iter()
+        let tiers: Vec<Tier> = tier_values.iter()
             .map(|(tier_id, values)| {
                 let tier = Tier::main_from_values(values, tier_id, Some(start_index))?;
 
@@ -504,43 +753,16 @@ impl Eaf {
     ///
     /// Mostly for internal use. Makes annotations less dependent,
     /// since they now contain explicit time slot values etc.
+    /// Indexes `Eaf` first if not indexed, since values are derived via the index.
     pub fn derive(&mut self) -> Result<(), EafError> {
-        // copy since otherwise error is raised
-        // better solution would be nice
-        let eaf_copy = self.to_owned();
+        if !self.indexed {
+            self.index()
+        }
 
-        // Iter mut over self...
+        // Tiers are borrowed mutably, index immutably.
         for tier in self.tiers.iter_mut() {
             for annotation in tier.annotations.iter_mut() {
-                // ...check if annotion is ref annotation...
-                let (ref1, ref2) = match annotation.is_ref() {
-                    true => {
-                        // ...then use the copy for deriving main annotation for ref annotations.
-                        let ma = eaf_copy.main_annotation(&annotation.id()).ok_or(
-                            EafError::AnnotationMainMissing((
-                                annotation.id().to_owned(),
-                                annotation.ref_id().map(|s| s.to_owned())
-                            )),
-                        )?;
-
-                        // Set main annotion ID for ref annotation...
-                        annotation.set_main(&ma.id());
-
-                        // ...then get annotation ID for main annotation.
-                        ma.ts_ref()
-                            .ok_or(EafError::TimeslotRefMissing(annotation.id().to_owned()))?
-                    }
-
-                    // Raise error if annotation in main tier returns no time slot references.
-                    false => annotation
-                        .ts_ref()
-                        .ok_or(EafError::TimeslotRefMissing(annotation.id().to_owned()))?,
-                };
-
-                let val1 = eaf_copy.ts_val(&ref1);
-                let val2 = eaf_copy.ts_val(&ref2);
-                annotation.set_ts_val(val1, val2);
-                annotation.set_tier_id(&tier.tier_id);
+                self.index.derive(annotation, &tier.tier_id)?;
             }
         }
 
@@ -554,77 +776,23 @@ impl Eaf {
     /// Indexes the ELAN-file with the following mappings:
     /// - `a2t`: Annotation ID to tier ID
     /// - `a2ref`: Annotation ID to ref annotation ID
-    /// - `t2a`: Tier ID to list of annotation ID:s
+    /// - `t2a`: Tier ID to annotation ID:s, in tier order
     /// - `t2ref`: Tier ID to ref tier ID
     /// - `id2ts`: Time slot ID to time slot value
     /// - `ts2id`: Time slot value to Time slot ID
     /// - `a2ts`: Annotation ID to time slot id/ref tuple, `(time_slot_ref1, time_slot_ref2)`.
-    /// - `a2idx`: Annotation ID to `(idx1, idx2)` in `Eaf.tiers[idx1].annotations[idx2]`
+    /// - `ts2a`: Time slot ID to ID:s of annotations referring to it
+    /// - `ref2a`: Annotation ID to ID:s of annotations referring to it
     /// - `t2idx`: Tier ID to `idx` in `Eaf.tiers[idx]`
+    /// - `time`, `t2time`: Time index for all annotations, and for each tier,
+    ///   used by `Eaf::annotations_at()`, `Eaf::annotations_in()`, `Eaf::nearest()`.
     ///
     /// Speeds up many "getter" methods, such as finding cross referenced annotations,
     /// time values for referred annotations etc. Done automatically on deserialization.
-    /// Re-run as necessary, after external edit etc. Automatic for internal methods,
-    /// such as adding an annotation or a tier.
+    /// Re-run as necessary, after external edit etc. Internal methods, such as adding
+    /// or removing an annotation or a tier, only update the affected entries.
     pub fn index(&mut self) {
-        let mut a2t: HashMap<String, String> = HashMap::new();
-        let mut a2ref: HashMap<String, String> = HashMap::new();
-        let mut t2a: HashMap<String, Vec<String>> = HashMap::new();
-        let mut t2ref: HashMap<String, String> = HashMap::new();
-        let mut a2ts: HashMap<String, (String, String)> = HashMap::new(); // Annotation ID -> time slot ref1, ref2
-        let mut a2idx: HashMap<String, (usize, usize)> = HashMap::new(); // Annotation ID -> tier idx, annot idx
-        let mut t2idx: HashMap<String, usize> = HashMap::new(); // Tier ID -> tier idx
-
-        self.tiers.iter().enumerate().for_each(|(idx_t, t)| {
-            // Tier ID -> tier idx in self.tiers
-            t2idx.insert(t.tier_id.to_owned(), idx_t);
-
-            // Tier ID -> Ref tier ID
-            if let Some(t_id) = t.parent_ref.to_owned() {
-                t2ref.insert(t.tier_id.to_owned(), t_id);
-            }
-
-            // Used for Tier ID -> [Annotation ID, ...]
-            let mut a_id: Vec<String> = Vec::new();
-
-            t.annotations.iter().enumerate().for_each(|(idx_a, a)| {
-                let id = a.id();
-
-                // Annotation ID -> (Tier index, Annotation index)
-                a2idx.insert(id.to_owned(), (idx_t, idx_a));
-
-                // Annotation ID -> Annotation ref ID
-                if let Some(ref_id) = a.ref_id() {
-                    a2ref.insert(id.to_owned(), ref_id.to_owned());
-                };
-
-                // Annotation ID -> Tier ID
-                a2t.insert(id.to_owned(), t.tier_id.to_owned());
-
-                // Annotation ID -> (time slot ref 1, time slot ref2)
-                if let Some((ref1, ref2)) = a.ts_ref() {
-                    a2ts.insert(id.to_owned(), (ref1, ref2));
-                }
-
-                a_id.push(id.to_owned());
-            });
-
-            // Tier ID -> [Annotation ID, ...]
-            t2a.insert(t.tier_id.to_owned(), a_id);
-        });
-
-        self.index = Index {
-            a2t,
-            a2ref,
-            t2a,
-            t2ref,
-            ts2tv: self.time_order.index(),
-            tv2ts: self.time_order.index_rev(),
-            a2ts,
-            a2idx,
-            t2idx,
-        };
-
+        self.index = Index::new(&self.tiers, &self.time_order);
         self.indexed = true;
     }
 
@@ -820,7 +988,21 @@ impl Eaf {
     /// `allow_negative` ignores if the resulting time values are negative,
     /// otherwise `EafError::ValueTooSmall(time_value)` is raised.
     pub fn shift(&mut self, shift_ms: i64, allow_negative: bool) -> Result<(), EafError> {
-        self.time_order.shift(shift_ms, allow_negative)
+        self.time_order.shift(shift_ms, allow_negative)?;
+
+        if self.indexed {
+            self.index.shift_time(shift_ms)
+        }
+        if self.derived {
+            self.tiers.iter_mut()
+                .flat_map(|t| t.annotations.iter_mut())
+                .for_each(|a| {
+                    let (val1, val2) = a.ts_val();
+                    a.set_ts_val(val1.map(|v| v + shift_ms), val2.map(|v| v + shift_ms))
+                });
+        }
+
+        Ok(())
     }
 
     /// Match annotation values against a string.
@@ -860,25 +1042,33 @@ impl Eaf {
     // }
 
     /// Match annotation values against a regular expression.
-    /// Returns a vec with tuples: `(Annotation Index, Tier ID, Annotation ID, Annotation value)`.
+    /// Returns a `QueryResult` for each matching annotation,
+    /// with matched spans, time bounds, and the surrounding
+    /// annotations in the same tier as context.
+    /// See `Kwic` for generating a keyword-in-context concordance.
     pub fn query_rx(
         &self,
         regex: &Regex
-    // ) -> Vec<(usize, &str, &str, &str, Option<&str>)> {
-    ) -> Vec<(usize, &str, &Annotation)> {
-        // (Annotation Index, Tier ID, Annotation ID, Annotation value)
+    ) -> Vec<QueryResult<'_>> {
         self.tiers.par_iter()
-            .filter_map(|t| {
-                let results = t.query_rx(regex);
-                if results.is_empty() {
-                    None
-                } else {
-                    Some(results)
-                }
-            })
-            .flatten()
+            .flat_map(|t| (0..t.len())
+                .into_par_iter()
+                .filter_map(|i| QueryResult::new(t, i, regex))
+                .collect::<Vec<_>>()
+            )
             .collect()
     }
+
+    /// Structured, multi-tier search. Returns all combinations of
+    /// annotations, one per search layer, that satisfy the search constraints.
+    /// See `Search`.
+    ///
+    /// Returns error if the search has no layers, or if a relation
+    /// refers to a layer that does not exist.
+    pub fn search(&self, search: &Search) -> Result<Vec<SearchHit<'_>>, EafError> {
+        search.run(self)
+    }
+
     // pub fn query_rx_old(&self, regex: &Regex) -> Vec<(usize, String, String, String, Option<String>)> {
     //     // (Annotation Index, Tier ID, Annotation ID, Annotation value)
     //     self.tiers.par_iter()
@@ -1019,12 +1209,46 @@ impl Eaf {
 
     /// Pushes a time slot to time order as last item.
     /// Ensures the time slot ID does not exist.
-    pub fn add_timeslot(&mut self, id: &str, val: Option<i64>, index: bool) -> Result<(), EafError> {
-        self.time_order.add(Some(id), val)?;
-        if index {
+    pub fn add_timeslot(&mut self, id: &str, val: Option<i64>) -> Result<(), EafError> {
+        if !self.indexed {
+            self.index()
+        }
+        if self.index.ts2tv.contains_key(id) {
+            return Err(EafError::TimeSlotIdExists(id.to_owned()))
+        }
+
+        self.time_order.push(&TimeSlot::new(id, val));
+        self.index.insert_timeslot(id, val);
+
+        Ok(())
+    }
+
+    /// Sets the time value for the time slot with specified ID.
+    /// Time values for annotations that use the time slot, including
+    /// referred annotations, are updated as well.
+    pub fn set_ts_val(&mut self, id: &str, val: Option<i64>) -> Result<(), EafError> {
+        if !self.indexed {
             self.index()
-        } else {
-            self.indexed = false
+        }
+
+        self.time_order.find_mut(id)
+            .ok_or_else(|| EafError::TimeslotIdInvalid(id.to_owned()))?
+            .time_value = val;
+        self.index.set_timeslot_value(id, val);
+
+        if self.derived {
+            let mut a_ids = self.index.ts2a.get(id).cloned().unwrap_or_default();
+            let dependents: Vec<String> = a_ids.iter()
+                .flat_map(|a_id| self.index.dependents(a_id))
+                .collect();
+            a_ids.extend(dependents);
+            for a_id in a_ids.iter() {
+                let Some((t_idx, a_idx)) = self.index.a2idx(a_id) else {
+                    continue
+                };
+                let tier = &mut self.tiers[t_idx];
+                self.index.derive(&mut tier.annotations[a_idx], &tier.tier_id)?;
+            }
         }
 
         Ok(())
@@ -1335,32 +1559,34 @@ impl Eaf {
         Ok(eaf)
     }
 
-    /// Attempts to add an annotation as last item in tier with specified tier ID,
-    /// together with corresponding time slot in time order.
+    /// Attempts to add an annotation to tier with specified tier ID, in time order,
+    /// together with corresponding time slots in time order if these do not exist.
     /// If time values are not set (or are intentionally `None`) in the annotation,
     /// time slots with empty time slot values will be created, but note that
     /// time slots with no time value can never be the the final time slot.
-    /// (Re-)index is optional. This is for cases where annotations are added in batch,
-    /// in which case it may be better to index only once when done.
+    /// Only the affected index entries are updated.
     pub fn add_annotation(
         &mut self,
         annotation: &Annotation,
         tier_id: &str,
-        index: bool,
     ) -> Result<(), EafError> {
-        // Derive if not done.
+        // Derive (and index) if not done.
         if !self.derived {
             self.derive()?
         }
 
         // Check if annotation with same ID already exists.
-        if matches!(&self.exists(&annotation.id()), (_, true, _)) {
+        if self.index.a2t.contains_key(annotation.id()) {
             return Err(EafError::AnnotationIdExists(annotation.id().to_owned()));
         }
 
+        let t_idx = *self.index.t2idx.get(tier_id)
+            .ok_or_else(|| EafError::TierIdInvalid(tier_id.to_owned()))?;
+
         // Ensure referred annotation ID exists if ref annotation
+        let mut new_ts: Vec<String> = Vec::new();
         if let Some(ref_id) = annotation.ref_id() {
-            if matches!(&self.exists(&ref_id), (_, false, _)) {
+            if !self.index.a2t.contains_key(ref_id) {
                 return Err(EafError::AnnotationIdInvalid(ref_id.into()));
             }
         } else {
@@ -1370,22 +1596,150 @@ impl Eaf {
             let (ts_val1, ts_val2) = annotation.ts_val();
 
             // Add time slots to time order. Only adds if it does not exist.
-            self.add_timeslot(&ts_id1, ts_val1, false)?;
-            self.add_timeslot(&ts_id2, ts_val2, false)?;
+            for (ts_id, ts_val) in [(ts_id1, ts_val1), (ts_id2, ts_val2)] {
+                if !self.index.ts2tv.contains_key(&ts_id) {
+                    self.add_timeslot(&ts_id, ts_val)?;
+                    new_ts.push(ts_id);
+                }
+            }
         }
 
-        self.get_tier_mut(tier_id)
-            .ok_or(EafError::TierIdInvalid(tier_id.to_owned()))?
-            .add(annotation)?;
+        // Set time values etc, so that the annotation can be positioned in tier.
+        let mut annotation = annotation.to_owned();
+        let added = self.index.derive(&mut annotation, tier_id)
+            .and_then(|_| self.tiers[t_idx].add(&annotation));
+
+        let a_idx = match added {
+            Ok(idx) => idx,
+            Err(err) => {
+                // Remove time slots added above.
+                for ts_id in new_ts.iter() {
+                    self.time_order.remove(ts_id);
+                    self.index.remove_timeslot(ts_id);
+                }
+                return Err(err)
+            }
+        };
+        self.index.insert_annotation(&annotation, tier_id, a_idx);
+
+        Ok(())
+    }
 
-        // Index or set `indexed` to false if not.
-        if index {
+    /// Removes annotation with specified annotation ID, together with all
+    /// annotations referring to it. Time slots no longer in use are removed,
+    /// and previous annotation references are updated.
+    /// Only the affected index entries are updated.
+    ///
+    /// Returns removed annotations, with the specified annotation first.
+    pub fn remove_annotation(&mut self, id: &str) -> Result<Vec<Annotation>, EafError> {
+        if !self.indexed {
             self.index()
-        } else {
-            self.indexed = false
         }
 
-        Ok(())
+        if !self.index.a2t.contains_key(id) {
+            return Err(EafError::AnnotationIdInvalid(id.to_owned()));
+        }
+
+        let mut a_ids = vec![id.to_owned()];
+        a_ids.extend(self.index.dependents(id));
+
+        // Remove dependents first, so that references are valid while removing.
+        let mut removed: Vec<Annotation> = Vec::new();
+        for a_id in a_ids.iter().rev() {
+            let Some((t_idx, a_idx)) = self.index.a2idx(a_id) else {
+                continue
+            };
+            let ts = self.index.a2ts.get(a_id).cloned();
+            let siblings = self.index.a2ref.get(a_id)
+                .and_then(|ref_id| self.index.ref2a.get(ref_id))
+                .cloned()
+                .unwrap_or_default();
+
+            self.index.remove_annotation(a_id);
+            let annotation = self.tiers[t_idx].annotations.remove(a_idx);
+
+            // Annotations following the removed one in a subdivision
+            // now follow the one preceding it.
+            for sibling in siblings.iter() {
+                let Some((t_idx, a_idx)) = self.index.a2idx(sibling) else {
+                    continue
+                };
+                let next = &mut self.tiers[t_idx].annotations[a_idx];
+                if next.previous() == Some(a_id) {
+                    match annotation.previous() {
+                        Some(prev) => next.set_previous(prev),
+                        None => next.unset_previous(),
+                    }
+                }
+            }
+
+            // Time slots no longer in use.
+            if let Some((ref1, ref2)) = ts {
+                edit::remove_unused_timeslots(self, &[ref1, ref2]);
+            }
+
+            removed.push(annotation);
+        }
+        removed.reverse();
+
+        Ok(removed)
+    }
+
+    /// Splits alignable annotation with specified ID at `ms` milliseconds,
+    /// which must be within the annotation's time span.
+    /// The annotation keeps its value and the first part of the time span,
+    /// and a new, empty annotation is added for the second part.
+    ///
+    /// Symbolic subdivisions referring to the annotation are distributed
+    /// between the two parts according to their position, as if evenly
+    /// dividing the time span. Other referred annotations are kept
+    /// with the first part. Fails if a time-aligned annotation
+    /// in a dependent tier crosses `ms`.
+    ///
+    /// Returns the ID of the new annotation.
+    pub fn split_annotation(&mut self, id: &str, ms: i64) -> Result<String, EafError> {
+        edit::split(self, id, ms)
+    }
+
+    /// Merges alignable annotation with specified ID with the next annotation
+    /// in the same tier. Annotation values are joined with a space, and
+    /// the merged annotation spans from the start of the first
+    /// to the end of the second.
+    ///
+    /// Symbolic subdivisions are joined into a single sequence,
+    /// other referred annotations in the same tier have their values joined.
+    ///
+    /// Returns the ID of the merged annotation, i.e. the first one.
+    pub fn merge_annotation_next(&mut self, id: &str) -> Result<String, EafError> {
+        edit::merge(self, id, true)
+    }
+
+    /// Merges alignable annotation with specified ID with the previous annotation
+    /// in the same tier. See `Eaf::merge_annotation_next()`.
+    ///
+    /// Returns the ID of the merged annotation, i.e. the previous one.
+    pub fn merge_annotation_previous(&mut self, id: &str) -> Result<String, EafError> {
+        edit::merge(self, id, false)
+    }
+
+    /// Moves alignable annotation with specified ID `shift_ms` milliseconds,
+    /// together with time-aligned annotations in dependent tiers contained
+    /// within its time span. Referred annotations follow via their references.
+    ///
+    /// Time slots shared with annotations that are not moved are left as is,
+    /// and new time slots are added for the moved annotations.
+    /// Fails if the result overlaps other annotations, starts before 0,
+    /// or is no longer contained in a parent annotation.
+    pub fn move_annotation(&mut self, id: &str, shift_ms: i64) -> Result<(), EafError> {
+        edit::shift(self, id, shift_ms)
+    }
+
+    /// Sets new start and/or end time in milliseconds for alignable annotation
+    /// with specified ID. `None` keeps the current value.
+    /// Annotations in dependent tiers sharing a boundary time slot
+    /// follow the new boundary. See `Eaf::move_annotation()`.
+    pub fn resize_annotation(&mut self, id: &str, start: Option<i64>, end: Option<i64>) -> Result<(), EafError> {
+        edit::resize(self, id, start, end)
     }
 
     /// Returns reference to annotion with specified annotation ID if it exits.
@@ -1394,8 +1748,8 @@ impl Eaf {
         // self.tiers
         //     .par_iter()
         //     .find_map_any(|t| t.annotations.par_iter().find_any(|a| a.id() == id))
-        let (t_idx, a_idx) = self.index.a2idx.get(id)?;
-        self.tiers.get(*t_idx)?.annotations.get(*a_idx)
+        let (t_idx, a_idx) = self.index.a2idx(id)?;
+        self.tiers.get(t_idx)?.annotations.get(a_idx)
     }
 
     /// Returns a mutable reference to annotion with specified annotation ID if it exits.
@@ -1404,8 +1758,80 @@ impl Eaf {
         // self.tiers
         //     .par_iter_mut()
         //     .find_map_any(|t| t.annotations.par_iter_mut().find_any(|a| a.id() == id))
-        let (t_idx, a_idx) = self.index.a2idx.get(id)?;
-        self.tiers.get_mut(*t_idx)?.annotations.get_mut(*a_idx)
+        let (t_idx, a_idx) = self.index.a2idx(id)?;
+        self.tiers.get_mut(t_idx)?.annotations.get_mut(a_idx)
+    }
+
+    /// Returns time index for the specified tier, or for all tiers if `tier_id` is `None`.
+    /// If `Eaf` is not indexed, a temporary time index is built.
+    fn time_index(&self, tier_id: Option<&str>) -> Option<Cow<'_, TimeIndex>> {
+        if self.indexed {
+            return match tier_id {
+                Some(id) => self.index.t2time.get(id).map(Cow::Borrowed),
+                None => Some(Cow::Borrowed(&self.index.time)),
+            }
+        }
+
+        let mut index = Index::new(&self.tiers, &self.time_order);
+        match tier_id {
+            Some(id) => index.t2time.remove(id).map(Cow::Owned),
+            None => Some(Cow::Owned(index.time)),
+        }
+    }
+
+    /// Returns annotations for time index lookups.
+    /// If `Eaf` is not indexed, annotations are looked up without the index.
+    fn annotations_from_ids(&self, ids: &[&str]) -> Vec<&Annotation> {
+        if self.indexed {
+            return ids.iter()
+                .filter_map(|id| self.get_annotation(id))
+                .collect()
+        }
+        let id2a: HashMap<&str, &Annotation> = self.tiers.iter()
+            .flat_map(|t| t.annotations.iter())
+            .map(|a| (a.id(), a))
+            .collect();
+        ids.iter()
+            .filter_map(|id| id2a.get(id).copied())
+            .collect()
+    }
+
+    /// Returns annotations active at `ms` milliseconds, sorted on start time,
+    /// in the specified tier, or in all tiers if `tier_id` is `None`.
+    /// Time spans are half-open, i.e. an annotation ending at `ms` is not included,
+    /// but zero-length annotations at `ms` are.
+    ///
+    /// Uses the time index, see `Eaf::index()`. Referred annotations
+    /// use the time values of their main annotation.
+    pub fn annotations_at(&self, ms: i64, tier_id: Option<&str>) -> Vec<&Annotation> {
+        self.time_index(tier_id)
+            .map(|ti| self.annotations_from_ids(&ti.at(ms)))
+            .unwrap_or_default()
+    }
+
+    /// Returns annotations in the time span `start..end` milliseconds, sorted on start time,
+    /// in the specified tier, or in all tiers if `tier_id` is `None`.
+    /// `mode` specifies whether annotations should be contained in, overlap,
+    /// or start within the time span.
+    ///
+    /// Uses the time index, see `Eaf::index()`. Referred annotations
+    /// use the time values of their main annotation.
+    pub fn annotations_in(&self, start: i64, end: i64, mode: RangeMode, tier_id: Option<&str>) -> Vec<&Annotation> {
+        self.time_index(tier_id)
+            .map(|ti| self.annotations_from_ids(&ti.range(start, end, mode)))
+            .unwrap_or_default()
+    }
+
+    /// Returns the annotation nearest to `ms` milliseconds,
+    /// in the specified tier, or in all tiers if `tier_id` is `None`.
+    /// This is an annotation active at `ms` if there is one, otherwise
+    /// the annotation with the closest start or end time.
+    ///
+    /// Uses the time index, see `Eaf::index()`.
+    pub fn nearest(&self, ms: i64, tier_id: Option<&str>) -> Option<&Annotation> {
+        let time_index = self.time_index(tier_id)?;
+        let id = time_index.nearest(ms)?;
+        self.annotations_from_ids(&[id]).pop()
     }
 
     /// Returns a reference to main annotation ID for specified ref annotation ID.
@@ -1562,9 +1988,15 @@ impl Eaf {
             let new = new_id(id);
             let tier = self.get_tier_mut(id).ok_or_else(|| EafError::TierIdInvalid(id.to_owned()))?;
             tier.tier_id = new.to_owned();
-            let mut children = self.child_tiers_mut(id)?;
-            for child in children.iter_mut() {
-                child.parent_ref = Some(new.to_owned())
+            tier.annotations.iter_mut()
+                .filter(|a| a.tier_id().is_some())
+                .for_each(|a| a.set_tier_id(&new));
+            // Any referred tier, not only children of main tiers.
+            self.tiers.iter_mut()
+                .filter(|t| t.parent_ref.as_deref() == Some(id))
+                .for_each(|t| t.parent_ref = Some(new.to_owned()));
+            if self.indexed {
+                self.index.rename_tier(id, &new)
             }
         // Change all tier IDs, including references
         } else {
@@ -1574,7 +2006,14 @@ impl Eaf {
                     if let Some(parent) = t.parent_ref.as_deref() {
                         t.parent_ref = Some(new_id(&parent))
                     }
+                    t.annotations.iter_mut()
+                        .filter(|a| a.tier_id().is_some())
+                        .for_each(|a| a.set_tier_id(&t.tier_id));
                 });
+            // New IDs may coincide with IDs not yet changed, re-index in full.
+            if self.indexed {
+                self.index()
+            }
         }
         Ok(())
     }
@@ -1599,11 +2038,18 @@ impl Eaf {
         tier: Option<Tier>,
         stereotype: Option<&StereoType>,
     ) -> Result<(), EafError> {
+        if !self.indexed {
+            self.index()
+        }
+
         match tier {
             Some(t) => {
                 // TODO referred tier may have time slots depending on linguistic type/stereo type
                 if t.is_main() {
                     let ext_time_order = TimeOrder::from_hashmap(t.lookup_timeslots());
+                    for ts in ext_time_order.iter() {
+                        self.index.insert_timeslot(&ts.time_slot_id, ts.time_value);
+                    }
                     self.time_order.join(&ext_time_order); // TODO should remap, dedup if necessary as well
                 }
 
@@ -1621,12 +2067,205 @@ impl Eaf {
             None => self.tiers.push(Tier::default()),
         }
 
-        self.index();
-        self.derive()?;
+        // Index and derive the new tier only.
+        let t_idx = self.tiers.len() - 1;
+        self.index.insert_tier(&self.tiers[t_idx], t_idx);
+        let tier = &mut self.tiers[t_idx];
+        for annotation in tier.annotations.iter_mut() {
+            self.index.derive(annotation, &tier.tier_id)?;
+        }
 
         Ok(())
     }
 
+    /// Removes tier with specified ID. `recursive` also removes all tiers
+    /// referring to it, directly or indirectly. Otherwise, a tier with
+    /// referred tiers raises `EafError::TierDependentsExist`.
+    /// Time slots no longer in use are removed.
+    /// Linguistic types are kept, even if no longer in use.
+    ///
+    /// Returns removed tiers, with the specified tier first.
+    pub fn remove_tier(&mut self, tier_id: &str, recursive: bool) -> Result<Vec<Tier>, EafError> {
+        edit::remove_tier(self, tier_id, recursive)
+    }
+
+    /// Copies tier with specified ID as `new_id`, with the same parent tier
+    /// and linguistic type. `recursive` also copies all tiers referring to it,
+    /// directly or indirectly, with `suffix` appended to their tier IDs,
+    /// e.g. `gloss` becomes `gloss-copy` for `suffix = "-copy"`.
+    ///
+    /// Copied annotations get new annotation IDs, and new time slots,
+    /// except for time slots shared with the parent tier (e.g. `Time_Subdivision`).
+    /// Copies are added as the final tiers.
+    pub fn copy_tier(&mut self, tier_id: &str, new_id: &str, recursive: bool, suffix: &str) -> Result<(), EafError> {
+        edit::copy_tier(self, tier_id, new_id, recursive, suffix)
+    }
+
+    /// Sets a new parent tier for referred tier with specified ID.
+    /// Tiers referring to it follow along.
+    ///
+    /// The tier's stereotype must fit the new parent:
+    /// - `Included_In`, `Time_Subdivision`: The parent must be time-aligned,
+    ///   and each annotation must be contained within a parent annotation.
+    ///   Time subdivisions must also cover the parent annotations without gaps.
+    /// - `Symbolic_Association`, `Symbolic_Subdivision`: Annotations refer to the
+    ///   parent annotation at the midpoint of their time span, which must be unique.
+    ///   Symbolic subdivisions, in the tier or in the parent tier, evenly divide
+    ///   the time span of their parent annotation, as displayed in ELAN.
+    ///   Associations can not share parent annotation. Subdivisions with the same
+    ///   parent annotation form a new sequence, in tier order.
+    ///
+    /// Raises `EafError::TierParentInvalid` if the tier does not fit the parent,
+    /// or if the parent is one of the tier's dependents.
+    pub fn reparent_tier(&mut self, tier_id: &str, parent_id: &str) -> Result<(), EafError> {
+        edit::reparent_tier(self, tier_id, parent_id)
+    }
+
+    /// Adds a main tier with annotations for the time spans where all specified
+    /// tiers have annotations, similar to ELAN's "Create Annotations from Overlaps".
+    /// E.g. co-speech segments for two speaker tiers.
+    /// Values are set according to `rule`.
+    pub fn tier_from_overlaps(&mut self, tier_ids: &[&str], new_id: &str, rule: &ValueRule) -> Result<(), EafError> {
+        computed::tier_from_overlaps(self, tier_ids, new_id, rule)
+    }
+
+    /// Adds a main tier with annotations for the gaps between annotations
+    /// in the specified tiers, i.e. where none of the tiers have annotations,
+    /// similar to ELAN's "Create Annotations from Gaps".
+    /// E.g. silence segments for all speaker tiers.
+    ///
+    /// The first gap starts at 0. Since EAF does not store the media duration,
+    /// the last gap ends at `end` milliseconds, e.g. the media duration, if specified,
+    /// otherwise only gaps up to the end of the last annotation are included.
+    ///
+    /// Gaps have no source annotations, so only `ValueRule::Label`
+    /// results in non-empty values.
+    pub fn tier_from_gaps(
+        &mut self,
+        tier_ids: &[&str],
+        new_id: &str,
+        end: Option<i64>,
+        rule: &ValueRule
+    ) -> Result<(), EafError> {
+        computed::tier_from_gaps(self, tier_ids, new_id, end, rule)
+    }
+
+    /// Adds a main tier with annotations for the time spans of annotations in
+    /// `tier_id`, minus those of annotations in the tiers in `subtract`,
+    /// similar to ELAN's "Create Annotations from Subtraction".
+    /// The source annotation for each time span is the one in `tier_id`.
+    /// Values are set according to `rule`.
+    pub fn tier_from_subtraction(
+        &mut self,
+        tier_id: &str,
+        subtract: &[&str],
+        new_id: &str,
+        rule: &ValueRule
+    ) -> Result<(), EafError> {
+        computed::tier_from_subtraction(self, tier_id, subtract, new_id, rule)
+    }
+
+    /// Adds a main tier with annotations for the time spans covered by
+    /// any of the specified tiers, similar to ELAN's "Merge Tiers".
+    /// Overlapping annotations are merged into a single annotation.
+    /// Values are set according to `rule`.
+    pub fn tier_from_merge(&mut self, tier_ids: &[&str], new_id: &str, rule: &ValueRule) -> Result<(), EafError> {
+        computed::tier_from_merge(self, tier_ids, new_id, rule)
+    }
+
+    /// Adds a tier with the tokens in each annotation of the tier with specified ID,
+    /// similar to ELAN's "Tokenize Tier". Annotations are split into tokens with `tokenizer`.
+    ///
+    /// The new tier refers to the tokenized tier and is either:
+    /// - `Symbolic_Subdivision` (`time_subdivision = false`): Tokens form a
+    ///   `PREVIOUS_ANNOTATION` chain for each tokenized annotation.
+    /// - `Time_Subdivision` (`time_subdivision = true`): Tokens divide the time span of
+    ///   the tokenized annotation, proportionally to their character length.
+    ///   Requires a time-aligned tier, otherwise `EafError::TierParentInvalid` is raised.
+    ///
+    /// A linguistic type and constraint for the stereotype are added if missing.
+    /// Annotations without tokens are skipped.
+    pub fn tokenize_tier(
+        &mut self,
+        tier_id: &str,
+        new_id: &str,
+        tokenizer: &Tokenizer,
+        time_subdivision: bool
+    ) -> Result<(), EafError> {
+        tokenize::tokenize_tier(self, tier_id, new_id, tokenizer, time_subdivision)
+    }
+
+    /// Converts a symbolic referred tier (`Symbolic_Subdivision`, `Symbolic_Association`)
+    /// to a time-aligned tier (`Time_Subdivision`, `Included_In`), e.g. to get
+    /// time values for tokens. The parent tier must be time-aligned.
+    ///
+    /// Each parent annotation is divided between the annotations referring to it,
+    /// in `PREVIOUS_ANNOTATION` order, proportionally to their grapheme count
+    /// (see `AnnotationValue::grapheme_count()`), or to fixed weights
+    /// set in `options`. New time slots are either unaligned, or get
+    /// interpolated time values.
+    ///
+    /// Annotation IDs are kept, so dependent tiers remain valid.
+    /// A linguistic type and constraint for the stereotype are added if missing.
+    pub fn tier_to_aligned(&mut self, tier_id: &str, options: &AlignOptions) -> Result<(), EafError> {
+        tokenize::tier_to_aligned(self, tier_id, options)
+    }
+
+    /// Converts a time-aligned referred tier (`Time_Subdivision`, `Included_In`)
+    /// to a `Symbolic_Subdivision` tier. Annotations refer to the parent annotation
+    /// at the midpoint of their time span, and those with the same parent annotation
+    /// form a `PREVIOUS_ANNOTATION` chain, in time order.
+    /// Time slots no longer in use are removed.
+    ///
+    /// Raises `EafError::TierParentInvalid` if the tier has time-aligned dependent tiers.
+    pub fn tier_to_symbolic(&mut self, tier_id: &str) -> Result<(), EafError> {
+        tokenize::tier_to_symbolic(self, tier_id)
+    }
+
+    /// Computes inter-annotator agreement between the tier with ID `tier_id`
+    /// and the tier with ID `other_tier_id` in `other`. For two tiers in the same file,
+    /// use `eaf.agreement("coder1", &eaf, "coder2", &options)`.
+    ///
+    /// Annotations are matched on time span, and the result contains
+    /// Cohen's kappa (as ELAN's modified kappa), Krippendorff's alpha
+    /// for the time values of matched annotations, Staccato segmentation agreement,
+    /// and overlap percentage, together with a confusion matrix over
+    /// annotation values and the list of matched and unmatched annotations.
+    /// See `AgreementOptions` for settings.
+    pub fn agreement(
+        &self,
+        tier_id: &str,
+        other: &Eaf,
+        other_tier_id: &str,
+        options: &AgreementOptions
+    ) -> Result<Agreement, EafError> {
+        agreement::agreement(self, tier_id, other, other_tier_id, options)
+    }
+
+    /// Computes conversation analysis statistics for the speakers in main tiers,
+    /// grouped on participant: speaking time, turns, pauses within turns,
+    /// speech rate, overlap initiations, and gaps and overlaps between
+    /// consecutive speakers, with the floor transfer offset distribution.
+    /// Only annotations with time values are included.
+    /// See `ConversationOptions` for settings.
+    ///
+    /// The result can be serialized to JSON, or to CSV tables
+    /// for speakers and floor transfers.
+    pub fn conversation_stats(&self, options: &ConversationOptions) -> Result<ConversationStats, EafError> {
+        conversation::conversation_stats(self, options)
+    }
+
+    /// Compares with `other`, e.g. a revised version of the same file.
+    /// Tiers are matched on tier ID, and annotations on time span and value
+    /// rather than annotation ID, since IDs are often renumbered on save.
+    /// Lists added, removed, retimed and relabelled annotations,
+    /// and changes to tier attributes, linguistic types and controlled vocabularies.
+    ///
+    /// `EafDiff` implements `Display` for a human-readable summary.
+    pub fn diff(&self, other: &Eaf) -> Result<EafDiff, EafError> {
+        diff::diff(self, other)
+    }
+
     pub fn add_linguistic_type(&mut self, ling_type: &LinguisticType, add_constraint: bool) {
         if add_constraint {
             match &ling_type.constraints {
@@ -1728,9 +2367,12 @@ impl Eaf {
     /// Generates and sets UUID v4 for all annotations.
     /// Part of EAF merge process.
     ///
-    /// Returns hashmap where key = annotation UUID,
-    /// value = old annotation ID.
-    pub(crate) fn tag(&mut self) {
+    /// Annotation references and previous annotation references
+    /// are updated to the new IDs. Previous annotation references
+    /// to annotations that do not exist are removed.
+    /// Returns error if a referred annotation refers to
+    /// an annotation that does not exist.
+    pub(crate) fn tag(&mut self) -> Result<(), EafError> {
         // 1. set primary ID for all annotatations
         let uuid2aid: HashMap<String, String> = self.tiers.iter_mut()
             .flat_map(|t| t.tag())
@@ -1740,18 +2382,21 @@ impl Eaf {
             .map(|(k, v)| (v.as_str(), k.as_str()))
             .collect();
         // 3. set referred annotation IDs (annotation attribute `ANNOTATION_REF`)
-        self.tiers.iter_mut()
-            .for_each(|t| {
-                t.annotations.iter_mut()
-                    .for_each(|a| {
-                        if let Some(id) = a.ref_id() {
-                            a.set_ref_id(
-                                aid2uuid.get(id)
-                                    .expect("Failed to map annotation ref ID to UUID")
-                            )
-                        }
-                    })
-            })
+        //    and previous annotation IDs (annotation attribute `PREVIOUS_ANNOTATION`)
+        for annotation in self.tiers.iter_mut().flat_map(|t| t.annotations.iter_mut()) {
+            if let Some(id) = annotation.ref_id() {
+                let uuid = aid2uuid.get(id)
+                    .ok_or(EafError::AnnotationRefMissing)?;
+                annotation.set_ref_id(uuid)
+            }
+            match annotation.previous().map(|id| aid2uuid.get(id)) {
+                Some(Some(uuid)) => annotation.set_previous(uuid),
+                Some(None) => annotation.unset_previous(),
+                None => (),
+            }
+        }
+
+        Ok(())
     }
 
     /// Generates and sets new time order based on derived
@@ -1772,10 +2417,84 @@ impl Eaf {
         };
     }
 
+    /// Returns the EAF version, if supported.
+    pub fn eaf_version(&self) -> Option<EafVersion> {
+        EafVersion::parse(&self.version)
+    }
+
+    /// Converts the EAF to the specified version,
+    /// updating version, format, and schema location.
+    ///
+    /// Controlled vocabularies are rewritten between the v2.7 form (`CV_ENTRY`, `DESCRIPTION` attribute)
+    /// and the multilingual v2.8+ form (`CV_ENTRY_ML`/`CVE_VALUE`, `DESCRIPTION` elements).
+    /// On upgrade, a `LANGUAGE` is added if there are none (`und`, undetermined),
+    /// and annotations with values that match an entry in the tier's CV are set to refer to it.
+    /// On downgrade to v2.7, only the first value for each CV entry is kept,
+    /// and languages, language and CV entry references, and license are removed.
+    ///
+    /// Returns information lost when downgrading.
+    pub fn convert_version(&mut self, target: EafVersion) -> Vec<ConversionLoss> {
+        convert(self, target)
+    }
+
+    /// Validates the EAF and returns all issues found,
+    /// e.g. references to time slots, annotations or linguistic types
+    /// that do not exist, duplicate IDs, or overlapping annotations.
+    /// See `ValidationReport`.
+    ///
+    /// Does not substitute validating against the EAF XML-schema.
+    pub fn validate(&self) -> ValidationReport {
+        ValidationReport::new(self)
+    }
+
+    /// Checks that annotations in referred tiers follow the constraints
+    /// for the tier's stereotype, and returns all violations found.
+    /// - `Time_Subdivision`: annotations must cover the parent annotation without gaps.
+    /// - `Included_In`: annotations must be within the parent annotation's time span.
+    /// - `Symbolic_Association`: at most one annotation per parent annotation.
+    /// - `Symbolic_Subdivision`: annotations referring to the same parent annotation
+    /// must form a single `PREVIOUS_ANNOTATION` chain.
+    ///
+    /// If `repair` is `true`, violations that can be fixed automatically are fixed,
+    /// and the violations that remain are returned. Gaps in time subdivisions
+    /// are closed, included annotations are trimmed to the parent annotation,
+    /// multiple symbolic associations are joined into one, and previous annotation
+    /// references are re-set in the order annotations appear in the tier.
+    /// Annotations outside of any parent annotation can not be repaired.
+    pub fn check_stereotypes(&mut self, repair: bool) -> Result<Vec<ValidationIssue>, EafError> {
+        if repair {
+            repair_stereotypes(self)?;
+            self.index();
+            self.derive()?;
+        }
+        Ok(stereotype_violations(self))
+    }
+
+    /// Reads an ELAN-file from disk and validates it.
+    /// See `Eaf::validate()`.
+    ///
+    /// Unlike `Eaf::read()`, broken references do not raise an error,
+    /// since time values etc are not derived.
+    pub fn validate_file(path: &Path) -> Result<ValidationReport, EafError> {
+        Ok(Self::de(path, false)?.validate())
+    }
+
     /// Merges EAF files. Tier with the same ID will be merged.
     /// Returns error if annotatations overlap.
     pub fn merge(eafs: &[Self]) -> Result<Self, EafError> {
-        merge_eafs(eafs.to_owned())
+        merge_eafs(eafs.to_owned(), OverlapStrategy::None)
+    }
+
+    /// Merges EAF files. Tier with the same ID will be merged.
+    /// Overlapping annotations in the same tier are resolved
+    /// according to `strategy`.
+    ///
+    /// Annotations in referred tiers are kept consistent
+    /// with their parent annotations, i.e. these are discarded
+    /// together with their parent, moved to the annotation their parent
+    /// was joined with, or trimmed to their parent's new time span.
+    pub fn merge_with(eafs: &[Self], strategy: OverlapStrategy) -> Result<Self, EafError> {
+        merge_eafs(eafs.to_owned(), strategy)
     }
 
     /// Checks if specified ID exists as either tier ID or annotation ID.
@@ -1784,9 +2503,9 @@ impl Eaf {
     pub fn exists(&self, id: &str) -> (bool, bool, bool) {
         (
             // use Index to check if `id` exists.
-            self.index.t2a.keys().any(|i| i == id), // tier id
-            self.index.a2t.keys().any(|i| i == id), // annotation id
-            self.index.ts2tv.keys().any(|i| i == id), // timeslot id
+            self.index.t2a.contains_key(id), // tier id
+            self.index.a2t.contains_key(id), // annotation id
+            self.index.ts2tv.contains_key(id), // timeslot id
         )
     }
 }
@@ -0,0 +1,1169 @@
+//! Editing operations, similar to those in ELAN.
+//!
+//! For annotations: splitting an annotation, merging it with an adjacent annotation,
+//! and moving or resizing it. Dependent annotations follow the edited annotation:
+//! - Referred annotations follow their parent annotation. On split, symbolic subdivisions
+//!   are distributed between the two parts according to their position, as if evenly
+//!   dividing the time span. On merge, symbolic subdivisions are concatenated into
+//!   a single chain, and symbolic associations in the same tier are joined.
+//! - Time-aligned annotations in dependent tiers (e.g. `Included_In`, `Time_Subdivision`),
+//!   that are contained within the annotation, are moved together with it.
+//!   On resize, those sharing a boundary time slot with the annotation follow the boundary.
+//!
+//! For tiers: removing or copying a tier together with its dependent tiers,
+//! and moving a referred tier to a new parent tier.
+//!
+//! Time slots shared with other annotations are only changed if all annotations using
+//! them follow the edit, otherwise a new time slot is created.
+//! Time slots no longer in use are removed. Index and derived values are updated
+//! incrementally.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{Annotation, Eaf, EafError, RangeMode, StereoType, Tier, TimeSlot};
+
+use super::{
+    merge::join_values,
+    validate::{check_time_subdivision, tier_stereotype, Location},
+};
+
+/// Alignable annotation with time slot references and time values.
+struct Aligned {
+    /// Index in `Eaf.tiers`.
+    t_idx: usize,
+    /// Index in `tier.annotations`.
+    a_idx: usize,
+    ts1: String,
+    ts2: String,
+    start: i64,
+    end: i64,
+}
+
+/// Returns alignable annotation with specified ID.
+/// Derives (and indexes) `Eaf` first, if not done.
+fn aligned(eaf: &mut Eaf, id: &str) -> Result<Aligned, EafError> {
+    if !eaf.derived {
+        eaf.derive()?
+    }
+
+    let (t_idx, a_idx) = eaf.index.a2idx(id)
+        .ok_or_else(|| EafError::AnnotationIdInvalid(id.to_owned()))?;
+    let annotation = &eaf.tiers[t_idx].annotations[a_idx];
+    if annotation.is_ref() {
+        return Err(EafError::AnnotationMainExpected(id.to_owned()))
+    }
+    let (ts1, ts2) = annotation.ts_ref()
+        .ok_or_else(|| EafError::TimeslotRefMissing(id.to_owned()))?;
+    let (Some(start), Some(end)) = annotation.ts_val() else {
+        return Err(EafError::TimeslotValMissing(id.to_owned()))
+    };
+
+    Ok(Aligned { t_idx, a_idx, ts1, ts2, start, end })
+}
+
+/// Derives values for an annotation and all annotations referring to it.
+fn rederive(eaf: &mut Eaf, id: &str) -> Result<(), EafError> {
+    let mut a_ids = vec![id.to_owned()];
+    a_ids.extend(eaf.index.dependents(id));
+    for a_id in a_ids.iter() {
+        let Some((t_idx, a_idx)) = eaf.index.a2idx(a_id) else {
+            continue
+        };
+        let tier = &mut eaf.tiers[t_idx];
+        eaf.index.derive(&mut tier.annotations[a_idx], &tier.tier_id)?;
+    }
+    Ok(())
+}
+
+/// Adds a new time slot in time value order, and returns its ID.
+fn add_timeslot(eaf: &mut Eaf, val: i64) -> String {
+    let id = eaf.time_order.gen_id();
+    eaf.time_order.insert(&TimeSlot::new(&id, Some(val)));
+    eaf.index.insert_timeslot(&id, Some(val));
+    id
+}
+
+/// Removes the specified time slots if no annotation uses them.
+pub(crate) fn remove_unused_timeslots(eaf: &mut Eaf, ts_ids: &[String]) {
+    for ts_id in ts_ids.iter() {
+        if eaf.index.ts2a.get(ts_id).is_none_or(|a_ids| a_ids.is_empty()) {
+            eaf.time_order.remove(ts_id);
+            eaf.index.remove_timeslot(ts_id);
+        }
+    }
+}
+
+/// Sets time slot references for an alignable annotation.
+fn set_ts(eaf: &mut Eaf, id: &str, ts1: &str, ts2: &str) -> Result<(), EafError> {
+    eaf.get_annotation_mut(id)
+        .ok_or_else(|| EafError::AnnotationIdInvalid(id.to_owned()))?
+        .set_ts_ref(ts1, ts2);
+    eaf.index.set_annotation_ts(id, ts1, ts2);
+    rederive(eaf, id)
+}
+
+/// Sets referred annotation for a referred annotation.
+fn set_ref(eaf: &mut Eaf, id: &str, ref_id: &str) -> Result<(), EafError> {
+    eaf.get_annotation_mut(id)
+        .ok_or_else(|| EafError::AnnotationIdInvalid(id.to_owned()))?
+        .set_ref_id(ref_id);
+    eaf.index.set_annotation_ref(id, ref_id);
+    rederive(eaf, id)
+}
+
+/// Returns `(tier ID, annotation ID)` for annotations directly
+/// referring to the specified annotation, in tier order.
+fn children(eaf: &Eaf, id: &str) -> Vec<(String, String)> {
+    let mut children: Vec<((usize, usize), String)> = eaf.index.ref2a.get(id)
+        .map(|a_ids| a_ids.iter()
+            .filter_map(|a_id| Some((eaf.index.a2idx(a_id)?, a_id.to_owned())))
+            .collect())
+        .unwrap_or_default();
+    children.sort();
+    children.into_iter()
+        .map(|((t_idx, _), a_id)| (eaf.tiers[t_idx].tier_id.to_owned(), a_id))
+        .collect()
+}
+
+fn is_subdivision(eaf: &Eaf, tier_id: &str) -> bool {
+    eaf.get_tier(tier_id)
+        .and_then(|t| tier_stereotype(eaf, t))
+        == Some(StereoType::SymbolicSubdivision)
+}
+
+/// Returns ID:s for the annotation and the time-aligned annotations
+/// in dependent tiers that are contained within its time span.
+fn followers(eaf: &Eaf, id: &str, annotation: &Aligned) -> HashSet<String> {
+    let mut followers: HashSet<String> = HashSet::from([id.to_owned()]);
+    for tier_id in descendant_tiers(eaf, &eaf.tiers[annotation.t_idx].tier_id).iter() {
+        followers.extend(
+            eaf.annotations_in(annotation.start, annotation.end, RangeMode::Contained, Some(tier_id))
+                .iter()
+                .filter(|a| !a.is_ref())
+                .map(|a| a.id().to_owned())
+        );
+    }
+    followers
+}
+
+/// Returns ID:s for tiers referring to the specified tier,
+/// directly or via other tiers, with parent tiers before their children.
+pub(crate) fn descendant_tiers(eaf: &Eaf, tier_id: &str) -> Vec<String> {
+    let mut tier_ids = vec![tier_id.to_owned()];
+    let mut i = 0;
+    while let Some(parent_id) = tier_ids.get(i) {
+        let children: Vec<String> = eaf.tiers.iter()
+            .filter(|t| t.parent_ref.as_ref() == Some(parent_id))
+            // Circular references are ignored.
+            .filter(|t| !tier_ids.contains(&t.tier_id))
+            .map(|t| t.tier_id.to_owned())
+            .collect();
+        tier_ids.extend(children);
+        i += 1;
+    }
+    tier_ids.remove(0);
+    tier_ids
+}
+
+/// Returns annotation indices in `tier`, grouped on referred annotation ID,
+/// in the order of first appearance. Symbolic subdivisions are ordered
+/// according to their `PREVIOUS_ANNOTATION` chain, other annotations
+/// in tier order.
+pub(crate) fn sequences(tier: &Tier) -> Vec<(&str, Vec<usize>)> {
+    let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+    for (a_idx, annotation) in tier.annotations.iter().enumerate() {
+        let Some(ref_id) = annotation.ref_id() else {
+            continue
+        };
+        match groups.iter_mut().find(|(r, _)| *r == ref_id) {
+            Some((_, group)) => group.push(a_idx),
+            None => groups.push((ref_id, vec![a_idx])),
+        }
+    }
+
+    for (_, group) in groups.iter_mut() {
+        // Previous annotation ID -> annotation index
+        let next: HashMap<&str, usize> = group.iter()
+            .filter_map(|i| Some((tier.annotations[*i].previous()?, *i)))
+            .collect();
+        let mut ordered: Vec<usize> = Vec::new();
+        let firsts: Vec<usize> = group.iter()
+            .copied()
+            .filter(|i| tier.annotations[*i].previous()
+                .is_none_or(|prev| !group.iter().any(|j| tier.annotations[*j].id() == prev)))
+            .collect();
+        for first in firsts {
+            let mut idx = Some(first);
+            while let Some(i) = idx.filter(|i| !ordered.contains(i)) {
+                ordered.push(i);
+                idx = next.get(tier.annotations[i].id()).copied();
+            }
+        }
+        // Circular references are kept in tier order.
+        ordered.extend(group.iter().filter(|i| !ordered.contains(i)).collect::<Vec<_>>());
+        *group = ordered;
+    }
+
+    groups
+}
+
+/// Returns time spans for the annotations in a tier, with symbolic subdivisions
+/// evenly dividing the time span of their parent annotation, in the order of
+/// their `PREVIOUS_ANNOTATION` chain. Symbolic associations
+/// get the time span of their parent annotation.
+fn symbolic_spans(eaf: &Eaf, tier_id: &str) -> HashMap<String, (f64, f64)> {
+    symbolic_spans_depth(eaf, tier_id, 0)
+}
+
+fn symbolic_spans_depth(eaf: &Eaf, tier_id: &str, depth: usize) -> HashMap<String, (f64, f64)> {
+    let mut spans: HashMap<String, (f64, f64)> = HashMap::new();
+    // Circular references are ignored.
+    let Some(tier) = eaf.get_tier(tier_id).filter(|_| depth <= eaf.tiers.len()) else {
+        return spans
+    };
+
+    let stereotype = tier_stereotype(eaf, tier);
+    let parent_id = match stereotype {
+        Some(StereoType::SymbolicAssociation | StereoType::SymbolicSubdivision) => tier.parent_ref.as_deref(),
+        _ => None,
+    };
+    let Some(parent_id) = parent_id else {
+        for annotation in tier.annotations.iter() {
+            if let (Some(start), Some(end)) = annotation.ts_val() {
+                spans.insert(annotation.id().to_owned(), (start as f64, end as f64));
+            }
+        }
+        return spans
+    };
+
+    let parent_spans = symbolic_spans_depth(eaf, parent_id, depth + 1);
+    for (ref_id, group) in sequences(tier) {
+        let Some((start, end)) = parent_spans.get(ref_id).copied() else {
+            continue
+        };
+        let step = (end - start) / group.len() as f64;
+        for (i, a_idx) in group.iter().enumerate() {
+            let span = match stereotype {
+                Some(StereoType::SymbolicSubdivision) => (start + step * i as f64, start + step * (i + 1) as f64),
+                _ => (start, end),
+            };
+            spans.insert(tier.annotations[*a_idx].id().to_owned(), span);
+        }
+    }
+
+    spans
+}
+
+/// Ensures that an annotation in a time-aligned referred tier
+/// is contained within an annotation in the parent tier.
+fn check_parent(eaf: &Eaf, id: &str, start: i64, end: i64) -> Result<(), EafError> {
+    match eaf.index.a2t.get(id)
+        .and_then(|t| eaf.get_tier(t))
+        .and_then(|t| t.parent_ref.as_deref())
+    {
+        Some(parent_id) => check_parent_tier(eaf, id, parent_id, start, end),
+        None => Ok(()),
+    }
+}
+
+/// Ensures that an annotation in a time-aligned referred tier
+/// is contained within an annotation in the specified parent tier.
+fn check_parent_tier(eaf: &Eaf, id: &str, parent_id: &str, start: i64, end: i64) -> Result<(), EafError> {
+    let contained = eaf.annotations_at(start, Some(parent_id)).iter()
+        .any(|p| matches!(p.ts_val(), (Some(p_start), Some(p_end)) if p_start <= start && p_end >= end));
+
+    match contained {
+        true => Ok(()),
+        false => Err(EafError::AnnotationParentMissing(id.to_owned())),
+    }
+}
+
+/// Ensures that new time values for time slots result in valid time spans
+/// for the annotations that follow the edit, that do not overlap
+/// other annotations in the same tier.
+fn check_spans(eaf: &Eaf, followers: &HashSet<String>, changes: &HashMap<String, i64>) -> Result<(), EafError> {
+    let value = |ts: &str| changes.get(ts).copied()
+        .or_else(|| eaf.index.ts2tv.get(ts).copied().flatten());
+
+    for id in followers.iter() {
+        let Some((ts1, ts2)) = eaf.index.a2ts.get(id) else {
+            continue
+        };
+        if !changes.contains_key(ts1) && !changes.contains_key(ts2) {
+            continue
+        }
+        let (Some(start), Some(end)) = (value(ts1), value(ts2)) else {
+            continue
+        };
+        if start < 0 {
+            return Err(EafError::ValueTooSmall(start))
+        }
+        if start >= end {
+            return Err(EafError::TimeSpanInvalid((start, end)))
+        }
+        let tier_id = eaf.index.a2t.get(id).map(|t| t.as_str());
+        if eaf.annotations_in(start, end, RangeMode::Overlapping, tier_id).iter()
+            .any(|a| !followers.contains(a.id()))
+        {
+            return Err(EafError::AnnotationOverlap)
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets new time values for time slots. Time slots also used by annotations
+/// that do not follow the edit are left as is, and a new time slot is created
+/// for the annotations that do.
+fn retime(eaf: &mut Eaf, followers: &HashSet<String>, changes: &HashMap<String, i64>) -> Result<(), EafError> {
+    for (ts_id, val) in changes.iter() {
+        let users = eaf.index.ts2a.get(ts_id).cloned().unwrap_or_default();
+
+        if users.iter().all(|a_id| followers.contains(a_id)) {
+            eaf.set_ts_val(ts_id, Some(*val))?;
+            // Keep time order sorted on time value.
+            if let Some(ts) = eaf.time_order.remove(ts_id) {
+                eaf.time_order.insert(&ts)
+            }
+            continue
+        }
+
+        let new_id = add_timeslot(eaf, *val);
+        for a_id in users.iter().filter(|a_id| followers.contains(*a_id)) {
+            let Some((ts1, ts2)) = eaf.index.a2ts.get(a_id).cloned() else {
+                continue
+            };
+            let swap = |ts: String| if &ts == ts_id { new_id.to_owned() } else { ts };
+            set_ts(eaf, a_id, &swap(ts1), &swap(ts2))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits alignable annotation at `ms` milliseconds, see `Eaf::split_annotation()`.
+pub(crate) fn split(eaf: &mut Eaf, id: &str, ms: i64) -> Result<String, EafError> {
+    let annotation = aligned(eaf, id)?;
+    if ms <= annotation.start {
+        return Err(EafError::ValueTooSmall(ms))
+    }
+    if ms >= annotation.end {
+        return Err(EafError::ValueTooLarge(ms))
+    }
+    let tier_id = eaf.tiers[annotation.t_idx].tier_id.to_owned();
+
+    // Time-aligned annotations in dependent tiers must not cross the split.
+    // A time slot at the split, e.g. a time subdivision boundary, is reused.
+    let mut ts_split: Option<String> = None;
+    for a_id in followers(eaf, id, &annotation).iter().filter(|a_id| *a_id != id) {
+        let Some((ts1, ts2)) = eaf.index.a2ts.get(a_id) else {
+            continue
+        };
+        let (Some(start), Some(end)) = (eaf.ts_val(ts1), eaf.ts_val(ts2)) else {
+            continue
+        };
+        if start < ms && end > ms {
+            return Err(EafError::AnnotationParentMissing(a_id.to_owned()))
+        }
+        if start == ms {
+            ts_split = Some(ts1.to_owned())
+        } else if end == ms {
+            ts_split = Some(ts2.to_owned())
+        }
+    }
+
+    // First part keeps the time slot for the start,
+    // second part the time slot for the end.
+    let ts_split = ts_split.unwrap_or_else(|| add_timeslot(eaf, ms));
+    set_ts(eaf, id, &annotation.ts1, &ts_split)?;
+
+    let new_id = eaf.generate_a_id().unwrap_or_else(|| "a1".to_owned());
+    let mut second = Annotation::alignable("", &new_id, &ts_split, &annotation.ts2);
+    eaf.index.derive(&mut second, &tier_id)?;
+    eaf.tiers[annotation.t_idx].annotations.insert(annotation.a_idx + 1, second.to_owned());
+    eaf.index.insert_annotation(&second, &tier_id, annotation.a_idx + 1);
+
+    // Symbolic subdivisions with a midpoint after the split
+    // are moved to the second part.
+    let duration = annotation.end - annotation.start;
+    let mut subdivisions: HashMap<String, Vec<String>> = HashMap::new();
+    for (child_tier, child) in children(eaf, id) {
+        if is_subdivision(eaf, &child_tier) {
+            subdivisions.entry(child_tier).or_default().push(child);
+        }
+    }
+    for a_ids in subdivisions.values() {
+        let len = a_ids.len() as i64;
+        let first = (0..len)
+            .position(|i| annotation.start + (2 * i + 1) * duration / (2 * len) >= ms)
+            .unwrap_or(a_ids.len());
+        for a_id in a_ids[first..].iter() {
+            set_ref(eaf, a_id, &new_id)?;
+        }
+        if let Some(a_id) = a_ids.get(first)
+            && let Some(first) = eaf.get_annotation_mut(a_id)
+        {
+            first.unset_previous();
+        }
+    }
+
+    Ok(new_id)
+}
+
+/// Merges alignable annotation with the next annotation in the same tier,
+/// or the previous one if `next` is `false`, see `Eaf::merge_annotation_next()`.
+pub(crate) fn merge(eaf: &mut Eaf, id: &str, next: bool) -> Result<String, EafError> {
+    let annotation = aligned(eaf, id)?;
+    let adjacent_id = match next {
+        true => annotation.a_idx.checked_add(1),
+        false => annotation.a_idx.checked_sub(1),
+    }
+        .and_then(|a_idx| eaf.tiers[annotation.t_idx].annotations.get(a_idx))
+        .map(|a| a.id().to_owned())
+        .ok_or_else(|| EafError::AnnotationAdjacentMissing(id.to_owned()))?;
+    let adjacent = aligned(eaf, &adjacent_id)?;
+
+    let ((first_id, first), (second_id, second)) = match next {
+        true => ((id.to_owned(), annotation), (adjacent_id, adjacent)),
+        false => ((adjacent_id, adjacent), (id.to_owned(), annotation)),
+    };
+
+    check_parent(eaf, &first_id, first.start, second.end)?;
+
+    let value = join_values(
+        eaf.tiers[first.t_idx].annotations[first.a_idx].to_str(),
+        eaf.tiers[second.t_idx].annotations[second.a_idx].to_str()
+    );
+    eaf.tiers[first.t_idx].annotations[first.a_idx].set_value(&value);
+
+    set_ts(eaf, &first_id, &first.ts1, &second.ts2)?;
+    merge_dependents(eaf, &first_id, &second_id)?;
+    eaf.remove_annotation(&second_id)?;
+    remove_unused_timeslots(eaf, &[first.ts2]);
+
+    Ok(first_id)
+}
+
+/// Moves annotations referring to `removed` to `kept`. Symbolic subdivisions
+/// are appended to those of `kept`, other referred annotations are joined
+/// with those of `kept` in the same tier, if any.
+fn merge_dependents(eaf: &mut Eaf, kept: &str, removed: &str) -> Result<(), EafError> {
+    let kept_children = children(eaf, kept);
+
+    for (tier_id, child) in children(eaf, removed) {
+        let last = kept_children.iter()
+            .rev()
+            .find(|(t, _)| *t == tier_id)
+            .map(|(_, a_id)| a_id.to_owned());
+
+        if is_subdivision(eaf, &tier_id) {
+            set_ref(eaf, &child, kept)?;
+            if let (Some(prev), Some(annotation)) = (last, eaf.get_annotation_mut(&child))
+                && annotation.previous().is_none()
+            {
+                annotation.set_previous(&prev);
+            }
+        } else if let Some(target) = last {
+            let value = join_values(
+                eaf.get_annotation(&target).map(|a| a.to_str()).unwrap_or_default(),
+                eaf.get_annotation(&child).map(|a| a.to_str()).unwrap_or_default(),
+            );
+            if let Some(annotation) = eaf.get_annotation_mut(&target) {
+                annotation.set_value(&value);
+            }
+            merge_dependents(eaf, &target, &child)?;
+            eaf.remove_annotation(&child)?;
+        } else {
+            set_ref(eaf, &child, kept)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves alignable annotation `shift_ms` milliseconds, see `Eaf::move_annotation()`.
+pub(crate) fn shift(eaf: &mut Eaf, id: &str, shift_ms: i64) -> Result<(), EafError> {
+    let annotation = aligned(eaf, id)?;
+    let followers = followers(eaf, id, &annotation);
+
+    let changes: HashMap<String, i64> = followers.iter()
+        .filter_map(|a_id| eaf.index.a2ts.get(a_id))
+        .flat_map(|(ts1, ts2)| [ts1, ts2])
+        .filter_map(|ts| Some((ts.to_owned(), eaf.index.ts2tv.get(ts).copied()?? + shift_ms)))
+        .collect();
+
+    check_parent(eaf, id, annotation.start + shift_ms, annotation.end + shift_ms)?;
+    check_spans(eaf, &followers, &changes)?;
+    retime(eaf, &followers, &changes)
+}
+
+/// Sets new start and/or end time for alignable annotation,
+/// see `Eaf::resize_annotation()`.
+pub(crate) fn resize(eaf: &mut Eaf, id: &str, start: Option<i64>, end: Option<i64>) -> Result<(), EafError> {
+    let annotation = aligned(eaf, id)?;
+    let start_ms = start.unwrap_or(annotation.start);
+    let end_ms = end.unwrap_or(annotation.end);
+    if start_ms >= end_ms {
+        return Err(EafError::TimeSpanInvalid((start_ms, end_ms)))
+    }
+
+    let followers = followers(eaf, id, &annotation);
+
+    let mut changes: HashMap<String, i64> = HashMap::new();
+    if start_ms != annotation.start {
+        changes.insert(annotation.ts1.to_owned(), start_ms);
+    }
+    if end_ms != annotation.end {
+        changes.insert(annotation.ts2.to_owned(), end_ms);
+    }
+
+    check_parent(eaf, id, start_ms, end_ms)?;
+    check_spans(eaf, &followers, &changes)?;
+
+    // Dependent annotations not following the boundaries
+    // must still be contained within the new time span.
+    for a_id in followers.iter().filter(|a_id| *a_id != id) {
+        if let Some((ts1, ts2)) = eaf.index.a2ts.get(a_id) {
+            let value = |ts: &str| changes.get(ts).copied()
+                .or_else(|| eaf.index.ts2tv.get(ts).copied().flatten());
+            if let (Some(a_start), Some(a_end)) = (value(ts1), value(ts2))
+                && (a_start < start_ms || a_end > end_ms)
+            {
+                return Err(EafError::AnnotationParentMissing(a_id.to_owned()))
+            }
+        }
+    }
+
+    retime(eaf, &followers, &changes)
+}
+
+/// Removes tier, optionally with its dependent tiers, see `Eaf::remove_tier()`.
+pub(crate) fn remove_tier(eaf: &mut Eaf, tier_id: &str, recursive: bool) -> Result<Vec<Tier>, EafError> {
+    if !eaf.indexed {
+        eaf.index()
+    }
+    if !eaf.index.t2idx.contains_key(tier_id) {
+        return Err(EafError::TierIdInvalid(tier_id.to_owned()))
+    }
+
+    let mut tier_ids = vec![tier_id.to_owned()];
+    tier_ids.extend(descendant_tiers(eaf, tier_id));
+    if !recursive && tier_ids.len() > 1 {
+        return Err(EafError::TierDependentsExist(tier_id.to_owned()))
+    }
+
+    // Dependent tiers first, so that references are valid while removing.
+    let mut removed: Vec<Tier> = Vec::new();
+    let mut ts_ids: Vec<String> = Vec::new();
+    for id in tier_ids.iter().rev() {
+        let Some(t_idx) = eaf.index.t2idx.get(id).copied() else {
+            continue
+        };
+        eaf.index.remove_tier(id);
+        let tier = eaf.tiers.remove(t_idx);
+        ts_ids.extend(tier.annotations.iter()
+            .filter_map(|a| a.ts_ref())
+            .flat_map(|(ref1, ref2)| [ref1, ref2]));
+        removed.push(tier);
+    }
+    removed.reverse();
+
+    ts_ids.sort();
+    ts_ids.dedup();
+    remove_unused_timeslots(eaf, &ts_ids);
+
+    Ok(removed)
+}
+
+/// Copies tier, optionally with its dependent tiers, see `Eaf::copy_tier()`.
+pub(crate) fn copy_tier(
+    eaf: &mut Eaf,
+    tier_id: &str,
+    new_id: &str,
+    recursive: bool,
+    suffix: &str
+) -> Result<(), EafError> {
+    if !eaf.indexed {
+        eaf.index()
+    }
+    let Some(tier) = eaf.get_tier(tier_id) else {
+        return Err(EafError::TierIdInvalid(tier_id.to_owned()))
+    };
+
+    let mut tier_ids = vec![tier_id.to_owned()];
+    if recursive {
+        tier_ids.extend(descendant_tiers(eaf, tier_id));
+    }
+
+    // Tier ID -> new tier ID
+    let mut tier_map: HashMap<String, String> = HashMap::new();
+    for (i, id) in tier_ids.iter().enumerate() {
+        let new = match i {
+            0 => new_id.to_owned(),
+            _ => format!("{id}{suffix}"),
+        };
+        if eaf.index.t2idx.contains_key(&new) || tier_map.values().any(|t| *t == new) {
+            return Err(EafError::TierIdExists(new))
+        }
+        tier_map.insert(id.to_owned(), new);
+    }
+
+    // Time slots shared with tiers the copied tier depends on,
+    // e.g. a time subdivision's boundaries, are kept as is.
+    // Other time slots are copied.
+    let mut ancestors: HashSet<&str> = HashSet::new();
+    let mut parent = tier.parent_ref.as_deref();
+    while let Some(parent_id) = parent {
+        if !ancestors.insert(parent_id) {
+            break
+        }
+        parent = eaf.get_tier(parent_id).and_then(|t| t.parent_ref.as_deref());
+    }
+    let shared = |ts_id: &str| eaf.index.ts2a.get(ts_id)
+        .is_some_and(|a_ids| a_ids.iter()
+            .any(|a_id| eaf.index.a2t.get(a_id).is_some_and(|t| ancestors.contains(t.as_str()))));
+
+    let annotations = tier_ids.iter()
+        .filter_map(|id| eaf.get_tier(id))
+        .flat_map(|t| t.annotations.iter());
+    let mut ts_ids: Vec<String> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    for (ref1, ref2) in annotations.clone().filter_map(|a| a.ts_ref()) {
+        for ts_id in [ref1, ref2] {
+            if !shared(&ts_id) && seen.insert(ts_id.to_owned()) {
+                ts_ids.push(ts_id);
+            }
+        }
+    }
+    let new_ts_ids = eaf.time_order.gen_id_multi(ts_ids.len());
+    let ts_map: HashMap<String, String> = ts_ids.iter().cloned()
+        .zip(new_ts_ids.iter().cloned())
+        .collect();
+
+    // Annotation ID -> new annotation ID
+    let a_ids: Vec<String> = annotations.clone().map(|a| a.id().to_owned()).collect();
+    let new_a_ids = match eaf.generate_a_id_multi(a_ids.len()) {
+        // No numerical annotation ID:s in use
+        ids if ids.len() < a_ids.len() => (1..=a_ids.len()).map(|n| format!("a{n}")).collect(),
+        ids => ids,
+    };
+    let a_map: HashMap<String, String> = a_ids.into_iter()
+        .zip(new_a_ids)
+        .collect();
+
+    let mut tiers: Vec<Tier> = Vec::new();
+    for id in tier_ids.iter() {
+        let Some(mut tier) = eaf.get_tier(id).cloned() else {
+            continue
+        };
+        tier.tier_id = tier_map[id].to_owned();
+        if let Some(parent_id) = tier.parent_ref.as_ref().and_then(|p| tier_map.get(p)) {
+            tier.parent_ref = Some(parent_id.to_owned());
+        }
+        for annotation in tier.annotations.iter_mut() {
+            if let Some(a_id) = a_map.get(annotation.id()) {
+                annotation.set_id(a_id);
+            }
+            if let Some(ref_id) = annotation.ref_id().and_then(|r| a_map.get(r)) {
+                annotation.set_ref_id(&ref_id.to_owned());
+            }
+            if let Some(prev) = annotation.previous().and_then(|p| a_map.get(p)) {
+                annotation.set_previous(&prev.to_owned());
+            }
+            if let Some((ref1, ref2)) = annotation.ts_ref() {
+                annotation.set_ts_ref(
+                    ts_map.get(&ref1).unwrap_or(&ref1),
+                    ts_map.get(&ref2).unwrap_or(&ref2)
+                );
+            }
+            if annotation.tier_id().is_some() {
+                annotation.set_tier_id(&tier.tier_id);
+            }
+        }
+        tiers.push(tier);
+    }
+
+    for (ts_id, new) in ts_ids.iter().zip(new_ts_ids.iter()) {
+        let val = eaf.index.ts2tv.get(ts_id).copied().flatten();
+        eaf.time_order.insert(&TimeSlot::new(new, val));
+        eaf.index.insert_timeslot(new, val);
+    }
+
+    // Parent tiers are added before their children,
+    // so that referred annotations can be derived.
+    for tier in tiers.into_iter() {
+        let t_idx = eaf.tiers.len();
+        eaf.index.insert_tier(&tier, t_idx);
+        eaf.tiers.push(tier);
+        let tier = &mut eaf.tiers[t_idx];
+        for annotation in tier.annotations.iter_mut() {
+            eaf.index.derive(annotation, &tier.tier_id)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets a new parent tier for a referred tier, see `Eaf::reparent_tier()`.
+pub(crate) fn reparent_tier(eaf: &mut Eaf, tier_id: &str, parent_id: &str) -> Result<(), EafError> {
+    if !eaf.derived {
+        eaf.derive()?
+    }
+    let invalid = || EafError::TierParentInvalid((tier_id.to_owned(), parent_id.to_owned()));
+
+    let tier = eaf.get_tier(tier_id)
+        .ok_or_else(|| EafError::TierIdInvalid(tier_id.to_owned()))?;
+    let parent = eaf.get_tier(parent_id)
+        .ok_or_else(|| EafError::TierIdInvalid(parent_id.to_owned()))?;
+    if tier.parent_ref.is_none() {
+        return Err(EafError::TierRefExpected(tier_id.to_owned()))
+    }
+    if tier.parent_ref.as_deref() == Some(parent_id) {
+        return Ok(())
+    }
+    if parent_id == tier_id || descendant_tiers(eaf, tier_id).iter().any(|t| t == parent_id) {
+        return Err(invalid())
+    }
+
+    // Time-aligned tiers require a time-aligned parent tier.
+    let parent_aligned = parent.parent_ref.is_none() || matches!(
+        tier_stereotype(eaf, parent),
+        Some(StereoType::IncludedIn | StereoType::TimeSubdivision)
+    );
+
+    // Annotation ID -> new referred annotation ID
+    let mut refs: Vec<(String, String)> = Vec::new();
+    let stereotype = tier_stereotype(eaf, tier);
+    match stereotype {
+        Some(StereoType::IncludedIn | StereoType::TimeSubdivision) => {
+            if !parent_aligned {
+                return Err(invalid())
+            }
+            for annotation in tier.annotations.iter() {
+                let (Some(start), Some(end)) = annotation.ts_val() else {
+                    return Err(EafError::TimeslotValMissing(annotation.id().to_owned()))
+                };
+                check_parent_tier(eaf, annotation.id(), parent_id, start, end)?;
+            }
+        },
+        Some(StereoType::SymbolicAssociation | StereoType::SymbolicSubdivision) => {
+            // Referred annotations are matched with the parent annotation
+            // active at the midpoint of their time span. Symbolic subdivisions,
+            // in either tier, divide the time span of their parent annotation.
+            let spans = symbolic_spans(eaf, tier_id);
+            let parent_spans = symbolic_spans(eaf, parent_id);
+            for annotation in tier.annotations.iter() {
+                let Some((start, end)) = spans.get(annotation.id()) else {
+                    return Err(EafError::TimeslotValMissing(annotation.id().to_owned()))
+                };
+                let mid = start + (end - start) / 2.;
+                let candidates: Vec<&str> = eaf.annotations_at(mid.floor() as i64, Some(parent_id)).iter()
+                    .map(|p| p.id())
+                    .filter(|p_id| parent_spans.get(*p_id)
+                        .is_some_and(|(p_start, p_end)| *p_start <= mid && mid < *p_end))
+                    .collect();
+                let ref_id = match candidates.as_slice() {
+                    [candidate] => candidate.to_string(),
+                    [] => return Err(EafError::AnnotationParentMissing(annotation.id().to_owned())),
+                    _ => return Err(invalid()),
+                };
+                // Only one annotation per parent annotation for associations.
+                if stereotype == Some(StereoType::SymbolicAssociation)
+                    && refs.iter().any(|(_, r)| *r == ref_id)
+                {
+                    return Err(invalid())
+                }
+                refs.push((annotation.id().to_owned(), ref_id));
+            }
+        },
+        _ => return Err(invalid()),
+    }
+
+    let old_parent_id = tier.parent_ref.to_owned().unwrap_or_default();
+    if let Some(tier) = eaf.get_tier_mut(tier_id) {
+        tier.parent_ref = Some(parent_id.to_owned())
+    }
+    eaf.index.set_tier_parent(tier_id, parent_id);
+
+    // Time subdivisions must cover the new parent annotations without gaps.
+    if stereotype == Some(StereoType::TimeSubdivision)
+        && check_time_subdivision(eaf).iter()
+            .any(|issue| matches!(&issue.location, Location::Annotation(a_id)
+                if eaf.index.a2t.get(a_id).is_some_and(|t| t == tier_id)))
+    {
+        if let Some(tier) = eaf.get_tier_mut(tier_id) {
+            tier.parent_ref = Some(old_parent_id.to_owned())
+        }
+        eaf.index.set_tier_parent(tier_id, &old_parent_id);
+        return Err(invalid())
+    }
+
+    for (a_id, ref_id) in refs.iter() {
+        set_ref(eaf, a_id, ref_id)?;
+    }
+
+    // Subdivisions with the same parent annotation form
+    // a new sequence, in tier order.
+    if stereotype == Some(StereoType::SymbolicSubdivision) {
+        let mut last: HashMap<&str, &str> = HashMap::new();
+        for (a_id, ref_id) in refs.iter() {
+            let prev = last.insert(ref_id, a_id);
+            if let Some(annotation) = eaf.get_annotation_mut(a_id) {
+                match prev {
+                    Some(prev) => annotation.set_previous(prev),
+                    None => annotation.unset_previous(),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eaf::test_util::{TestEaf, aligned, assert_valid, referred, subdivision};
+
+    /// Main tier `A`, with a `Symbolic_Subdivision` tier `W`,
+    /// a `Time_Subdivision` tier `T` and a `Symbolic_Association` tier `G`.
+    fn eaf() -> Eaf {
+        TestEaf::new()
+            .time_slots(&[0, 500, 1000, 2000, 3000, 4000])
+            .tier("A", "default-lt", None, &[
+                aligned("a1", "ts1", "ts3", "one"),
+                aligned("a2", "ts3", "ts4", "two"),
+                aligned("a3", "ts5", "ts6", "three"),
+            ])
+            .linguistic_type("words", Some("Symbolic_Subdivision"))
+            .tier("W", "words", Some("A"), &[
+                referred("a4", "a1", "w1"),
+                subdivision("a5", "a1", "a4", "w2"),
+                referred("a6", "a2", "w3"),
+            ])
+            .linguistic_type("segments", Some("Time_Subdivision"))
+            .tier("T", "segments", Some("A"), &[
+                aligned("a7", "ts1", "ts2", "t1"),
+                aligned("a8", "ts2", "ts3", "t2"),
+            ])
+            .linguistic_type("gloss", Some("Symbolic_Association"))
+            .tier("G", "gloss", Some("A"), &[
+                referred("a9", "a1", "g1"),
+                referred("a10", "a2", "g2"),
+            ])
+            .eaf()
+    }
+
+    /// Main tiers `A`, `B` and `C`. `A` has a `Symbolic_Subdivision` tier `W`,
+    /// with a further subdivision `M` and an association `G`, as well as
+    /// an association `X` and a `Time_Subdivision` tier `T`.
+    fn tiers() -> Eaf {
+        TestEaf::new()
+            .time_slots(&[0, 500, 1000, 2000])
+            .linguistic_type("words", Some("Symbolic_Subdivision"))
+            .linguistic_type("segments", Some("Time_Subdivision"))
+            .linguistic_type("gloss", Some("Symbolic_Association"))
+            .tier("A", "default-lt", None, &[
+                aligned("a1", "ts1", "ts3", "one"),
+                aligned("a2", "ts3", "ts4", "two"),
+            ])
+            .tier("W", "words", Some("A"), &[
+                referred("a3", "a1", "w1"),
+                subdivision("a4", "a1", "a3", "w2"),
+                referred("a5", "a2", "w3"),
+            ])
+            .tier("M", "words", Some("W"), &[
+                referred("a6", "a3", "m1"),
+                referred("a7", "a4", "m2"),
+                subdivision("a8", "a4", "a7", "m3"),
+            ])
+            .tier("G", "gloss", Some("W"), &[
+                referred("a9", "a3", "g1"),
+                referred("a10", "a4", "g2"),
+                referred("a11", "a5", "g3"),
+            ])
+            .tier("X", "gloss", Some("A"), &[referred("a12", "a1", "x1")])
+            .tier("T", "segments", Some("A"), &[
+                aligned("a13", "ts1", "ts2", "t1"),
+                aligned("a14", "ts2", "ts3", "t2"),
+            ])
+            .tier("B", "default-lt", None, &[aligned("a15", "ts1", "ts4", "b")])
+            .tier("C", "default-lt", None, &[aligned("a16", "ts1", "ts3", "c")])
+            .eaf()
+    }
+
+    fn ref_id<'a>(eaf: &'a Eaf, id: &str) -> Option<&'a str> {
+        eaf.get_annotation(id).unwrap().ref_id()
+    }
+
+    fn previous<'a>(eaf: &'a Eaf, id: &str) -> Option<&'a str> {
+        eaf.get_annotation(id).unwrap().previous()
+    }
+
+    fn span(eaf: &Eaf, id: &str) -> (Option<i64>, Option<i64>) {
+        eaf.get_annotation(id).unwrap().ts_val()
+    }
+
+    /// Annotation IDs in tier order.
+    fn ids(eaf: &Eaf, tier_id: &str) -> Vec<String> {
+        eaf.get_tier(tier_id).unwrap().annotations.iter()
+            .map(|a| a.id().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn split() {
+        let mut eaf = eaf();
+        let new_id = eaf.split_annotation("a1", 500).unwrap();
+
+        assert_eq!(ids(&eaf, "A"), ["a1", &new_id, "a2", "a3"]);
+        assert_eq!(span(&eaf, "a1"), (Some(0), Some(500)));
+        assert_eq!(span(&eaf, &new_id), (Some(500), Some(1000)));
+        // Time subdivision boundary at the split is reused.
+        assert_eq!(eaf.get_annotation(&new_id).unwrap().ts_ref(), Some(("ts2".to_owned(), "ts3".to_owned())));
+        assert_eq!(eaf.time_order.len(), 6);
+        assert_eq!(eaf.get_annotation("a1").unwrap().to_str(), "one");
+        assert_eq!(eaf.get_annotation(&new_id).unwrap().to_str(), "");
+
+        // Second subdivision moves to the second part, and starts a new sequence.
+        assert_eq!(eaf.get_annotation("a4").unwrap().ref_id(), Some("a1"));
+        let a5 = eaf.get_annotation("a5").unwrap();
+        assert_eq!(a5.ref_id(), Some(new_id.as_str()));
+        assert_eq!(a5.previous(), None);
+        assert_eq!(span(&eaf, "a5"), (Some(500), Some(1000)));
+        // Associations stay with the first part.
+        assert_eq!(eaf.get_annotation("a9").unwrap().ref_id(), Some("a1"));
+        assert_valid(&eaf);
+    }
+
+    #[test]
+    fn split_invalid() {
+        let mut eaf = eaf();
+        assert!(matches!(eaf.split_annotation("a1", 0), Err(EafError::ValueTooSmall(0))));
+        assert!(matches!(eaf.split_annotation("a1", 1000), Err(EafError::ValueTooLarge(1000))));
+        // Time subdivision a7 crosses 250.
+        assert!(matches!(eaf.split_annotation("a1", 250), Err(EafError::AnnotationParentMissing(id)) if id == "a7"));
+        assert!(matches!(eaf.split_annotation("a4", 250), Err(EafError::AnnotationMainExpected(_))));
+        assert!(matches!(eaf.split_annotation("x", 250), Err(EafError::AnnotationIdInvalid(_))));
+        assert_eq!(eaf.to_string(None).unwrap(), self::eaf().to_string(None).unwrap());
+    }
+
+    #[test]
+    fn merge_next() {
+        let mut eaf = eaf();
+        assert_eq!(eaf.merge_annotation_next("a1").unwrap(), "a1");
+
+        assert_eq!(ids(&eaf, "A"), ["a1", "a3"]);
+        assert!(eaf.get_annotation("a2").is_none());
+        let a1 = eaf.get_annotation("a1").unwrap();
+        assert_eq!(a1.to_str(), "one two");
+        assert_eq!(a1.ts_val(), (Some(0), Some(2000)));
+
+        // Subdivisions form a single sequence.
+        let a6 = eaf.get_annotation("a6").unwrap();
+        assert_eq!(a6.ref_id(), Some("a1"));
+        assert_eq!(a6.previous(), Some("a5"));
+        // Associations are joined.
+        assert_eq!(ids(&eaf, "G"), ["a9"]);
+        assert_eq!(eaf.get_annotation("a9").unwrap().to_str(), "g1 g2");
+        // Time slot still used by the time subdivision a8 is kept.
+        assert_eq!(span(&eaf, "a8"), (Some(500), Some(1000)));
+        assert_eq!(eaf.time_order.len(), 6);
+    }
+
+    #[test]
+    fn merge_previous() {
+        let mut eaf = eaf();
+        assert_eq!(eaf.merge_annotation_previous("a3").unwrap(), "a2");
+        assert_eq!(ids(&eaf, "A"), ["a1", "a2"]);
+        assert_eq!(span(&eaf, "a2"), (Some(1000), Some(4000)));
+        assert_eq!(eaf.get_annotation("a2").unwrap().to_str(), "two three");
+        // Unused time slots are removed.
+        assert_eq!(eaf.time_order.len(), 4);
+        assert_valid(&eaf);
+
+        assert!(matches!(eaf.merge_annotation_previous("a1"), Err(EafError::AnnotationAdjacentMissing(_))));
+        assert!(matches!(eaf.merge_annotation_next("a2"), Err(EafError::AnnotationAdjacentMissing(_))));
+    }
+
+    #[test]
+    fn move_annotation() {
+        let mut eaf = eaf();
+        eaf.move_annotation("a2", 500).unwrap();
+
+        // The time slot shared with a1 is left as is.
+        assert_eq!(span(&eaf, "a1"), (Some(0), Some(1000)));
+        assert_eq!(span(&eaf, "a2"), (Some(1500), Some(2500)));
+        assert_eq!(eaf.time_order.len(), 7);
+        // Referred annotations follow.
+        assert_eq!(span(&eaf, "a6"), (Some(1500), Some(2500)));
+        assert_eq!(span(&eaf, "a10"), (Some(1500), Some(2500)));
+        assert_eq!(eaf.annotations_at(1200, Some("A")).len(), 0);
+        assert_eq!(eaf.annotations_at(2200, Some("A"))[0].id(), "a2");
+        assert_valid(&eaf);
+
+        // Overlaps a2.
+        assert!(matches!(eaf.move_annotation("a3", -1000), Err(EafError::AnnotationOverlap)));
+        assert!(matches!(eaf.move_annotation("a1", -100), Err(EafError::ValueTooSmall(-100))));
+        // Overlaps a8 in the same tier.
+        assert!(matches!(eaf.move_annotation("a7", 100), Err(EafError::AnnotationOverlap)));
+        // Time subdivision no longer contained within its parent annotation.
+        assert!(matches!(eaf.move_annotation("a8", 100), Err(EafError::AnnotationParentMissing(_))));
+
+        eaf.move_annotation("a3", -500).unwrap();
+        assert_eq!(span(&eaf, "a3"), (Some(2500), Some(3500)));
+        assert_valid(&eaf);
+    }
+
+    #[test]
+    fn move_with_dependents() {
+        let mut eaf = eaf();
+        eaf.move_annotation("a2", 1000).unwrap();
+        eaf.move_annotation("a1", 200).unwrap();
+
+        assert_eq!(span(&eaf, "a1"), (Some(200), Some(1200)));
+        // Time subdivisions move together with their parent annotation.
+        assert_eq!(span(&eaf, "a7"), (Some(200), Some(700)));
+        assert_eq!(span(&eaf, "a8"), (Some(700), Some(1200)));
+        // Referred annotations use the time values of their main annotation.
+        assert_eq!(span(&eaf, "a5"), (Some(200), Some(1200)));
+        assert_valid(&eaf);
+    }
+
+    #[test]
+    fn resize() {
+        let mut eaf = eaf();
+        eaf.resize_annotation("a1", None, Some(900)).unwrap();
+
+        assert_eq!(span(&eaf, "a1"), (Some(0), Some(900)));
+        // The time subdivision sharing the boundary follows it.
+        assert_eq!(span(&eaf, "a8"), (Some(500), Some(900)));
+        // The adjacent annotation keeps its time slot.
+        assert_eq!(span(&eaf, "a2"), (Some(1000), Some(2000)));
+        assert_valid(&eaf);
+
+        eaf.resize_annotation("a3", Some(2500), Some(4500)).unwrap();
+        assert_eq!(span(&eaf, "a3"), (Some(2500), Some(4500)));
+        assert_valid(&eaf);
+
+        assert!(matches!(eaf.resize_annotation("a3", Some(4500), None), Err(EafError::TimeSpanInvalid(_))));
+        assert!(matches!(eaf.resize_annotation("a2", None, Some(3000)), Err(EafError::AnnotationOverlap)));
+        // a8 would end before it starts.
+        assert!(matches!(eaf.resize_annotation("a1", None, Some(400)), Err(EafError::TimeSpanInvalid(_))));
+        // Time subdivision a7 is not contained within the parent annotation.
+        assert!(matches!(eaf.resize_annotation("a7", None, Some(1000)), Err(EafError::AnnotationParentMissing(_))));
+        assert_eq!(span(&eaf, "a1"), (Some(0), Some(900)));
+
+        // a7 shares the start time slot and follows.
+        eaf.resize_annotation("a1", Some(100), None).unwrap();
+        assert_eq!(span(&eaf, "a7"), (Some(100), Some(500)));
+        assert_valid(&eaf);
+    }
+
+    #[test]
+    fn remove_tier() {
+        let mut eaf = tiers();
+        assert!(matches!(eaf.remove_tier("W", false), Err(EafError::TierDependentsExist(_))));
+        assert!(matches!(eaf.remove_tier("Y", false), Err(EafError::TierIdInvalid(_))));
+
+        let removed = eaf.remove_tier("W", true).unwrap();
+        let removed_ids: Vec<&str> = removed.iter().map(|t| t.tier_id.as_str()).collect();
+        assert_eq!(removed_ids, ["W", "M", "G"]);
+        assert!(eaf.get_tier("M").is_none());
+        assert!(eaf.get_annotation("a3").is_none());
+        assert_valid(&eaf);
+
+        // Time slots only used by the removed tier are removed.
+        eaf.remove_tier("T", false).unwrap();
+        assert_eq!(eaf.time_order.len(), 3);
+        assert!(eaf.annotations_at(250, Some("T")).is_empty());
+        assert_eq!(eaf.annotations_at(250, None).len(), 4);
+        assert_valid(&eaf);
+    }
+
+    #[test]
+    fn copy_tier() {
+        let mut eaf = tiers();
+        eaf.copy_tier("W", "W2", true, "-copy").unwrap();
+
+        assert_eq!(eaf.tiers.len(), 11);
+        let m = eaf.get_tier("M-copy").unwrap();
+        assert_eq!(m.parent_ref.as_deref(), Some("W2"));
+        assert_eq!(eaf.get_tier("W2").unwrap().parent_ref.as_deref(), Some("A"));
+        assert_eq!(eaf.get_tier("G-copy").unwrap().parent_ref.as_deref(), Some("W2"));
+
+        // Copies get new annotation IDs, with references within the copies.
+        let w2 = ids(&eaf, "W2");
+        let m2 = ids(&eaf, "M-copy");
+        assert!(w2.iter().chain(m2.iter()).all(|id| id.trim_start_matches('a').parse::<usize>().unwrap() > 16));
+        assert_eq!(ref_id(&eaf, &w2[0]), Some("a1"));
+        assert_eq!(previous(&eaf, &w2[1]), Some(w2[0].as_str()));
+        assert_eq!(ref_id(&eaf, &m2[2]), Some(w2[1].as_str()));
+        assert_eq!(previous(&eaf, &m2[2]), Some(m2[1].as_str()));
+        assert_eq!(eaf.get_annotation(&m2[2]).unwrap().to_str(), "m3");
+        assert_valid(&eaf);
+
+        // Time slots shared with the parent tier are kept, others are copied.
+        eaf.copy_tier("T", "T2", false, "").unwrap();
+        assert_eq!(eaf.time_order.len(), 5);
+        let t2 = ids(&eaf, "T2");
+        assert_eq!(eaf.get_annotation(&t2[0]).unwrap().ts_ref().unwrap().0, "ts1");
+        assert_eq!(span(&eaf, &t2[0]), (Some(0), Some(500)));
+        assert_eq!(eaf.annotations_at(750, Some("T2"))[0].id(), t2[1]);
+        assert_valid(&eaf);
+
+        assert!(matches!(eaf.copy_tier("A", "B", false, ""), Err(EafError::TierIdExists(_))));
+        assert!(matches!(eaf.copy_tier("W", "W3", true, "-copy"), Err(EafError::TierIdExists(id)) if id == "M-copy"));
+    }
+
+    #[test]
+    fn reparent_symbolic() {
+        let mut eaf = tiers();
+
+        // Morphemes evenly divide their word, and all fall within a1.
+        eaf.reparent_tier("M", "A").unwrap();
+        assert_eq!(eaf.get_tier("M").unwrap().parent_ref.as_deref(), Some("A"));
+        assert_eq!(["a6", "a7", "a8"].map(|id| ref_id(&eaf, id)), [Some("a1"); 3]);
+        assert_eq!(["a6", "a7", "a8"].map(|id| previous(&eaf, id)), [None, Some("a6"), Some("a7")]);
+        assert_valid(&eaf);
+
+        // Several words per parent annotation, mapped via the
+        // position of each morpheme in the sequence.
+        eaf.reparent_tier("M", "W").unwrap();
+        assert_eq!(["a6", "a7", "a8"].map(|id| ref_id(&eaf, id)), [Some("a3"), Some("a4"), Some("a4")]);
+        assert_eq!(["a6", "a7", "a8"].map(|id| previous(&eaf, id)), [None, None, Some("a7")]);
+        assert_valid(&eaf);
+
+        // Glosses follow their word down to the morpheme at its midpoint.
+        eaf.reparent_tier("X", "W").unwrap();
+        assert_eq!(ref_id(&eaf, "a12"), Some("a4"));
+        assert_valid(&eaf);
+
+        // No morpheme for w3.
+        assert!(matches!(eaf.reparent_tier("G", "M"), Err(EafError::AnnotationParentMissing(id)) if id == "a11"));
+        // Two associations for a1.
+        assert!(matches!(eaf.reparent_tier("G", "A"), Err(EafError::TierParentInvalid(_))));
+        assert_eq!(eaf.get_tier("G").unwrap().parent_ref.as_deref(), Some("W"));
+    }
+
+    #[test]
+    fn reparent_aligned() {
+        let mut eaf = tiers();
+
+        // Time subdivisions would not cover b.
+        assert!(matches!(eaf.reparent_tier("T", "B"), Err(EafError::TierParentInvalid(_))));
+        assert_eq!(eaf.get_tier("T").unwrap().parent_ref.as_deref(), Some("A"));
+        assert_valid(&eaf);
+
+        eaf.reparent_tier("T", "C").unwrap();
+        assert_eq!(eaf.get_tier("T").unwrap().parent_ref.as_deref(), Some("C"));
+        assert_valid(&eaf);
+
+        // Time-aligned tiers require a time-aligned parent.
+        assert!(matches!(eaf.reparent_tier("T", "W"), Err(EafError::TierParentInvalid(_))));
+        // Not a referred tier.
+        assert!(matches!(eaf.reparent_tier("A", "B"), Err(EafError::TierRefExpected(_))));
+        // Parent is a dependent tier.
+        assert!(matches!(eaf.reparent_tier("W", "M"), Err(EafError::TierParentInvalid(_))));
+    }
+}
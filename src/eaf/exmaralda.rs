@@ -0,0 +1,766 @@
+//! EXMARaLDA basic transcription (`.exb`), for exchanging transcriptions with
+//! the [EXMARaLDA Partitur-Editor](https://exmaralda.org).
+//!
+//! Mapping between EAF and EXMARaLDA:
+//! - The common timeline maps to the time order, with one timeline item per time slot.
+//!   Timeline items without time values map to unaligned time slots and vice versa.
+//! - Speakers in the speaker table map to tier participants, via the speaker abbreviation.
+//! - Tier categories map to linguistic types.
+//! - User defined meta information maps to header properties.
+//!
+//! EXMARaLDA has no tier hierarchy. On import all tiers become main tiers,
+//! and on export referred tiers become annotation tiers (type `a`) that are
+//! aligned to the timeline via their parent annotations. Annotations in
+//! `Symbolic_Subdivision` tiers are aligned to new timeline items without time
+//! values, inserted within the span of the parent annotation.
+//! Information that can not be expressed in EXMARaLDA is reported as `ConversionLoss`.
+
+use std::{collections::{HashMap, HashSet}, fs::File, io::Write, path::Path};
+
+use quick_xml::se::Serializer;
+use serde::{Deserialize, Serialize};
+
+use crate::EafError;
+
+use super::{
+    validate::tier_stereotype,
+    Annotation,
+    ConversionLoss,
+    Eaf,
+    Header,
+    LinguisticType,
+    Location,
+    MediaDescriptor,
+    Property,
+    StereoType,
+    Tier,
+    TimeOrder,
+    TimeSlot,
+};
+
+/// EXMARaLDA basic transcription.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename = "basic-transcription")]
+pub struct Exmaralda {
+    pub head: ExbHead,
+    #[serde(rename = "basic-body")]
+    pub body: ExbBody,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExbHead {
+    #[serde(rename = "meta-information", default)]
+    pub meta_information: ExbMetaInformation,
+    #[serde(default)]
+    pub speakertable: ExbSpeakerTable,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExbMetaInformation {
+    #[serde(rename = "project-name", default)]
+    pub project_name: String,
+    #[serde(rename = "transcription-name", default)]
+    pub transcription_name: String,
+    /// Media files.
+    #[serde(rename = "referenced-file", default)]
+    pub referenced_files: Vec<ExbReferencedFile>,
+    #[serde(rename = "ud-meta-information", default)]
+    pub ud_meta_information: ExbUdInformation,
+    #[serde(default)]
+    pub comment: String,
+    #[serde(rename = "transcription-convention", default)]
+    pub transcription_convention: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExbReferencedFile {
+    #[serde(rename = "@url")]
+    pub url: String,
+}
+
+/// User defined attributes.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExbUdInformation {
+    #[serde(rename = "ud-information", default)]
+    pub items: Vec<ExbUdItem>,
+}
+
+/// User defined attribute, e.g.
+/// `<ud-information attribute-name="age">3;2</ud-information>`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExbUdItem {
+    #[serde(rename = "@attribute-name")]
+    pub attribute_name: String,
+    #[serde(rename = "$text", default)]
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExbSpeakerTable {
+    #[serde(rename = "speaker", default)]
+    pub speakers: Vec<ExbSpeaker>,
+}
+
+/// Speaker, referred to by tiers via speaker ID.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExbSpeaker {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(default)]
+    pub abbreviation: String,
+    #[serde(default)]
+    pub sex: ExbSex,
+    #[serde(rename = "languages-used", default)]
+    pub languages_used: ExbLanguages,
+    #[serde(default)]
+    pub l1: ExbLanguages,
+    #[serde(default)]
+    pub l2: ExbLanguages,
+    #[serde(rename = "ud-speaker-information", default)]
+    pub ud_speaker_information: ExbUdInformation,
+    #[serde(default)]
+    pub comment: String,
+}
+
+/// Speaker sex: `m`, `f`, or `u` (unknown).
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ExbSex {
+    #[serde(rename = "@value")]
+    pub value: String,
+}
+
+impl Default for ExbSex {
+    fn default() -> Self {
+        Self { value: "u".to_owned() }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExbLanguages {
+    #[serde(rename = "language", default)]
+    pub languages: Vec<ExbLanguage>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExbLanguage {
+    #[serde(rename = "@lang")]
+    pub lang: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExbBody {
+    #[serde(rename = "common-timeline", default)]
+    pub common_timeline: ExbTimeline,
+    #[serde(rename = "tier", default)]
+    pub tiers: Vec<ExbTier>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExbTimeline {
+    #[serde(rename = "tli", default)]
+    pub items: Vec<ExbTimelineItem>,
+}
+
+/// Timeline item (point in time).
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExbTimelineItem {
+    #[serde(rename = "@id")]
+    pub id: String,
+    /// Time in seconds. Optional.
+    #[serde(rename = "@time", skip_serializing_if = "Option::is_none")]
+    pub time: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExbTier {
+    #[serde(rename = "@id")]
+    pub id: String,
+    /// Speaker ID.
+    #[serde(rename = "@speaker", skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    #[serde(rename = "@category", default)]
+    pub category: String,
+    /// Tier type: `t` (transcription), `a` (annotation), or `d` (description).
+    #[serde(rename = "@type", default)]
+    pub tier_type: String,
+    #[serde(rename = "@display-name", skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(rename = "event", default)]
+    pub events: Vec<ExbEvent>,
+}
+
+/// Event (annotation), between two timeline items.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExbEvent {
+    /// Timeline item ID.
+    #[serde(rename = "@start")]
+    pub start: String,
+    /// Timeline item ID.
+    #[serde(rename = "@end")]
+    pub end: String,
+    #[serde(rename = "$text", default)]
+    pub value: String,
+}
+
+impl ExbUdInformation {
+    fn from_properties(properties: &[Property]) -> Self {
+        Self {
+            items: properties.iter()
+                .filter_map(|p| p.name.as_ref().map(|n| ExbUdItem {
+                    attribute_name: n.to_owned(),
+                    value: p.value.to_owned(),
+                }))
+                .collect()
+        }
+    }
+
+    fn to_properties(&self) -> Vec<Property> {
+        self.items.iter()
+            .map(|i| Property {
+                name: Some(i.attribute_name.to_owned()),
+                value: i.value.to_owned(),
+            })
+            .collect()
+    }
+}
+
+/// Timeline under construction on export. Timeline items without time values
+/// are inserted directly after an existing item, so that nested subdivisions
+/// end up within the span of their parent annotation.
+struct Timeline {
+    /// Time slots from the time order, sorted on time value.
+    items: Vec<(String, Option<i64>)>,
+    /// Timeline item ID to items inserted directly after it.
+    inserted: HashMap<String, Vec<String>>,
+    /// All timeline item IDs, including inserted ones.
+    ids: HashSet<String>,
+    next: usize,
+}
+
+impl Timeline {
+    fn new(eaf: &Eaf) -> Self {
+        // Time slots without time value sort with the closest preceding value,
+        // so that their position relative to other time slots is kept.
+        let mut previous = 0;
+        let mut items: Vec<(i64, String, Option<i64>)> = eaf.time_order.time_slots.iter()
+            .map(|ts| {
+                previous = ts.time_value.unwrap_or(previous);
+                (previous, ts.time_slot_id.to_owned(), ts.time_value)
+            })
+            .collect();
+        items.sort_by_key(|(key, ..)| *key);
+
+        Self {
+            ids: items.iter().map(|(_, id, _)| id.to_owned()).collect(),
+            items: items.into_iter().map(|(_, id, val)| (id, val)).collect(),
+            inserted: HashMap::new(),
+            next: 1,
+        }
+    }
+
+    /// Inserts `count` new timeline items without time values
+    /// directly after `after`, and returns their IDs.
+    fn insert(&mut self, after: &str, count: usize) -> Vec<String> {
+        let mut new_ids: Vec<String> = Vec::new();
+        while new_ids.len() < count {
+            let id = format!("T{}", self.next);
+            self.next += 1;
+            if self.ids.insert(id.to_owned()) {
+                new_ids.push(id);
+            }
+        }
+        let inserted = self.inserted.entry(after.to_owned()).or_default();
+        inserted.splice(0..0, new_ids.iter().cloned());
+        new_ids
+    }
+
+    fn flatten(&self, id: &str, time: Option<i64>, items: &mut Vec<ExbTimelineItem>) {
+        items.push(ExbTimelineItem {
+            id: id.to_owned(),
+            time: time.map(|t| t as f64 / 1000.),
+        });
+        for inserted in self.inserted.get(id).into_iter().flatten() {
+            self.flatten(inserted, None, items);
+        }
+    }
+
+    fn to_exb(&self) -> ExbTimeline {
+        let mut items: Vec<ExbTimelineItem> = Vec::new();
+        for (id, time) in self.items.iter() {
+            self.flatten(id, *time, &mut items);
+        }
+        ExbTimeline { items }
+    }
+}
+
+/// Orders annotations referring to the same parent via
+/// previous annotation references, if these form a single chain.
+fn order_subdivision<'a>(annotations: &[&'a Annotation]) -> Vec<&'a Annotation> {
+    let mut ordered: Vec<&Annotation> = Vec::new();
+    let mut previous: Option<&str> = None;
+    while let Some(next) = annotations.iter().find(|a| a.previous() == previous) {
+        ordered.push(next);
+        previous = Some(next.id());
+        if ordered.len() == annotations.len() {
+            break;
+        }
+    }
+    match ordered.len() == annotations.len() {
+        true => ordered,
+        false => annotations.to_vec(),
+    }
+}
+
+impl Exmaralda {
+    /// Reads and parses an EXMARaLDA basic transcription.
+    pub fn read(path: &Path) -> Result<Self, EafError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses EXMARaLDA basic transcription content.
+    pub fn parse(content: &str) -> Result<Self, EafError> {
+        quick_xml::de::from_str(content.trim_start_matches('\u{feff}'))
+            .map_err(|e| EafError::QuickXMLDeError(e))
+    }
+
+    /// Serializes to EXMARaLDA basic transcription string, indented with two spaces.
+    pub fn to_string(&self) -> Result<String, EafError> {
+        let mut xml = String::new();
+        let mut ser = Serializer::new(&mut xml);
+        ser.indent(' ', 2);
+        self.serialize(ser).map_err(|e| EafError::QuickXMLSeError(e))?;
+
+        Ok([
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            xml.as_str()
+        ].join("\n"))
+    }
+
+    /// Serializes and writes EXMARaLDA basic transcription to disk.
+    pub fn write(&self, path: &Path) -> Result<(), EafError> {
+        let mut outfile = File::create(&path)?;
+        outfile.write_all(self.to_string()?.as_bytes()).map_err(|e| EafError::IOError(e))
+    }
+
+    /// Generates an `Eaf` with one main tier per EXMARaLDA tier.
+    /// Timeline items are converted to time slots in timeline order,
+    /// with time slot IDs `ts1`, `ts2`, ..., and time values
+    /// rounded to milliseconds.
+    ///
+    /// The tier ID is the tier's display name if set and unique, otherwise
+    /// the EXMARaLDA tier ID. The participant is the speaker abbreviation,
+    /// or the speaker ID if the abbreviation is empty.
+    ///
+    /// Returns error if an event refers to a timeline item that does not exist.
+    pub fn to_eaf(&self) -> Result<Eaf, EafError> {
+        let mut time_order = TimeOrder::new();
+        // Timeline item ID -> (time slot ID, timeline index)
+        let mut tlis: HashMap<&str, (String, usize)> = HashMap::new();
+        for (i, tli) in self.body.common_timeline.items.iter().enumerate() {
+            let ts_id = format!("ts{}", i + 1);
+            time_order.push(&TimeSlot::new(&ts_id, tli.time.map(|t| (t * 1000.).round() as i64)));
+            tlis.insert(&tli.id, (ts_id, i));
+        }
+
+        let speakers: HashMap<&str, &str> = self.head.speakertable.speakers.iter()
+            .map(|s| (s.id.as_str(), match s.abbreviation.trim() {
+                "" => s.id.as_str(),
+                a => a,
+            }))
+            .collect();
+
+        let mut tiers: Vec<Tier> = Vec::new();
+        let mut linguistic_types: Vec<LinguisticType> = Vec::new();
+        let mut a_id = 1;
+        for exb_tier in self.body.tiers.iter() {
+            let mut events: Vec<(usize, &ExbEvent, &str, &str)> = Vec::new();
+            for event in exb_tier.events.iter() {
+                let (start, end) = match (tlis.get(event.start.as_str()), tlis.get(event.end.as_str())) {
+                    (Some(s), Some(e)) => (s, e),
+                    _ => return Err(EafError::ExmaraldaInvalid(format!(
+                        "Event in tier '{}' refers to unknown timeline item '{}' or '{}'",
+                        exb_tier.id, event.start, event.end
+                    ))),
+                };
+                events.push((start.1, event, &start.0, &end.0));
+            }
+            events.sort_by_key(|(i, ..)| *i);
+
+            let annotations = events.iter()
+                .map(|(_, event, start, end)| {
+                    let annotation = Annotation::builder()
+                        .annotation_id(format!("a{a_id}"))
+                        .annotation_value(event.value.to_owned())
+                        .time_slot_refs(*start, *end)
+                        .build();
+                    a_id += 1;
+                    annotation
+                })
+                .collect::<Result<Vec<Annotation>, EafError>>()?;
+
+            let lt_id = match exb_tier.category.trim() {
+                "" => LinguisticType::default().linguistic_type_id,
+                c => c.to_owned(),
+            };
+            let lt = LinguisticType::new(&lt_id, None);
+            if !linguistic_types.contains(&lt) {
+                linguistic_types.push(lt);
+            }
+
+            let tier_id = match exb_tier.display_name.as_deref().map(|n| n.trim()) {
+                Some(name) if !name.is_empty() && !tiers.iter().any(|t| t.tier_id == name) => name.to_owned(),
+                _ => exb_tier.id.to_owned(),
+            };
+            let mut tier = Tier::new(&tier_id, Some(&annotations), Some(&lt_id), None);
+            tier.participant = exb_tier.speaker.as_deref()
+                .map(|s| speakers.get(s).copied().unwrap_or(s).to_owned());
+            tiers.push(tier);
+        }
+
+        let meta = &self.head.meta_information;
+        let mut header = Header::default();
+        for file in meta.referenced_files.iter().filter(|f| !f.url.is_empty()) {
+            let mut mdsc = MediaDescriptor {
+                media_url: file.url.to_owned(),
+                ..MediaDescriptor::default()
+            };
+            // Sets mime type. Relative paths, e.g. "recording.wav",
+            // are also kept as relative media URL.
+            mdsc.set_media_rel(Path::new(&file.url), false);
+            if file.url.contains(':') {
+                mdsc.relative_media_url = None;
+            }
+            header.media_descriptor.push(mdsc);
+        }
+        header.properties = meta.ud_meta_information.to_properties();
+
+        // Tiers are added after building, since the builder's overlap check
+        // requires time values, which timeline items may lack.
+        let mut eaf = Eaf::builder()
+            .header(header)
+            .time_order(time_order)
+            .linguistic_types(linguistic_types)
+            .build()?;
+        eaf.tiers = tiers;
+
+        eaf.index();
+        eaf.derive()?;
+
+        Ok(eaf)
+    }
+
+    /// Generates an EXMARaLDA basic transcription from an `Eaf`, and returns
+    /// it together with the information that could not be converted.
+    ///
+    /// Time slots become timeline items, keeping time slot IDs.
+    /// Main tiers are exported as transcription tiers (type `t`), and referred tiers
+    /// as annotation tiers (type `a`). EXMARaLDA tier IDs are `TIE0`, `TIE1`, ...,
+    /// with the EAF tier ID as display name. Each participant becomes a speaker,
+    /// with the speaker ID `SPK0`, `SPK1`, ..., and the participant as abbreviation.
+    /// See module documentation for details.
+    pub fn from_eaf(eaf: &Eaf) -> Result<(Self, Vec<ConversionLoss>), EafError> {
+        let mut losses: Vec<ConversionLoss> = Vec::new();
+        let mut timeline = Timeline::new(eaf);
+
+        // Annotation ID -> (start timeline item ID, end timeline item ID)
+        let mut spans: HashMap<&str, (String, String)> = HashMap::new();
+
+        // Tiers are aligned parents first, since referred annotations
+        // are aligned via the span of their parent annotation.
+        let mut queue: Vec<&Tier> = eaf.main_tiers().collect();
+        let mut i = 0;
+        while i < queue.len() {
+            let tier = queue[i];
+            i += 1;
+            queue.extend(eaf.tiers.iter()
+                .filter(|t| t.parent_ref.as_deref() == Some(tier.tier_id.as_str())));
+
+            let location = Location::Tier(tier.tier_id.to_owned());
+            let stereotype = tier_stereotype(eaf, tier);
+            if let (Some(parent), Some(st)) = (tier.parent_ref.as_deref(), stereotype.as_ref()) {
+                losses.push(ConversionLoss::new(location.to_owned(), &format!(
+                    "Tier hierarchy not kept: exported as independent tier, \
+                    with '{}' relation to parent tier '{parent}' removed",
+                    st.to_string()
+                )));
+            }
+
+            match stereotype {
+                Some(StereoType::SymbolicSubdivision) => {
+                    // Parent annotation ID -> referring annotations, in tier order
+                    let mut children: Vec<(&str, Vec<&Annotation>)> = Vec::new();
+                    for annotation in tier.iter() {
+                        let Some(ref_id) = annotation.ref_id() else {
+                            continue;
+                        };
+                        match children.iter_mut().find(|(id, _)| *id == ref_id) {
+                            Some((_, annotations)) => annotations.push(annotation),
+                            None => children.push((ref_id, vec![annotation])),
+                        }
+                    }
+                    let mut inserted = 0;
+                    for (ref_id, annotations) in children {
+                        let Some((start, end)) = spans.get(ref_id).cloned() else {
+                            continue;
+                        };
+                        let mut points = vec![start];
+                        points.extend(timeline.insert(&points[0], annotations.len() - 1));
+                        points.push(end);
+                        inserted += annotations.len() - 1;
+                        for (annotation, span) in order_subdivision(&annotations).into_iter().zip(points.windows(2)) {
+                            spans.insert(annotation.id(), (span[0].to_owned(), span[1].to_owned()));
+                        }
+                    }
+                    if inserted > 0 {
+                        losses.push(ConversionLoss::new(location.to_owned(), &format!(
+                            "Symbolic subdivisions aligned to {inserted} new timeline item/s without time values"
+                        )));
+                    }
+                },
+                _ => {
+                    for annotation in tier.iter() {
+                        let span = match (annotation.ts_ref(), annotation.ref_id()) {
+                            (Some(ts_ref), _) => Some(ts_ref),
+                            (None, Some(ref_id)) => spans.get(ref_id).cloned(),
+                            (None, None) => None,
+                        };
+                        if let Some(span) = span {
+                            spans.insert(annotation.id(), span);
+                        }
+                    }
+                },
+            }
+        }
+
+        let common_timeline = timeline.to_exb();
+        let positions: HashMap<&str, usize> = common_timeline.items.iter()
+            .enumerate()
+            .map(|(i, tli)| (tli.id.as_str(), i))
+            .collect();
+
+        let mut speakers: Vec<ExbSpeaker> = Vec::new();
+        let mut tiers: Vec<ExbTier> = Vec::new();
+        for (t_idx, tier) in eaf.tiers.iter().enumerate() {
+            let speaker = match tier.participant.as_deref().map(|p| p.trim()) {
+                Some(p) if !p.is_empty() => match speakers.iter().find(|s| s.abbreviation == p) {
+                    Some(s) => Some(s.id.to_owned()),
+                    None => {
+                        let id = format!("SPK{}", speakers.len());
+                        speakers.push(ExbSpeaker {
+                            id: id.to_owned(),
+                            abbreviation: p.to_owned(),
+                            ..ExbSpeaker::default()
+                        });
+                        Some(id)
+                    }
+                },
+                _ => None,
+            };
+
+            let mut unaligned = 0;
+            let mut events: Vec<ExbEvent> = tier.iter()
+                .filter_map(|a| match spans.get(a.id()) {
+                    Some((start, end)) => Some(ExbEvent {
+                        start: start.to_owned(),
+                        end: end.to_owned(),
+                        value: a.to_str().to_owned(),
+                    }),
+                    None => {
+                        unaligned += 1;
+                        None
+                    }
+                })
+                .collect();
+            events.sort_by_key(|e| positions.get(e.start.as_str()).copied());
+            if unaligned > 0 {
+                losses.push(ConversionLoss::new(Location::Tier(tier.tier_id.to_owned()),
+                    &format!("{unaligned} annotation/s without time slot or parent annotation removed")));
+            }
+
+            tiers.push(ExbTier {
+                id: format!("TIE{t_idx}"),
+                speaker,
+                category: tier.linguistic_type_ref.to_owned(),
+                tier_type: match tier.is_ref() {
+                    true => "a".to_owned(),
+                    false => "t".to_owned(),
+                },
+                display_name: Some(tier.tier_id.to_owned()),
+                events,
+            });
+        }
+
+        for cv in eaf.controlled_vocabularies.iter() {
+            losses.push(ConversionLoss::new(Location::ControlledVocabulary(cv.cv_id.to_owned()),
+                "Controlled vocabulary removed"));
+        }
+
+        let exb = Self {
+            head: ExbHead {
+                meta_information: ExbMetaInformation {
+                    referenced_files: eaf.header.media_descriptor.iter()
+                        .map(|m| ExbReferencedFile {
+                            url: m.relative_media_url().unwrap_or(m.media_url()).to_owned()
+                        })
+                        .collect(),
+                    ud_meta_information: ExbUdInformation::from_properties(&eaf.header.properties),
+                    ..ExbMetaInformation::default()
+                },
+                speakertable: ExbSpeakerTable { speakers },
+            },
+            body: ExbBody {
+                common_timeline,
+                tiers,
+            },
+        };
+
+        Ok((exb, losses))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eaf::test_util::{TestEaf, aligned, referred, subdivision};
+
+    const EXB: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<basic-transcription>
+    <head>
+        <meta-information>
+            <project-name/>
+            <transcription-name/>
+            <referenced-file url="recording.wav"/>
+            <ud-meta-information>
+                <ud-information attribute-name="place">Lund</ud-information>
+            </ud-meta-information>
+            <comment/>
+            <transcription-convention/>
+        </meta-information>
+        <speakertable>
+            <speaker id="SPK0">
+                <abbreviation>A</abbreviation>
+                <sex value="f"/>
+            </speaker>
+        </speakertable>
+    </head>
+    <basic-body>
+        <common-timeline>
+            <tli id="T0" time="0.0"/>
+            <tli id="T1"/>
+            <tli id="T2" time="1.5"/>
+            <tli id="T3" time="2.25"/>
+        </common-timeline>
+        <tier id="TIE0" speaker="SPK0" category="v" type="t" display-name="A [v]">
+            <event start="T1" end="T2">world</event>
+            <event start="T0" end="T1">hello</event>
+        </tier>
+        <tier id="TIE1" speaker="SPK0" category="de" type="a" display-name="A [de]">
+            <event start="T0" end="T3">hallo welt</event>
+        </tier>
+    </basic-body>
+</basic-transcription>"#;
+
+    /// Main tier `A`, with `Symbolic_Association` tier `G`
+    /// and `Symbolic_Subdivision` tier `W`.
+    fn eaf() -> Eaf {
+        TestEaf::new()
+            .time_slots(&[0, 1000])
+            .tier("A", "default-lt", None, &[aligned("a1", "ts1", "ts2", "hello world")])
+            .participant("Anna")
+            .linguistic_type("gloss", Some("Symbolic_Association"))
+            .tier("G", "gloss", Some("A"), &[referred("a2", "a1", "greeting")])
+            .linguistic_type("words", Some("Symbolic_Subdivision"))
+            .tier("W", "words", Some("A"), &[
+                subdivision("a4", "a1", "a3", "world"),
+                referred("a3", "a1", "hello"),
+            ])
+            .eaf()
+    }
+
+    /// Values with start and end time, in timeline order.
+    fn events(exb: &Exmaralda, tier_id: &str) -> Vec<(String, Option<f64>, Option<f64>)> {
+        let times: HashMap<&str, Option<f64>> = exb.body.common_timeline.items.iter()
+            .map(|tli| (tli.id.as_str(), tli.time))
+            .collect();
+        exb.body.tiers.iter()
+            .find(|t| t.display_name.as_deref() == Some(tier_id))
+            .unwrap()
+            .events.iter()
+            .map(|e| (e.value.to_owned(), times[e.start.as_str()], times[e.end.as_str()]))
+            .collect()
+    }
+
+    #[test]
+    fn to_eaf() {
+        let eaf = Exmaralda::parse(EXB).unwrap().to_eaf().unwrap();
+        assert_eq!(eaf.main_tier_ids(), vec!["A [v]", "A [de]"]);
+
+        let tier = eaf.get_tier("A [v]").unwrap();
+        assert_eq!(tier.participant.as_deref(), Some("A"));
+        assert_eq!(tier.linguistic_type_ref, "v");
+        // Events sorted on timeline order, the unaligned timeline item
+        // becomes an unaligned time slot.
+        let annotations: Vec<(&str, Option<i64>, Option<i64>)> = tier.iter()
+            .map(|a| (a.to_str(), a.ts_val().0, a.ts_val().1))
+            .collect();
+        assert_eq!(annotations, vec![("hello", Some(0), None), ("world", None, Some(1500))]);
+        assert_eq!(eaf.ts_val("ts4"), Some(2250));
+
+        let properties: Vec<(Option<&str>, &str)> = eaf.header.properties.iter()
+            .map(|p| (p.name.as_deref(), p.value.as_str()))
+            .collect();
+        assert_eq!(properties, vec![(Some("place"), "Lund")]);
+        assert_eq!(eaf.header.media_descriptor[0].relative_media_url(), Some("recording.wav"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let exb = Exmaralda::parse(EXB).unwrap();
+        let (exb2, losses) = Exmaralda::from_eaf(&exb.to_eaf().unwrap()).unwrap();
+        assert!(losses.is_empty());
+        for tier_id in ["A [v]", "A [de]"] {
+            let mut expected = events(&exb, tier_id);
+            expected.sort_by(|a, b| a.0.cmp(&b.0));
+            let mut found = events(&exb2, tier_id);
+            found.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(found, expected);
+        }
+        assert_eq!(exb2.head.speakertable.speakers.len(), 1);
+        assert_eq!(exb2.head.speakertable.speakers[0].abbreviation, "A");
+        assert_eq!(exb2.head.meta_information.ud_meta_information, exb.head.meta_information.ud_meta_information);
+
+        // Serialized output parses to the same document.
+        assert_eq!(Exmaralda::parse(&exb2.to_string().unwrap()).unwrap(), exb2);
+    }
+
+    #[test]
+    fn from_eaf() {
+        let eaf = eaf();
+        let (exb, losses) = Exmaralda::from_eaf(&eaf).unwrap();
+
+        let tier_types: Vec<(&str, &str)> = exb.body.tiers.iter()
+            .map(|t| (t.display_name.as_deref().unwrap(), t.tier_type.as_str()))
+            .collect();
+        assert_eq!(tier_types, vec![("A", "t"), ("G", "a"), ("W", "a")]);
+
+        assert_eq!(events(&exb, "G"), vec![("greeting".to_owned(), Some(0.), Some(1.))]);
+        // Subdivisions in previous annotation order, aligned via a new timeline item.
+        assert_eq!(events(&exb, "W"), vec![
+            ("hello".to_owned(), Some(0.), None),
+            ("world".to_owned(), None, Some(1.)),
+        ]);
+        assert_eq!(exb.body.common_timeline.items.len(), 3);
+
+        // Hierarchy for G and W, and the new timeline item for W.
+        let mut locations: Vec<String> = losses.iter().map(|l| l.location.to_string()).collect();
+        locations.sort();
+        assert_eq!(locations, vec!["tier 'G'", "tier 'W'", "tier 'W'"]);
+    }
+
+    #[test]
+    fn invalid() {
+        let exb = Exmaralda::parse(&EXB.replace(r#"end="T3""#, r#"end="T9""#)).unwrap();
+        assert!(matches!(exb.to_eaf(), Err(EafError::ExmaraldaInvalid(_))));
+        assert!(Exmaralda::parse("<basic-transcription>").is_err());
+    }
+}
@@ -0,0 +1,924 @@
+//! FLEx interlinear text (`.flextext`), for exchanging glossed texts with
+//! [SIL FieldWorks Language Explorer](https://software.sil.org/fieldworks/).
+//!
+//! Mapping between EAF and flextext:
+//! - Phrases become annotations in a main tier, words a `Symbolic_Subdivision`
+//!   tier of the main tier, and morphemes a `Symbolic_Subdivision` tier of the word tier.
+//! - All other items, e.g. glosses, free translations and parts of speech,
+//!   become `Symbolic_Association` tiers of the phrase, word or morpheme tier.
+//!   Morpheme types (`stem`, `suffix`, ...) are kept in the tier `morph-type`.
+//! - Tier IDs are in the form `<LEVEL>-<ITEM_TYPE>-<LANGUAGE>`, e.g. `word-gls-en`.
+//!   On export, item type and language are taken from tier IDs in this form.
+//!   For other tiers, the main tier and subdivision tiers are exported as `txt` items,
+//!   and association tiers as `gls` items, with the tier language or the first EAF language.
+//! - FLEx GUIDs for phrases, words and morphemes are kept as external references
+//!   (`EXT_REF`), with the value `urn:uuid:<GUID>`.
+//! - Phrase time offsets map to annotation time spans. Phrases without time offsets
+//!   follow the previous phrase, with a duration of `PHRASE_DURATION` milliseconds.
+//! - Phrase speakers map to tier participants. If the phrases have more than one speaker,
+//!   each speaker gets a separate set of tiers, with the speaker as tier ID suffix,
+//!   e.g. `word-gls-en@Anna`.
+//! - Paragraph boundaries and text titles are not kept, and only
+//!   symbolic tiers are exported below the main tier.
+
+use std::{collections::HashMap, fs::File, io::Write, path::Path};
+
+use quick_xml::se::Serializer;
+use serde::{Deserialize, Serialize};
+
+use crate::{eaf::ExternalRef, Annotation, Eaf, EafError, Language, LinguisticType, StereoType, Tier};
+
+/// Duration in milliseconds for phrases without time offsets.
+pub const PHRASE_DURATION: i64 = 1000;
+
+/// Prefix for GUIDs stored as external reference values.
+const GUID_PREFIX: &str = "urn:uuid:";
+
+/// FLEx interlinear text document.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename = "document")]
+pub struct FlexText {
+    #[serde(rename = "@version", skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(rename = "interlinear-text", default)]
+    pub texts: Vec<InterlinearText>,
+}
+
+/// A single text.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct InterlinearText {
+    #[serde(rename = "@guid", skip_serializing_if = "Option::is_none")]
+    pub guid: Option<String>,
+    /// Text level items, e.g. title.
+    #[serde(rename = "item", default)]
+    pub items: Vec<FlexItem>,
+    #[serde(default)]
+    pub paragraphs: Paragraphs,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub languages: Option<FlexLanguages>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Paragraphs {
+    #[serde(rename = "paragraph", default)]
+    pub paragraphs: Vec<Paragraph>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Paragraph {
+    #[serde(rename = "@guid", skip_serializing_if = "Option::is_none")]
+    pub guid: Option<String>,
+    #[serde(default)]
+    pub phrases: Phrases,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Phrases {
+    #[serde(rename = "phrase", default)]
+    pub phrases: Vec<Phrase>,
+}
+
+/// Phrase (segment).
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Phrase {
+    #[serde(rename = "@guid", skip_serializing_if = "Option::is_none")]
+    pub guid: Option<String>,
+    /// Start time in milliseconds.
+    #[serde(rename = "@begin-time-offset", skip_serializing_if = "Option::is_none")]
+    pub begin_time_offset: Option<i64>,
+    /// End time in milliseconds.
+    #[serde(rename = "@end-time-offset", skip_serializing_if = "Option::is_none")]
+    pub end_time_offset: Option<i64>,
+    #[serde(rename = "@speaker", skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    /// Phrase level items, e.g. segment number, free translation.
+    #[serde(rename = "item", default)]
+    pub items: Vec<FlexItem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub words: Option<Words>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Words {
+    #[serde(rename = "word", default)]
+    pub words: Vec<Word>,
+}
+
+/// Word, or punctuation.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Word {
+    #[serde(rename = "@guid", skip_serializing_if = "Option::is_none")]
+    pub guid: Option<String>,
+    /// Word level items, e.g. text, gloss, part of speech.
+    #[serde(rename = "item", default)]
+    pub items: Vec<FlexItem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub morphemes: Option<Morphemes>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Morphemes {
+    #[serde(rename = "morph", default)]
+    pub morphs: Vec<Morph>,
+}
+
+/// Morpheme.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Morph {
+    /// Morpheme type, e.g. `stem`, `suffix`.
+    #[serde(rename = "@type", skip_serializing_if = "Option::is_none")]
+    pub morph_type: Option<String>,
+    #[serde(rename = "@guid", skip_serializing_if = "Option::is_none")]
+    pub guid: Option<String>,
+    /// Morpheme level items, e.g. text, citation form, gloss.
+    #[serde(rename = "item", default)]
+    pub items: Vec<FlexItem>,
+}
+
+/// Item with a value for a specific type and language,
+/// e.g. `<item type="gls" lang="en">dog</item>`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct FlexItem {
+    /// Item type, e.g. `txt`, `gls`, `segnum`, `punct`.
+    #[serde(rename = "@type")]
+    pub item_type: String,
+    #[serde(rename = "@lang")]
+    pub lang: String,
+    #[serde(rename = "$text", default)]
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct FlexLanguages {
+    #[serde(rename = "language", default)]
+    pub languages: Vec<FlexLanguage>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct FlexLanguage {
+    #[serde(rename = "@lang")]
+    pub lang: String,
+    #[serde(rename = "@font", skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    #[serde(rename = "@vernacular", skip_serializing_if = "Option::is_none")]
+    pub vernacular: Option<bool>,
+}
+
+impl FlexItem {
+    fn new(item_type: &str, lang: &str, value: &str) -> Self {
+        Self {
+            item_type: item_type.to_owned(),
+            lang: lang.to_owned(),
+            value: value.to_owned(),
+        }
+    }
+}
+
+/// Item types for the annotations in a subdivision tier,
+/// i.e. words or morphemes.
+fn is_baseline(item_type: &str) -> bool {
+    matches!(item_type, "txt" | "punct")
+}
+
+/// Returns `true` if `value` only contains punctuation.
+fn is_punctuation(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_punctuation() || "¡¿«»“”‘’…–—".contains(c))
+}
+
+/// Tiers generated on import, in the order these are first added.
+struct TierSet {
+    tiers: Vec<Tier>,
+    linguistic_types: Vec<LinguisticType>,
+}
+
+impl TierSet {
+    /// Appends annotation to tier, adding the tier if it does not exist.
+    fn push(
+        &mut self,
+        tier_id: &str,
+        parent: &str,
+        stereotype: StereoType,
+        lang: Option<&str>,
+        participant: Option<&str>,
+        annotation: Annotation
+    ) {
+        if let Some(tier) = self.tiers.iter_mut().find(|t| t.tier_id == tier_id) {
+            tier.annotations.push(annotation);
+            return
+        }
+        // Linguistic type ID is tier ID without language
+        // and speaker suffix, e.g. "word-gls".
+        let base_id = tier_id.split('@').next().unwrap_or(tier_id);
+        let lt_id = match lang {
+            Some(l) => base_id.strip_suffix(&format!("-{l}")).unwrap_or(base_id),
+            None => base_id,
+        };
+        let lt = LinguisticType::new(lt_id, Some(&stereotype));
+        if !self.linguistic_types.contains(&lt) {
+            self.linguistic_types.push(lt);
+        }
+        let mut tier = Tier::new(tier_id, Some(&[annotation]), Some(lt_id), Some(parent));
+        tier.lang_ref = lang.map(String::from);
+        tier.participant = participant.map(String::from);
+        self.tiers.push(tier);
+    }
+}
+
+/// Generates annotation IDs and external references for GUIDs on import.
+struct Ids {
+    next: i64,
+    external_refs: Vec<ExternalRef>,
+}
+
+impl Ids {
+    fn annotation_id(&mut self) -> String {
+        self.next += 1;
+        format!("a{}", self.next - 1)
+    }
+
+    /// Adds external reference for `guid`, and returns its ID.
+    fn ext_ref(&mut self, guid: Option<&str>) -> Option<String> {
+        let guid = guid?;
+        let ext_ref_id = format!("guid_{guid}");
+        if !self.external_refs.iter().any(|e| e.ext_ref_id == ext_ref_id) {
+            self.external_refs.push(ExternalRef {
+                ext_ref_id: ext_ref_id.to_owned(),
+                ref_type: "resource_url".to_owned(),
+                value: format!("{GUID_PREFIX}{guid}"),
+            });
+        }
+        Some(ext_ref_id)
+    }
+}
+
+/// Builds referred annotation.
+fn ref_annotation(
+    id: &str,
+    value: &str,
+    parent_id: &str,
+    previous: Option<&str>,
+    ext_ref: Option<String>
+) -> Result<Annotation, EafError> {
+    let mut annotation = match previous {
+        Some(prev) => Annotation::builder()
+            .annotation_id(id)
+            .annotation_value(value)
+            .annotation_ref(parent_id)
+            .previous_annotation(prev)
+            .build()?,
+        None => Annotation::builder()
+            .annotation_id(id)
+            .annotation_value(value)
+            .annotation_ref(parent_id)
+            .build()?,
+    };
+    annotation.set_ext_ref(ext_ref.as_deref());
+    Ok(annotation)
+}
+
+impl FlexText {
+    /// Reads and parses a flextext file.
+    pub fn read(path: &Path) -> Result<Self, EafError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses flextext content.
+    pub fn parse(content: &str) -> Result<Self, EafError> {
+        quick_xml::de::from_str(content.trim_start_matches('\u{feff}'))
+            .map_err(|e| EafError::QuickXMLDeError(e))
+    }
+
+    /// Serializes to flextext string, indented with two spaces.
+    pub fn to_string(&self) -> Result<String, EafError> {
+        let mut xml = String::new();
+        let mut ser = Serializer::new(&mut xml);
+        ser.indent(' ', 2);
+        self.serialize(ser).map_err(|e| EafError::QuickXMLSeError(e))?;
+
+        Ok([
+            r#"<?xml version="1.0" encoding="utf-8"?>"#,
+            xml.as_str()
+        ].join("\n"))
+    }
+
+    /// Serializes and writes flextext to disk.
+    pub fn write(&self, path: &Path) -> Result<(), EafError> {
+        let mut outfile = File::create(&path)?;
+        outfile.write_all(self.to_string()?.as_bytes()).map_err(|e| EafError::IOError(e))
+    }
+
+    /// Returns all phrases in all texts and paragraphs, in document order.
+    pub fn phrases(&self) -> Vec<&Phrase> {
+        self.texts.iter()
+            .flat_map(|t| t.paragraphs.paragraphs.iter())
+            .flat_map(|p| p.phrases.phrases.iter())
+            .collect()
+    }
+
+    /// Generates an `Eaf` with phrases in a main tier, and words, morphemes,
+    /// and their items in referred tiers. See module documentation for details.
+    ///
+    /// Since annotations in a tier may not overlap,
+    /// a phrase that overlaps the next one by the same speaker is truncated.
+    /// Returns error if two phrases by the same speaker start at the same time.
+    pub fn to_eaf(&self) -> Result<Eaf, EafError> {
+        let all_phrases = self.phrases();
+
+        // Phrases with time spans for each speaker,
+        // in the order the speakers first appear.
+        let mut speakers: Vec<(Option<&str>, Vec<(&Phrase, i64, i64)>)> = Vec::new();
+        let mut speaker_index: HashMap<Option<&str>, usize> = HashMap::new();
+        let mut cursor = 0;
+        for phrase in all_phrases.iter() {
+            let start = phrase.begin_time_offset.unwrap_or(cursor);
+            let end = phrase.end_time_offset
+                .filter(|end| *end >= start)
+                .unwrap_or(start + PHRASE_DURATION);
+            cursor = end;
+            let speaker = phrase.speaker.as_deref();
+            let i = *speaker_index.entry(speaker).or_insert_with(|| {
+                speakers.push((speaker, Vec::new()));
+                speakers.len() - 1
+            });
+            speakers[i].1.push((phrase, start, end));
+        }
+        for (speaker, phrases) in speakers.iter_mut() {
+            phrases.sort_by_key(|(_, start, end)| (*start, *end));
+            for i in 1..phrases.len() {
+                let next_start = phrases[i].1;
+                if phrases[i-1].1 == next_start {
+                    return Err(EafError::FlexTextInvalid(format!(
+                        "Two phrases by speaker '{}' start at {next_start} ms",
+                        speaker.unwrap_or_default()
+                    )))
+                }
+                if phrases[i-1].2 > next_start {
+                    phrases[i-1].2 = next_start;
+                }
+            }
+        }
+
+        // The main tier contains the first phrase level item, usually the segment number.
+        let (main_type, main_lang) = all_phrases.iter()
+            .find_map(|p| p.items.first())
+            .map(|i| (i.item_type.to_owned(), i.lang.to_owned()))
+            .unwrap_or(("segnum".to_owned(), "und".to_owned()));
+        let main_id = format!("phrase-{main_type}-{main_lang}");
+        // Words and morphemes each go in a single subdivision tier,
+        // in the language of the first baseline item.
+        let word_lang = all_phrases.iter()
+            .flat_map(|p| p.words.iter().flat_map(|w| w.words.iter()))
+            .find_map(|w| w.items.iter().find(|i| is_baseline(&i.item_type)))
+            .map(|i| i.lang.to_owned())
+            .unwrap_or(main_lang.to_owned());
+        let word_id = format!("word-txt-{word_lang}");
+        let morph_lang = all_phrases.iter()
+            .flat_map(|p| p.words.iter().flat_map(|w| w.words.iter()))
+            .flat_map(|w| w.morphemes.iter().flat_map(|m| m.morphs.iter()))
+            .find_map(|m| m.items.iter().find(|i| i.item_type == "txt"))
+            .map(|i| i.lang.to_owned())
+            .unwrap_or(word_lang.to_owned());
+        let morph_id = format!("morph-txt-{morph_lang}");
+
+        // Tier ID suffix for a speaker, if there is more than one.
+        let multiple = speakers.len() > 1;
+        let suffix = |speaker: Option<&str>| match (multiple, speaker) {
+            (true, Some(s)) => format!("@{s}"),
+            _ => String::new(),
+        };
+
+        let tier_values: Vec<(String, Vec<(String, i64, i64)>)> = speakers.iter()
+            .map(|(speaker, phrases)| (
+                format!("{main_id}{}", suffix(*speaker)),
+                phrases.iter()
+                    .map(|(p, start, end)| (
+                        p.items.iter()
+                            .find(|i| i.item_type == main_type && i.lang == main_lang)
+                            .map(|i| i.value.to_owned())
+                            .unwrap_or_default(),
+                        *start,
+                        *end
+                    ))
+                    .collect()
+            ))
+            .collect();
+
+        let mut eaf = match tier_values.is_empty() {
+            true => Eaf::from_tier_values(&[(main_id.to_owned(), Vec::new())])?,
+            false => Eaf::from_tier_values(&tier_values)?,
+        };
+
+        let mut ids = Ids {
+            next: eaf.generate_a_id_num().unwrap_or(1),
+            external_refs: Vec::new(),
+        };
+        let mut tiers = TierSet {
+            tiers: Vec::new(),
+            linguistic_types: Vec::new(),
+        };
+
+        for (speaker, phrases) in speakers.iter() {
+            let suffix = suffix(*speaker);
+            let main_id = format!("{main_id}{suffix}");
+            let word_id = format!("{word_id}{suffix}");
+            let morph_id = format!("{morph_id}{suffix}");
+
+            let main_tier = eaf.get_tier_mut(&main_id)
+                .ok_or_else(|| EafError::TierIdInvalid(main_id.to_owned()))?;
+            main_tier.lang_ref = Some(main_lang.to_owned());
+            main_tier.participant = speaker.map(String::from);
+
+            for ((phrase, ..), annotation) in phrases.iter().zip(main_tier.annotations.iter_mut()) {
+                annotation.set_ext_ref(ids.ext_ref(phrase.guid.as_deref()).as_deref());
+                let p_id = annotation.id().to_owned();
+
+                for item in phrase.items.iter().filter(|i| !(i.item_type == main_type && i.lang == main_lang)) {
+                    let a = ref_annotation(&ids.annotation_id(), &item.value, &p_id, None, None)?;
+                    tiers.push(&format!("phrase-{}-{}{suffix}", item.item_type, item.lang), &main_id,
+                        StereoType::SymbolicAssociation, Some(&item.lang), *speaker, a);
+                }
+
+                let mut previous_word: Option<String> = None;
+                for word in phrase.words.iter().flat_map(|w| w.words.iter()) {
+                    let w_id = ids.annotation_id();
+                    let value = word.items.iter()
+                        .find(|i| is_baseline(&i.item_type))
+                        .map(|i| i.value.as_str())
+                        .unwrap_or_default();
+                    let a = ref_annotation(&w_id, value, &p_id, previous_word.as_deref(), ids.ext_ref(word.guid.as_deref()))?;
+                    tiers.push(&word_id, &main_id, StereoType::SymbolicSubdivision, Some(&word_lang), *speaker, a);
+                    previous_word = Some(w_id.to_owned());
+
+                    for item in word.items.iter().filter(|i| !is_baseline(&i.item_type)) {
+                        let a = ref_annotation(&ids.annotation_id(), &item.value, &w_id, None, None)?;
+                        tiers.push(&format!("word-{}-{}{suffix}", item.item_type, item.lang), &word_id,
+                            StereoType::SymbolicAssociation, Some(&item.lang), *speaker, a);
+                    }
+
+                    let mut previous_morph: Option<String> = None;
+                    for morph in word.morphemes.iter().flat_map(|m| m.morphs.iter()) {
+                        let m_id = ids.annotation_id();
+                        let value = morph.items.iter()
+                            .find(|i| i.item_type == "txt")
+                            .map(|i| i.value.as_str())
+                            .unwrap_or_default();
+                        let a = ref_annotation(&m_id, value, &w_id, previous_morph.as_deref(), ids.ext_ref(morph.guid.as_deref()))?;
+                        tiers.push(&morph_id, &word_id, StereoType::SymbolicSubdivision, Some(&morph_lang), *speaker, a);
+                        previous_morph = Some(m_id.to_owned());
+
+                        if let Some(morph_type) = morph.morph_type.as_deref() {
+                            let a = ref_annotation(&ids.annotation_id(), morph_type, &m_id, None, None)?;
+                            tiers.push(&format!("morph-type{suffix}"), &morph_id,
+                                StereoType::SymbolicAssociation, None, *speaker, a);
+                        }
+                        let baseline = morph.items.iter().position(|i| i.item_type == "txt");
+                        for (_, item) in morph.items.iter().enumerate().filter(|(j, _)| Some(*j) != baseline) {
+                            let a = ref_annotation(&ids.annotation_id(), &item.value, &m_id, None, None)?;
+                            tiers.push(&format!("morph-{}-{}{suffix}", item.item_type, item.lang), &morph_id,
+                                StereoType::SymbolicAssociation, Some(&item.lang), *speaker, a);
+                        }
+                    }
+                }
+            }
+        }
+
+        for lt in tiers.linguistic_types.iter() {
+            eaf.add_linguistic_type(lt, true);
+        }
+        eaf.tiers.extend(tiers.tiers);
+        eaf.external_refs = ids.external_refs;
+
+        let mut languages: Vec<String> = self.texts.iter()
+            .flat_map(|t| t.languages.iter().flat_map(|l| l.languages.iter()))
+            .map(|l| l.lang.to_owned())
+            .collect();
+        languages.extend(eaf.tiers.iter().filter_map(|t| t.lang_ref.to_owned()));
+        for lang in languages {
+            if !eaf.languages.iter().any(|l| l.lang_id == lang) {
+                eaf.languages.push(Language { lang_id: lang, ..Language::default() });
+            }
+        }
+
+        eaf.index();
+        eaf.derive()?;
+
+        Ok(eaf)
+    }
+
+    /// Generates flextext from the main tiers in an `Eaf`, with one phrase per
+    /// main tier annotation, sorted on start time. Words and morphemes are
+    /// generated from `Symbolic_Subdivision` tiers, and other items from
+    /// `Symbolic_Association` tiers. See module documentation for details.
+    pub fn from_eaf(eaf: &Eaf) -> Result<Self, EafError> {
+        let export = Export::new(eaf);
+
+        let mut phrases: Vec<(Option<i64>, Phrase)> = Vec::new();
+        for tier in eaf.main_tiers() {
+            let key = export.key(tier, "txt");
+            let (words, items) = export.children(tier);
+            for annotation in tier.iter() {
+                let mut phrase = Phrase {
+                    guid: export.guid(annotation),
+                    begin_time_offset: annotation.ts_val().0,
+                    end_time_offset: annotation.ts_val().1,
+                    speaker: tier.participant.to_owned(),
+                    items: vec![FlexItem::new(&key.0, &key.1, annotation.to_str())],
+                    words: None,
+                };
+                phrase.items.extend(export.items(&items, annotation.id()).into_iter()
+                    .map(|(_, item)| item));
+                if let Some(word_tier) = words {
+                    phrase.words = Some(Words {
+                        words: export.words(word_tier, annotation.id())
+                    });
+                }
+                phrases.push((phrase.begin_time_offset, phrase));
+            }
+        }
+        phrases.sort_by_key(|(start, _)| start.unwrap_or(i64::MAX));
+
+        Ok(Self {
+            version: Some("2".to_owned()),
+            texts: vec![InterlinearText {
+                guid: None,
+                items: Vec::new(),
+                paragraphs: Paragraphs {
+                    paragraphs: vec![Paragraph {
+                        guid: None,
+                        phrases: Phrases {
+                            phrases: phrases.into_iter().map(|(_, p)| p).collect(),
+                        },
+                    }],
+                },
+                languages: Some(FlexLanguages {
+                    languages: export.languages(),
+                }),
+            }],
+        })
+    }
+}
+
+/// Lookups for exporting an `Eaf` as flextext.
+struct Export<'a> {
+    eaf: &'a Eaf,
+    /// Linguistic type ID to stereotype.
+    stereotypes: HashMap<&'a str, &'a str>,
+    /// External reference ID to GUID.
+    guids: HashMap<&'a str, &'a str>,
+    /// Parent annotation ID to referring annotations in each tier.
+    children: HashMap<(&'a str, &'a str), Vec<&'a Annotation>>,
+    default_lang: String,
+}
+
+impl<'a> Export<'a> {
+    fn new(eaf: &'a Eaf) -> Self {
+        let mut children: HashMap<(&str, &str), Vec<&Annotation>> = HashMap::new();
+        for tier in eaf.ref_tiers() {
+            for annotation in tier.iter() {
+                if let Some(ref_id) = annotation.ref_id() {
+                    children.entry((tier.tier_id.as_str(), ref_id)).or_default().push(annotation);
+                }
+            }
+        }
+        // Order subdivisions via previous annotation references.
+        for annotations in children.values_mut() {
+            if annotations.len() > 1 {
+                let mut ordered: Vec<&Annotation> = Vec::new();
+                let mut previous: Option<&str> = None;
+                while let Some(next) = annotations.iter().find(|a| a.previous() == previous) {
+                    ordered.push(next);
+                    previous = Some(next.id());
+                    if ordered.len() == annotations.len() {
+                        break;
+                    }
+                }
+                if ordered.len() == annotations.len() {
+                    *annotations = ordered;
+                }
+            }
+        }
+
+        Self {
+            eaf,
+            stereotypes: eaf.linguistic_types.iter()
+                .filter_map(|lt| lt.constraints.as_deref().map(|c| (lt.linguistic_type_id.as_str(), c)))
+                .collect(),
+            guids: eaf.external_refs.iter()
+                .filter_map(|e| e.value.strip_prefix(GUID_PREFIX).map(|g| (e.ext_ref_id.as_str(), g)))
+                .collect(),
+            children,
+            default_lang: eaf.languages.first()
+                .map(|l| l.lang_id.to_owned())
+                .unwrap_or("und".to_owned()),
+        }
+    }
+
+    /// Item type and language for a tier, from a tier ID
+    /// in the form `<LEVEL>-<ITEM_TYPE>-<LANGUAGE>[@<SPEAKER>]`, or defaults.
+    fn key(&self, tier: &Tier, default_type: &str) -> (String, String) {
+        let parts: Vec<&str> = tier.tier_id.splitn(3, '-').collect();
+        match parts.as_slice() {
+            [level, item_type, lang] if ["phrase", "word", "morph"].contains(level) => {
+                // Language without speaker suffix, e.g. "en" for "word-gls-en@Anna"
+                let lang = lang.split('@').next().unwrap_or(lang);
+                (item_type.to_string(), lang.to_string())
+            },
+            _ => (
+                default_type.to_owned(),
+                tier.lang_ref.to_owned().unwrap_or(self.default_lang.to_owned())
+            ),
+        }
+    }
+
+    fn guid(&self, annotation: &Annotation) -> Option<String> {
+        annotation.ext_ref()
+            .and_then(|e| self.guids.get(e.as_str()).map(|g| g.to_string()))
+    }
+
+    /// Returns the first symbolic subdivision tier, and all
+    /// symbolic association tiers that refer to `tier`.
+    fn children(&self, tier: &Tier) -> (Option<&'a Tier>, Vec<&'a Tier>) {
+        let stereotype = |t: &Tier| self.stereotypes.get(t.linguistic_type_ref.as_str()).copied();
+        let child_tiers: Vec<&Tier> = self.eaf.tiers.iter()
+            .filter(|t| t.parent_ref.as_deref() == Some(tier.tier_id.as_str()))
+            .collect();
+        (
+            child_tiers.iter()
+                .find(|t| stereotype(t) == Some("Symbolic_Subdivision"))
+                .copied(),
+            child_tiers.into_iter()
+                .filter(|t| stereotype(t) == Some("Symbolic_Association"))
+                .collect()
+        )
+    }
+
+    /// Returns annotations in `tier` that refer to `parent_id`.
+    fn referring(&self, tier: &Tier, parent_id: &str) -> Vec<&'a Annotation> {
+        self.children.get(&(tier.tier_id.as_str(), parent_id))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Items from association tiers for the annotation `parent_id`,
+    /// with the tier ID for each item.
+    fn items(&self, tiers: &[&'a Tier], parent_id: &str) -> Vec<(&'a str, FlexItem)> {
+        tiers.iter()
+            .flat_map(|t| {
+                let (item_type, lang) = self.key(t, "gls");
+                self.referring(t, parent_id).into_iter()
+                    .map(move |a| (t.tier_id.as_str(), FlexItem::new(&item_type, &lang, a.to_str())))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn words(&self, word_tier: &'a Tier, phrase_id: &str) -> Vec<Word> {
+        let (item_type, lang) = self.key(word_tier, "txt");
+        let (morph_tier, item_tiers) = self.children(word_tier);
+        self.referring(word_tier, phrase_id).into_iter()
+            .map(|a| {
+                let items = self.items(&item_tiers, a.id());
+                let morphs = morph_tier.map(|t| self.morphs(t, a.id())).unwrap_or_default();
+                let item_type = match items.is_empty() && morphs.is_empty() && is_punctuation(a.to_str()) {
+                    true => "punct",
+                    false => item_type.as_str(),
+                };
+                let mut word = Word {
+                    guid: self.guid(a),
+                    items: vec![FlexItem::new(item_type, &lang, a.to_str())],
+                    morphemes: None,
+                };
+                word.items.extend(items.into_iter().map(|(_, item)| item));
+                if !morphs.is_empty() {
+                    word.morphemes = Some(Morphemes { morphs });
+                }
+                word
+            })
+            .collect()
+    }
+
+    fn morphs(&self, morph_tier: &'a Tier, word_id: &str) -> Vec<Morph> {
+        let (item_type, lang) = self.key(morph_tier, "txt");
+        let (_, item_tiers) = self.children(morph_tier);
+        self.referring(morph_tier, word_id).into_iter()
+            .map(|a| {
+                let mut morph = Morph {
+                    morph_type: None,
+                    guid: self.guid(a),
+                    items: vec![FlexItem::new(&item_type, &lang, a.to_str())],
+                };
+                for (tier_id, item) in self.items(&item_tiers, a.id()) {
+                    match tier_id.split('@').next() == Some("morph-type") {
+                        true => morph.morph_type = Some(item.value),
+                        false => morph.items.push(item),
+                    }
+                }
+                morph
+            })
+            .collect()
+    }
+
+    /// Languages used in the EAF. Languages for main tier text,
+    /// words and morphemes are vernacular.
+    fn languages(&self) -> Vec<FlexLanguage> {
+        let mut vernacular: Vec<String> = Vec::new();
+        for tier in self.eaf.tiers.iter() {
+            let is_text = match tier.is_main() {
+                true => true,
+                false => self.stereotypes.get(tier.linguistic_type_ref.as_str()) == Some(&"Symbolic_Subdivision"),
+            };
+            let (item_type, lang) = self.key(tier, "txt");
+            if is_text && item_type == "txt" && !vernacular.contains(&lang) {
+                vernacular.push(lang);
+            }
+        }
+        let mut langs: Vec<String> = vernacular.to_owned();
+        for lang in self.eaf.languages.iter().map(|l| &l.lang_id)
+            .chain(self.eaf.tiers.iter().filter_map(|t| t.lang_ref.as_ref()))
+        {
+            if !langs.contains(lang) {
+                langs.push(lang.to_owned());
+            }
+        }
+        langs.into_iter()
+            .map(|lang| FlexLanguage {
+                vernacular: Some(vernacular.contains(&lang)),
+                lang,
+                font: None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLEXTEXT: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<document version="2">
+  <interlinear-text guid="t1">
+    <item type="title" lang="en">Dogs</item>
+    <paragraphs>
+      <paragraph guid="p1">
+        <phrases>
+          <phrase guid="ph1" begin-time-offset="0" end-time-offset="1500">
+            <item type="segnum" lang="en">1</item>
+            <item type="gls" lang="en">The dogs ran.</item>
+            <words>
+              <word guid="w1">
+                <item type="txt" lang="xyz">hundar</item>
+                <item type="gls" lang="en">dogs</item>
+                <morphemes>
+                  <morph type="stem" guid="m1">
+                    <item type="txt" lang="xyz">hund</item>
+                    <item type="gls" lang="en">dog</item>
+                  </morph>
+                  <morph type="suffix" guid="m2">
+                    <item type="txt" lang="xyz">-ar</item>
+                    <item type="gls" lang="en">PL</item>
+                  </morph>
+                </morphemes>
+              </word>
+              <word guid="w2">
+                <item type="txt" lang="xyz">sprang</item>
+              </word>
+              <word>
+                <item type="punct" lang="xyz">.</item>
+              </word>
+            </words>
+          </phrase>
+          <phrase guid="ph2">
+            <item type="segnum" lang="en">2</item>
+          </phrase>
+        </phrases>
+      </paragraph>
+    </paragraphs>
+    <languages>
+      <language lang="xyz" vernacular="true"/>
+      <language lang="en"/>
+    </languages>
+  </interlinear-text>
+</document>"#;
+
+    fn phrase(speaker: &str, segnum: &str, start: i64, end: i64) -> Phrase {
+        Phrase {
+            speaker: Some(speaker.to_owned()),
+            begin_time_offset: Some(start),
+            end_time_offset: Some(end),
+            items: vec![FlexItem::new("segnum", "en", segnum)],
+            ..Phrase::default()
+        }
+    }
+
+    fn flextext(phrases: Vec<Phrase>) -> FlexText {
+        FlexText {
+            version: Some("2".to_owned()),
+            texts: vec![InterlinearText {
+                paragraphs: Paragraphs {
+                    paragraphs: vec![Paragraph { guid: None, phrases: Phrases { phrases } }]
+                },
+                ..InterlinearText::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn to_eaf() {
+        let eaf = FlexText::parse(FLEXTEXT).unwrap().to_eaf().unwrap();
+        assert_eq!(eaf.main_tier_ids(), vec!["phrase-segnum-en"]);
+
+        let main = eaf.get_tier("phrase-segnum-en").unwrap();
+        let spans: Vec<(Option<i64>, Option<i64>)> = main.iter().map(|a| a.ts_val()).collect();
+        // Second phrase has no time offsets, and follows the first one.
+        assert_eq!(spans, vec![(Some(0), Some(1500)), (Some(1500), Some(1500 + PHRASE_DURATION))]);
+
+        assert_eq!(eaf.get_tier("phrase-gls-en").unwrap().values(), vec!["The dogs ran."]);
+        assert_eq!(eaf.get_tier("word-txt-xyz").unwrap().values(), vec!["hundar", "sprang", "."]);
+        assert_eq!(eaf.get_tier("word-gls-en").unwrap().values(), vec!["dogs"]);
+        assert_eq!(eaf.get_tier("morph-txt-xyz").unwrap().values(), vec!["hund", "-ar"]);
+        assert_eq!(eaf.get_tier("morph-gls-en").unwrap().values(), vec!["dog", "PL"]);
+        assert_eq!(eaf.get_tier("morph-type").unwrap().values(), vec!["stem", "suffix"]);
+
+        let words = eaf.get_tier("word-txt-xyz").unwrap();
+        assert_eq!(words.linguistic_type_ref, "word-txt");
+        assert_eq!(words.annotations[1].previous(), Some(words.annotations[0].id()));
+        assert_eq!(
+            eaf.external_refs.iter().find(|e| e.ext_ref_id == "guid_w1").map(|e| e.value.as_str()),
+            Some("urn:uuid:w1")
+        );
+        let mut langs: Vec<&str> = eaf.languages.iter().map(|l| l.lang_id.as_str()).collect();
+        langs.sort();
+        assert_eq!(langs, vec!["en", "xyz"]);
+        assert!(eaf.validate().is_empty());
+    }
+
+    #[test]
+    fn round_trip() {
+        let flex = FlexText::parse(FLEXTEXT).unwrap();
+        let flex2 = FlexText::from_eaf(&flex.to_eaf().unwrap()).unwrap();
+        let phrases = flex.phrases();
+        let phrases2 = flex2.phrases();
+        assert_eq!(phrases2.len(), 2);
+        assert_eq!(phrases2[0].guid, phrases[0].guid);
+        assert_eq!(phrases2[0].items, phrases[0].items);
+        // All words, including punctuation and morphemes with types, are kept.
+        assert_eq!(phrases2[0].words, phrases[0].words);
+
+        let languages = flex2.texts[0].languages.as_ref().unwrap();
+        assert!(languages.languages.iter().any(|l| l.lang == "xyz" && l.vernacular == Some(true)));
+        assert!(languages.languages.iter().any(|l| l.lang == "en" && l.vernacular == Some(false)));
+
+        // Serialized output parses to the same document.
+        assert_eq!(FlexText::parse(&flex2.to_string().unwrap()).unwrap(), flex2);
+    }
+
+    #[test]
+    fn speakers() {
+        // Overlapping phrases by different speakers
+        let flex = flextext(vec![
+            phrase("Anna", "1", 0, 2000),
+            phrase("Bo", "2", 1000, 3000),
+            phrase("Anna", "3", 2500, 4000),
+        ]);
+        let eaf = flex.to_eaf().unwrap();
+        assert_eq!(eaf.main_tier_ids(), vec!["phrase-segnum-en@Anna", "phrase-segnum-en@Bo"]);
+        assert_eq!(eaf.get_tier("phrase-segnum-en@Anna").unwrap().participant.as_deref(), Some("Anna"));
+        assert_eq!(eaf.get_tier("phrase-segnum-en@Anna").unwrap().values(), vec!["1", "3"]);
+        assert_eq!(eaf.get_tier("phrase-segnum-en@Bo").unwrap().values(), vec!["2"]);
+
+        let flex = FlexText::from_eaf(&eaf).unwrap();
+        let speakers: Vec<(Option<&str>, &str)> = flex.phrases().into_iter()
+            .map(|p| (p.speaker.as_deref(), p.items[0].value.as_str()))
+            .collect();
+        assert_eq!(speakers, vec![(Some("Anna"), "1"), (Some("Bo"), "2"), (Some("Anna"), "3")]);
+    }
+
+    #[test]
+    fn overlap() {
+        let eaf = flextext(vec![
+            phrase("Anna", "1", 0, 2000),
+            phrase("Anna", "2", 1000, 3000),
+        ]).to_eaf().unwrap();
+        let spans: Vec<(Option<i64>, Option<i64>)> = eaf.get_tier("phrase-segnum-en").unwrap().iter()
+            .map(|a| a.ts_val())
+            .collect();
+        assert_eq!(spans, vec![(Some(0), Some(1000)), (Some(1000), Some(3000))]);
+
+        // Truncating would result in a zero length annotation
+        let flex = flextext(vec![
+            phrase("Anna", "1", 1000, 2000),
+            phrase("Anna", "2", 1000, 3000),
+        ]);
+        assert!(matches!(flex.to_eaf(), Err(EafError::FlexTextInvalid(_))));
+    }
+
+    #[test]
+    fn from_eaf() {
+        let eaf = Eaf::from_values(&[("hi".to_owned(), 0, 1000)], Some("text")).unwrap();
+        let flex = FlexText::from_eaf(&eaf).unwrap();
+        let phrases = flex.phrases();
+        assert_eq!(phrases.len(), 1);
+        assert_eq!(phrases[0].items, vec![FlexItem::new("txt", "und", "hi")]);
+        assert_eq!((phrases[0].begin_time_offset, phrases[0].end_time_offset), (Some(0), Some(1000)));
+    }
+}
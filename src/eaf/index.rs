@@ -5,34 +5,53 @@
 //! Contains the following mappings:
 //! - `a2t`: Annotation ID to tier ID
 //! - `a2ref`: Annotation ID to ref annotation ID
-//! - `t2a`: Tier ID to list of annotation ID:s
+//! - `t2a`: Tier ID to annotation ID:s, in tier order
 //! - `t2ref`: Tier ID to ref tier ID
 //! - `id2ts`: Time slot ID to time slot value
 //! - `ts2id`: Time slot value to Time slot ID
 //! - `a2ts`: Annotation ID to time slot id/ref tuple, `(time_slot_ref1, time_slot_ref2)`.
-//! - `a2idx`: Annotation ID to `(AnnotationDocument.tiers[idx1], tier.annotations[idx2])`
+//! - `ts2a`: Time slot ID to ID:s of annotations referring to it
+//! - `ref2a`: Annotation ID to ID:s of annotations referring to it
 //! - `t2idx`: Tier ID to `AnnotationDocument.tiers[idx]`
+//! - `time`: Time index for all annotations
+//! - `t2time`: Tier ID to time index for annotations in tier
+//!
+//! Positions in `Eaf.tiers`, `(AnnotationDocument.tiers[idx1], tier.annotations[idx2])`,
+//! are looked up via `Index::a2idx()`, rather than stored per annotation, since these
+//! change for all following annotations when an annotation is added or removed.
+//!
+//! Built in full by `Eaf::index()`. Methods that add, remove or edit annotations,
+//! tiers and time slots, such as `Eaf::add_annotation()`, only update the affected entries.
+//! Adding or removing an annotation is `O(log n)`, adding a tier is `O(k log n)`
+//! for a tier with `k` annotations.
 
 use std::collections::HashMap;
 
+use crate::{Annotation, EafError, Tier, TimeOrder};
+
+use super::TimeIndex;
+use super::time_index::priority;
+
 #[derive(Debug, Clone)]
 /// Index with mappings for:
 /// - Annotation ID to tier ID
 /// - Annotation ID to ref annotation ID
-/// - Tier ID to list of annotation ID:s
+/// - Tier ID to annotation ID:s, in tier order
 /// - Tier ID to ref tier ID
 /// - Time slot ID to time slot value
 /// - Time slot value to Time slot ID
 /// - Annotation ID to time slot id/ref tuple, `(time_slot_ref1, time_slot_ref2)`.
-/// - Annotation ID to `(AnnotationDocument.tiers[idx1], tier.annotations[idx2])`
+/// - Time slot ID to annotation ID:s
+/// - Annotation ID to referring annotation ID:s
 /// - Tier ID to `AnnotationDocument.tiers[idx]`
+/// - Time index for all annotations, and for each tier
 pub struct Index {
     /// Key: Annotation ID. Value: Tier ID.
     pub a2t: HashMap<String, String>,
     /// Key: Annotation ID. Value: Ref annotation ID.
     pub a2ref: HashMap<String, String>,
-    /// Key: Tier ID. Value: List of Annotation ID:s in tier.
-    pub t2a: HashMap<String, Vec<String>>,
+    /// Key: Tier ID. Value: Annotation ID:s in tier, in tier order.
+    pub(crate) t2a: HashMap<String, Sequence>,
     /// Key: Tier ID. Value: Ref Tier ID.
     pub t2ref: HashMap<String, String>,
     /// Key: Time slot ID. Value: Time slot value (optional).
@@ -42,13 +61,17 @@ pub struct Index {
     pub tv2ts: HashMap<i64, String>,
     /// Key: Annotation ID. Value: Time slot id/ref tuple, `(time_slot_ref1, time_slot_ref2)`.
     pub a2ts: HashMap<String, (String, String)>,
-    /// Key: Annotation ID. Value: Index in `AnnotationDocument.tiers` (i.e. which tier),
-    /// and index in `tier.annotations` (i.e. which annotation), `(idx1, idx2)`.
-    /// I.e. `AnnotationDocument.tiers[idx1].annotations[idx2]`.
-    pub a2idx: HashMap<String, (usize, usize)>,
+    /// Key: Time slot ID. Value: ID:s of alignable annotations referring to the time slot.
+    pub ts2a: HashMap<String, Vec<String>>,
+    /// Key: Annotation ID. Value: ID:s of referred annotations referring to the annotation.
+    pub ref2a: HashMap<String, Vec<String>>,
     /// Key: Tier ID. Value: Tier index in `AnnotationDocument.tiers`.
     /// I.e. `AnnotationDocument.tiers[idx]`.
     pub t2idx: HashMap<String, usize>,
+    /// Time index for all annotations.
+    pub time: TimeIndex,
+    /// Key: Tier ID. Value: Time index for annotations in tier.
+    pub t2time: HashMap<String, TimeIndex>,
 }
 
 impl Default for Index {
@@ -61,8 +84,848 @@ impl Default for Index {
             ts2tv: HashMap::new(),
             tv2ts: HashMap::new(),
             a2ts: HashMap::new(),
-            a2idx: HashMap::new(),
+            ts2a: HashMap::new(),
+            ref2a: HashMap::new(),
             t2idx: HashMap::new(),
+            time: TimeIndex::default(),
+            t2time: HashMap::new(),
+        }
+    }
+}
+
+impl Index {
+    /// Indexes tiers and time order. See `Eaf::index()`.
+    pub(crate) fn new(tiers: &[Tier], time_order: &TimeOrder) -> Self {
+        let mut index = Self {
+            ts2tv: time_order.index(),
+            tv2ts: time_order.index_rev(),
+            ..Self::default()
+        };
+
+        for (t_idx, tier) in tiers.iter().enumerate() {
+            index.insert_tier_entries(tier, t_idx);
+        }
+
+        // Referred annotations use the time values of their main annotation,
+        // so time indexes are built once all annotations are indexed.
+        let mut all: Vec<(i64, i64, String)> = Vec::new();
+        for tier in tiers.iter() {
+            let spans = index.spans(tier);
+            all.extend(spans.iter().cloned());
+            index.t2time.insert(tier.tier_id.to_owned(), TimeIndex::new(spans));
+        }
+        index.time = TimeIndex::new(all);
+
+        index
+    }
+
+    /// Returns index in `AnnotationDocument.tiers` (i.e. which tier),
+    /// and index in `tier.annotations` (i.e. which annotation), `(idx1, idx2)`,
+    /// for the specified annotation ID.
+    /// I.e. `AnnotationDocument.tiers[idx1].annotations[idx2]`.
+    pub fn a2idx(&self, id: &str) -> Option<(usize, usize)> {
+        let tier_id = self.a2t.get(id)?;
+        Some((*self.t2idx.get(tier_id)?, self.t2a.get(tier_id)?.position(id)?))
+    }
+
+    /// Returns the ID of the main annotation for the specified annotation ID,
+    /// following annotation references. Depth is limited, in case of circular references.
+    pub(crate) fn main_id<'a>(&'a self, id: &'a str) -> &'a str {
+        let mut id = id;
+        for _ in 0..=self.t2idx.len() {
+            match self.a2ref.get(id) {
+                Some(ref_id) => id = ref_id,
+                None => break,
+            }
+        }
+        id
+    }
+
+    /// Returns the time span in milliseconds for the specified annotation ID.
+    /// Referred annotations use the time values of their main annotation.
+    pub(crate) fn span(&self, id: &str) -> Option<(i64, i64)> {
+        let (ref1, ref2) = self.a2ts.get(self.main_id(id))?;
+        match (self.ts2tv.get(ref1), self.ts2tv.get(ref2)) {
+            (Some(Some(start)), Some(Some(end))) => Some((*start, *end)),
+            _ => None,
+        }
+    }
+
+    /// Returns ID:s for all annotations referring to the specified annotation ID,
+    /// directly or indirectly, ordered parent-first.
+    pub(crate) fn dependents(&self, id: &str) -> Vec<String> {
+        let mut dependents: Vec<String> = self.ref2a.get(id).cloned().unwrap_or_default();
+        let mut i = 0;
+        // Depth is limited, in case of circular references.
+        while i < dependents.len() && i <= self.a2ref.len() {
+            if let Some(ids) = self.ref2a.get(&dependents[i]) {
+                dependents.extend(ids.iter().cloned());
+            }
+            i += 1;
+        }
+        dependents
+    }
+
+    /// Derives and sets time values, main annotation ID (referred annotations only)
+    /// and tier ID for an annotation. The annotation itself does not need to be indexed,
+    /// but its time slots or referred annotation must be.
+    pub(crate) fn derive(&self, annotation: &mut Annotation, tier_id: &str) -> Result<(), EafError> {
+        let (ref1, ref2) = match annotation.ref_id() {
+            Some(ref_id) => {
+                let main_id = self.main_id(ref_id).to_owned();
+                if self.a2ref.contains_key(&main_id) || !self.a2t.contains_key(&main_id) {
+                    return Err(EafError::AnnotationMainMissing((
+                        annotation.id().to_owned(),
+                        Some(ref_id.to_owned())
+                    )))
+                }
+                let ts = self.a2ts.get(&main_id)
+                    .ok_or_else(|| EafError::TimeslotRefMissing(annotation.id().to_owned()))?
+                    .to_owned();
+                annotation.set_main(&main_id);
+                ts
+            },
+            None => annotation.ts_ref()
+                .ok_or_else(|| EafError::TimeslotRefMissing(annotation.id().to_owned()))?,
+        };
+
+        let val1 = self.ts2tv.get(&ref1).copied().flatten();
+        let val2 = self.ts2tv.get(&ref2).copied().flatten();
+        annotation.set_ts_val(val1, val2);
+        annotation.set_tier_id(tier_id);
+
+        Ok(())
+    }
+
+    /// Adds a time slot.
+    pub(crate) fn insert_timeslot(&mut self, id: &str, value: Option<i64>) {
+        self.ts2tv.insert(id.to_owned(), value);
+        if let Some(val) = value {
+            self.tv2ts.insert(val, id.to_owned());
+        }
+    }
+
+    /// Removes a time slot.
+    pub(crate) fn remove_timeslot(&mut self, id: &str) {
+        if let Some(Some(val)) = self.ts2tv.remove(id)
+            && self.tv2ts.get(&val).is_some_and(|ts| ts == id)
+        {
+            self.tv2ts.remove(&val);
+        }
+        self.ts2a.remove(id);
+    }
+
+    /// Sets the value for a time slot, and updates the time index
+    /// for all annotations that depend on the time slot.
+    pub(crate) fn set_timeslot_value(&mut self, id: &str, value: Option<i64>) {
+        let annotations = self.ts2a.remove(id);
+        self.remove_timeslot(id);
+        self.insert_timeslot(id, value);
+        if let Some(annotations) = annotations {
+            for a_id in annotations.iter() {
+                self.retime_dependents(a_id);
+            }
+            self.ts2a.insert(id.to_owned(), annotations);
+        }
+    }
+
+    /// Sets time slot references for an alignable annotation,
+    /// and updates the time index for it and its dependents.
+    pub(crate) fn set_annotation_ts(&mut self, id: &str, ref1: &str, ref2: &str) {
+        if let Some((old1, old2)) = self.a2ts.remove(id) {
+            for ts in [old1, old2] {
+                if let Some(ids) = self.ts2a.get_mut(&ts) {
+                    ids.retain(|a_id| a_id != id);
+                }
+            }
+        }
+        self.ts2a.entry(ref1.to_owned()).or_default().push(id.to_owned());
+        if ref2 != ref1 {
+            self.ts2a.entry(ref2.to_owned()).or_default().push(id.to_owned());
+        }
+        self.a2ts.insert(id.to_owned(), (ref1.to_owned(), ref2.to_owned()));
+        self.retime_dependents(id);
+    }
+
+    /// Sets referred annotation ID for a referred annotation,
+    /// and updates the time index for it and its dependents.
+    pub(crate) fn set_annotation_ref(&mut self, id: &str, ref_id: &str) {
+        if let Some(old) = self.a2ref.insert(id.to_owned(), ref_id.to_owned())
+            && let Some(ids) = self.ref2a.get_mut(&old)
+        {
+            ids.retain(|a_id| a_id != id);
+        }
+        self.ref2a.entry(ref_id.to_owned()).or_default().push(id.to_owned());
+        self.retime_dependents(id);
+    }
+
+    /// Shifts all time slot values and time indexes by `shift_ms` milliseconds.
+    pub(crate) fn shift_time(&mut self, shift_ms: i64) {
+        self.ts2tv.values_mut()
+            .for_each(|val| *val = val.map(|v| v + shift_ms));
+        self.tv2ts = self.tv2ts.drain()
+            .map(|(val, id)| (val + shift_ms, id))
+            .collect();
+        self.t2time.values_mut()
+            .for_each(|time| time.shift_time(shift_ms));
+        self.time.shift_time(shift_ms);
+    }
+
+    /// Updates the time index for an annotation and all annotations referring to it.
+    fn retime_dependents(&mut self, id: &str) {
+        self.retime_annotation(id);
+        for dependent in self.dependents(id) {
+            self.retime_annotation(&dependent);
+        }
+    }
+
+    /// Updates the time index for an annotation, e.g. after a time slot value has changed.
+    fn retime_annotation(&mut self, id: &str) {
+        let Some(tier_id) = self.a2t.get(id) else {
+            return
+        };
+        let span = self.span(id);
+        let tier_time = self.t2time.get_mut(tier_id);
+        for time in tier_time.into_iter().chain(std::iter::once(&mut self.time)) {
+            match span {
+                Some((start, end)) => time.insert(start, end, id),
+                None => time.remove(id),
+            }
+        }
+    }
+
+    /// Adds an annotation, located at `tier.annotations[a_idx]` in the tier
+    /// with the specified tier ID, i.e. the annotation was inserted into the tier.
+    pub(crate) fn insert_annotation(&mut self, annotation: &Annotation, tier_id: &str, a_idx: usize) {
+        let id = annotation.id();
+
+        self.t2a.entry(tier_id.to_owned())
+            .or_default()
+            .insert(a_idx, id);
+        self.insert_annotation_entries(annotation, tier_id);
+
+        if let Some((start, end)) = self.span(id) {
+            let tier_time = self.t2time.get_mut(tier_id);
+            for time in tier_time.into_iter().chain(std::iter::once(&mut self.time)) {
+                time.insert(start, end, id);
+            }
+        }
+    }
+
+    /// Removes an annotation. Annotations referring to it must be removed first.
+    pub(crate) fn remove_annotation(&mut self, id: &str) {
+        let Some(tier_id) = self.a2t.remove(id) else {
+            return
+        };
+
+        if let Some(ref_id) = self.a2ref.remove(id)
+            && let Some(ids) = self.ref2a.get_mut(&ref_id)
+        {
+            ids.retain(|a_id| a_id != id);
+        }
+        self.ref2a.remove(id);
+
+        if let Some((ref1, ref2)) = self.a2ts.remove(id) {
+            for ts in [ref1, ref2] {
+                if let Some(ids) = self.ts2a.get_mut(&ts) {
+                    ids.retain(|a_id| a_id != id);
+                }
+            }
+        }
+
+        if let Some(a_ids) = self.t2a.get_mut(&tier_id) {
+            a_ids.remove(id);
+        }
+
+        let tier_time = self.t2time.get_mut(&tier_id);
+        for time in tier_time.into_iter().chain(std::iter::once(&mut self.time)) {
+            time.remove(id);
+        }
+    }
+
+    /// Adds a tier, located at `Eaf.tiers[t_idx]`. Tiers at `t_idx` and later
+    /// are expected to have been shifted one step, i.e. the tier was inserted.
+    /// Time slots for the tier must be indexed first.
+    pub(crate) fn insert_tier(&mut self, tier: &Tier, t_idx: usize) {
+        // Tiers after the inserted one.
+        self.t2idx.values_mut()
+            .filter(|idx| **idx >= t_idx)
+            .for_each(|idx| *idx += 1);
+
+        self.insert_tier_entries(tier, t_idx);
+
+        let spans = self.spans(tier);
+        for (start, end, id) in spans.iter() {
+            self.time.insert(*start, *end, id);
+        }
+        self.t2time.insert(tier.tier_id.to_owned(), TimeIndex::new(spans));
+    }
+
+    /// Removes a tier and its annotations, i.e. the tier was removed from `Eaf.tiers`.
+    /// Annotations referring to annotations in the tier must be removed first.
+    pub(crate) fn remove_tier(&mut self, tier_id: &str) {
+        let Some(t_idx) = self.t2idx.remove(tier_id) else {
+            return
+        };
+
+        let a_ids = self.t2a.remove(tier_id).unwrap_or_default();
+        for a_id in a_ids.ids() {
+            self.a2t.remove(a_id);
+            self.ref2a.remove(a_id);
+            if let Some(ref_id) = self.a2ref.remove(a_id)
+                && let Some(ids) = self.ref2a.get_mut(&ref_id)
+            {
+                ids.retain(|id| id != a_id);
+            }
+            if let Some((ref1, ref2)) = self.a2ts.remove(a_id) {
+                for ts in [ref1, ref2] {
+                    if let Some(ids) = self.ts2a.get_mut(&ts) {
+                        ids.retain(|id| id != a_id);
+                    }
+                }
+            }
+            self.time.remove(a_id);
+        }
+        self.t2ref.remove(tier_id);
+        self.t2time.remove(tier_id);
+
+        // Tiers after the removed one.
+        self.t2idx.values_mut()
+            .filter(|idx| **idx > t_idx)
+            .for_each(|idx| *idx -= 1);
+    }
+
+    /// Sets parent tier for a tier.
+    pub(crate) fn set_tier_parent(&mut self, tier_id: &str, parent_id: &str) {
+        self.t2ref.insert(tier_id.to_owned(), parent_id.to_owned());
+    }
+
+    /// Renames a tier, including references from child tiers.
+    pub(crate) fn rename_tier(&mut self, tier_id: &str, new_id: &str) {
+        if let Some(idx) = self.t2idx.remove(tier_id) {
+            self.t2idx.insert(new_id.to_owned(), idx);
+        }
+        if let Some(ref_id) = self.t2ref.remove(tier_id) {
+            self.t2ref.insert(new_id.to_owned(), ref_id);
+        }
+        self.t2ref.values_mut()
+            .filter(|ref_id| *ref_id == tier_id)
+            .for_each(|ref_id| *ref_id = new_id.to_owned());
+        if let Some(time) = self.t2time.remove(tier_id) {
+            self.t2time.insert(new_id.to_owned(), time);
+        }
+        if let Some(a_ids) = self.t2a.remove(tier_id) {
+            for a_id in a_ids.ids() {
+                if let Some(t_id) = self.a2t.get_mut(a_id) {
+                    *t_id = new_id.to_owned()
+                }
+            }
+            self.t2a.insert(new_id.to_owned(), a_ids);
+        }
+    }
+
+    /// Adds all entries for a tier and its annotations, except the time index.
+    fn insert_tier_entries(&mut self, tier: &Tier, t_idx: usize) {
+        // Tier ID -> tier idx
+        self.t2idx.insert(tier.tier_id.to_owned(), t_idx);
+
+        // Tier ID -> Ref tier ID
+        if let Some(ref_id) = &tier.parent_ref {
+            self.t2ref.insert(tier.tier_id.to_owned(), ref_id.to_owned());
+        }
+
+        // Tier ID -> [Annotation ID, ...]
+        self.t2a.insert(
+            tier.tier_id.to_owned(),
+            Sequence::new(tier.annotations.iter().map(|a| a.id()))
+        );
+
+        for annotation in tier.annotations.iter() {
+            self.insert_annotation_entries(annotation, &tier.tier_id);
+        }
+    }
+
+    /// Adds all entries for an annotation, except tier annotation list and time index.
+    fn insert_annotation_entries(&mut self, annotation: &Annotation, tier_id: &str) {
+        let id = annotation.id();
+
+        // Annotation ID -> Tier ID
+        self.a2t.insert(id.to_owned(), tier_id.to_owned());
+
+        // Annotation ID -> Annotation ref ID, and reverse
+        if let Some(ref_id) = annotation.ref_id() {
+            self.a2ref.insert(id.to_owned(), ref_id.to_owned());
+            self.ref2a.entry(ref_id.to_owned()).or_default().push(id.to_owned());
+        }
+
+        // Annotation ID -> (time slot ref 1, time slot ref2), and reverse
+        if let Some((ref1, ref2)) = annotation.ts_ref() {
+            self.ts2a.entry(ref1.to_owned()).or_default().push(id.to_owned());
+            if ref2 != ref1 {
+                self.ts2a.entry(ref2.to_owned()).or_default().push(id.to_owned());
+            }
+            self.a2ts.insert(id.to_owned(), (ref1, ref2));
+        }
+    }
+
+    /// Returns time spans for the annotations in a tier,
+    /// `(start, end, annotation ID)`, for the time index.
+    fn spans(&self, tier: &Tier) -> Vec<(i64, i64, String)> {
+        tier.annotations.iter()
+            .filter_map(|a| {
+                self.span(a.id()).map(|(start, end)| (start, end, a.id().to_owned()))
+            })
+            .collect()
+    }
+}
+
+/// Annotation ID:s for a tier, in tier order.
+///
+/// Implemented as an implicit treap, i.e. a randomized balanced binary tree ordered
+/// on position, where each node holds the size of its subtree and links to its parent.
+/// Adding, removing, and finding the position of an annotation ID are `O(log n)`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Sequence {
+    /// Tree nodes. Positions of removed nodes are reused.
+    nodes: Vec<SequenceNode>,
+    /// Positions in `nodes` not in use.
+    free: Vec<usize>,
+    root: Option<usize>,
+    /// Key: Annotation ID. Value: Position in `nodes`.
+    slots: HashMap<String, usize>,
+}
+
+/// Tree node for an annotation ID.
+#[derive(Debug, Clone)]
+struct SequenceNode {
+    id: String,
+    priority: u64,
+    left: Option<usize>,
+    right: Option<usize>,
+    parent: Option<usize>,
+    /// Number of nodes in the subtree rooted at this node.
+    size: usize,
+}
+
+impl Sequence {
+    /// Builds sequence from annotation ID:s, in tier order.
+    pub(crate) fn new<'a>(ids: impl IntoIterator<Item = &'a str>) -> Self {
+        // The right spine of the tree built so far is kept on a stack,
+        // see `TimeIndex::new()`.
+        let mut sequence = Self::default();
+        let mut spine: Vec<usize> = Vec::new();
+        for id in ids {
+            let node = sequence.alloc(id);
+            let mut left = None;
+            while let Some(last) = spine.last().copied()
+                && sequence.nodes[last].priority < sequence.nodes[node].priority
+            {
+                left = spine.pop();
+            }
+            sequence.nodes[node].left = left;
+            if let Some(last) = spine.last().copied() {
+                sequence.nodes[last].right = Some(node);
+            }
+            spine.push(node);
+        }
+        sequence.root = spine.first().copied();
+        sequence.update_all(sequence.root);
+
+        sequence
+    }
+
+    /// Adds a node for an annotation ID, not yet linked into the tree,
+    /// and returns its position.
+    fn alloc(&mut self, id: &str) -> usize {
+        let node = SequenceNode {
+            id: id.to_owned(),
+            priority: priority(id),
+            left: None,
+            right: None,
+            parent: None,
+            size: 1,
+        };
+        let pos = match self.free.pop() {
+            Some(pos) => {
+                self.nodes[pos] = node;
+                pos
+            },
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        self.slots.insert(id.to_owned(), pos);
+        pos
+    }
+
+    fn size(&self, node: Option<usize>) -> usize {
+        node.map_or(0, |n| self.nodes[n].size)
+    }
+
+    /// Sets subtree size for a node, and links its children to it.
+    fn update(&mut self, node: usize) {
+        let (left, right) = (self.nodes[node].left, self.nodes[node].right);
+        self.nodes[node].size = 1 + self.size(left) + self.size(right);
+        for child in [left, right].into_iter().flatten() {
+            self.nodes[child].parent = Some(node);
+        }
+    }
+
+    /// Sets subtree sizes and parent links for all nodes in a subtree.
+    fn update_all(&mut self, node: Option<usize>) {
+        if let Some(n) = node {
+            self.update_all(self.nodes[n].left);
+            self.update_all(self.nodes[n].right);
+            self.update(n);
+        }
+    }
+
+    /// Sets the root node.
+    fn set_root(&mut self, node: Option<usize>) {
+        if let Some(n) = node {
+            self.nodes[n].parent = None;
+        }
+        self.root = node;
+    }
+
+    /// Splits a subtree into the first `pos` nodes, and the remaining nodes.
+    fn split(&mut self, node: Option<usize>, pos: usize) -> (Option<usize>, Option<usize>) {
+        let Some(n) = node else {
+            return (None, None)
+        };
+        let left_size = self.size(self.nodes[n].left);
+        if left_size < pos {
+            let (left, right) = self.split(self.nodes[n].right, pos - left_size - 1);
+            self.nodes[n].right = left;
+            self.update(n);
+            (Some(n), right)
+        } else {
+            let (left, right) = self.split(self.nodes[n].left, pos);
+            self.nodes[n].left = right;
+            self.update(n);
+            (left, Some(n))
+        }
+    }
+
+    /// Joins two subtrees, with the nodes in `left` first.
+    fn merge(&mut self, left: Option<usize>, right: Option<usize>) -> Option<usize> {
+        let (Some(l), Some(r)) = (left, right) else {
+            return left.or(right)
+        };
+        if self.nodes[l].priority > self.nodes[r].priority {
+            let merged = self.merge(self.nodes[l].right, right);
+            self.nodes[l].right = merged;
+            self.update(l);
+            Some(l)
+        } else {
+            let merged = self.merge(left, self.nodes[r].left);
+            self.nodes[r].left = merged;
+            self.update(r);
+            Some(r)
+        }
+    }
+
+    /// Number of annotation ID:s.
+    pub(crate) fn len(&self) -> usize {
+        self.size(self.root)
+    }
+
+    /// Inserts an annotation ID at position `pos`, shifting all following ID:s.
+    /// Inserts last if `pos` is out of bounds.
+    pub(crate) fn insert(&mut self, pos: usize, id: &str) {
+        let node = self.alloc(id);
+        let (left, right) = self.split(self.root, pos);
+        let left = self.merge(left, Some(node));
+        let root = self.merge(left, right);
+        self.set_root(root);
+    }
+
+    /// Removes an annotation ID, if it exists.
+    pub(crate) fn remove(&mut self, id: &str) {
+        let Some(pos) = self.position(id) else {
+            return
+        };
+        let (left, right) = self.split(self.root, pos);
+        let (node, right) = self.split(right, 1);
+        let root = self.merge(left, right);
+        self.set_root(root);
+        if let Some(n) = node {
+            self.slots.remove(id);
+            self.free.push(n);
+        }
+    }
+
+    /// Returns position for an annotation ID, i.e. the index in `tier.annotations`.
+    pub(crate) fn position(&self, id: &str) -> Option<usize> {
+        let mut node = *self.slots.get(id)?;
+        let mut pos = self.size(self.nodes[node].left);
+        while let Some(parent) = self.nodes[node].parent {
+            if self.nodes[parent].right == Some(node) {
+                pos += self.size(self.nodes[parent].left) + 1;
+            }
+            node = parent;
+        }
+        Some(pos)
+    }
+
+    /// Returns annotation ID:s, in tier order.
+    pub(crate) fn ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = Vec::with_capacity(self.len());
+        let mut stack: Vec<usize> = Vec::new();
+        let mut node = self.root;
+        while node.is_some() || !stack.is_empty() {
+            while let Some(n) = node {
+                stack.push(n);
+                node = self.nodes[n].left;
+            }
+            if let Some(n) = stack.pop() {
+                ids.push(&self.nodes[n].id);
+                node = self.nodes[n].right;
+            }
+        }
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Eaf, RangeMode};
+    use crate::eaf::test_util::{TestEaf, aligned, referred};
+
+    /// Main tiers `A` and `B`, with a `Symbolic_Association` tier `G` under `A`.
+    fn eaf() -> Eaf {
+        TestEaf::new()
+            .time_slots(&[0, 1000, 2000, 3000, 500, 2500])
+            .tier("A", "default-lt", None, &[
+                aligned("a1", "ts1", "ts2", "one"),
+                aligned("a2", "ts3", "ts4", "two"),
+            ])
+            .linguistic_type("gloss", Some("Symbolic_Association"))
+            .tier("G", "gloss", Some("A"), &[
+                referred("a3", "a1", "1"),
+                referred("a4", "a2", "2"),
+            ])
+            .tier("B", "default-lt", None, &[aligned("a5", "ts5", "ts6", "three")])
+            .eaf()
+    }
+
+    /// All indexed annotation ID:s, sorted on time.
+    fn all(time: &TimeIndex) -> Vec<&str> {
+        time.range(i64::MIN / 2, i64::MAX / 2, RangeMode::Contained)
+    }
+
+    /// Sorted, non-empty lists of IDs.
+    fn normalized(map: &HashMap<String, Vec<String>>) -> HashMap<String, Vec<String>> {
+        map.iter()
+            .filter(|(_, ids)| !ids.is_empty())
+            .map(|(id, ids)| {
+                let mut ids = ids.to_owned();
+                ids.sort();
+                (id.to_owned(), ids)
+            })
+            .collect()
+    }
+
+    /// Asserts that the incrementally updated index, and derived
+    /// annotation values, equal those of a full re-index.
+    fn assert_consistent(eaf: &Eaf) {
+        let (index, fresh) = (&eaf.index, Index::new(&eaf.tiers, &eaf.time_order));
+        assert_eq!(index.a2t, fresh.a2t);
+        assert_eq!(index.a2ref, fresh.a2ref);
+        assert_eq!(index.t2ref, fresh.t2ref);
+        assert_eq!(index.ts2tv, fresh.ts2tv);
+        assert_eq!(index.a2ts, fresh.a2ts);
+        assert_eq!(index.t2idx, fresh.t2idx);
+        assert_eq!(normalized(&index.ts2a), normalized(&fresh.ts2a));
+        assert_eq!(normalized(&index.ref2a), normalized(&fresh.ref2a));
+        let mut values: Vec<&i64> = index.tv2ts.keys().collect();
+        let mut fresh_values: Vec<&i64> = fresh.tv2ts.keys().collect();
+        values.sort();
+        fresh_values.sort();
+        assert_eq!(values, fresh_values);
+
+        assert_eq!(all(&index.time), all(&fresh.time));
+        assert_eq!(index.t2time.len(), fresh.t2time.len());
+        for (tier_id, time) in fresh.t2time.iter() {
+            assert_eq!(all(&index.t2time[tier_id]), all(time), "tier '{tier_id}'");
+        }
+
+        assert_eq!(index.t2a.len(), eaf.tiers.len());
+        for (t_idx, tier) in eaf.tiers.iter().enumerate() {
+            let a_ids: Vec<&str> = tier.iter().map(|a| a.id()).collect();
+            assert_eq!(index.t2a[&tier.tier_id].ids(), a_ids, "tier '{}'", tier.tier_id);
+            for (a_idx, annotation) in tier.iter().enumerate() {
+                assert_eq!(index.a2idx(annotation.id()), Some((t_idx, a_idx)));
+
+                let ts_val = fresh.a2ts.get(fresh.main_id(annotation.id()))
+                    .map(|(ref1, ref2)| (fresh.ts2tv[ref1], fresh.ts2tv[ref2]))
+                    .unwrap_or_default();
+                assert_eq!(annotation.ts_val(), ts_val, "{}", annotation.id());
+                assert_eq!(annotation.tier_id().as_deref(), Some(tier.tier_id.as_str()));
+                if annotation.ref_id().is_some() {
+                    assert_eq!(annotation.main(), Some(fresh.main_id(annotation.id())));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn build() {
+        let eaf = eaf();
+        assert_consistent(&eaf);
+        assert_eq!(eaf.index.main_id("a4"), "a2");
+        assert_eq!(eaf.index.span("a4"), Some((2000, 3000)));
+        assert_eq!(eaf.index.dependents("a1"), vec!["a3"]);
+        assert_eq!(eaf.index.a2idx("a5"), Some((2, 0)));
+        assert_eq!(eaf.index.a2idx("a99"), None);
+        assert_eq!(all(&eaf.index.time), vec!["a1", "a3", "a5", "a2", "a4"]);
+    }
+
+    #[test]
+    fn add_annotation() {
+        let mut eaf = eaf();
+        // Inserted between existing annotations, existing time slots
+        let a = Annotation::alignable("between", "a6", "ts2", "ts3");
+        eaf.add_annotation(&a, "A").unwrap();
+        assert_eq!(eaf.get_tier("A").unwrap().values(), vec!["one", "between", "two"]);
+        assert_eq!(eaf.index.a2idx("a2"), Some((0, 2)));
+        assert_consistent(&eaf);
+
+        // Appended, new time slots
+        let a = Annotation::alignable("four", "a7", "ts9", "ts10").with_ts_val(4000, 5000);
+        eaf.add_annotation(&a, "B").unwrap();
+        assert_consistent(&eaf);
+
+        let mut a = Annotation::referred("gloss", "a8", "a6", None);
+        a.set_ts_val(None, None);
+        eaf.add_annotation(&a, "G").unwrap();
+        assert_consistent(&eaf);
+        assert_eq!(eaf.get_annotation("a8").unwrap().ts_val(), (Some(1000), Some(2000)));
+
+        // Overlap, existing ID, unknown parent
+        let a = Annotation::alignable("overlap", "a9", "ts5", "ts6");
+        assert!(eaf.add_annotation(&a, "A").is_err());
+        assert!(eaf.add_annotation(&Annotation::referred("x", "a1", "a2", None), "G").is_err());
+        assert!(eaf.add_annotation(&Annotation::referred("x", "a9", "a99", None), "G").is_err());
+        assert_consistent(&eaf);
+    }
+
+    #[test]
+    fn remove_annotation() {
+        let mut eaf = eaf();
+        let removed = eaf.remove_annotation("a1").unwrap();
+        assert_eq!(removed.iter().map(|a| a.id()).collect::<Vec<_>>(), vec!["a1", "a3"]);
+        assert!(eaf.time_order.find("ts1").is_none());
+        assert_eq!(eaf.index.a2idx("a4"), Some((1, 0)));
+        assert_consistent(&eaf);
+
+        eaf.remove_annotation("a4").unwrap();
+        assert_consistent(&eaf);
+        assert!(eaf.remove_annotation("a4").is_err());
+        assert_eq!(all(&eaf.index.time), vec!["a5", "a2"]);
+    }
+
+    /// Many annotations added and removed at arbitrary positions in a tier.
+    #[test]
+    fn many_edits() {
+        let mut eaf = TestEaf::new()
+            .time_slots(&[0, 100_000])
+            .tier("A", "default-lt", None, &[aligned("a0", "ts1", "ts2", "all")])
+            .tier("B", "default-lt", None, &[])
+            .eaf();
+        let mut seed: u64 = 7;
+        let mut next = |max: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % max
+        };
+        let mut a_ids: Vec<String> = Vec::new();
+        for i in 0..300 {
+            // Slots of 100 ms, so that annotations never overlap,
+            // nor share time values with other time slots.
+            let start = (next(999) + 1) as i64 * 100;
+            let a = Annotation::alignable("x", &format!("b{i}"), &format!("ts_b{i}a"), &format!("ts_b{i}b"))
+                .with_ts_val(start, start + 50);
+            if eaf.annotations_at(start, Some("B")).is_empty() {
+                eaf.add_annotation(&a, "B").unwrap();
+                a_ids.push(a.id().to_owned());
+            }
+            if i % 3 == 0 && !a_ids.is_empty() {
+                let id = a_ids.swap_remove(next(a_ids.len() as u64) as usize);
+                eaf.remove_annotation(&id).unwrap();
+            }
+        }
+        assert_consistent(&eaf);
+        assert_eq!(eaf.get_tier("B").unwrap().len(), a_ids.len());
+    }
+
+    #[test]
+    fn set_ts_val() {
+        let mut eaf = eaf();
+        // Moves a2 (and a4 via a2) before a5 in time order
+        eaf.set_ts_val("ts3", Some(200)).unwrap();
+        eaf.set_ts_val("ts4", Some(400)).unwrap();
+        assert_consistent(&eaf);
+        assert_eq!(eaf.get_annotation("a4").unwrap().ts_val(), (Some(200), Some(400)));
+        assert_eq!(eaf.annotations_at(300, None).iter().map(|a| a.id()).collect::<Vec<_>>(), vec!["a1", "a3", "a2", "a4"]);
+
+        // Unaligned time slot removes annotations from the time index
+        eaf.set_ts_val("ts6", None).unwrap();
+        assert_consistent(&eaf);
+        assert!(eaf.annotations_at(600, Some("B")).is_empty());
+        assert!(eaf.set_ts_val("ts99", Some(0)).is_err());
+    }
+
+    #[test]
+    fn tiers() {
+        let mut eaf = eaf();
+        let a = Annotation::alignable("new", "a10", "ts20", "ts21").with_ts_val(5000, 6000);
+        let tier = Tier::new("C", Some(&[a]), None, None);
+        eaf.add_tier(Some(tier), None).unwrap();
+        assert_consistent(&eaf);
+        assert_eq!(eaf.index.a2idx("a10"), Some((3, 0)));
+        assert_eq!(eaf.annotations_at(5500, None).iter().map(|a| a.to_str()).collect::<Vec<_>>(), vec!["new"]);
+
+        eaf.prefix_tier_id_mut("B", "x-").unwrap();
+        assert_consistent(&eaf);
+        assert_eq!(eaf.index.a2idx("a5"), Some((2, 0)));
+        eaf.shift(1000, false).unwrap();
+        assert_consistent(&eaf);
+        assert_eq!(eaf.get_annotation("a5").unwrap().ts_val(), (Some(1500), Some(3500)));
+
+        eaf.copy_tier("A", "D", true, "-copy").unwrap();
+        assert_consistent(&eaf);
+        eaf.remove_tier("A", true).unwrap();
+        assert_consistent(&eaf);
+        assert_eq!(eaf.index.a2idx("a5"), Some((0, 0)));
+        assert_eq!(eaf.index.a2idx("a1"), None);
+        assert!(eaf.annotations_at(1500, None).iter().all(|a| a.id() != "a1"));
+    }
+
+    #[test]
+    fn sequence() {
+        let ids: Vec<String> = (0..200).map(|i| format!("a{i}")).collect();
+        let mut sequence = Sequence::new(ids.iter().map(|id| id.as_str()));
+        let mut expected = ids.to_owned();
+        for i in 0..100 {
+            let pos = (i * 37) % expected.len();
+            if i % 2 == 0 {
+                let id = format!("b{i}");
+                sequence.insert(pos, &id);
+                expected.insert(pos, id);
+            } else {
+                sequence.remove(&expected.remove(pos));
+            }
+        }
+        sequence.insert(usize::MAX, "last");
+        expected.push("last".to_owned());
+        sequence.remove("a99999");
+
+        assert_eq!(sequence.ids(), expected);
+        for (pos, id) in expected.iter().enumerate() {
+            assert_eq!(sequence.position(id), Some(pos));
         }
+        assert_eq!(sequence.position("x"), None);
     }
 }
@@ -19,7 +19,7 @@ pub struct LinguisticType {
     #[serde(rename="@GRAPHIC_REFERENCES")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub graphic_references: Option<bool>,
-    #[serde(rename="@CONTROLLED_VOCABULARY")]
+    #[serde(rename="@CONTROLLED_VOCABULARY_REF", alias="@CONTROLLED_VOCABULARY")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub controlled_vocabulary: Option<String>,
     #[serde(rename="@EXT_REF")]
@@ -0,0 +1,670 @@
+//! Lossless round-trip for ELAN-files.
+//!
+//! `Eaf` only models the elements and attributes in the EAF specification,
+//! so serializing a deserialized file drops anything else, and re-orders
+//! attributes, comments, and whitespace. For an ELAN-file read with
+//! `Eaf::read_lossless()`, the original XML is kept, together with the
+//! `Eaf` as serialized directly after reading it.
+//!
+//! On serialization, the current `Eaf` is compared with this baseline.
+//! If nothing changed, the original XML is returned as is.
+//! Otherwise the changes (added, removed, or edited elements, attributes and text)
+//! are applied to the original XML, so that unknown elements and attributes,
+//! comments, attribute order, element order and indentation are preserved
+//! for everything that was not edited.
+//!
+//! Elements are matched on element name and ID (e.g. `TIER_ID`, `ANNOTATION_ID`),
+//! or position among siblings with the same name if there is no ID.
+
+use std::collections::HashMap;
+
+use quick_xml::{escape::escape, events::{BytesStart, Event}, Reader};
+
+use crate::EafError;
+
+/// Attributes that identify an element among its siblings.
+/// The first one found is used.
+const ID_ATTRIBUTES: [&str; 15] = [
+    "TIER_ID",
+    "ANNOTATION_ID",
+    "TIME_SLOT_ID",
+    "LINGUISTIC_TYPE_ID",
+    "CV_ID",
+    "CVE_ID",
+    "STEREOTYPE",
+    "LANG_ID",
+    "LANGUAGE_CODE",
+    "LEX_REF_ID",
+    "EXT_REF_ID",
+    "MEDIA_URL",
+    "LINK_URL",
+    "NAME",
+    "LANG_REF",
+];
+
+/// Original XML source for an `Eaf` read in lossless mode.
+#[derive(Clone, PartialEq)]
+pub(crate) struct Source {
+    /// Original XML.
+    xml: String,
+    /// `Eaf` serialized directly after deserialization,
+    /// used to find edits.
+    baseline: String,
+}
+
+impl std::fmt::Debug for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Source")
+            .field("len", &self.xml.len())
+            .finish()
+    }
+}
+
+impl Source {
+    /// `xml` is the original XML, `baseline` the deserialized `Eaf`
+    /// serialized without indentation.
+    pub(crate) fn new(xml: String, baseline: String) -> Self {
+        Self { xml, baseline }
+    }
+
+    /// Applies the difference between the baseline and `current`
+    /// (the `Eaf` serialized without indentation) to the original XML.
+    pub(crate) fn apply(&self, current: &str) -> Result<String, EafError> {
+        if current == self.baseline {
+            return Ok(self.xml.to_owned())
+        }
+
+        let mut orig = parse(&self.xml)?;
+        let base = parse(&self.baseline)?;
+        let new = parse(current)?;
+
+        let (Some(base_root), Some(new_root)) = (root(&base), root(&new)) else {
+            return Err(EafError::XmlInvalid("No root element".to_owned()))
+        };
+        let Some(orig_root) = orig.iter_mut().find_map(|n| match n {
+            Node::Element(e) => Some(e),
+            _ => None
+        }) else {
+            return Err(EafError::XmlInvalid("No root element".to_owned()))
+        };
+
+        let layout = Layout::new(orig_root);
+        *orig_root = merge(orig_root, base_root, new_root, "", &layout);
+
+        let mut xml = String::with_capacity(self.xml.len());
+        orig.iter().for_each(|n| n.write(&mut xml));
+
+        Ok(xml)
+    }
+}
+
+/// XML node.
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Element(Element),
+    /// Raw, escaped text, including entity references.
+    Text(String),
+    /// Raw markup written as is, i.e. comments,
+    /// CDATA, XML declaration, processing instructions.
+    Markup(String),
+}
+
+impl Node {
+    fn write(&self, xml: &mut String) {
+        match self {
+            Node::Element(e) => e.write(xml),
+            Node::Text(t) | Node::Markup(t) => xml.push_str(t),
+        }
+    }
+
+    fn is_whitespace(&self) -> bool {
+        matches!(self, Node::Text(t) if t.trim().is_empty())
+    }
+}
+
+/// XML element.
+#[derive(Debug, Clone, PartialEq)]
+struct Element {
+    name: String,
+    /// Attributes with unescaped values, in document order.
+    attributes: Vec<(String, String)>,
+    /// Original tag content (name and attributes).
+    /// `None` if attributes were edited.
+    raw: Option<String>,
+    /// Self-closing tag, if there are no children.
+    empty: bool,
+    children: Vec<Node>,
+}
+
+/// Key that identifies an element among its siblings:
+/// `(name, ID, occurrence)`.
+type Key = (String, Option<String>, usize);
+
+impl Element {
+    fn from_start(start: &BytesStart, empty: bool) -> Result<Self, EafError> {
+        let attributes = start.attributes()
+            .map(|attr| {
+                let attr = attr.map_err(quick_xml::Error::from)?;
+                Ok((
+                    std::str::from_utf8(attr.key.as_ref())?.to_owned(),
+                    attr.unescape_value()?.to_string()
+                ))
+            })
+            .collect::<Result<Vec<_>, EafError>>()?;
+
+        Ok(Self {
+            name: std::str::from_utf8(start.name().as_ref())?.to_owned(),
+            attributes,
+            raw: Some(std::str::from_utf8(start)?.to_owned()),
+            empty,
+            children: Vec::new(),
+        })
+    }
+
+    /// Adds child node, joining adjacent text nodes.
+    fn push(&mut self, node: Node) {
+        push(&mut self.children, node)
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|n| match n {
+            Node::Element(e) => Some(e),
+            _ => None
+        })
+    }
+
+    fn has_elements(&self) -> bool {
+        self.elements().next().is_some()
+    }
+
+    /// Raw text content, excluding child elements.
+    fn text(&self) -> String {
+        self.children.iter()
+            .filter_map(|n| match n {
+                Node::Text(t) => Some(t.as_str()),
+                _ => None
+            })
+            .collect()
+    }
+
+    /// ID for the element, or its only child element if there is none,
+    /// e.g. `ANNOTATION` is identified by `ALIGNABLE_ANNOTATION`'s `ANNOTATION_ID`.
+    /// Elements with several child elements, e.g. `TIME_ORDER`, are not identified
+    /// by a child, since the first child may be removed.
+    fn id(&self) -> Option<String> {
+        let id = |e: &Element| ID_ATTRIBUTES.iter()
+            .find_map(|a| e.attribute(a))
+            .map(|v| v.to_owned());
+        id(self).or_else(|| {
+            let mut elements = self.elements();
+            match (elements.next(), elements.next()) {
+                (Some(e), None) => id(e),
+                _ => None
+            }
+        })
+    }
+
+    fn write(&self, xml: &mut String) {
+        xml.push('<');
+        match &self.raw {
+            Some(raw) => xml.push_str(raw),
+            None => {
+                xml.push_str(&self.name);
+                for (key, value) in self.attributes.iter() {
+                    xml.push_str(&format!(" {key}=\"{}\"", escape(value.as_str())));
+                }
+            }
+        }
+        if self.empty && self.children.is_empty() {
+            xml.push_str("/>");
+            return
+        }
+        xml.push('>');
+        self.children.iter().for_each(|n| n.write(xml));
+        xml.push_str(&format!("</{}>", self.name));
+    }
+}
+
+/// Adds node, joining adjacent text nodes,
+/// since text and entity references are separate events.
+fn push(nodes: &mut Vec<Node>, node: Node) {
+    if let (Some(Node::Text(last)), Node::Text(text)) = (nodes.last_mut(), &node) {
+        last.push_str(text);
+        return
+    }
+    nodes.push(node)
+}
+
+/// Parses XML into a list of top-level nodes.
+fn parse(xml: &str) -> Result<Vec<Node>, EafError> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(false);
+
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut stack: Vec<Element> = Vec::new();
+
+    loop {
+        let node = match reader.read_event()? {
+            Event::Start(e) => {
+                stack.push(Element::from_start(&e, false)?);
+                continue;
+            },
+            Event::End(e) => match stack.pop() {
+                Some(element) => Node::Element(element),
+                None => return Err(EafError::XmlInvalid(format!(
+                    "Unexpected closing tag '{}'",
+                    String::from_utf8_lossy(e.name().as_ref())
+                ))),
+            },
+            Event::Empty(e) => Node::Element(Element::from_start(&e, true)?),
+            Event::Text(e) => Node::Text(std::str::from_utf8(&e)?.to_owned()),
+            Event::GeneralRef(e) => Node::Text(format!("&{};", std::str::from_utf8(&e)?)),
+            Event::CData(e) => Node::Markup(format!("<![CDATA[{}]]>", std::str::from_utf8(&e)?)),
+            Event::Comment(e) => Node::Markup(format!("<!--{}-->", std::str::from_utf8(&e)?)),
+            Event::Decl(e) => Node::Markup(format!("<?{}?>", std::str::from_utf8(&e)?)),
+            Event::PI(e) => Node::Markup(format!("<?{}?>", std::str::from_utf8(&e)?)),
+            Event::DocType(e) => Node::Markup(format!("<!DOCTYPE {}>", std::str::from_utf8(&e)?)),
+            Event::Eof => break,
+        };
+        match stack.last_mut() {
+            Some(parent) => parent.push(node),
+            None => push(&mut nodes, node),
+        }
+    }
+
+    if let Some(element) = stack.last() {
+        return Err(EafError::XmlInvalid(format!("Unclosed element '{}'", element.name)))
+    }
+
+    Ok(nodes)
+}
+
+fn root(nodes: &[Node]) -> Option<&Element> {
+    nodes.iter().find_map(|n| match n {
+        Node::Element(e) => Some(e),
+        _ => None
+    })
+}
+
+/// Returns key for each child element, `None` for other nodes.
+fn keys(children: &[Node]) -> Vec<Option<Key>> {
+    let mut count: HashMap<(String, Option<String>), usize> = HashMap::new();
+    children.iter()
+        .map(|n| match n {
+            Node::Element(e) => {
+                let (name, id) = (e.name.to_owned(), e.id());
+                let c = count.entry((name.to_owned(), id.to_owned())).or_insert(0);
+                *c += 1;
+                Some((name, id, *c))
+            },
+            _ => None
+        })
+        .collect()
+}
+
+/// Child elements mapped to their keys.
+fn keyed(element: &Element) -> HashMap<Key, &Element> {
+    keys(&element.children).into_iter()
+        .zip(element.children.iter())
+        .filter_map(|(k, n)| match (k, n) {
+            (Some(k), Node::Element(e)) => Some((k, e)),
+            _ => None
+        })
+        .collect()
+}
+
+/// Returns the indentation on the last line of a whitespace text node.
+fn line_indent(node: Option<&Node>) -> Option<&str> {
+    match node {
+        Some(Node::Text(t)) if t.trim().is_empty() => t.rsplit_once('\n').map(|(_, i)| i),
+        _ => None
+    }
+}
+
+/// Indentation in the original XML, used for added elements.
+struct Layout {
+    /// Original XML is indented.
+    pretty: bool,
+    /// Indentation unit, e.g. four spaces.
+    unit: String,
+}
+
+impl Layout {
+    fn new(root: &Element) -> Self {
+        let unit = root.children.iter()
+            .zip(root.children.iter().skip(1))
+            .find_map(|(ws, n)| match n {
+                Node::Element(_) => line_indent(Some(ws)),
+                _ => None
+            });
+        Self {
+            pretty: unit.is_some(),
+            unit: unit.unwrap_or("    ").to_owned(),
+        }
+    }
+
+    /// Indents an added element, where `indent` is the indentation
+    /// for `element` itself.
+    fn format(&self, element: &Element, indent: &str) -> Element {
+        if !self.pretty || !element.has_elements() {
+            return element.to_owned()
+        }
+        let child_indent = format!("{indent}{}", self.unit);
+        let mut children: Vec<Node> = Vec::new();
+        for node in element.children.iter() {
+            match node {
+                Node::Element(e) => {
+                    children.push(Node::Text(format!("\n{child_indent}")));
+                    children.push(Node::Element(self.format(e, &child_indent)));
+                },
+                n => children.push(n.to_owned()),
+            }
+        }
+        children.push(Node::Text(format!("\n{indent}")));
+        Element {
+            children,
+            ..element.to_owned()
+        }
+    }
+}
+
+/// Applies the difference between `base` and `new` to `orig`,
+/// where `indent` is the indentation for `orig`.
+fn merge(orig: &Element, base: &Element, new: &Element, indent: &str, layout: &Layout) -> Element {
+    // Attributes: keep order and attributes not modelled in `Eaf`,
+    // apply edited, added, and removed attributes.
+    let mut attributes = orig.attributes.to_owned();
+    let len = attributes.len();
+    attributes.retain(|(k, _)| base.attribute(k).is_none() || new.attribute(k).is_some());
+    let mut edited = attributes.len() != len;
+    for (key, value) in new.attributes.iter() {
+        if base.attribute(key) == Some(value.as_str()) {
+            continue;
+        }
+        match attributes.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => if v != value {
+                *v = value.to_owned();
+                edited = true;
+            },
+            None => {
+                attributes.push((key.to_owned(), value.to_owned()));
+                edited = true;
+            }
+        }
+    }
+
+    let mut element = Element {
+        name: orig.name.to_owned(),
+        attributes,
+        raw: if edited { None } else { orig.raw.to_owned() },
+        empty: orig.empty,
+        children: orig.children.to_owned(),
+    };
+
+    // Text content, e.g. annotation values.
+    if !base.has_elements() && !new.has_elements() {
+        let text = new.text();
+        if base.text() != text {
+            element.children.retain(|n| !matches!(n, Node::Text(_)));
+            if !text.is_empty() {
+                element.children.push(Node::Text(text));
+            }
+        }
+        return element
+    }
+
+    let base_children = keyed(base);
+    let new_children = keyed(new);
+
+    // Merge or remove modelled child elements, keep everything else.
+    let mut children: Vec<Node> = Vec::new();
+    let mut positions: HashMap<Key, usize> = HashMap::new();
+    for (node, key) in orig.children.iter().zip(keys(&orig.children)) {
+        let (Node::Element(child), Some(key)) = (node, key) else {
+            children.push(node.to_owned());
+            continue;
+        };
+        match (base_children.get(&key), new_children.get(&key)) {
+            (Some(b), Some(n)) => {
+                let merged = match b == n {
+                    true => child.to_owned(),
+                    false => {
+                        let child_indent = line_indent(children.last())
+                            .map(|i| i.to_owned())
+                            .unwrap_or(format!("{indent}{}", layout.unit));
+                        merge(child, b, n, &child_indent, layout)
+                    },
+                };
+                positions.insert(key, children.len());
+                children.push(Node::Element(merged));
+            },
+            (Some(_), None) => {
+                // Removed, including preceding indentation
+                if children.last().map(|n| n.is_whitespace()).unwrap_or(false) {
+                    children.pop();
+                }
+            },
+            // Not modelled in `Eaf`
+            (None, _) => children.push(node.to_owned()),
+        }
+    }
+
+    // Added elements, inserted after the preceding element in `new`.
+    // `None` means before the first child element.
+    let mut added: HashMap<Option<usize>, Vec<&Element>> = HashMap::new();
+    let mut anchor: Option<usize> = None;
+    for (key, child) in keys(&new.children).into_iter().zip(new.children.iter()) {
+        let (Some(key), Node::Element(child)) = (key, child) else {
+            continue;
+        };
+        if let Some(pos) = positions.get(&key) {
+            anchor = Some(*pos);
+            continue;
+        }
+        // Added on deserialization, e.g. default values, but not in the original XML.
+        if base_children.get(&key) == Some(&child) {
+            continue;
+        }
+        added.entry(anchor).or_default().push(child);
+    }
+
+    if added.is_empty() {
+        element.children = children;
+        return element
+    }
+
+    let child_indent = format!("{indent}{}", layout.unit);
+    let first = children.iter().position(|n| matches!(n, Node::Element(_)));
+    let mut merged: Vec<Node> = Vec::new();
+
+    // No child elements left to anchor to.
+    if first.is_none() {
+        while children.last().map(|n| n.is_whitespace()).unwrap_or(false) {
+            children.pop();
+        }
+        merged.extend(children);
+        for child in added.remove(&None).unwrap_or_default() {
+            if layout.pretty {
+                merged.push(Node::Text(format!("\n{child_indent}")));
+            }
+            merged.push(Node::Element(layout.format(child, &child_indent)));
+        }
+        if layout.pretty {
+            merged.push(Node::Text(format!("\n{indent}")));
+        }
+        element.empty = false;
+        element.children = merged;
+        return element
+    }
+
+    for (i, node) in children.iter().enumerate() {
+        // Before first element, copying its indentation
+        if Some(i) == first && let Some(nodes) = added.get(&None) {
+            let ws = merged.last().filter(|n| n.is_whitespace()).cloned();
+            let indent = line_indent(ws.as_ref()).unwrap_or(&child_indent).to_owned();
+            for child in nodes {
+                merged.push(Node::Element(layout.format(child, &indent)));
+                if let Some(ws) = &ws {
+                    merged.push(ws.to_owned());
+                }
+            }
+        }
+        merged.push(node.to_owned());
+        // After anchor, copying its indentation
+        if let Some(nodes) = added.get(&Some(i)) {
+            let ws = i.checked_sub(1)
+                .and_then(|p| children.get(p))
+                .filter(|n| n.is_whitespace())
+                .cloned();
+            let indent = line_indent(ws.as_ref()).unwrap_or(&child_indent).to_owned();
+            for child in nodes {
+                if let Some(ws) = &ws {
+                    merged.push(ws.to_owned());
+                }
+                merged.push(Node::Element(layout.format(child, &indent)));
+            }
+        }
+    }
+
+    element.children = merged;
+    element
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Eaf;
+    use crate::eaf::test_util::TempDir;
+
+    /// EAF v2.8 with a comment, an unknown element, an unknown attribute,
+    /// non-standard attribute order, and a CV with a `DESCRIPTION` element.
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT VERSION="2.8" FORMAT="2.8" DATE="2024-01-01T00:00:00+00:00" AUTHOR="">
+  <HEADER TIME_UNITS="milliseconds" MEDIA_FILE="">
+    <PROPERTY NAME="lastUsedAnnotationId">2</PROPERTY>
+  </HEADER>
+  <!-- edited by hand -->
+  <TIME_ORDER>
+    <TIME_SLOT TIME_VALUE="0" TIME_SLOT_ID="ts1"/>
+    <TIME_SLOT TIME_VALUE="1000" TIME_SLOT_ID="ts2"/>
+    <TIME_SLOT TIME_VALUE="2000" TIME_SLOT_ID="ts3"/>
+  </TIME_ORDER>
+  <TIER TIER_ID="A" LINGUISTIC_TYPE_REF="default-lt" X-CUSTOM="yes">
+    <ANNOTATION>
+      <ALIGNABLE_ANNOTATION ANNOTATION_ID="a1" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts2">
+        <ANNOTATION_VALUE>one &amp; two</ANNOTATION_VALUE>
+      </ALIGNABLE_ANNOTATION>
+    </ANNOTATION>
+    <ANNOTATION>
+      <ALIGNABLE_ANNOTATION ANNOTATION_ID="a2" TIME_SLOT_REF1="ts2" TIME_SLOT_REF2="ts3">
+        <ANNOTATION_VALUE>three</ANNOTATION_VALUE>
+      </ALIGNABLE_ANNOTATION>
+    </ANNOTATION>
+  </TIER>
+  <X-UNKNOWN note="kept"/>
+  <LINGUISTIC_TYPE LINGUISTIC_TYPE_ID="default-lt" TIME_ALIGNABLE="true" GRAPHIC_REFERENCES="false"/>
+  <LANGUAGE LANG_ID="eng"/>
+  <CONTROLLED_VOCABULARY CV_ID="cv">
+    <DESCRIPTION LANG_REF="eng">Colours</DESCRIPTION>
+    <CV_ENTRY_ML CVE_ID="cve1">
+      <CVE_VALUE LANG_REF="eng">red</CVE_VALUE>
+    </CV_ENTRY_ML>
+  </CONTROLLED_VOCABULARY>
+</ANNOTATION_DOCUMENT>
+"#;
+
+    fn read() -> Eaf {
+        Eaf::de_str_lossless(XML.to_owned()).unwrap()
+    }
+
+    #[test]
+    fn unchanged() {
+        let eaf = read();
+        assert!(eaf.is_lossless());
+        assert_eq!(eaf.to_string_lossless().unwrap(), XML);
+    }
+
+    #[test]
+    fn unchanged_file() {
+        let tmp = TempDir::new();
+        let path = tmp.path().join("lossless.eaf");
+        std::fs::write(&path, XML).unwrap();
+        let eaf = Eaf::read_lossless(&path).unwrap();
+        eaf.write_lossless(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), XML);
+    }
+
+    #[test]
+    fn cv_description() {
+        let eaf = read();
+        let cv = &eaf.controlled_vocabularies[0];
+        assert_eq!(cv.descriptions.len(), 1);
+        assert_eq!(cv.descriptions[0].value(), Some("Colours"));
+    }
+
+    #[test]
+    fn edits_only() {
+        let mut eaf = read();
+        eaf.get_annotation_mut("a2").unwrap().set_value("drei");
+        let xml = eaf.to_string_lossless().unwrap();
+        assert_eq!(xml, XML.replace(">three<", ">drei<"));
+    }
+
+    #[test]
+    fn removed() {
+        let mut eaf = read();
+        eaf.tiers[0].annotations.remove(0);
+        eaf.time_order.time_slots.retain(|ts| ts.time_slot_id != "ts1");
+        eaf.index();
+
+        let xml = eaf.to_string_lossless().unwrap();
+        let expected = XML.replace(r#"
+    <TIME_SLOT TIME_VALUE="0" TIME_SLOT_ID="ts1"/>"#, "")
+            .replace(r#"
+    <ANNOTATION>
+      <ALIGNABLE_ANNOTATION ANNOTATION_ID="a1" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts2">
+        <ANNOTATION_VALUE>one &amp; two</ANNOTATION_VALUE>
+      </ALIGNABLE_ANNOTATION>
+    </ANNOTATION>"#, "");
+        assert_eq!(xml, expected);
+
+        // Result is still a valid EAF.
+        let eaf2 = Eaf::de_str(&xml, true).unwrap();
+        assert_eq!(eaf2.get_tier("A").unwrap().values(), vec!["three"]);
+    }
+
+    #[test]
+    fn added() {
+        let mut eaf = read();
+        let annotation = crate::Annotation::alignable("four", "a3", "ts4", "ts5")
+            .with_ts_val(3000, 4000);
+        eaf.add_annotation(&annotation, "A").unwrap();
+
+        let xml = eaf.to_string_lossless().unwrap();
+        assert!(xml.contains(r#"    <TIME_SLOT TIME_VALUE="2000" TIME_SLOT_ID="ts3"/>
+    <TIME_SLOT TIME_SLOT_ID="ts4" TIME_VALUE="3000"/>"#));
+        assert!(xml.contains(r#"    </ANNOTATION>
+    <ANNOTATION>
+      <ALIGNABLE_ANNOTATION ANNOTATION_ID="a3" TIME_SLOT_REF1="ts4" TIME_SLOT_REF2="ts5">
+        <ANNOTATION_VALUE>four</ANNOTATION_VALUE>
+      </ALIGNABLE_ANNOTATION>
+    </ANNOTATION>
+  </TIER>"#));
+        // Everything not edited is kept as is.
+        assert!(xml.contains("<!-- edited by hand -->"));
+        assert!(xml.contains(r#"<X-UNKNOWN note="kept"/>"#));
+        assert!(xml.contains(r#"<TIER TIER_ID="A" LINGUISTIC_TYPE_REF="default-lt" X-CUSTOM="yes">"#));
+    }
+
+    #[test]
+    fn not_lossless() {
+        let eaf = Eaf::from_values(&[("a".to_owned(), 0, 100)], None).unwrap();
+        assert!(!eaf.is_lossless());
+        assert!(eaf.to_string_lossless().is_err());
+    }
+}
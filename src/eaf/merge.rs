@@ -15,20 +15,17 @@ use super::{controlled_vocabulary, language, overlap};
 ///
 /// Media files from the first Eaf will be used.
 ///
-/// Currently returns error on
+/// Overlapping annotations in the same tier are resolved
+/// according to `strategy`, see `OverlapStrategy`.
+/// `OverlapStrategy::None` returns error on
 /// annotation overlaps in the same tier.
 ///
-/// TODO 1. Handle overlaps, e.g. pass enum with values such as
-/// TODO    'Join', 'DiscardFirst', 'DiscardLast' ...annotations contained
-/// TODO    within timespan of other annotations?
-///
 /// TODO 2. Option for prefixing referred tier ID in order not
 /// TODO    to collide with other Eaf when parent tier ID is not
 /// TODO    the same.
 ///
 /// TODO 3. Option to specify new media to link.
-// pub(crate) fn merge_eafs(eafs: Vec<Eaf>, overlap: OverlapStrategy) -> Result<Eaf, EafError> {
-pub(crate) fn merge_eafs(eafs: Vec<Eaf>) -> Result<Eaf, EafError> {
+pub(crate) fn merge_eafs(eafs: Vec<Eaf>, strategy: OverlapStrategy) -> Result<Eaf, EafError> {
     if eafs.is_empty() {
         return Err(EafError::NoData);
     }
@@ -51,11 +48,17 @@ pub(crate) fn merge_eafs(eafs: Vec<Eaf>) -> Result<Eaf, EafError> {
     // key: tier ID, value: tiers with same tier ID
     // let mut tiers: HashMap<String, Vec<Tier>> = HashMap::new();
     let mut tiers: HashMap<String, Tier> = HashMap::new();
+    // key: annotation UUID, value: parent annotation UUID
+    let mut parents: HashMap<String, String> = HashMap::new();
     // note: index may be removed in future release
     for eaf in eafs.iter_mut() {
         eaf.index();
         eaf.derive()?; // sets explicit timestamps for all annotations
-        eaf.tag(); // generates and sets annotation IDs to UUID v4 as unique ID across files
+        eaf.tag()?; // generates and sets annotation IDs to UUID v4 as unique ID across files
+
+        // Parent annotations must be resolved per file,
+        // since overlaps may make these ambiguous once merged.
+        parents.extend(parent_annotations(eaf));
 
         linguistic_types.extend(eaf.linguistic_types.to_owned());
         locales.extend(eaf.locales.to_owned());
@@ -83,6 +86,7 @@ pub(crate) fn merge_eafs(eafs: Vec<Eaf>) -> Result<Eaf, EafError> {
     merged_eaf.controlled_vocabularies = controlled_vocabulary.into_iter().collect();
 
     merged_eaf.tiers = tiers.values().cloned().collect::<Vec<Tier>>();
+    resolve_overlaps(&mut merged_eaf, &parents, &strategy)?;
     merged_eaf.generate_timeorder();
 
     merged_eaf.tiers.sort_by_key(|t| t.tier_id.to_owned());
@@ -120,27 +124,450 @@ pub(crate) fn merge_tiers(tiers: &[Tier]) -> Result<Tier, EafError> {
     Ok(merged_tier)
 }
 
-pub(crate) fn merge_annotations(annotations: &[Annotation], overlap: OverlapStrategy) {}
+/// Annotations affected by resolving overlaps,
+/// used for updating dependent annotations in child tiers.
+#[derive(Debug, Default)]
+pub(crate) struct Resolved {
+    /// IDs for discarded annotations.
+    discarded: HashSet<String>,
+    /// Key: ID for annotation joined with another, and then discarded,
+    /// value: ID for the annotation it was joined with.
+    joined: HashMap<String, String>,
+    /// Key: ID for annotation with new time span,
+    /// value: new time span `(start_ms, end_ms)`.
+    resized: HashMap<String, (i64, i64)>,
+}
+
+/// Returns parent annotation for all annotations in referred tiers,
+/// as `HashMap<annotation_ID, parent_annotation_ID>`.
+///
+/// For symbolically associated annotations this is the referred annotation
+/// (`ANNOTATION_REF`), for time aligned annotations in referred tiers
+/// (e.g. `Included_In`) this is the annotation in the parent tier,
+/// whose time span contains the annotation.
+///
+/// Annotations must have time values set.
+fn parent_annotations(eaf: &Eaf) -> HashMap<String, String> {
+    let mut parents: HashMap<String, String> = HashMap::new();
+
+    for tier in eaf.tiers.iter() {
+        let Some(parent_tier) = tier.parent_ref.as_deref().and_then(|id| eaf.get_tier(id)) else {
+            continue
+        };
+
+        for annotation in tier.iter() {
+            if let Some(ref_id) = annotation.ref_id() {
+                parents.insert(annotation.id().to_owned(), ref_id.to_owned());
+                continue
+            }
+            if let (Some(start), Some(end)) = annotation.ts_val() {
+                let parent = parent_tier.iter()
+                    .find(|p| match p.ts_val() {
+                        (Some(p_start), Some(p_end)) => p_start <= start && end <= p_end,
+                        _ => false
+                    });
+                if let Some(p) = parent {
+                    parents.insert(annotation.id().to_owned(), p.id().to_owned());
+                }
+            }
+        }
+    }
+
+    parents
+}
+
+/// Resolves overlaps for all tiers in a merged `Eaf`, starting with main tiers
+/// and then moving down the tier hierarchy, so that dependent annotations
+/// in referred tiers are kept consistent with their (possibly discarded,
+/// joined, or resized) parent annotations:
+/// - Dependents of discarded annotations are discarded.
+/// - Dependents of joined annotations are moved to the annotation
+/// they were joined with.
+/// - Time aligned dependents of resized annotations are trimmed
+/// to the new time span, or discarded if they fall outside of it.
+///
+/// Annotations must have time values set and be tagged (unique IDs).
+pub(crate) fn resolve_overlaps(
+    eaf: &mut Eaf,
+    parents: &HashMap<String, String>,
+    strategy: &OverlapStrategy
+) -> Result<(), EafError> {
+    // Tier ID -> depth in tier hierarchy, main tiers = 0.
+    let parent_tiers: HashMap<String, Option<String>> = eaf.tiers.iter()
+        .map(|t| (t.tier_id.to_owned(), t.parent_ref.to_owned()))
+        .collect();
+    let depth = |tier_id: &str| -> usize {
+        let mut depth = 0;
+        let mut id = tier_id;
+        while let Some(Some(parent)) = parent_tiers.get(id) {
+            depth += 1;
+            id = parent;
+            if depth > parent_tiers.len() { break } // circular references
+        }
+        depth
+    };
+    eaf.tiers.sort_by_cached_key(|t| depth(&t.tier_id));
+
+    let association = StereoType::SymbolicAssociation.to_string();
+    let associated_lt: HashSet<String> = eaf.linguistic_types.iter()
+        .filter(|lt| lt.constraints.as_ref() == Some(&association))
+        .map(|lt| lt.linguistic_type_id.to_owned())
+        .collect();
+
+    let mut resolved = Resolved::default();
+
+    for tier in eaf.tiers.iter_mut() {
+        if tier.is_ref() {
+            update_dependents(tier, parents, &mut resolved);
+        }
+
+        if tier.annotations.iter().all(|a| a.ts_ref().is_some()) {
+            // Alignable annotations, main tier or time aligned referred tier.
+            tier.annotations = merge_annotations(&tier.annotations, strategy, &mut resolved)?;
+        } else if associated_lt.contains(&tier.linguistic_type_ref) {
+            // Symbolic association allows a single annotation per parent.
+            join_associations(tier, &mut resolved);
+        } else {
+            // Symbolic subdivision, re-order annotations referring to the same parent.
+            relink_subdivisions(tier);
+        }
+    }
+
+    Ok(())
+}
+
+/// Updates annotations in a referred tier according to
+/// how their parent annotations were resolved.
+fn update_dependents(
+    tier: &mut Tier,
+    parents: &HashMap<String, String>,
+    resolved: &mut Resolved
+) {
+    let mut annotations: Vec<Annotation> = Vec::new();
+
+    for mut annotation in tier.annotations.drain(..) {
+        let Some(parent) = parents.get(annotation.id()) else {
+            annotations.push(annotation);
+            continue
+        };
+
+        // Follow chain of joins, e.g. a3 -> a2 -> a1
+        let mut target = parent;
+        while let Some(t) = resolved.joined.get(target) {
+            target = t;
+        }
+
+        if target != parent {
+            if annotation.is_ref() {
+                annotation.set_ref_id(target);
+            }
+        } else if resolved.discarded.contains(parent) {
+            resolved.discarded.insert(annotation.id().to_owned());
+            continue
+        }
+
+        if let Some((start, end)) = resolved.resized.get(target).copied()
+            && !annotation.is_ref()
+            && let (Some(a_start), Some(a_end)) = annotation.ts_val()
+        {
+            let (new_start, new_end) = (a_start.clamp(start, end), a_end.clamp(start, end));
+            if new_start >= new_end && a_start < a_end {
+                resolved.discarded.insert(annotation.id().to_owned());
+                continue
+            }
+            if (new_start, new_end) != (a_start, a_end) {
+                annotation.set_ts_val(Some(new_start), Some(new_end));
+                resolved.resized.insert(annotation.id().to_owned(), (new_start, new_end));
+            }
+        }
+
+        annotations.push(annotation);
+    }
+
+    tier.annotations = annotations;
+}
+
+/// Joins symbolically associated annotations
+/// that refer to the same parent annotation.
+fn join_associations(tier: &mut Tier, resolved: &mut Resolved) {
+    let mut annotations: Vec<Annotation> = Vec::new();
+    // Parent annotation ID -> index in `annotations`
+    let mut first: HashMap<String, usize> = HashMap::new();
+
+    for annotation in tier.annotations.drain(..) {
+        let Some(ref_id) = annotation.ref_id().map(String::from) else {
+            annotations.push(annotation);
+            continue
+        };
+        match first.get(&ref_id) {
+            Some(idx) => {
+                let kept = &mut annotations[*idx];
+                let value = join_values(kept.to_str(), annotation.to_str());
+                kept.set_value(&value);
+                resolved.joined.insert(annotation.id().to_owned(), kept.id().to_owned());
+                resolved.discarded.insert(annotation.id().to_owned());
+            },
+            None => {
+                first.insert(ref_id, annotations.len());
+                annotations.push(annotation);
+            }
+        }
+    }
+
+    tier.annotations = annotations;
+}
+
+/// Sets previous annotation for symbolic subdivisions,
+/// so that annotations referring to the same parent form a single chain
+/// in the order they appear in the tier.
+fn relink_subdivisions(tier: &mut Tier) {
+    // Parent annotation ID -> last annotation ID in chain
+    let mut last: HashMap<String, String> = HashMap::new();
+    for annotation in tier.iter_mut() {
+        let Some(ref_id) = annotation.ref_id().map(String::from) else {
+            continue
+        };
+        if let Some(prev) = last.get(&ref_id) {
+            annotation.set_previous(prev);
+        }
+        last.insert(ref_id, annotation.id().to_owned());
+    }
+}
+
+/// Joins two annotation values with a space,
+/// ignoring empty values.
+pub(crate) fn join_values(value1: &str, value2: &str) -> String {
+    match (value1.is_empty(), value2.is_empty()) {
+        (_, true) => value1.to_owned(),
+        (true, false) => value2.to_owned(),
+        (false, false) => format!("{value1} {value2}"),
+    }
+}
+
+/// Resolves overlaps between alignable annotations in a single tier
+/// according to `strategy`, and returns the resulting annotations
+/// in chronological order. Discarded, joined and resized annotations
+/// are registered in `resolved`.
+///
+/// Annotations without time values set are not evaluated and retained as is.
+pub(crate) fn merge_annotations(
+    annotations: &[Annotation],
+    strategy: &OverlapStrategy,
+    resolved: &mut Resolved,
+) -> Result<Vec<Annotation>, EafError> {
+    let (mut timed, untimed): (Vec<Annotation>, Vec<Annotation>) = annotations.iter()
+        .cloned()
+        .partition(|a| matches!(a.ts_val(), (Some(_), Some(_))));
+    timed.sort_by_key(|a| a.ts_val());
+
+    // Time values are guaranteed to be set at this point
+    let span = |a: &Annotation| match a.ts_val() {
+        (Some(start), Some(end)) => (start, end),
+        _ => unreachable!()
+    };
+
+    let mut merged: Vec<Annotation> = Vec::new();
+
+    'annotations: for mut annotation in timed.into_iter() {
+        while let Some(prev) = merged.last_mut() {
+            let (p_start, p_end) = span(prev);
+            let (a_start, a_end) = span(&annotation);
+
+            if p_end <= a_start {
+                break
+            }
+
+            match strategy {
+                OverlapStrategy::None => return Err(EafError::AnnotationOverlap),
+                OverlapStrategy::Join => {
+                    let end = p_end.max(a_end);
+                    let value = join_values(prev.to_str(), annotation.to_str());
+                    prev.set_value(&value);
+                    if end != p_end {
+                        prev.set_ts_val(Some(p_start), Some(end));
+                        resolved.resized.insert(prev.id().to_owned(), (p_start, end));
+                    }
+                    resolved.joined.insert(annotation.id().to_owned(), prev.id().to_owned());
+                    resolved.discarded.insert(annotation.id().to_owned());
+                    continue 'annotations
+                },
+                OverlapStrategy::DiscardFirst => {
+                    if let Some(discarded) = merged.pop() {
+                        resolved.discarded.insert(discarded.id().to_owned());
+                    }
+                },
+                OverlapStrategy::DiscardLast => {
+                    resolved.discarded.insert(annotation.id().to_owned());
+                    continue 'annotations
+                },
+                OverlapStrategy::PrioritizeFirst => {
+                    // Last annotation is contained within the first one
+                    if a_end <= p_end {
+                        resolved.discarded.insert(annotation.id().to_owned());
+                        continue 'annotations
+                    }
+                    annotation.set_ts_val(Some(p_end), Some(a_end));
+                    resolved.resized.insert(annotation.id().to_owned(), (p_end, a_end));
+                    break
+                },
+                OverlapStrategy::PrioritizeLast => {
+                    // First annotation starts at the same time as the last one
+                    if a_start <= p_start {
+                        if let Some(discarded) = merged.pop() {
+                            resolved.discarded.insert(discarded.id().to_owned());
+                        }
+                        continue
+                    }
+                    prev.set_ts_val(Some(p_start), Some(a_start));
+                    resolved.resized.insert(prev.id().to_owned(), (p_start, a_start));
+                    break
+                },
+            }
+        }
 
+        merged.push(annotation);
+    }
+
+    merged.extend(untimed);
+
+    Ok(merged)
+}
+
+/// Strategy for resolving overlapping annotations in the same tier,
+/// e.g. when merging EAF files.
+///
+/// "First" and "last" refer to chronological order,
+/// i.e. the annotation with the earliest start time is the first one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum OverlapStrategy {
     /// Do nothing. For e.g. raising
     /// errors on overlaps.
+    #[default]
     None,
     /// Join annotations, i.e.
     /// new time span will be first annotation's
     /// start time, and last annotation's end time.
+    /// Annotation values are joined with a space.
     Join,
     /// Discard first annotation.
     DiscardFirst,
     /// Discard last annotation.
     DiscardLast,
-    /// First annotation's end time will move
-    /// that of the second annotation's start time.
+    /// First annotation is kept intact.
+    /// Last annotation's start time will move
+    /// to the first annotation's end time.
+    /// Discarded if contained within the first annotation.
     PrioritizeFirst,
-    /// Second annotation's start time will move
-    /// that of the first annotation's end time.
+    /// Last annotation is kept intact.
+    /// First annotation's end time will move
+    /// to the last annotation's start time.
+    /// Discarded if it starts at the same time as the last annotation.
     PrioritizeLast,
-    // annotation contained within other annotation's timespan?
 }
 
-pub(crate) fn merge2(eafs: Vec<Eaf>) {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eaf::test_util::{TestEaf, aligned, referred, subdivision};
+
+    /// Main tier `A` with a symbolically associated tier `A-gloss`.
+    fn eaf(value: &str, start: i64, end: i64) -> Eaf {
+        TestEaf::new()
+            .time_slots(&[start, end])
+            .tier("A", "default-lt", None, &[aligned("a1", "ts1", "ts2", value)])
+            .linguistic_type("gloss", Some("Symbolic_Association"))
+            .tier("A-gloss", "gloss", Some("A"), &[referred("a2", "a1", &format!("g-{value}"))])
+            .eaf()
+    }
+
+    /// Returns `(value, start_ms, end_ms)` for main tier `A`,
+    /// and values for referred tier `A-gloss`.
+    /// Main tier (value, start, end) and gloss tier values.
+    type Values = (Vec<(String, i64, i64)>, Vec<String>);
+
+    fn values(eaf: &Eaf) -> Values {
+        let main = eaf.get_tier("A").unwrap().iter()
+            .map(|a| {
+                let (t1, t2) = a.ts_val();
+                (a.to_str().to_owned(), t1.unwrap(), t2.unwrap())
+            })
+            .collect();
+        let gloss = eaf.get_tier("A-gloss").unwrap().values()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        (main, gloss)
+    }
+
+    fn merge(strategy: OverlapStrategy) -> Result<Values, EafError> {
+        let eaf = Eaf::merge_with(&[eaf("x", 0, 1000), eaf("y", 500, 1500)], strategy)?;
+        Ok(values(&eaf))
+    }
+
+    fn main(values: &[(&str, i64, i64)]) -> Vec<(String, i64, i64)> {
+        values.iter().map(|(v, t1, t2)| (v.to_string(), *t1, *t2)).collect()
+    }
+
+    #[test]
+    fn no_strategy() {
+        assert!(matches!(merge(OverlapStrategy::None), Err(EafError::AnnotationOverlap)));
+        let eaf = Eaf::merge(&[eaf("x", 0, 1000), eaf("y", 1000, 1500)]).unwrap();
+        assert_eq!(values(&eaf).1, vec!["g-x", "g-y"]);
+    }
+
+    #[test]
+    fn join() {
+        let (main_values, gloss) = merge(OverlapStrategy::Join).unwrap();
+        assert_eq!(main_values, main(&[("x y", 0, 1500)]));
+        assert_eq!(gloss, vec!["g-x g-y"]);
+    }
+
+    #[test]
+    fn discard() {
+        let (main_values, gloss) = merge(OverlapStrategy::DiscardFirst).unwrap();
+        assert_eq!(main_values, main(&[("y", 500, 1500)]));
+        assert_eq!(gloss, vec!["g-y"]);
+
+        let (main_values, gloss) = merge(OverlapStrategy::DiscardLast).unwrap();
+        assert_eq!(main_values, main(&[("x", 0, 1000)]));
+        assert_eq!(gloss, vec!["g-x"]);
+    }
+
+    #[test]
+    fn prioritize() {
+        let (main_values, gloss) = merge(OverlapStrategy::PrioritizeFirst).unwrap();
+        assert_eq!(main_values, main(&[("x", 0, 1000), ("y", 1000, 1500)]));
+        assert_eq!(gloss, vec!["g-x", "g-y"]);
+
+        let (main_values, gloss) = merge(OverlapStrategy::PrioritizeLast).unwrap();
+        assert_eq!(main_values, main(&[("x", 0, 500), ("y", 500, 1500)]));
+        assert_eq!(gloss, vec!["g-x", "g-y"]);
+    }
+
+    #[test]
+    fn prioritize_contained() {
+        let eaf = Eaf::merge_with(&[eaf("x", 0, 1000), eaf("y", 200, 800)], OverlapStrategy::PrioritizeFirst).unwrap();
+        assert_eq!(values(&eaf), (main(&[("x", 0, 1000)]), vec!["g-x".to_owned()]));
+    }
+
+    #[test]
+    fn dangling_references() {
+        // Symbolic subdivision where `a3` follows a removed annotation.
+        let doc = TestEaf::new()
+            .time_slots(&[0, 1000])
+            .tier("A", "default-lt", None, &[aligned("a1", "ts1", "ts2", "x")])
+            .linguistic_type("words", Some("Symbolic_Subdivision"))
+            .tier("W", "words", Some("A"), &[
+                referred("a2", "a1", "w1"),
+                subdivision("a3", "a1", "a9", "w2"),
+            ]);
+        let merged = Eaf::merge(&[doc.eaf()]).unwrap();
+        let words = &merged.get_tier("W").unwrap().annotations;
+        assert_eq!(words.len(), 2);
+        assert_eq!(words[0].previous(), None);
+        assert_eq!(words[1].previous(), Some(words[0].id()));
+
+        let mut eaf = doc.tier("G", "words", Some("W"), &[referred("a4", "a8", "g")]).eaf_underived();
+        assert!(matches!(eaf.tag(), Err(EafError::AnnotationRefMissing)));
+    }
+}
@@ -38,8 +38,25 @@ pub mod controlled_vocabulary;
 pub mod external_ref;
 pub mod json;
 pub mod validate;
-pub(crate) mod query;
+pub mod query;
 pub mod merge;
+pub mod textgrid;
+pub mod subtitle;
+pub mod search;
+pub mod corpus;
+pub(crate) mod lossless;
+pub mod version;
+pub mod chat;
+pub mod flextext;
+pub mod exmaralda;
+pub mod tabular;
+pub mod time_index;
+pub(crate) mod edit;
+pub mod computed;
+pub mod tokenize;
+pub mod agreement;
+pub mod conversation;
+pub mod diff;
 
 pub use eaf::{Eaf, Scope};
 pub use builder::EafBuilder;
@@ -50,11 +67,12 @@ pub use linked_file_descriptor::LinkedFileDescriptor;
 pub use property::Property;
 pub use timeorder::{TimeOrder, TimeSlot};
 pub use tier::Tier;
-pub use annotation::{Annotation, AlignableAnnotation, RefAnnotation};
+pub use annotation::{Annotation, AlignableAnnotation, RefAnnotation, Overlap};
 pub use linguistic_type::{LinguisticType, Constraint, StereoType};
 pub use language::Language;
 pub use lexicon_ref::LexiconRef;
 pub use index::Index; // should perhaps not be public
+pub use time_index::{TimeIndex, RangeMode};
 pub use locale::Locale;
 pub use controlled_vocabulary::{
     ControlledVocabulary,
@@ -67,8 +85,26 @@ pub use controlled_vocabulary::{
 };
 pub use external_ref::{ExternalRef, ReferenceType};
 pub use json::{JsonAnnotation, JsonEaf, JsonTier};
-pub use query::QueryResult;
+pub use query::{QueryResult, Kwic, KwicLine};
 pub use merge::OverlapStrategy;
+pub use textgrid::{TextGrid, TextGridTier};
+pub use subtitle::{Subtitles, SubtitleFormat, SubtitleOptions};
+pub use search::{Search, SearchHit, HitAnnotation, Layer, TierSelector, ValueMatch, Relation, AllenRelation};
+pub use corpus::{Corpus, CorpusHit, FileStats, TierSummary};
+pub use version::{EafVersion, ConversionLoss};
+pub use chat::{Chat, ChatParticipant, ChatUtterance, ChatDependent};
+pub use flextext::{FlexText, FlexItem, Phrase, Word, Morph};
+pub use exmaralda::{Exmaralda, ExbTier, ExbEvent, ExbSpeaker, ExbTimelineItem};
+pub use tabular::{Table, TabColumn, TabOptions, TimeFormat, CsvMapping, RowError};
+pub use computed::ValueRule;
+pub use tokenize::{Tokenizer, AlignOptions};
+pub use agreement::{Agreement, AgreementOptions, ConfusionMatrix, SegmentMatch, SegmentAnnotation};
+pub use conversation::{ConversationStats, ConversationOptions, SpeakerStats, FloorTransfer, Distribution};
+pub use diff::{EafDiff, TierDiff, ItemDiff, AnnotationDiff, DiffAnnotation, AttributeChange, DiffStatus};
+pub use validate::{ValidationReport, ValidationIssue, IssueCode, Severity, Location};
 
 pub(crate) use validate::{overlap, ts_duplicates};
 pub(crate) use eaf::{xsi_no_name_space_schema_location, xmlns_xsi, today};
+
+#[cfg(test)]
+mod test_util;
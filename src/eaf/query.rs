@@ -1,54 +1,456 @@
-use std::{io::{self, Write}, fmt::Display};
+//! Query results for regular expression searches in annotation values,
+//! with the matched spans, surrounding annotations in the same tier as context,
+//! and keyword-in-context (KWIC) concordance output.
+//!
+//! Example:
+//! ```ignore
+//! let rx = Regex::new(r"\bba\w*")?;
+//! let results = eaf.query_rx(&rx);
+//! // 30 graphemes of context on either side of each match
+//! let kwic = Kwic::new(&results, 30);
+//! kwic.print(Some(Color::Green))?;
+//! kwic.write_csv(Path::new("concordance.csv"))?;
+//! ```
+
+use std::{fmt::Display, fs::File, io::{self, Write}, path::Path};
+
+use regex::Regex;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
-use regex::{self, Regex};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{Annotation, EafError, Tier};
 
-#[derive(Debug, Default)]
+/// Part of an annotation value, tagged whether it is a match or not.
+#[derive(Debug, Default, Clone)]
 pub struct Token<'a> {
     value: &'a str,
+    /// Byte offset in annotation value.
+    offset: usize,
     is_match: bool,
     color: Option<Color>
 }
 
-#[derive(Debug, Default)]
+impl <'a> Token<'a> {
+    pub fn value(&self) -> &'a str {
+        self.value
+    }
+
+    /// Byte offset in annotation value.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn is_match(&self) -> bool {
+        self.is_match
+    }
+
+    pub fn color(&self) -> Option<Color> {
+        self.color
+    }
+}
+
+/// Annotation that matched a query.
+#[derive(Debug, Clone)]
 pub struct QueryResult<'a> {
-    // annotation_index: usize,
-    // tier_id: &'a str,
-    // annotation_id: &'a str,
-    // // annotation_value: &'a str,
-    // ref_annotation_id: &'a str,
-    /// Each word/token from the original annotation,
+    /// Annotation index, starting on 1,
+    /// corresponding to annotation order in the tier.
+    /// Same as `HitAnnotation::index` and `Eaf::query()`.
+    pub annotation_index: usize,
+    pub tier_id: &'a str,
+    pub annotation: &'a Annotation,
+    /// Annotation start time in milliseconds.
+    /// Requires derived time values.
+    pub start: Option<i64>,
+    /// Annotation end time in milliseconds.
+    /// Requires derived time values.
+    pub end: Option<i64>,
+    /// Annotations that precede the matched annotation in the same tier,
+    /// in tier order.
+    pub preceding: &'a [Annotation],
+    /// Annotations that follow the matched annotation in the same tier,
+    /// in tier order.
+    pub following: &'a [Annotation],
+    /// Each part of the annotation value,
     /// separated, tagged whether it is a match or not
     tokens: Vec<Token<'a>>
 }
 
 impl <'a> Display for QueryResult<'a> {
+    /// Annotation value with matches enclosed in square brackets,
+    /// prefixed with tier ID, annotation index and time span.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        todo!()
+        let value: String = self.tokens.iter()
+            .map(|t| match t.is_match {
+                true => format!("[{}]", t.value),
+                false => t.value.to_owned(),
+            })
+            .collect();
+        write!(f, "{}:{} {}-{} {}",
+            self.tier_id,
+            self.annotation_index,
+            self.start.map(|t| t.to_string()).unwrap_or("?".to_owned()),
+            self.end.map(|t| t.to_string()).unwrap_or("?".to_owned()),
+            value
+        )
     }
 }
 
 impl <'a> QueryResult<'a> {
-    pub fn new(value: &str, rx: &Regex) {
-        // regex.split(haystack)
-        // regex.
-        // for (i, m) in value.match_indices(rx) {
+    /// Matches the annotation at `index` in `tier.annotations` against `rx`.
+    /// Note that `index` starts on 0, whereas the resulting
+    /// `QueryResult::annotation_index` starts on 1.
+    /// Returns `None` if the index is out of bounds or the value does not match.
+    pub fn new(tier: &'a Tier, index: usize, rx: &Regex) -> Option<Self> {
+        let annotation = tier.annotations.get(index)?;
+        let value = annotation.to_str();
+
+        let mut tokens: Vec<Token> = Vec::new();
+        let mut last = 0;
+        for m in rx.find_iter(value).filter(|m| !m.is_empty()) {
+            if m.start() > last {
+                tokens.push(Token { value: &value[last..m.start()], offset: last, is_match: false, color: None });
+            }
+            tokens.push(Token { value: m.as_str(), offset: m.start(), is_match: true, color: None });
+            last = m.end();
+        }
+        if tokens.is_empty() {
+            return None
+        }
+        if last < value.len() {
+            tokens.push(Token { value: &value[last..], offset: last, is_match: false, color: None });
+        }
 
-        // }
+        let (start, end) = annotation.ts_val();
+
+        Some(Self {
+            annotation_index: index + 1,
+            tier_id: &tier.tier_id,
+            annotation,
+            start,
+            end,
+            preceding: &tier.annotations[..index],
+            following: &tier.annotations[index + 1 ..],
+            tokens
+        })
     }
 
-    // fn set_match_color(&mut self, color: Color) -> io::Result<()> {
-    fn set_match_color(&mut self, color: Color) {
-        let mut stdout = StandardStream::stdout(ColorChoice::Always);
-        stdout.set_color(ColorSpec::new().set_fg(Some(color))).unwrap(); //?;
-        write!(&mut stdout, "green text!");
+    /// Sets the color used for matches when printing
+    /// results with `QueryResult::write_color()`.
+    pub fn set_match_color(&mut self, color: Color) {
         self.tokens.iter_mut()
             .for_each(|t| if t.is_match {
                 t.color = Some(color)
             })
     }
 
+    /// Returns all parts of the annotation value, matching or not.
+    pub fn tokens(&self) -> &[Token<'a>] {
+        &self.tokens
+    }
+
+    /// Returns matched parts of the annotation value.
+    pub fn matches(&self) -> impl Iterator<Item = &Token<'a>> {
+        self.tokens.iter().filter(|t| t.is_match)
+    }
 
     pub fn is_match(&self) -> bool {
         self.tokens.iter().any(|t| t.is_match)
     }
+
+    /// Writes annotation value, with matches highlighted in the
+    /// color set via `QueryResult::set_match_color()`.
+    pub fn write_color<W: WriteColor>(&self, writer: &mut W) -> io::Result<()> {
+        for token in self.tokens.iter() {
+            match token.color {
+                Some(c) => {
+                    writer.set_color(ColorSpec::new().set_fg(Some(c)).set_bold(true))?;
+                    write!(writer, "{}", token.value)?;
+                    writer.reset()?;
+                },
+                None => write!(writer, "{}", token.value)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the keyword-in-context line for each match,
+    /// with at most `width` graphemes of context on either side.
+    /// Context includes surrounding annotations in the same tier,
+    /// separated by a single space.
+    pub fn kwic(&self, width: usize) -> Vec<KwicLine> {
+        let value = self.annotation.to_str();
+        self.matches()
+            .map(|m| {
+                let mut before = take_context(self.preceding.iter().rev().map(|a| a.to_str()), width);
+                before.reverse();
+                before.push(&value[..m.offset]);
+                let mut after = vec![&value[m.offset + m.value.len() ..]];
+                after.extend(take_context(self.following.iter().map(|a| a.to_str()), width));
+
+                KwicLine {
+                    tier_id: self.tier_id.to_owned(),
+                    annotation_index: self.annotation_index,
+                    annotation_id: self.annotation.id().to_owned(),
+                    start: self.start,
+                    end: self.end,
+                    left: last_graphemes(&join_context(&before), width),
+                    keyword: m.value.to_owned(),
+                    right: first_graphemes(&join_context(&after), width),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Collects annotation values until their combined length
+/// exceeds `width` bytes, so that context
+/// is never built from the whole tier.
+fn take_context<'a>(values: impl Iterator<Item = &'a str>, width: usize) -> Vec<&'a str> {
+    let mut len = 0;
+    let mut context = Vec::new();
+    for value in values {
+        if len > width {
+            break;
+        }
+        len += value.len() + 1;
+        context.push(value);
+    }
+    context
+}
+
+/// Joins annotation values and the part of the matched annotation
+/// before or after the match, normalizing whitespace, but keeping
+/// a leading/trailing space if there is one to mark word boundaries.
+fn join_context(parts: &[&str]) -> String {
+    let joined = parts.iter()
+        .filter(|p| !p.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut context = joined.split_whitespace().collect::<Vec<_>>().join(" ");
+    if joined.starts_with(char::is_whitespace) {
+        context.insert(0, ' ');
+    }
+    if joined.ends_with(char::is_whitespace) && !context.trim().is_empty() {
+        context.push(' ');
+    }
+    context
+}
+
+/// Returns the last `n` graphemes in `text`.
+fn last_graphemes(text: &str, n: usize) -> String {
+    let graphemes: Vec<&str> = text.graphemes(true).collect();
+    graphemes[graphemes.len().saturating_sub(n)..].concat()
+}
+
+/// Returns the first `n` graphemes in `text`.
+fn first_graphemes(text: &str, n: usize) -> String {
+    text.graphemes(true).take(n).collect()
+}
+
+/// Keyword-in-context line for a single match.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KwicLine {
+    pub tier_id: String,
+    /// Annotation index, starting on 1,
+    /// corresponding to annotation order in the tier.
+    pub annotation_index: usize,
+    pub annotation_id: String,
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+    /// Context preceding the match.
+    pub left: String,
+    /// The matched text.
+    pub keyword: String,
+    /// Context following the match.
+    pub right: String,
+}
+
+/// Keyword-in-context (KWIC) concordance,
+/// with one line per match.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Kwic {
+    /// Context width in graphemes on either side of the keyword.
+    pub width: usize,
+    pub lines: Vec<KwicLine>,
+}
+
+impl Kwic {
+    /// Generates concordance from query results,
+    /// with at most `width` graphemes of context on either side
+    /// of each match.
+    pub fn new(results: &[QueryResult], width: usize) -> Self {
+        Self {
+            width,
+            lines: results.iter()
+                .flat_map(|r| r.kwic(width))
+                .collect()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &KwicLine> {
+        self.lines.iter()
+    }
+
+    /// Left context, right aligned to the context width.
+    fn padded_left(&self, line: &KwicLine) -> String {
+        let pad = self.width.saturating_sub(line.left.graphemes(true).count());
+        format!("{}{}", " ".repeat(pad), line.left)
+    }
+
+    /// Writes concordance with keywords aligned,
+    /// and highlighted in `color` if set.
+    pub fn write_color<W: WriteColor>(&self, writer: &mut W, color: Option<Color>) -> io::Result<()> {
+        for line in self.lines.iter() {
+            write!(writer, "{}", self.padded_left(line))?;
+            writer.set_color(ColorSpec::new().set_fg(color).set_bold(color.is_some()))?;
+            write!(writer, "{}", line.keyword)?;
+            writer.reset()?;
+            writeln!(writer, "{}", line.right)?;
+        }
+        Ok(())
+    }
+
+    /// Prints concordance to stdout, with keywords highlighted
+    /// in `color` if set and the terminal supports it.
+    pub fn print(&self, color: Option<Color>) -> io::Result<()> {
+        let mut stdout = StandardStream::stdout(ColorChoice::Auto);
+        self.write_color(&mut stdout, color)
+    }
+
+    /// Returns concordance as plain text with keywords aligned.
+    /// Keywords are separated from the context by a tab.
+    pub fn to_plain(&self) -> String {
+        self.lines.iter()
+            .map(|l| format!("{}\t{}\t{}\n", self.padded_left(l), l.keyword, l.right))
+            .collect()
+    }
+
+    /// Returns concordance as CSV with a header row.
+    /// Columns: tier ID, annotation index, annotation ID,
+    /// start (ms), end (ms), left context, keyword, right context.
+    pub fn to_csv(&self) -> Result<String, EafError> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(["tier_id", "annotation_index", "annotation_id", "start", "end", "left", "keyword", "right"])?;
+        for line in self.lines.iter() {
+            writer.write_record([
+                line.tier_id.to_owned(),
+                line.annotation_index.to_string(),
+                line.annotation_id.to_owned(),
+                line.start.map(|t| t.to_string()).unwrap_or_default(),
+                line.end.map(|t| t.to_string()).unwrap_or_default(),
+                line.left.to_owned(),
+                line.keyword.to_owned(),
+                line.right.to_owned(),
+            ])?;
+        }
+        let bytes = writer.into_inner().map_err(|e| EafError::IOError(e.into_error()))?;
+        Ok(String::from_utf8_lossy(&bytes).to_string())
+    }
+
+    /// Writes concordance as CSV to disk. See `Kwic::to_csv()`.
+    pub fn write_csv(&self, path: &Path) -> Result<(), EafError> {
+        let mut outfile = File::create(&path)?;
+        outfile.write_all(self.to_csv()?.as_bytes()).map_err(|e| EafError::IOError(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use termcolor::{Buffer, NoColor};
+
+    use super::*;
+    use crate::Eaf;
+
+    fn eaf() -> Eaf {
+        Eaf::from_values(&[
+            ("the big cat".to_owned(), 0, 1000),
+            ("sat on a mat".to_owned(), 1000, 2000),
+            ("and a bat".to_owned(), 2000, 3000),
+        ], Some("tx")).unwrap()
+    }
+
+    #[test]
+    fn query_result() {
+        let eaf = eaf();
+        let rx = Regex::new(r"\w?at\b").unwrap();
+        let mut results = eaf.query_rx(&rx);
+        results.sort_by_key(|r| r.annotation_index);
+
+        assert_eq!(results.len(), 3);
+        let r = &results[1];
+        assert_eq!(r.annotation_index, 2);
+        assert_eq!((r.start, r.end), (Some(1000), Some(2000)));
+        assert_eq!(r.matches().map(|t| (t.value(), t.offset())).collect::<Vec<_>>(), vec![("sat", 0), ("mat", 9)]);
+        assert_eq!(r.tokens().iter().map(|t| t.value()).collect::<String>(), "sat on a mat");
+        assert_eq!(r.preceding.len(), 1);
+        assert_eq!(r.following.len(), 1);
+        assert_eq!(r.to_string(), "tx:2 1000-2000 [sat] on a [mat]");
+
+        let tier = eaf.get_tier("tx").unwrap();
+        assert!(QueryResult::new(tier, 0, &Regex::new("dog").unwrap()).is_none());
+        assert!(QueryResult::new(tier, 3, &rx).is_none());
+    }
+
+    #[test]
+    fn match_color() {
+        let eaf = eaf();
+        let tier = eaf.get_tier("tx").unwrap();
+        let mut result = QueryResult::new(tier, 0, &Regex::new("cat").unwrap()).unwrap();
+        result.set_match_color(Color::Green);
+        assert!(result.matches().all(|t| t.color() == Some(Color::Green)));
+        assert!(result.tokens().iter().filter(|t| !t.is_match()).all(|t| t.color().is_none()));
+
+        let mut plain = NoColor::new(Vec::new());
+        result.write_color(&mut plain).unwrap();
+        assert_eq!(String::from_utf8(plain.into_inner()).unwrap(), "the big cat");
+
+        let mut ansi = Buffer::ansi();
+        result.write_color(&mut ansi).unwrap();
+        let ansi = String::from_utf8(ansi.into_inner()).unwrap();
+        assert!(ansi.starts_with("the big \u{1b}["));
+        assert!(ansi.contains("cat"));
+    }
+
+    #[test]
+    fn kwic() {
+        let eaf = eaf();
+        let tier = eaf.get_tier("tx").unwrap();
+        let result = QueryResult::new(tier, 1, &Regex::new("on").unwrap()).unwrap();
+        let kwic = Kwic::new(&[result], 8);
+
+        assert_eq!(kwic.len(), 1);
+        let line = &kwic.lines[0];
+        assert_eq!(line.left, "cat sat ");
+        assert_eq!(line.keyword, "on");
+        assert_eq!(line.right, " a mat a");
+        assert_eq!(kwic.to_plain(), "cat sat \ton\t a mat a\n");
+        assert_eq!(
+            kwic.to_csv().unwrap(),
+            "tier_id,annotation_index,annotation_id,start,end,left,keyword,right\n\
+            tx,2,a2,1000,2000,cat sat ,on, a mat a\n"
+        );
+
+        let mut plain = NoColor::new(Vec::new());
+        kwic.write_color(&mut plain, Some(Color::Red)).unwrap();
+        assert_eq!(String::from_utf8(plain.into_inner()).unwrap(), "cat sat on a mat a\n");
+    }
+
+    #[test]
+    fn kwic_padding() {
+        let eaf = eaf();
+        let tier = eaf.get_tier("tx").unwrap();
+        let result = QueryResult::new(tier, 0, &Regex::new("the").unwrap()).unwrap();
+        let kwic = Kwic::new(&[result], 5);
+        assert_eq!(kwic.lines[0].left, "");
+        assert_eq!(kwic.to_plain(), "     \tthe\t big \n");
+    }
 }
@@ -0,0 +1,511 @@
+//! Structured, multi-tier search, similar to ELAN's "Structured Search Multiple Layer".
+//!
+//! A search consists of layers and relations between these.
+//! Each layer selects annotations via a tier selector (tier ID, participant,
+//! linguistic type) and a value constraint (substring, regular expression,
+//! controlled vocabulary entry). Relations constrain how annotations
+//! in two layers relate to each other structurally (parent/child)
+//! or in time (overlap, distance, Allen's interval relations).
+//!
+//! Each hit contains one annotation per layer, in the order layers were added.
+//!
+//! Example:
+//! ```ignore
+//! // A token matching "^ba" on tier "tokens", whose parent on tier "gloss"
+//! // has the CV entry "cveid_12", and which overlaps an annotation on tier "gesture".
+//! let search = Search::new()
+//!     .layer(TierSelector::Id("tokens".into()), ValueMatch::regex("^ba")?) // layer 0
+//!     .layer(TierSelector::Id("gloss".into()), ValueMatch::CvEntry("cveid_12".into())) // layer 1
+//!     .layer(TierSelector::Id("gesture".into()), ValueMatch::Any) // layer 2
+//!     .relation(0, Relation::ChildOf, 1)
+//!     .relation(0, Relation::Overlaps, 2);
+//! let hits = eaf.search(&search)?;
+//! ```
+
+use std::collections::HashSet;
+
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use regex::Regex;
+
+use crate::{Annotation, CvType, Eaf, EafError, Tier};
+
+/// Selects which tiers a layer applies to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TierSelector {
+    /// All tiers.
+    Any,
+    /// Tier ID.
+    Id(String),
+    /// Tiers with specified participant.
+    Participant(String),
+    /// Tiers with specified linguistic type.
+    LinguisticType(String),
+}
+
+impl TierSelector {
+    /// Returns `true` if `tier` is selected.
+    pub fn is_match(&self, tier: &Tier) -> bool {
+        match self {
+            TierSelector::Any => true,
+            TierSelector::Id(id) => &tier.tier_id == id,
+            TierSelector::Participant(p) => tier.participant.as_ref() == Some(p),
+            TierSelector::LinguisticType(lt) => &tier.linguistic_type_ref == lt,
+        }
+    }
+}
+
+/// Constraint on annotation values.
+#[derive(Debug, Clone)]
+pub enum ValueMatch {
+    /// Any value, including empty ones.
+    Any,
+    /// Value contains pattern.
+    Substring{pattern: String, ignore_case: bool},
+    /// Value equals pattern.
+    Exact{pattern: String, ignore_case: bool},
+    /// Value matches regular expression.
+    Regex(Regex),
+    /// Annotation refers to the controlled vocabulary entry with
+    /// this ID (`CVE_REF`), or its value equals one of the entry's values.
+    CvEntry(String),
+}
+
+impl ValueMatch {
+    /// Substring match.
+    pub fn substring(pattern: &str, ignore_case: bool) -> Self {
+        Self::Substring{pattern: pattern.to_owned(), ignore_case}
+    }
+
+    /// Exact match.
+    pub fn exact(pattern: &str, ignore_case: bool) -> Self {
+        Self::Exact{pattern: pattern.to_owned(), ignore_case}
+    }
+
+    /// Regular expression match. Returns error if `pattern` is invalid.
+    pub fn regex(pattern: &str) -> Result<Self, EafError> {
+        Ok(Self::Regex(Regex::new(pattern)?))
+    }
+
+    /// Returns `true` if annotation matches. `cv_values` are the values
+    /// for the controlled vocabulary entry in `ValueMatch::CvEntry`.
+    fn is_match(&self, annotation: &Annotation, cv_values: &HashSet<String>) -> bool {
+        let value = annotation.to_str();
+        match self {
+            ValueMatch::Any => true,
+            ValueMatch::Substring{pattern, ignore_case: true} => value.to_lowercase().contains(&pattern.to_lowercase()),
+            ValueMatch::Substring{pattern, ignore_case: false} => value.contains(pattern.as_str()),
+            ValueMatch::Exact{pattern, ignore_case: true} => value.to_lowercase() == pattern.to_lowercase(),
+            ValueMatch::Exact{pattern, ignore_case: false} => value == pattern,
+            ValueMatch::Regex(rx) => rx.is_match(value),
+            ValueMatch::CvEntry(cve_id) => annotation.cve_ref().as_ref() == Some(cve_id)
+                || cv_values.contains(value),
+        }
+    }
+}
+
+/// Allen's interval relations between two annotations `a`, `b`,
+/// read as "`a` *relation* `b`", e.g. "`a` before `b`".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AllenRelation {
+    /// `a` ends before `b` starts.
+    Before,
+    /// `a` starts after `b` ends.
+    After,
+    /// `a` ends when `b` starts.
+    Meets,
+    /// `a` starts when `b` ends.
+    MetBy,
+    /// `a` starts before `b`, and ends within `b`.
+    Overlaps,
+    /// `a` starts within `b`, and ends after `b`.
+    OverlappedBy,
+    /// `a` starts with `b`, and ends before `b`.
+    Starts,
+    /// `a` starts with `b`, and ends after `b`.
+    StartedBy,
+    /// `a` starts after `b` starts, and ends before `b` ends.
+    During,
+    /// `a` starts before `b` starts, and ends after `b` ends.
+    Contains,
+    /// `a` ends with `b`, and starts after `b`.
+    Finishes,
+    /// `a` ends with `b`, and starts before `b`.
+    FinishedBy,
+    /// `a` and `b` have the same time span.
+    Equals,
+}
+
+impl AllenRelation {
+    /// Returns `true` if time spans `a`, `b` (`(start, end)`) are in this relation.
+    pub fn holds(&self, a: (i64, i64), b: (i64, i64)) -> bool {
+        let ((a1, a2), (b1, b2)) = (a, b);
+        match self {
+            AllenRelation::Before => a2 < b1,
+            AllenRelation::After => a1 > b2,
+            AllenRelation::Meets => a2 == b1,
+            AllenRelation::MetBy => a1 == b2,
+            AllenRelation::Overlaps => a1 < b1 && b1 < a2 && a2 < b2,
+            AllenRelation::OverlappedBy => b1 < a1 && a1 < b2 && b2 < a2,
+            AllenRelation::Starts => a1 == b1 && a2 < b2,
+            AllenRelation::StartedBy => a1 == b1 && a2 > b2,
+            AllenRelation::During => a1 > b1 && a2 < b2,
+            AllenRelation::Contains => a1 < b1 && a2 > b2,
+            AllenRelation::Finishes => a2 == b2 && a1 > b1,
+            AllenRelation::FinishedBy => a2 == b2 && a1 < b1,
+            AllenRelation::Equals => a1 == b1 && a2 == b2,
+        }
+    }
+}
+
+/// Relation between annotations in two layers `a`, `b`,
+/// read as "`a` *relation* `b`", e.g. "`a` child of `b`".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Relation {
+    /// `b` is the parent annotation of `a`, i.e.
+    /// `a` refers to `b`, or `a` is a time aligned annotation
+    /// in a child tier of `b`'s tier, within `b`'s time span.
+    ChildOf,
+    /// `a` is the parent annotation of `b`.
+    ParentOf,
+    /// `a` and `b` share some part of their time spans.
+    Overlaps,
+    /// The gap between `a` and `b` is at most this many milliseconds,
+    /// in either direction. Overlapping annotations have no gap.
+    Within(i64),
+    /// Allen's interval relations.
+    Allen(AllenRelation),
+}
+
+/// A layer in a structured search.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub tiers: TierSelector,
+    pub value: ValueMatch,
+}
+
+/// Structured, multi-tier search.
+/// Layers are referred to by index, in the order these are added.
+#[derive(Debug, Clone, Default)]
+pub struct Search {
+    pub layers: Vec<Layer>,
+    /// Relations in the form `(layer_index_a, relation, layer_index_b)`.
+    pub relations: Vec<(usize, Relation, usize)>,
+}
+
+/// An annotation in a search hit.
+#[derive(Debug, Clone, Copy)]
+pub struct HitAnnotation<'a> {
+    pub tier_id: &'a str,
+    /// Annotation index, starting on 1,
+    /// corresponding to annotation order in the tier.
+    /// Same as `QueryResult::annotation_index` and `Eaf::query()`.
+    pub index: usize,
+    pub annotation: &'a Annotation,
+}
+
+/// A search hit, with one annotation per layer,
+/// in the order layers were added.
+#[derive(Debug, Clone)]
+pub struct SearchHit<'a> {
+    pub annotations: Vec<HitAnnotation<'a>>,
+}
+
+impl<'a> SearchHit<'a> {
+    /// Returns annotation for layer at index `layer`.
+    pub fn get(&self, layer: usize) -> Option<&HitAnnotation<'a>> {
+        self.annotations.get(layer)
+    }
+}
+
+/// Candidate annotation for a layer, with its tier.
+struct Candidate<'a> {
+    tier: &'a Tier,
+    hit: HitAnnotation<'a>,
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a layer. The first layer added has index 0.
+    pub fn layer(self, tiers: TierSelector, value: ValueMatch) -> Self {
+        let mut layers = self.layers;
+        layers.push(Layer{tiers, value});
+        Self {
+            layers,
+            ..self
+        }
+    }
+
+    /// Adds relation "`a` *relation* `b`",
+    /// where `a` and `b` are layer indices.
+    pub fn relation(self, a: usize, relation: Relation, b: usize) -> Self {
+        let mut relations = self.relations;
+        relations.push((a, relation, b));
+        Self {
+            relations,
+            ..self
+        }
+    }
+
+    /// Runs search on `eaf`. Time values must be derived,
+    /// which is done automatically on deserialization.
+    ///
+    /// Returns all combinations of annotations that satisfy
+    /// every layer and relation.
+    pub fn run<'a>(&self, eaf: &'a Eaf) -> Result<Vec<SearchHit<'a>>, EafError> {
+        if self.layers.is_empty() {
+            return Err(EafError::NoData)
+        }
+        if let Some((a, _, b)) = self.relations.iter()
+            .find(|(a, _, b)| *a >= self.layers.len() || *b >= self.layers.len() || a == b)
+        {
+            return Err(EafError::SearchInvalid(format!("Invalid relation between layer {a} and layer {b}")))
+        }
+
+        let candidates: Vec<Vec<Candidate>> = self.layers.iter()
+            .map(|layer| Self::candidates(eaf, layer))
+            .collect();
+
+        let hits = candidates[0].par_iter()
+            .flat_map(|first| {
+                let mut hits: Vec<SearchHit> = Vec::new();
+                let mut assigned: Vec<&Candidate> = vec![first];
+                self.extend(eaf, &candidates, &mut assigned, &mut hits);
+                hits
+            })
+            .collect();
+
+        Ok(hits)
+    }
+
+    /// Returns annotations in selected tiers with matching values.
+    fn candidates<'a>(eaf: &'a Eaf, layer: &Layer) -> Vec<Candidate<'a>> {
+        let cv_values: HashSet<String> = match &layer.value {
+            ValueMatch::CvEntry(cve_id) => eaf.controlled_vocabularies.iter()
+                .flat_map(|cv| cv.iter())
+                .flat_map(|cv_type| match cv_type {
+                    CvType::CvEntryMl(entry) if &entry.cve_id == cve_id => entry.cve_values.iter()
+                        .map(|v| v.value.to_owned())
+                        .collect(),
+                    _ => Vec::new()
+                })
+                .collect(),
+            _ => HashSet::new(),
+        };
+
+        eaf.tiers.iter()
+            .filter(|t| layer.tiers.is_match(t))
+            .flat_map(|tier| tier.iter()
+                .enumerate()
+                .filter(|(_, a)| layer.value.is_match(a, &cv_values))
+                .map(move |(i, annotation)| Candidate {
+                    tier,
+                    hit: HitAnnotation { tier_id: &tier.tier_id, index: i + 1, annotation }
+                })
+            )
+            .collect()
+    }
+
+    /// Depth-first search for hits, assigning one candidate
+    /// per layer in layer order.
+    fn extend<'a, 'c>(
+        &self,
+        eaf: &'a Eaf,
+        candidates: &'c [Vec<Candidate<'a>>],
+        assigned: &mut Vec<&'c Candidate<'a>>,
+        hits: &mut Vec<SearchHit<'a>>
+    ) {
+        let layer = assigned.len();
+        // Check relations between the latest layer and those already assigned.
+        let satisfied = self.relations.iter()
+            .filter(|(a, _, b)| (*a == layer - 1 && *b < layer) || (*b == layer - 1 && *a < layer))
+            .all(|(a, relation, b)| Self::holds(eaf, assigned[*a], *relation, assigned[*b]));
+        if !satisfied {
+            return
+        }
+
+        if layer == candidates.len() {
+            hits.push(SearchHit {
+                annotations: assigned.iter().map(|c| c.hit).collect()
+            });
+            return
+        }
+
+        for candidate in candidates[layer].iter() {
+            // The same annotation can not be used for several layers.
+            if assigned.iter().any(|c| std::ptr::eq(c.hit.annotation, candidate.hit.annotation)) {
+                continue
+            }
+            assigned.push(candidate);
+            self.extend(eaf, candidates, assigned, hits);
+            assigned.pop();
+        }
+    }
+
+    /// Returns `true` if "`a` *relation* `b`" holds.
+    fn holds(eaf: &Eaf, a: &Candidate, relation: Relation, b: &Candidate) -> bool {
+        let span = |c: &Candidate| match c.hit.annotation.ts_val() {
+            (Some(start), Some(end)) => Some((start, end)),
+            _ => None
+        };
+
+        match relation {
+            Relation::ChildOf => Self::is_parent(eaf, b, a),
+            Relation::ParentOf => Self::is_parent(eaf, a, b),
+            Relation::Overlaps => match (span(a), span(b)) {
+                (Some((a1, a2)), Some((b1, b2))) => a1 < b2 && b1 < a2,
+                _ => false
+            },
+            Relation::Within(ms) => match (span(a), span(b)) {
+                (Some((a1, a2)), Some((b1, b2))) => (b1 - a2).max(a1 - b2).max(0) <= ms,
+                _ => false
+            },
+            Relation::Allen(allen) => match (span(a), span(b)) {
+                (Some(a), Some(b)) => allen.holds(a, b),
+                _ => false
+            },
+        }
+    }
+
+    /// Returns `true` if `parent` is the parent annotation of `child`.
+    fn is_parent(eaf: &Eaf, parent: &Candidate, child: &Candidate) -> bool {
+        if child.tier.parent_ref.as_deref() != Some(parent.tier.tier_id.as_str()) {
+            return false
+        }
+        let (p, c) = (parent.hit.annotation, child.hit.annotation);
+        match c.ref_id() {
+            Some(ref_id) => ref_id == p.id(),
+            // Time aligned annotation in referred tier.
+            None => match (p.ts_val(), c.ts_val()) {
+                ((Some(p1), Some(p2)), (Some(c1), Some(c2))) => p1 <= c1 && c2 <= p2,
+                // Unaligned time slots, compare time slot references.
+                _ => match (p.ts_ref(), c.ts_ref()) {
+                    (Some((p1, p2)), Some((c1, c2))) => {
+                        let (p1, p2) = (eaf.ts_val(&p1), eaf.ts_val(&p2));
+                        let (c1, c2) = (eaf.ts_val(&c1), eaf.ts_val(&c2));
+                        matches!((p1, p2, c1, c2), (Some(p1), Some(p2), Some(c1), Some(c2)) if p1 <= c1 && c2 <= p2)
+                    },
+                    _ => false
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eaf::test_util::{TestEaf, aligned, referred};
+
+    /// Main tier `A` (participant "Anna") with a symbolically associated tier `G`,
+    /// and main tier `B`.
+    fn eaf() -> Eaf {
+        TestEaf::new()
+            .time_slots(&[0, 1000, 2000, 1500, 2500])
+            .tier("A", "default-lt", None, &[
+                aligned("a1", "ts1", "ts2", "banana"),
+                aligned("a2", "ts2", "ts3", "bandana"),
+            ])
+            .participant("Anna")
+            .linguistic_type("gloss", Some("Symbolic_Association"))
+            .tier("G", "gloss", Some("A"), &[
+                referred("a3", "a1", "N"),
+                referred("a4", "a2", "V"),
+            ])
+            .tier("B", "default-lt", None, &[aligned("a5", "ts4", "ts5", "point")])
+            .eaf()
+    }
+
+    /// Returns annotation IDs for each hit.
+    fn ids(hits: &[SearchHit]) -> Vec<Vec<String>> {
+        let mut ids: Vec<Vec<String>> = hits.iter()
+            .map(|h| h.annotations.iter().map(|a| a.annotation.id().to_owned()).collect())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn single_layer() {
+        let eaf = eaf();
+        let search = Search::new()
+            .layer(TierSelector::Participant("Anna".to_owned()), ValueMatch::regex("^ban").unwrap());
+        let hits = eaf.search(&search).unwrap();
+        assert_eq!(ids(&hits), vec![vec!["a1"], vec!["a2"]]);
+
+        let search = Search::new()
+            .layer(TierSelector::Any, ValueMatch::exact("BANDANA", true));
+        let hits = eaf.search(&search).unwrap();
+        assert_eq!(ids(&hits), vec![vec!["a2"]]);
+        assert_eq!(hits[0].get(0).unwrap().tier_id, "A");
+        // Annotation index starts on 1
+        assert_eq!(hits[0].get(0).unwrap().index, 2);
+    }
+
+    #[test]
+    fn parent_and_overlap() {
+        let eaf = eaf();
+        // Token on A, whose gloss is "V", and which overlaps an annotation on B.
+        let search = Search::new()
+            .layer(TierSelector::Id("A".to_owned()), ValueMatch::substring("an", false))
+            .layer(TierSelector::LinguisticType("gloss".to_owned()), ValueMatch::exact("V", false))
+            .layer(TierSelector::Id("B".to_owned()), ValueMatch::Any)
+            .relation(0, Relation::ParentOf, 1)
+            .relation(0, Relation::Overlaps, 2);
+        assert_eq!(ids(&eaf.search(&search).unwrap()), vec![vec!["a2", "a4", "a5"]]);
+
+        let search = Search::new()
+            .layer(TierSelector::Id("G".to_owned()), ValueMatch::Any)
+            .layer(TierSelector::Id("A".to_owned()), ValueMatch::Any)
+            .relation(0, Relation::ChildOf, 1);
+        assert_eq!(ids(&eaf.search(&search).unwrap()), vec![vec!["a3", "a1"], vec!["a4", "a2"]]);
+    }
+
+    #[test]
+    fn time_relations() {
+        let eaf = eaf();
+        let search = |relation: Relation| {
+            let search = Search::new()
+                .layer(TierSelector::Id("A".to_owned()), ValueMatch::Any)
+                .layer(TierSelector::Id("B".to_owned()), ValueMatch::Any)
+                .relation(0, relation, 1);
+            ids(&eaf.search(&search).unwrap())
+        };
+        assert_eq!(search(Relation::Within(500)), vec![vec!["a1", "a5"], vec!["a2", "a5"]]);
+        assert_eq!(search(Relation::Within(499)), vec![vec!["a2", "a5"]]);
+        assert_eq!(search(Relation::Allen(AllenRelation::Before)), vec![vec!["a1", "a5"]]);
+        assert_eq!(search(Relation::Allen(AllenRelation::Overlaps)), vec![vec!["a2", "a5"]]);
+        assert!(search(Relation::Allen(AllenRelation::During)).is_empty());
+    }
+
+    #[test]
+    fn allen_relations() {
+        use AllenRelation::*;
+        let b = (10, 20);
+        let cases = [
+            (Before, (0, 5)), (After, (25, 30)), (Meets, (0, 10)), (MetBy, (20, 30)),
+            (Overlaps, (5, 15)), (OverlappedBy, (15, 25)), (Starts, (10, 15)),
+            (StartedBy, (10, 25)), (During, (12, 18)), (Contains, (5, 25)),
+            (Finishes, (15, 20)), (FinishedBy, (5, 20)), (Equals, (10, 20)),
+        ];
+        for (relation, a) in cases {
+            let holding: Vec<AllenRelation> = cases.iter()
+                .map(|(r, _)| *r)
+                .filter(|r| r.holds(a, b))
+                .collect();
+            assert_eq!(holding, vec![relation], "{a:?} {relation:?} {b:?}");
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let eaf = eaf();
+        assert!(eaf.search(&Search::new()).is_err());
+        let search = Search::new()
+            .layer(TierSelector::Any, ValueMatch::Any)
+            .relation(0, Relation::Overlaps, 1);
+        assert!(matches!(eaf.search(&search), Err(EafError::SearchInvalid(_))));
+    }
+}
@@ -0,0 +1,598 @@
+//! Subtitles, for exporting a tier as captions
+//! in the [SubRip](https://en.wikipedia.org/wiki/SubRip) (`.srt`)
+//! or [WebVTT](https://www.w3.org/TR/webvtt1/) (`.vtt`) formats, and for importing
+//! existing subtitles as a main tier.
+//!
+//! On export, annotations are converted to cues according to `SubtitleOptions`:
+//! - Text is wrapped to a maximum line length (in graphemes).
+//! - Annotations that are too long in time or text are split into several cues
+//! at word boundaries. The time span is distributed according to grapheme count.
+//! - Annotations that are too short in time are merged with the following annotation
+//! if it fits and the gap between them is small enough,
+//! otherwise extended if this does not cause an overlap.
+//! - Cues never overlap, since many players do not handle this.
+//! Cues starting at the same time are merged.
+//! - Speakers are labelled with WebVTT voice tags. SRT has no markup for speakers,
+//! so these are not exported.
+//!
+//! On import, cue identifiers, settings and formatting tags are discarded.
+
+use std::{fs::File, io::Write, path::Path};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{Eaf, EafError};
+
+/// Subtitle format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubtitleFormat {
+    /// SubRip, `.srt`
+    Srt,
+    /// WebVTT, `.vtt`
+    WebVtt,
+}
+
+/// Options for converting annotations to subtitle cues.
+#[derive(Debug, Clone)]
+pub struct SubtitleOptions {
+    /// Label cues with the tier participant, if set.
+    /// WebVTT only, as a voice tag (`<v Speaker>`),
+    /// since SRT has no markup for speakers.
+    pub speaker: bool,
+    /// Maximum number of graphemes per line.
+    /// Lines are wrapped at word boundaries,
+    /// so a single word may still exceed this.
+    pub max_line_length: Option<usize>,
+    /// Maximum number of lines per cue.
+    pub max_lines: Option<usize>,
+    /// Minimum cue duration in milliseconds.
+    pub min_duration: Option<i64>,
+    /// Maximum cue duration in milliseconds.
+    pub max_duration: Option<i64>,
+    /// Maximum gap in milliseconds between a cue that is too short
+    /// and the following cue for these to be merged.
+    pub max_merge_gap: i64,
+}
+
+impl Default for SubtitleOptions {
+    /// Defaults to commonly used caption limits:
+    /// two lines of at most 42 graphemes,
+    /// shown for 1 to 7 seconds. Short cues are merged
+    /// across gaps of at most 500 milliseconds.
+    fn default() -> Self {
+        Self {
+            speaker: false,
+            max_line_length: Some(42),
+            max_lines: Some(2),
+            min_duration: Some(1000),
+            max_duration: Some(7000),
+            max_merge_gap: 500,
+        }
+    }
+}
+
+/// Subtitle cue. Times in milliseconds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cue {
+    pub start: i64,
+    pub end: i64,
+    /// Speaker label.
+    pub speaker: Option<String>,
+    /// Cue text, with lines separated by `\n`.
+    pub text: String,
+}
+
+/// Subtitles, as a list of cues.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Subtitles {
+    pub cues: Vec<Cue>,
+}
+
+impl Cue {
+    /// Returns cue duration in milliseconds.
+    pub fn duration(&self) -> i64 {
+        self.end - self.start
+    }
+
+    /// Returns cue text as a single line.
+    fn flat_text(&self) -> String {
+        self.text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// Serializes cue for the specified format,
+    /// where `index` is the cue's position (starting on 1).
+    fn to_string(&self, index: usize, format: SubtitleFormat) -> String {
+        let timing = format!("{} --> {}", timestamp(self.start, format), timestamp(self.end, format));
+        let text = match (&self.speaker, format) {
+            (_, SubtitleFormat::Srt) => self.text.to_owned(),
+            (Some(s), SubtitleFormat::WebVtt) => format!("<v {s}>{}", escape_vtt(&self.text)),
+            (None, SubtitleFormat::WebVtt) => escape_vtt(&self.text),
+        };
+        match format {
+            SubtitleFormat::Srt => format!("{index}\n{timing}\n{text}\n"),
+            SubtitleFormat::WebVtt => format!("{timing}\n{text}\n"),
+        }
+    }
+}
+
+/// Formats milliseconds as `HH:MM:SS,mmm` (SRT)
+/// or `HH:MM:SS.mmm` (WebVTT).
+fn timestamp(ms: i64, format: SubtitleFormat) -> String {
+    let ms = ms.max(0);
+    let sep = match format {
+        SubtitleFormat::Srt => ',',
+        SubtitleFormat::WebVtt => '.',
+    };
+    format!(
+        "{:02}:{:02}:{:02}{sep}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+/// Parses `HH:MM:SS,mmm`, `HH:MM:SS.mmm`, or `MM:SS.mmm` (WebVTT)
+/// to milliseconds.
+fn parse_timestamp(ts: &str) -> Result<i64, EafError> {
+    let invalid = || EafError::SubtitleInvalid(format!("Invalid timestamp '{ts}'"));
+
+    let (hms, frac) = ts.trim()
+        .split_once([',', '.'])
+        .ok_or_else(invalid)?;
+    let ms: i64 = match frac.len() {
+        3 => frac.parse().map_err(|_| invalid())?,
+        _ => return Err(invalid()),
+    };
+    let units = hms.split(':')
+        .map(|u| u.parse::<i64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, EafError>>()?;
+    let (h, m, s) = match units[..] {
+        [h, m, s] => (h, m, s),
+        [m, s] => (0, m, s),
+        _ => return Err(invalid()),
+    };
+
+    Ok(((h * 60 + m) * 60 + s) * 1000 + ms)
+}
+
+/// Escapes characters with special meaning in WebVTT cue text.
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Removes formatting tags, e.g. `<i>`, `<b>`, `<c.yellow>`,
+/// and unescapes WebVTT character references.
+/// Returns text and speaker, if a WebVTT voice tag (`<v Speaker>`) was found.
+fn strip_tags(text: &str) -> (String, Option<String>) {
+    let mut stripped = String::new();
+    let mut speaker: Option<String> = None;
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        stripped.push_str(&rest[..start]);
+        match rest[start..].find('>') {
+            Some(end) => {
+                let tag = &rest[start + 1 .. start + end];
+                if let Some(v) = tag.strip_prefix('v').filter(|v| v.starts_with([' ', '.'])) {
+                    // "<v.loud Speaker>" -> "Speaker"
+                    let name = v.split_once(' ').map(|(_, n)| n.trim()).unwrap_or("");
+                    if speaker.is_none() && !name.is_empty() {
+                        speaker = Some(name.to_owned());
+                    }
+                }
+                rest = &rest[start + end + 1 ..];
+            },
+            None => {
+                // Unclosed '<', keep as text
+                stripped.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    stripped.push_str(rest);
+
+    let stripped = stripped
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&amp;", "&");
+
+    (stripped, speaker)
+}
+
+/// Returns number of graphemes in `text`.
+fn len(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+/// Wraps text at word boundaries into lines of at most
+/// `max` graphemes. Words longer than `max` are kept intact.
+fn wrap(text: &str, max: Option<usize>) -> Vec<String> {
+    let Some(max) = max else {
+        return vec![text.split_whitespace().collect::<Vec<_>>().join(" ")]
+    };
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && len(&line) + 1 + len(word) > max {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+impl Subtitles {
+    /// Generates subtitles from the annotations in the specified tier.
+    ///
+    /// Annotations must have time values set
+    /// (`Eaf::derive()` is run on deserialization),
+    /// which means referred tiers can also be exported.
+    /// Empty annotations are ignored.
+    pub fn from_tier(
+        eaf: &Eaf,
+        tier_id: &str,
+        options: &SubtitleOptions
+    ) -> Result<Self, EafError> {
+        let tier = eaf.get_tier(tier_id)
+            .ok_or_else(|| EafError::TierIdInvalid(tier_id.to_owned()))?;
+
+        let speaker = match options.speaker {
+            true => tier.participant.to_owned().filter(|p| !p.is_empty()),
+            false => None,
+        };
+
+        let mut cues = tier.iter()
+            .filter(|a| !a.to_str().trim().is_empty())
+            .map(|a| match a.ts_val() {
+                (Some(start), Some(end)) => Ok(Cue {
+                    start,
+                    end,
+                    speaker: speaker.to_owned(),
+                    text: a.to_str().to_owned(),
+                }),
+                _ => Err(EafError::TimeslotValMissing(a.id().to_owned()))
+            })
+            .collect::<Result<Vec<_>, EafError>>()?;
+        cues.sort_by_key(|c| (c.start, c.end));
+
+        let mut subtitles = Self { cues };
+        subtitles.normalize(options);
+
+        Ok(subtitles)
+    }
+
+    /// Adjusts cues to fit the limits in `options`.
+    /// Short cues are merged or extended, long cues are split,
+    /// text is wrapped, and overlaps are removed.
+    /// Cues starting at the same time are merged.
+    pub fn normalize(&mut self, options: &SubtitleOptions) {
+        let max_chars = options.max_line_length
+            .map(|l| l * options.max_lines.unwrap_or(1));
+        let fits = |text: &str, duration: i64| {
+            max_chars.map(|m| len(text) <= m).unwrap_or(true)
+                && options.max_duration.map(|d| duration <= d).unwrap_or(true)
+        };
+
+        self.cues.sort_by_key(|c| (c.start, c.end));
+
+        // Merge cues that are too short with the next one,
+        // if the gap between them is small enough and the result fits.
+        let mut merged: Vec<Cue> = Vec::new();
+        for cue in self.cues.drain(..) {
+            if let Some(prev) = merged.last_mut() {
+                let too_short = options.min_duration
+                    .map(|d| prev.duration() < d)
+                    .unwrap_or(false);
+                let text = format!("{} {}", prev.flat_text(), cue.flat_text());
+                if too_short
+                    && cue.start - prev.end <= options.max_merge_gap
+                    && prev.speaker == cue.speaker
+                    && fits(&text, cue.end.max(prev.end) - prev.start)
+                {
+                    prev.end = cue.end.max(prev.end);
+                    prev.text = text;
+                    continue;
+                }
+            }
+            merged.push(cue);
+        }
+
+        // Split cues with too much text or too long duration
+        // into parts at word boundaries.
+        let mut split: Vec<Cue> = Vec::new();
+        for cue in merged.into_iter() {
+            let text = cue.flat_text();
+            let mut parts = 1;
+            if let Some(max) = max_chars {
+                parts = parts.max(len(&text).div_ceil(max.max(1)));
+            }
+            if let Some(max) = options.max_duration {
+                parts = parts.max((cue.duration() as usize).div_ceil(max.max(1) as usize));
+            }
+            let words: Vec<&str> = text.split_whitespace().collect();
+            let parts = parts.clamp(1, words.len().max(1));
+
+            if parts == 1 {
+                split.push(cue);
+                continue;
+            }
+
+            // Distribute words evenly by grapheme count, then time by grapheme count.
+            let total = len(&text).max(1);
+            let target = total.div_ceil(parts);
+            let mut chunks: Vec<Vec<&str>> = vec![Vec::new()];
+            for word in words.iter() {
+                let chunk_len = len(&chunks.last().unwrap().join(" "));
+                if chunk_len > 0
+                    && chunk_len + 1 + len(word) > target
+                    && chunks.len() < parts
+                {
+                    chunks.push(Vec::new());
+                }
+                chunks.last_mut().unwrap().push(word);
+            }
+
+            let mut start = cue.start;
+            let mut offset = 0;
+            let n = chunks.len();
+            for (i, chunk) in chunks.into_iter().enumerate() {
+                let chunk = chunk.join(" ");
+                offset += len(&chunk) + 1;
+                let end = match i + 1 == n {
+                    true => cue.end,
+                    false => cue.start + (cue.duration() as f64 * (offset.min(total) as f64 / total as f64)).round() as i64,
+                };
+                split.push(Cue {
+                    start,
+                    end,
+                    speaker: cue.speaker.to_owned(),
+                    text: chunk,
+                });
+                start = end;
+            }
+        }
+
+        // Merge cues starting at the same time, since these can not
+        // be separated by truncating. Speakers are kept if equal.
+        let mut split = split.into_iter()
+            .fold(Vec::<Cue>::new(), |mut cues, cue| {
+                match cues.last_mut() {
+                    Some(prev) if prev.start == cue.start => {
+                        prev.end = prev.end.max(cue.end);
+                        prev.text = format!("{} {}", prev.flat_text(), cue.flat_text());
+                        if prev.speaker != cue.speaker {
+                            prev.speaker = None;
+                        }
+                    },
+                    _ => cues.push(cue),
+                }
+                cues
+            });
+
+        // Extend cues that are still too short, without causing overlaps,
+        // then truncate overlapping cues and wrap text.
+        let next_starts: Vec<Option<i64>> = split.iter()
+            .skip(1)
+            .map(|c| Some(c.start))
+            .chain(std::iter::once(None))
+            .collect();
+        for (cue, next) in split.iter_mut().zip(next_starts) {
+            if let Some(min) = options.min_duration && cue.duration() < min {
+                cue.end = cue.start + min;
+            }
+            if let Some(next) = next && cue.end > next {
+                cue.end = next;
+            }
+            cue.text = wrap(&cue.text, options.max_line_length).join("\n");
+        }
+
+        self.cues = split;
+    }
+
+    /// Serializes subtitles in the specified format.
+    pub fn to_string(&self, format: SubtitleFormat) -> String {
+        let cues = self.cues.iter()
+            .enumerate()
+            .map(|(i, c)| c.to_string(i + 1, format))
+            .collect::<Vec<_>>()
+            .join("\n");
+        match format {
+            SubtitleFormat::Srt => cues,
+            SubtitleFormat::WebVtt => format!("WEBVTT\n\n{cues}"),
+        }
+    }
+
+    /// Serializes and writes subtitles to disk.
+    pub fn write(&self, path: &Path, format: SubtitleFormat) -> Result<(), EafError> {
+        let mut outfile = File::create(&path)?;
+        outfile.write_all(self.to_string(format).as_bytes()).map_err(|e| EafError::IOError(e))
+    }
+
+    /// Reads and parses a subtitle file. The format is determined from
+    /// the content (WebVTT files start with `WEBVTT`).
+    pub fn read(path: &Path) -> Result<Self, EafError> {
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(&content)
+    }
+
+    /// Parses SRT or WebVTT content. The format is determined from
+    /// the content (WebVTT files start with `WEBVTT`).
+    pub fn parse(content: &str) -> Result<Self, EafError> {
+        let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+        let is_vtt = content.starts_with("WEBVTT");
+
+        let mut cues: Vec<Cue> = Vec::new();
+        for (i, block) in content.split("\n\n").enumerate() {
+            let lines: Vec<&str> = block.lines()
+                .filter(|l| !l.trim().is_empty())
+                .collect();
+            if lines.is_empty() {
+                continue;
+            }
+            // WebVTT header and non-cue blocks
+            if is_vtt && (i == 0 || ["NOTE", "STYLE", "REGION"].iter().any(|b| lines[0].starts_with(b))) {
+                continue;
+            }
+
+            // Skip cue identifier/SRT index, if present.
+            let Some(t) = lines.iter().position(|l| l.contains("-->")) else {
+                return Err(EafError::SubtitleInvalid(format!("No cue timing in '{block}'")))
+            };
+            let (start, end) = lines[t].split_once("-->").unwrap();
+            // WebVTT cue settings, e.g. "position:10%", follow the end time.
+            let end = end.split_whitespace().next().unwrap_or("");
+
+            let (text, speaker) = strip_tags(&lines[t+1..].join("\n"));
+            cues.push(Cue {
+                start: parse_timestamp(start)?,
+                end: parse_timestamp(end)?,
+                speaker,
+                text,
+            });
+        }
+
+        Ok(Self { cues })
+    }
+
+    /// Generates an `Eaf` with a single main tier
+    /// with one annotation per cue. Multi-line cue text is joined
+    /// with a space. If all cues have the same speaker
+    /// (WebVTT voice tag), the tier participant is set to this.
+    ///
+    /// Since annotations in a tier may not overlap,
+    /// cues that overlap the next cue are truncated.
+    pub fn to_eaf(&self, tier_id: &str) -> Result<Eaf, EafError> {
+        let mut values: Vec<(String, i64, i64)> = self.cues.iter()
+            .map(|c| (c.flat_text(), c.start, c.end))
+            .collect();
+        values.sort_by_key(|(_, start, end)| (*start, *end));
+        for i in 1..values.len() {
+            let next_start = values[i].1;
+            if values[i-1].2 > next_start {
+                values[i-1].2 = next_start;
+            }
+        }
+
+        let mut eaf = Eaf::from_values(&values, Some(tier_id))?;
+
+        if let Some(speaker) = self.cues.first().and_then(|c| c.speaker.to_owned())
+            && self.cues.iter().all(|c| c.speaker.as_ref() == Some(&speaker))
+            && let Some(tier) = eaf.get_tier_mut(tier_id)
+        {
+            tier.participant = Some(speaker);
+        }
+
+        Ok(eaf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(start: i64, end: i64, text: &str) -> Cue {
+        Cue { start, end, speaker: None, text: text.to_owned() }
+    }
+
+    fn unlimited() -> SubtitleOptions {
+        SubtitleOptions {
+            speaker: false,
+            max_line_length: None,
+            max_lines: None,
+            min_duration: None,
+            max_duration: None,
+            max_merge_gap: 0,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut eaf = Eaf::from_values(&[
+            ("one two".to_owned(), 1000, 2500),
+            ("a < b & c".to_owned(), 3000, 4200),
+        ], Some("tx")).unwrap();
+        eaf.get_tier_mut("tx").unwrap().participant = Some("Anna".to_owned());
+
+        let options = SubtitleOptions { speaker: true, ..unlimited() };
+        for (format, speaker) in [(SubtitleFormat::Srt, None), (SubtitleFormat::WebVtt, Some("Anna"))] {
+            let content = eaf.to_subtitles("tx", format, &options).unwrap();
+            let subtitles = Subtitles::parse(&content).unwrap();
+            let speaker = speaker.map(|s| s.to_owned());
+            assert_eq!(subtitles.cues, vec![
+                Cue { start: 1000, end: 2500, speaker: speaker.to_owned(), text: "one two".to_owned() },
+                Cue { start: 3000, end: 4200, speaker: speaker.to_owned(), text: "a < b & c".to_owned() },
+            ]);
+
+            let eaf2 = subtitles.to_eaf("tx").unwrap();
+            assert_eq!(eaf2.get_tier("tx").unwrap().participant, speaker);
+            assert_eq!(eaf2.get_tier("tx").unwrap().values(), vec!["one two", "a < b & c"]);
+        }
+    }
+
+    #[test]
+    fn parse_vtt() {
+        let vtt = "WEBVTT\n\nNOTE a comment\n\nid-1\n01:02.500 --> 01:04.000 position:10%\n<v.loud Bo><i>hi</i> there\n";
+        let subtitles = Subtitles::parse(vtt).unwrap();
+        assert_eq!(subtitles.cues, vec![Cue {
+            start: 62500,
+            end: 64000,
+            speaker: Some("Bo".to_owned()),
+            text: "hi there".to_owned(),
+        }]);
+        assert!(Subtitles::parse("1\nno timing\n").is_err());
+    }
+
+    #[test]
+    fn merge_short_cues_within_gap() {
+        let options = SubtitleOptions { min_duration: Some(1000), max_merge_gap: 200, ..unlimited() };
+
+        let mut subtitles = Subtitles { cues: vec![cue(0, 400, "a"), cue(500, 1500, "b")] };
+        subtitles.normalize(&options);
+        assert_eq!(subtitles.cues, vec![cue(0, 1500, "a b")]);
+
+        // Gap too large: extended instead of merged.
+        let mut subtitles = Subtitles { cues: vec![cue(0, 400, "a"), cue(5000, 6000, "b")] };
+        subtitles.normalize(&options);
+        assert_eq!(subtitles.cues, vec![cue(0, 1000, "a"), cue(5000, 6000, "b")]);
+    }
+
+    #[test]
+    fn merge_same_start() {
+        let mut subtitles = Subtitles { cues: vec![cue(2000, 3000, "c"), cue(0, 1000, "a"), cue(0, 1500, "b")] };
+        subtitles.normalize(&unlimited());
+        assert_eq!(subtitles.cues, vec![cue(0, 1500, "a b"), cue(2000, 3000, "c")]);
+    }
+
+    #[test]
+    fn split_and_wrap() {
+        let options = SubtitleOptions { max_line_length: Some(5), max_lines: Some(2), ..unlimited() };
+        let mut subtitles = Subtitles { cues: vec![cue(0, 2000, "aaaa bbbb cccc dddd")] };
+        subtitles.normalize(&options);
+        assert_eq!(subtitles.cues.len(), 2);
+        assert_eq!(subtitles.cues[0].text, "aaaa\nbbbb");
+        assert_eq!(subtitles.cues[1].text, "cccc\ndddd");
+        assert_eq!(subtitles.cues[0].start, 0);
+        assert_eq!(subtitles.cues[0].end, subtitles.cues[1].start);
+        assert_eq!(subtitles.cues[1].end, 2000);
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(3_723_004, SubtitleFormat::Srt), "01:02:03,004");
+        assert_eq!(timestamp(3_723_004, SubtitleFormat::WebVtt), "01:02:03.004");
+        assert_eq!(parse_timestamp("01:02:03,004").unwrap(), 3_723_004);
+        assert!(parse_timestamp("01:02:03,4").is_err());
+    }
+}
@@ -0,0 +1,1076 @@
+//! Tab-delimited text export, similar to ELAN's "Export as Tab-delimited Text",
+//! and CSV/TSV import with a column mapping.
+//!
+//! Two layouts are supported:
+//! - One row per annotation (default), with the columns specified in `TabOptions::columns`.
+//! - One column per tier (`TabOptions::tier_columns`), with one row per main annotation.
+//!   The columns in `TabOptions::columns` describe the main annotation, followed by
+//!   one column per exported tier, containing the values of the annotations in that tier
+//!   that belong to the main annotation. Cells for tiers in other tier hierarchies are empty.
+//!
+//! Example:
+//! ```ignore
+//! use eaf_rs::{Eaf, TabColumn, TabOptions, TimeFormat};
+//! let eaf = Eaf::read(Path::new("my.eaf"))?;
+//! let options = TabOptions {
+//!     columns: vec![TabColumn::TierId, TabColumn::Begin(TimeFormat::Seconds), TabColumn::Value],
+//!     tiers: Some(vec!["speaker1".to_owned()]),
+//!     ..TabOptions::default()
+//! };
+//! eaf.write_tab_delimited(Path::new("my.txt"), &options)?;
+//! ```
+//!
+//! On import, each row becomes an annotation, with columns mapped according to
+//! `CsvMapping`. Rows that specify a parent tier become referred tiers, see
+//! `CsvMapping::to_eaf()`. Rows that can not be imported are skipped and
+//! reported as `RowError`, with the line number in the CSV file.
+//!
+//! Example:
+//! ```ignore
+//! use eaf_rs::{CsvMapping, Eaf, TimeFormat};
+//! let mapping = CsvMapping {
+//!     parent: Some("parent".to_owned()),
+//!     time_format: TimeFormat::Seconds,
+//!     ..CsvMapping::default()
+//! };
+//! let (eaf, errors) = Eaf::from_csv(Path::new("my.csv"), &mapping)?;
+//! for err in errors.iter() {
+//!     println!("{err}");
+//! }
+//! ```
+
+use std::{collections::HashMap, fmt::Display, fs::File, io::Write, path::Path};
+
+use crate::{
+    Annotation,
+    Constraint,
+    Eaf,
+    EafError,
+    Header,
+    LinguisticType,
+    StereoType,
+    Tier,
+    TimeOrder,
+    TimeSlot,
+};
+
+/// Time format for time columns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeFormat {
+    /// Milliseconds, e.g. `83456`.
+    Milliseconds,
+    /// Seconds with millisecond precision, e.g. `83.456`.
+    Seconds,
+    /// `hh:mm:ss.mmm`, e.g. `00:01:23.456`.
+    Timestamp,
+}
+
+impl TimeFormat {
+    /// Formats `ms` milliseconds.
+    pub fn format(&self, ms: i64) -> String {
+        match self {
+            TimeFormat::Milliseconds => ms.to_string(),
+            TimeFormat::Seconds => format!("{:.3}", ms as f64 / 1000.),
+            TimeFormat::Timestamp => {
+                let sign = if ms < 0 { "-" } else { "" };
+                let ms = ms.abs();
+                format!(
+                    "{sign}{:02}:{:02}:{:02}.{:03}",
+                    ms / 3_600_000,
+                    ms / 60_000 % 60,
+                    ms / 1000 % 60,
+                    ms % 1000
+                )
+            },
+        }
+    }
+
+    /// Parses a time value in this format to milliseconds.
+    /// Timestamps may use `,` as decimal separator, and may omit hours.
+    /// Returns `None` if the value can not be parsed.
+    pub fn parse(&self, value: &str) -> Option<i64> {
+        let value = value.trim();
+        let ms = match self {
+            TimeFormat::Milliseconds => value.parse::<f64>().ok()?,
+            TimeFormat::Seconds => value.parse::<f64>().ok()? * 1000.,
+            TimeFormat::Timestamp => {
+                let (sign, value) = match value.strip_prefix('-') {
+                    Some(v) => (-1., v),
+                    None => (1., value),
+                };
+                let units: Vec<&str> = value.split(':').collect();
+                let (h, m, s) = match units[..] {
+                    [h, m, s] => (h.parse::<u64>().ok()?, m.parse::<u64>().ok()?, s),
+                    [m, s] => (0, m.parse::<u64>().ok()?, s),
+                    _ => return None,
+                };
+                let s = s.replace(',', ".").parse::<f64>().ok()?;
+                sign * (((h * 60 + m) * 60) as f64 + s) * 1000.
+            },
+        };
+        ms.is_finite().then(|| ms.round() as i64)
+    }
+
+    /// Column header suffix, as used by ELAN.
+    fn label(&self) -> &str {
+        match self {
+            TimeFormat::Milliseconds => "msec",
+            TimeFormat::Seconds => "ss.msec",
+            TimeFormat::Timestamp => "hh:mm:ss.ms",
+        }
+    }
+}
+
+/// Column in tab-delimited export.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TabColumn {
+    TierId,
+    Participant,
+    Annotator,
+    /// Annotation start time.
+    Begin(TimeFormat),
+    /// Annotation end time.
+    End(TimeFormat),
+    /// Annotation duration.
+    Duration(TimeFormat),
+    /// Annotation value.
+    Value,
+    /// Value of the parent annotation.
+    /// Empty for main tier annotations.
+    Parent,
+    /// Values of the annotations in the specified tier that belong to the
+    /// annotation or its closest ancestor, e.g. the parent annotation in a parent
+    /// tier, the child annotations in a child tier, or the translation of
+    /// the main annotation in another child tier of the main tier.
+    /// Values are separated by a space, and empty for unrelated tiers.
+    Tier(String),
+}
+
+impl TabColumn {
+    /// Column header, as used by ELAN where applicable.
+    fn header(&self) -> String {
+        match self {
+            TabColumn::TierId => "Tier".to_owned(),
+            TabColumn::Participant => "Participant".to_owned(),
+            TabColumn::Annotator => "Annotator".to_owned(),
+            TabColumn::Begin(f) => format!("Begin Time - {}", f.label()),
+            TabColumn::End(f) => format!("End Time - {}", f.label()),
+            TabColumn::Duration(f) => format!("Duration - {}", f.label()),
+            TabColumn::Value => "Annotation".to_owned(),
+            TabColumn::Parent => "Parent".to_owned(),
+            TabColumn::Tier(tier_id) => tier_id.to_owned(),
+        }
+    }
+}
+
+/// Options for tab-delimited export.
+#[derive(Debug, Clone)]
+pub struct TabOptions {
+    /// Columns, in order.
+    pub columns: Vec<TabColumn>,
+    /// One row per main annotation, followed by
+    /// one column per exported tier.
+    pub tier_columns: bool,
+    /// Tiers to export. All tiers if `None`.
+    pub tiers: Option<Vec<String>>,
+    /// Only export annotations that overlap the time span
+    /// `(start, end)` in milliseconds. Both are optional.
+    pub time_range: (Option<i64>, Option<i64>),
+    /// Include column headers as the first row.
+    pub header: bool,
+    /// Column delimiter.
+    pub delimiter: u8,
+}
+
+impl Default for TabOptions {
+    /// Defaults to tier ID, begin time, end time, duration
+    /// (all in milliseconds), and annotation value,
+    /// tab-separated with column headers.
+    fn default() -> Self {
+        Self {
+            columns: vec![
+                TabColumn::TierId,
+                TabColumn::Begin(TimeFormat::Milliseconds),
+                TabColumn::End(TimeFormat::Milliseconds),
+                TabColumn::Duration(TimeFormat::Milliseconds),
+                TabColumn::Value,
+            ],
+            tier_columns: false,
+            tiers: None,
+            time_range: (None, None),
+            header: true,
+            delimiter: b'\t',
+        }
+    }
+}
+
+/// Table with an optional header row.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    pub header: Option<Vec<String>>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Annotation lookup for parent and child tier values.
+struct Relations<'a> {
+    /// Annotation ID -> parent annotation. For time alignable annotations in
+    /// referred tiers (`Included_In`, `Time_Subdivision`), the parent is the
+    /// annotation in the parent tier that contains the annotation's start time.
+    parents: HashMap<&'a str, &'a Annotation>,
+    /// (Tier ID, ancestor annotation ID) -> values in that tier that
+    /// descend from the ancestor, including the ancestor itself.
+    descendants: HashMap<(&'a str, &'a str), Vec<&'a str>>,
+}
+
+impl<'a> Relations<'a> {
+    fn new(eaf: &'a Eaf) -> Self {
+        let annotations: HashMap<&str, &Annotation> = eaf.tiers.iter()
+            .flat_map(|t| t.iter().map(|a| (a.id(), a)))
+            .collect();
+
+        let mut parents: HashMap<&str, &Annotation> = HashMap::new();
+        for tier in eaf.tiers.iter() {
+            // Parent tier annotations with time values, sorted on start time
+            let mut spans: Vec<(i64, i64, &Annotation)> = tier.parent_ref.as_deref()
+                .and_then(|p| eaf.get_tier(p))
+                .map(|pt| pt.iter()
+                    .filter_map(|a| match a.ts_val() {
+                        (Some(s), Some(e)) => Some((s, e, a)),
+                        _ => None,
+                    })
+                    .collect())
+                .unwrap_or_default();
+            spans.sort_by_key(|(s, ..)| *s);
+            for annotation in tier.iter() {
+                let parent = match (annotation.ref_id(), annotation.ts_val().0) {
+                    (Some(ref_id), _) => annotations.get(ref_id).copied(),
+                    (None, Some(start)) => spans[..spans.partition_point(|(s, ..)| *s <= start)]
+                        .last()
+                        .filter(|(_, e, _)| start < *e)
+                        .map(|(.., a)| *a),
+                    (None, None) => None,
+                };
+                if let Some(p) = parent {
+                    parents.insert(annotation.id(), p);
+                }
+            }
+        }
+
+        let mut descendants: HashMap<(&str, &str), Vec<&str>> = HashMap::new();
+        for tier in eaf.tiers.iter() {
+            let mut sorted: Vec<&Annotation> = tier.iter().collect();
+            sorted.sort_by_key(|a| a.ts_val().0);
+            for annotation in sorted {
+                let mut current = Some(annotation);
+                // Limit depth, in case of circular references.
+                for _ in 0..=eaf.tiers.len() {
+                    let Some(a) = current else {
+                        break;
+                    };
+                    descendants.entry((tier.tier_id.as_str(), a.id()))
+                        .or_default()
+                        .push(annotation.to_str());
+                    current = parents.get(a.id()).copied();
+                }
+            }
+        }
+
+        Self { parents, descendants }
+    }
+
+    fn parent(&self, annotation: &Annotation) -> Option<&'a Annotation> {
+        self.parents.get(annotation.id()).copied()
+    }
+
+    /// Values in `tier_id` related to `annotation`, see `TabColumn::Tier`.
+    fn values(&self, tier_id: &str, annotation: &'a Annotation) -> String {
+        let mut current = Some(annotation);
+        while let Some(a) = current {
+            if let Some(values) = self.descendants.get(&(tier_id, a.id())) {
+                return values.join(" ")
+            }
+            current = self.parent(a);
+        }
+        String::new()
+    }
+}
+
+/// Returns `true` if annotation overlaps the time range.
+/// Annotations without time values are always included.
+fn in_range(annotation: &Annotation, range: (Option<i64>, Option<i64>)) -> bool {
+    let (start, end) = annotation.ts_val();
+    let after_start = match (range.0, end) {
+        (Some(r), Some(e)) => e > r,
+        _ => true,
+    };
+    let before_end = match (range.1, start) {
+        (Some(r), Some(s)) => s < r,
+        _ => true,
+    };
+    after_start && before_end
+}
+
+impl Table {
+    /// Generates table from `eaf` according to `options`.
+    /// Rows are ordered by tier, then start time.
+    ///
+    /// Returns error if a tier in `TabOptions::tiers` or
+    /// `TabColumn::Tier` does not exist.
+    pub fn from_eaf(eaf: &Eaf, options: &TabOptions) -> Result<Self, EafError> {
+        let get_tier = |id: &str| eaf.get_tier(id).ok_or_else(|| EafError::TierIdInvalid(id.to_owned()));
+
+        let tiers: Vec<&Tier> = match &options.tiers {
+            Some(ids) => ids.iter().map(|id| get_tier(id)).collect::<Result<_, _>>()?,
+            None => eaf.tiers.iter().collect(),
+        };
+        let column_tiers: HashMap<&str, &Tier> = options.columns.iter()
+            .filter_map(|c| match c {
+                TabColumn::Tier(id) => Some(id.as_str()),
+                _ => None,
+            })
+            .map(|id| get_tier(id).map(|t| (id, t)))
+            .collect::<Result<_, _>>()?;
+
+        let relations = Relations::new(eaf);
+
+        let cell = |column: &TabColumn, tier: &Tier, annotation: &Annotation| -> String {
+            let (start, end) = annotation.ts_val();
+            match column {
+                TabColumn::TierId => tier.tier_id.to_owned(),
+                TabColumn::Participant => tier.participant.to_owned().unwrap_or_default(),
+                TabColumn::Annotator => tier.annotator.to_owned().unwrap_or_default(),
+                TabColumn::Begin(f) => start.map(|t| f.format(t)).unwrap_or_default(),
+                TabColumn::End(f) => end.map(|t| f.format(t)).unwrap_or_default(),
+                TabColumn::Duration(f) => match (start, end) {
+                    (Some(s), Some(e)) => f.format(e - s),
+                    _ => String::new(),
+                },
+                TabColumn::Value => annotation.to_str().to_owned(),
+                TabColumn::Parent => relations.parent(annotation)
+                    .map(|a| a.to_str().to_owned())
+                    .unwrap_or_default(),
+                TabColumn::Tier(id) => column_tiers.get(id.as_str())
+                    .map(|t| relations.values(&t.tier_id, annotation))
+                    .unwrap_or_default(),
+            }
+        };
+
+        // Annotations sorted on start time, in the exported tiers or,
+        // for one column per tier, in the main tiers of the exported tiers.
+        let row_tiers: Vec<&Tier> = match options.tier_columns {
+            true => eaf.main_tiers()
+                .filter(|m| tiers.iter().any(|t| t.tier_id == m.tier_id || root_tier(eaf, t) == m.tier_id))
+                .collect(),
+            false => tiers.to_owned(),
+        };
+
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        for tier in row_tiers {
+            let mut annotations: Vec<&Annotation> = tier.iter()
+                .filter(|a| in_range(a, options.time_range))
+                .collect();
+            annotations.sort_by_key(|a| a.ts_val().0);
+
+            for annotation in annotations {
+                let mut row: Vec<String> = options.columns.iter()
+                    .map(|c| cell(c, tier, annotation))
+                    .collect();
+                if options.tier_columns {
+                    row.extend(tiers.iter().map(|t| relations.values(&t.tier_id, annotation)));
+                }
+                rows.push(row);
+            }
+        }
+
+        let header = options.header.then(|| {
+            let mut header: Vec<String> = options.columns.iter().map(|c| c.header()).collect();
+            if options.tier_columns {
+                header.extend(tiers.iter().map(|t| t.tier_id.to_owned()));
+            }
+            header
+        });
+
+        Ok(Self { header, rows })
+    }
+
+    /// Serializes table with the specified delimiter.
+    /// Values are only quoted if these contain the delimiter,
+    /// a quote, or a line break.
+    pub fn to_string(&self, delimiter: u8) -> Result<String, EafError> {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_writer(Vec::new());
+        if let Some(header) = &self.header {
+            writer.write_record(header)?;
+        }
+        for row in self.rows.iter() {
+            writer.write_record(row)?;
+        }
+        let bytes = writer.into_inner().map_err(|e| EafError::IOError(e.into_error()))?;
+        Ok(String::from_utf8_lossy(&bytes).to_string())
+    }
+
+    /// Serializes and writes table to disk. See `Table::to_string()`.
+    pub fn write(&self, path: &Path, delimiter: u8) -> Result<(), EafError> {
+        let mut outfile = File::create(&path)?;
+        outfile.write_all(self.to_string(delimiter)?.as_bytes()).map_err(|e| EafError::IOError(e))
+    }
+}
+
+/// Returns the ID of the main tier at the top of `tier`'s hierarchy.
+fn root_tier<'a>(eaf: &'a Eaf, tier: &'a Tier) -> &'a str {
+    let mut current = tier;
+    // Limit depth, in case of circular references.
+    for _ in 0..eaf.tiers.len() {
+        match current.parent_ref.as_deref().and_then(|p| eaf.get_tier(p)) {
+            Some(parent) => current = parent,
+            None => break,
+        }
+    }
+    &current.tier_id
+}
+
+/// Column mapping for CSV/TSV import. Columns are
+/// referred to by name, as specified in the header row.
+#[derive(Debug, Clone)]
+pub struct CsvMapping {
+    /// Tier ID column.
+    pub tier_id: String,
+    /// Annotation start time column.
+    pub start: String,
+    /// Annotation end time column.
+    pub end: String,
+    /// Format for start and end time values.
+    pub time_format: TimeFormat,
+    /// Annotation value column.
+    pub value: String,
+    /// Participant column. Optional.
+    pub participant: Option<String>,
+    /// Parent tier ID column. Optional.
+    /// Rows with a parent tier ID become annotations in a referred tier.
+    pub parent: Option<String>,
+    /// Column delimiter, e.g. `b','` for CSV or `b'\t'` for TSV.
+    pub delimiter: u8,
+}
+
+impl Default for CsvMapping {
+    /// Defaults to comma-separated columns `tier`, `start`, `end`,
+    /// and `value`, with times in milliseconds.
+    fn default() -> Self {
+        Self {
+            tier_id: "tier".to_owned(),
+            start: "start".to_owned(),
+            end: "end".to_owned(),
+            time_format: TimeFormat::Milliseconds,
+            value: "value".to_owned(),
+            participant: None,
+            parent: None,
+            delimiter: b',',
+        }
+    }
+}
+
+/// Row that could not be imported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowError {
+    /// Line number in the CSV file, starting on 1.
+    pub line: u64,
+    pub message: String,
+}
+
+impl Display for RowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Parsed CSV row.
+struct Row {
+    line: u64,
+    tier_id: String,
+    start: i64,
+    end: i64,
+    value: String,
+    participant: Option<String>,
+    parent: Option<String>,
+}
+
+impl Row {
+    fn error(&self, message: &str) -> RowError {
+        RowError { line: self.line, message: message.to_owned() }
+    }
+}
+
+/// Annotation ID, start time, end time.
+type Span = (String, i64, i64);
+
+/// Tier under construction on import.
+struct ImportTier {
+    tier_id: String,
+    participant: Option<String>,
+    parent: Option<String>,
+    rows: Vec<Row>,
+    /// Imported annotations.
+    spans: Vec<Span>,
+}
+
+impl CsvMapping {
+    /// Returns the index for `column` in `header`.
+    fn index(header: &csv::StringRecord, column: &str) -> Result<usize, EafError> {
+        header.iter()
+            .position(|h| h.trim() == column)
+            .ok_or_else(|| EafError::CsvInvalid(format!("Column '{column}' not found")))
+    }
+
+    /// Parses rows, and returns these grouped on tier ID, in the
+    /// order tiers first appear, together with rows that failed to parse.
+    fn rows(&self, content: &[u8]) -> Result<(Vec<ImportTier>, Vec<RowError>), EafError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .flexible(true)
+            .from_reader(content.strip_prefix(b"\xef\xbb\xbf").unwrap_or(content));
+
+        let header = reader.headers()?.to_owned();
+        let tier_idx = Self::index(&header, &self.tier_id)?;
+        let start_idx = Self::index(&header, &self.start)?;
+        let end_idx = Self::index(&header, &self.end)?;
+        let value_idx = Self::index(&header, &self.value)?;
+        let participant_idx = self.participant.as_deref()
+            .map(|c| Self::index(&header, c))
+            .transpose()?;
+        let parent_idx = self.parent.as_deref()
+            .map(|c| Self::index(&header, c))
+            .transpose()?;
+
+        let mut tiers: Vec<ImportTier> = Vec::new();
+        let mut errors: Vec<RowError> = Vec::new();
+
+        for record in reader.records() {
+            // Malformed rows, e.g. invalid UTF-8, are reported
+            // and skipped, rather than aborting the import.
+            let record = match record {
+                Ok(r) => r,
+                Err(e) => {
+                    let line = e.position().map(|p| p.line()).unwrap_or_default();
+                    errors.push(RowError { line, message: e.to_string() });
+                    continue;
+                }
+            };
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+            let field = |idx: usize| record.get(idx).map(|f| f.trim()).unwrap_or_default();
+            let optional = |idx: Option<usize>| idx.map(field)
+                .filter(|f| !f.is_empty())
+                .map(String::from);
+
+            let tier_id = field(tier_idx);
+            if tier_id.is_empty() {
+                errors.push(RowError { line, message: "No tier ID".to_owned() });
+                continue;
+            }
+            let (start, end) = match (self.time_format.parse(field(start_idx)), self.time_format.parse(field(end_idx))) {
+                (Some(s), Some(e)) => (s, e),
+                _ => {
+                    errors.push(RowError { line, message: format!(
+                        "Invalid time value '{}' or '{}'",
+                        field(start_idx),
+                        field(end_idx)
+                    )});
+                    continue;
+                }
+            };
+            if start < 0 || end <= start {
+                errors.push(RowError { line, message: format!(
+                    "Invalid time span {start}-{end} ms"
+                )});
+                continue;
+            }
+            let row = Row {
+                line,
+                tier_id: tier_id.to_owned(),
+                start,
+                end,
+                value: record.get(value_idx).unwrap_or_default().to_owned(),
+                participant: optional(participant_idx),
+                parent: optional(parent_idx),
+            };
+
+            match tiers.iter_mut().find(|t| t.tier_id == row.tier_id) {
+                Some(tier) => {
+                    if tier.parent != row.parent {
+                        errors.push(row.error(&format!(
+                            "Parent tier '{}' differs from '{}' set for tier '{}' on a previous line",
+                            row.parent.as_deref().unwrap_or_default(),
+                            tier.parent.as_deref().unwrap_or_default(),
+                            tier.tier_id
+                        )));
+                        continue;
+                    }
+                    if tier.participant.is_none() {
+                        tier.participant = row.participant.to_owned();
+                    }
+                    tier.rows.push(row);
+                },
+                None => tiers.push(ImportTier {
+                    tier_id: row.tier_id.to_owned(),
+                    participant: row.participant.to_owned(),
+                    parent: row.parent.to_owned(),
+                    rows: vec![row],
+                    spans: Vec::new(),
+                }),
+            }
+        }
+
+        Ok((tiers, errors))
+    }
+
+    /// Generates an `Eaf` from CSV/TSV `content`, with one tier per tier ID.
+    /// The first row must contain column names.
+    ///
+    /// Rows without a parent tier become main tiers. Rows with a parent tier
+    /// become a referred tier with the stereotype:
+    /// - `Symbolic_Association` if the time span of each row exactly matches
+    ///   that of a unique annotation in the parent tier.
+    /// - `Included_In` otherwise, in which case each row must be within
+    ///   the time span of an annotation in the parent tier.
+    ///
+    /// Rows that can not be imported, e.g. due to invalid CSV, invalid time values,
+    /// overlaps with a previous row in the same tier, or an unknown parent
+    /// tier, are skipped and returned as `RowError`.
+    ///
+    /// Returns error if a mapped column does not exist,
+    /// or if the header row can not be read.
+    pub fn to_eaf(&self, content: &str) -> Result<(Eaf, Vec<RowError>), EafError> {
+        self.bytes_to_eaf(content.as_bytes())
+    }
+
+    /// Generates an `Eaf` from CSV/TSV content that may contain
+    /// invalid UTF-8, which is reported per row. See `CsvMapping::to_eaf()`.
+    fn bytes_to_eaf(&self, content: &[u8]) -> Result<(Eaf, Vec<RowError>), EafError> {
+        let (mut tiers, mut errors) = self.rows(content)?;
+
+        let mut time_order = TimeOrder::new();
+        let mut linguistic_types: Vec<LinguisticType> = vec![LinguisticType::default()];
+        let mut eaf_tiers: Vec<(usize, Tier)> = Vec::new();
+        let (mut a_id, mut ts_id) = (1, 1);
+
+        // Tiers are added parents first.
+        let mut done: Vec<bool> = vec![false; tiers.len()];
+        while let Some(t_idx) = (0..tiers.len()).find(|i| !done[*i] && match &tiers[*i].parent {
+            Some(p) => tiers.iter().enumerate().any(|(j, t)| done[j] && &t.tier_id == p),
+            None => true,
+        }) {
+            done[t_idx] = true;
+
+            let parent_spans: Option<Vec<Span>> = tiers[t_idx].parent.as_ref()
+                .and_then(|p| tiers.iter().find(|t| &t.tier_id == p))
+                .map(|t| t.spans.to_owned());
+
+            let tier = &mut tiers[t_idx];
+            tier.rows.sort_by_key(|r| (r.start, r.end));
+
+            // Parent annotation for each row, if parent tier is set
+            let mut rows: Vec<(&Row, Option<&Span>)> = Vec::new();
+            for row in tier.rows.iter() {
+                if let Some((previous, _)) = rows.last() && row.start < previous.end {
+                    errors.push(row.error(&format!("Overlaps previous annotation in tier '{}'", tier.tier_id)));
+                    continue;
+                }
+                match &parent_spans {
+                    Some(spans) => match spans.iter().find(|(_, s, e)| *s <= row.start && row.end <= *e) {
+                        Some(span) => rows.push((row, Some(span))),
+                        None => errors.push(row.error(&format!(
+                            "Not within an annotation in parent tier '{}'",
+                            tier.parent.as_deref().unwrap_or_default()
+                        ))),
+                    },
+                    None => rows.push((row, None)),
+                }
+            }
+
+            let stereotype = match parent_spans {
+                Some(_) => {
+                    let mut parents: Vec<&str> = Vec::new();
+                    let associated = rows.iter().all(|(row, span)| match span {
+                        Some((id, s, e)) if *s == row.start && *e == row.end && !parents.contains(&id.as_str()) => {
+                            parents.push(id);
+                            true
+                        },
+                        _ => false,
+                    });
+                    match associated {
+                        true => Some(StereoType::SymbolicAssociation),
+                        false => Some(StereoType::IncludedIn),
+                    }
+                },
+                None => None,
+            };
+
+            let mut annotations: Vec<Annotation> = Vec::new();
+            let mut spans: Vec<Span> = Vec::new();
+            for (row, parent) in rows {
+                let id = format!("a{a_id}");
+                a_id += 1;
+                let annotation = match (&stereotype, parent) {
+                    (Some(StereoType::SymbolicAssociation), Some((parent_id, ..))) => Annotation::builder()
+                        .annotation_id(&id)
+                        .annotation_value(&row.value)
+                        .annotation_ref(parent_id)
+                        .time_values(row.start, row.end)
+                        .build()?,
+                    _ => {
+                        let (ts1, ts2) = (format!("ts{ts_id}"), format!("ts{}", ts_id + 1));
+                        ts_id += 2;
+                        time_order.push(&TimeSlot::new(&ts1, Some(row.start)));
+                        time_order.push(&TimeSlot::new(&ts2, Some(row.end)));
+                        Annotation::builder()
+                            .annotation_id(&id)
+                            .annotation_value(&row.value)
+                            .time_slot_refs(ts1, ts2)
+                            .time_values(row.start, row.end)
+                            .build()?
+                    }
+                };
+                annotations.push(annotation);
+                spans.push((id, row.start, row.end));
+            }
+            tier.spans = spans;
+
+            let lt_id = match &stereotype {
+                Some(st) => {
+                    let lt = LinguisticType::new(&st.to_string(), Some(st));
+                    if !linguistic_types.contains(&lt) {
+                        linguistic_types.push(lt);
+                    }
+                    st.to_string()
+                },
+                None => LinguisticType::default().linguistic_type_id,
+            };
+            let mut eaf_tier = Tier::new(&tier.tier_id, Some(&annotations), Some(&lt_id), tier.parent.as_deref());
+            eaf_tier.participant = tier.participant.to_owned();
+            eaf_tiers.push((t_idx, eaf_tier));
+        }
+
+        // Tiers with an unknown parent tier, or circular parent references.
+        for tier in tiers.iter().enumerate().filter(|(i, _)| !done[*i]).map(|(_, t)| t) {
+            for row in tier.rows.iter() {
+                errors.push(row.error(&format!(
+                    "Parent tier '{}' not found, or refers back to tier '{}'",
+                    tier.parent.as_deref().unwrap_or_default(),
+                    tier.tier_id
+                )));
+            }
+        }
+        errors.sort_by_key(|e| e.line);
+
+        // Keep the order tiers first appear in.
+        eaf_tiers.sort_by_key(|(i, _)| *i);
+
+        let mut eaf = Eaf::builder()
+            .header(Header::default())
+            .time_order(time_order)
+            .tiers(eaf_tiers.into_iter().map(|(_, t)| t).collect())
+            .linguistic_types(linguistic_types.to_owned())
+            .build()?;
+
+        for lt in linguistic_types.iter() {
+            if let Some(c) = lt.constraints.as_ref().map(Constraint::from_string)
+                && !eaf.constraints.contains(&c)
+            {
+                eaf.add_constraint(&c);
+            }
+        }
+
+        eaf.index();
+        eaf.derive()?;
+
+        Ok((eaf, errors))
+    }
+
+    /// Reads CSV/TSV file and generates an `Eaf`. See `CsvMapping::to_eaf()`.
+    /// Rows that are not valid UTF-8 are returned as `RowError`.
+    pub fn read(&self, path: &Path) -> Result<(Eaf, Vec<RowError>), EafError> {
+        self.bytes_to_eaf(&std::fs::read(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eaf::test_util::{TestEaf, aligned, referred};
+
+    /// Main tiers `A` (participant `Anna`) and `B`, with
+    /// `Symbolic_Association` tier `G` and `Included_In` tier `W` under `A`.
+    fn eaf() -> Eaf {
+        TestEaf::new()
+            .time_slots(&[0, 500, 1000, 2000, 3500])
+            .tier("A", "default-lt", None, &[
+                aligned("a2", "ts4", "ts5", "bye"),
+                aligned("a1", "ts1", "ts3", "hi there"),
+            ])
+            .participant("Anna")
+            .linguistic_type("gloss", Some("Symbolic_Association"))
+            .tier("G", "gloss", Some("A"), &[referred("a3", "a1", "greeting")])
+            .linguistic_type("words", Some("Included_In"))
+            .tier("W", "words", Some("A"), &[
+                aligned("a4", "ts1", "ts2", "hi"),
+                aligned("a5", "ts2", "ts3", "there"),
+            ])
+            .tier("B", "default-lt", None, &[aligned("a6", "ts3", "ts4", r#"ok, "fine""#)])
+            .eaf()
+    }
+
+    #[test]
+    fn time_format() {
+        assert_eq!(TimeFormat::Milliseconds.format(83456), "83456");
+        assert_eq!(TimeFormat::Seconds.format(83456), "83.456");
+        assert_eq!(TimeFormat::Timestamp.format(3_683_456), "01:01:23.456");
+        assert_eq!(TimeFormat::Timestamp.format(-1500), "-00:00:01.500");
+
+        assert_eq!(TimeFormat::Milliseconds.parse("83456"), Some(83456));
+        assert_eq!(TimeFormat::Seconds.parse(" 83.4564 "), Some(83456));
+        assert_eq!(TimeFormat::Timestamp.parse("01:01:23.456"), Some(3_683_456));
+        assert_eq!(TimeFormat::Timestamp.parse("01:23,456"), Some(83456));
+        assert_eq!(TimeFormat::Timestamp.parse("-00:00:01.500"), Some(-1500));
+        assert_eq!(TimeFormat::Timestamp.parse("1:2:3:4"), None);
+        assert_eq!(TimeFormat::Seconds.parse("abc"), None);
+        assert_eq!(TimeFormat::Seconds.parse("inf"), None);
+
+        for ms in [0, 999, 83456, 3_683_456] {
+            for f in [TimeFormat::Milliseconds, TimeFormat::Seconds, TimeFormat::Timestamp] {
+                assert_eq!(f.parse(&f.format(ms)), Some(ms));
+            }
+        }
+    }
+
+    #[test]
+    fn annotation_rows() {
+        let table = Table::from_eaf(&eaf(), &TabOptions::default()).unwrap();
+        assert_eq!(table.header.as_ref().unwrap(), &vec![
+            "Tier", "Begin Time - msec", "End Time - msec", "Duration - msec", "Annotation"
+        ]);
+        // Tier order, then start time
+        let rows: Vec<Vec<&str>> = table.rows.iter()
+            .map(|r| r.iter().map(|c| c.as_str()).collect())
+            .collect();
+        assert_eq!(rows, vec![
+            vec!["A", "0", "1000", "1000", "hi there"],
+            vec!["A", "2000", "3500", "1500", "bye"],
+            vec!["G", "0", "1000", "1000", "greeting"],
+            vec!["W", "0", "500", "500", "hi"],
+            vec!["W", "500", "1000", "500", "there"],
+            vec!["B", "1000", "2000", "1000", "ok, \"fine\""],
+        ]);
+    }
+
+    #[test]
+    fn columns() {
+        let options = TabOptions {
+            columns: vec![
+                TabColumn::Participant,
+                TabColumn::Begin(TimeFormat::Timestamp),
+                TabColumn::Value,
+                TabColumn::Parent,
+                TabColumn::Tier("G".to_owned()),
+                TabColumn::Tier("W".to_owned()),
+            ],
+            tiers: Some(vec!["A".to_owned(), "W".to_owned(), "B".to_owned()]),
+            header: false,
+            ..TabOptions::default()
+        };
+        let table = Table::from_eaf(&eaf(), &options).unwrap();
+        assert!(table.header.is_none());
+        assert_eq!(table.rows, vec![
+            vec!["Anna", "00:00:00.000", "hi there", "", "greeting", "hi there"],
+            vec!["Anna", "00:00:02.000", "bye", "", "", ""],
+            // Time aligned child annotations get the gloss via their parent
+            vec!["", "00:00:00.000", "hi", "hi there", "greeting", "hi"],
+            vec!["", "00:00:00.500", "there", "hi there", "greeting", "there"],
+            vec!["", "00:00:01.000", "ok, \"fine\"", "", "", ""],
+        ]);
+    }
+
+    #[test]
+    fn tier_columns() {
+        let options = TabOptions {
+            columns: vec![TabColumn::TierId, TabColumn::Begin(TimeFormat::Seconds)],
+            tier_columns: true,
+            tiers: Some(vec!["G".to_owned(), "W".to_owned()]),
+            ..TabOptions::default()
+        };
+        let table = Table::from_eaf(&eaf(), &options).unwrap();
+        assert_eq!(table.header.unwrap(), vec!["Tier", "Begin Time - ss.msec", "G", "W"]);
+        // One row per annotation in the main tier of the exported tiers
+        assert_eq!(table.rows, vec![
+            vec!["A", "0.000", "greeting", "hi there"],
+            vec!["A", "2.000", "", ""],
+        ]);
+    }
+
+    #[test]
+    fn time_range() {
+        let options = TabOptions {
+            columns: vec![TabColumn::Value],
+            tiers: Some(vec!["A".to_owned(), "B".to_owned()]),
+            time_range: (Some(1000), Some(2500)),
+            header: false,
+            ..TabOptions::default()
+        };
+        // Annotations sharing only a boundary with the range are excluded.
+        let table = Table::from_eaf(&eaf(), &options).unwrap();
+        assert_eq!(table.rows, vec![vec!["bye"], vec!["ok, \"fine\""]]);
+    }
+
+    #[test]
+    fn serialize() {
+        let options = TabOptions {
+            columns: vec![TabColumn::TierId, TabColumn::Value],
+            tiers: Some(vec!["B".to_owned()]),
+            ..TabOptions::default()
+        };
+        let table = Table::from_eaf(&eaf(), &options).unwrap();
+        assert_eq!(table.to_string(b'\t').unwrap(), "Tier\tAnnotation\nB\t\"ok, \"\"fine\"\"\"\n");
+        assert_eq!(table.to_string(b';').unwrap(), "Tier;Annotation\nB;\"ok, \"\"fine\"\"\"\n");
+    }
+
+    #[test]
+    fn invalid_tier() {
+        let options = TabOptions {
+            tiers: Some(vec!["X".to_owned()]),
+            ..TabOptions::default()
+        };
+        assert!(matches!(Table::from_eaf(&eaf(), &options), Err(EafError::TierIdInvalid(id)) if id == "X"));
+        let options = TabOptions {
+            columns: vec![TabColumn::Tier("X".to_owned())],
+            ..TabOptions::default()
+        };
+        assert!(Table::from_eaf(&eaf(), &options).is_err());
+    }
+
+    #[test]
+    fn csv_import() {
+        let csv = "\u{feff}tier,start,end,value,speaker\n\
+            A,0,1000,hi there,Anna\n\
+            B,1000,2000,\"ok, fine\",\n\
+            A,2000,3500,bye,\n";
+        let mapping = CsvMapping {
+            participant: Some("speaker".to_owned()),
+            ..CsvMapping::default()
+        };
+        let (eaf, errors) = mapping.to_eaf(csv).unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(eaf.main_tier_ids(), vec!["A", "B"]);
+        let a = eaf.get_tier("A").unwrap();
+        assert_eq!(a.participant.as_deref(), Some("Anna"));
+        let annotations: Vec<(&str, Option<i64>, Option<i64>)> = a.iter()
+            .map(|a| (a.to_str(), a.ts_val().0, a.ts_val().1))
+            .collect();
+        assert_eq!(annotations, vec![("hi there", Some(0), Some(1000)), ("bye", Some(2000), Some(3500))]);
+        assert_eq!(eaf.get_tier("B").unwrap().values(), vec!["ok, fine"]);
+    }
+
+    #[test]
+    fn csv_parent_tiers() {
+        let tsv = "tier\tstart\tend\tvalue\tparent\n\
+            G\t00:00.000\t00:01.000\tgreeting\tA\n\
+            A\t00:00.000\t00:01.000\thi there\t\n\
+            W\t00:00.000\t00:00.500\thi\tA\n\
+            W\t00:00.500\t00:01.000\tthere\tA\n";
+        let mapping = CsvMapping {
+            parent: Some("parent".to_owned()),
+            time_format: TimeFormat::Timestamp,
+            delimiter: b'\t',
+            ..CsvMapping::default()
+        };
+        let (eaf, errors) = mapping.to_eaf(tsv).unwrap();
+        assert!(errors.is_empty());
+        // Tier order as first appearing, parents added first.
+        assert_eq!(eaf.tiers.iter().map(|t| t.tier_id.as_str()).collect::<Vec<_>>(), vec!["G", "A", "W"]);
+        let g = eaf.get_tier("G").unwrap();
+        assert_eq!(g.linguistic_type_ref, "Symbolic_Association");
+        assert_eq!(g.annotations[0].ref_id(), Some(eaf.get_tier("A").unwrap().annotations[0].id()));
+        assert_eq!(eaf.get_tier("W").unwrap().linguistic_type_ref, "Included_In");
+        assert!(eaf.validate().is_empty());
+    }
+
+    #[test]
+    fn csv_row_errors() {
+        let csv = "tier,start,end,value,parent\n\
+            A,0,1000,a,\n\
+            ,0,1000,no tier,\n\
+            A,x,1000,invalid time,\n\
+            A,1000,1000,zero length,\n\
+            A,500,1500,overlap,\n\
+            A,2000,3000,b,\n\
+            G,2500,3500,outside parent,A\n\
+            A,3000,4000,other parent,B\n\
+            X,0,1000,unknown parent,Y\n";
+        let mapping = CsvMapping {
+            parent: Some("parent".to_owned()),
+            ..CsvMapping::default()
+        };
+        let (eaf, errors) = mapping.to_eaf(csv).unwrap();
+        assert_eq!(errors.iter().map(|e| e.line).collect::<Vec<_>>(), vec![3, 4, 5, 6, 8, 9, 10]);
+        assert_eq!(eaf.get_tier("A").unwrap().values(), vec!["a", "b"]);
+        assert!(eaf.get_tier("G").unwrap().annotations.is_empty());
+        assert!(eaf.get_tier("X").is_none());
+
+        let mapping = CsvMapping {
+            value: "text".to_owned(),
+            ..CsvMapping::default()
+        };
+        assert!(matches!(mapping.to_eaf(csv), Err(EafError::CsvInvalid(_))));
+    }
+
+    #[test]
+    fn csv_invalid_utf8() {
+        let mut content = b"tier,start,end,value\nA,0,1000,a\nA,1000,2000,".to_vec();
+        content.extend([0xff, 0xfe]);
+        content.extend(b"\nA,2000,3000,c\n");
+        let path = std::env::temp_dir().join(format!("{}.csv", uuid::Uuid::new_v4()));
+        std::fs::write(&path, &content).unwrap();
+
+        let result = CsvMapping::default().read(&path);
+        std::fs::remove_file(&path).unwrap();
+        let (eaf, errors) = result.unwrap();
+        assert_eq!(errors.iter().map(|e| e.line).collect::<Vec<_>>(), vec![3]);
+        assert_eq!(eaf.get_tier("A").unwrap().values(), vec!["a", "c"]);
+    }
+
+    #[test]
+    fn export_import() {
+        let eaf = eaf();
+        let options = TabOptions {
+            columns: vec![
+                TabColumn::TierId,
+                TabColumn::Begin(TimeFormat::Seconds),
+                TabColumn::End(TimeFormat::Seconds),
+                TabColumn::Value,
+            ],
+            tiers: Some(vec!["A".to_owned(), "B".to_owned()]),
+            ..TabOptions::default()
+        };
+        let tsv = Table::from_eaf(&eaf, &options).unwrap().to_string(b'\t').unwrap();
+        let mapping = CsvMapping {
+            tier_id: "Tier".to_owned(),
+            start: "Begin Time - ss.msec".to_owned(),
+            end: "End Time - ss.msec".to_owned(),
+            value: "Annotation".to_owned(),
+            time_format: TimeFormat::Seconds,
+            delimiter: b'\t',
+            ..CsvMapping::default()
+        };
+        let (eaf2, errors) = mapping.to_eaf(&tsv).unwrap();
+        assert!(errors.is_empty());
+        for tier_id in ["A", "B"] {
+            let spans = |eaf: &Eaf| -> Vec<(String, Option<i64>, Option<i64>)> {
+                let mut spans: Vec<_> = eaf.get_tier(tier_id).unwrap().iter()
+                    .map(|a| (a.to_str().to_owned(), a.ts_val().0, a.ts_val().1))
+                    .collect();
+                spans.sort_by_key(|s| s.1);
+                spans
+            };
+            assert_eq!(spans(&eaf2), spans(&eaf));
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! Helpers for unit tests: a builder for small EAF documents,
+//! and assertions shared between test modules.
+
+use std::path::{Path, PathBuf};
+
+use crate::Eaf;
+
+/// Builds a small EAF document for tests, as XML that is then deserialized.
+///
+/// Time slots are numbered in the order they are added (`ts1`, `ts2`, ...),
+/// and annotations refer to these by ID. The linguistic type `default-lt`
+/// (time alignable, no constraint) is always included.
+///
+/// ```ignore
+/// let eaf = TestEaf::new()
+///     .time_slots(&[0, 1000])
+///     .tier("A", "default-lt", None, &[aligned("a1", "ts1", "ts2", "one")])
+///     .linguistic_type("gloss", Some("Symbolic_Association"))
+///     .tier("G", "gloss", Some("A"), &[referred("a2", "a1", "1")])
+///     .eaf();
+/// ```
+#[derive(Debug, Clone)]
+pub(crate) struct TestEaf {
+    version: String,
+    time_slots: Vec<Option<i64>>,
+    tiers: Vec<TestTier>,
+    types: Vec<String>,
+    constraints: Vec<String>,
+    other: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct TestTier {
+    tier_id: String,
+    linguistic_type: String,
+    parent: Option<String>,
+    participant: Option<String>,
+    annotations: Vec<String>,
+}
+
+impl TestEaf {
+    /// New EAF v3.0 document with the linguistic type `default-lt`.
+    pub(crate) fn new() -> Self {
+        Self {
+            version: "3.0".to_owned(),
+            time_slots: Vec::new(),
+            tiers: Vec::new(),
+            types: vec![linguistic_type("default-lt", None)],
+            constraints: Vec::new(),
+            other: Vec::new(),
+        }
+    }
+
+    /// Sets EAF version, e.g. `2.7`.
+    pub(crate) fn version(mut self, version: &str) -> Self {
+        self.version = version.to_owned();
+        self
+    }
+
+    /// Adds time slots with time values.
+    pub(crate) fn time_slots(mut self, values: &[i64]) -> Self {
+        self.time_slots.extend(values.iter().map(|v| Some(*v)));
+        self
+    }
+
+    /// Adds a time slot, with or without a time value.
+    pub(crate) fn time_slot(mut self, value: Option<i64>) -> Self {
+        self.time_slots.push(value);
+        self
+    }
+
+    /// Adds a tier, with annotations generated by `aligned()`, `referred()`
+    /// or `subdivision()`.
+    pub(crate) fn tier(mut self, tier_id: &str, linguistic_type: &str, parent: Option<&str>, annotations: &[String]) -> Self {
+        self.tiers.push(TestTier {
+            tier_id: tier_id.to_owned(),
+            linguistic_type: linguistic_type.to_owned(),
+            parent: parent.map(String::from),
+            participant: None,
+            annotations: annotations.to_vec(),
+        });
+        self
+    }
+
+    /// Sets participant for the last added tier.
+    pub(crate) fn participant(mut self, participant: &str) -> Self {
+        if let Some(tier) = self.tiers.last_mut() {
+            tier.participant = Some(participant.to_owned())
+        }
+        self
+    }
+
+    /// Adds a linguistic type, optionally with a stereotype, e.g. `Symbolic_Association`,
+    /// together with the corresponding constraint.
+    pub(crate) fn linguistic_type(mut self, id: &str, stereotype: Option<&str>) -> Self {
+        self.types.push(linguistic_type(id, stereotype));
+        if let Some(stereotype) = stereotype {
+            let constraint = format!(r#"<CONSTRAINT DESCRIPTION="" STEREOTYPE="{stereotype}"/>"#);
+            if !self.constraints.contains(&constraint) {
+                self.constraints.push(constraint);
+            }
+        }
+        self
+    }
+
+    /// Adds XML at the end of the document, e.g. controlled vocabularies.
+    pub(crate) fn xml(mut self, xml: &str) -> Self {
+        self.other.push(xml.to_owned());
+        self
+    }
+
+    /// Returns the document as XML.
+    pub(crate) fn to_xml(&self) -> String {
+        let time_slots: Vec<String> = self.time_slots.iter()
+            .enumerate()
+            .map(|(i, value)| match value {
+                Some(v) => format!(r#"<TIME_SLOT TIME_SLOT_ID="ts{}" TIME_VALUE="{v}"/>"#, i + 1),
+                None => format!(r#"<TIME_SLOT TIME_SLOT_ID="ts{}"/>"#, i + 1),
+            })
+            .collect();
+        let tiers: Vec<String> = self.tiers.iter()
+            .map(|t| {
+                let parent = t.parent.as_ref()
+                    .map(|p| format!(r#" PARENT_REF="{p}""#))
+                    .unwrap_or_default();
+                let participant = t.participant.as_ref()
+                    .map(|p| format!(r#" PARTICIPANT="{p}""#))
+                    .unwrap_or_default();
+                format!(
+                    r#"<TIER LINGUISTIC_TYPE_REF="{}"{parent}{participant} TIER_ID="{}">{}</TIER>"#,
+                    t.linguistic_type,
+                    t.tier_id,
+                    t.annotations.concat()
+                )
+            })
+            .collect();
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT AUTHOR="" DATE="2024-01-01T00:00:00+00:00" FORMAT="{v}" VERSION="{v}">
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds"/>
+    <TIME_ORDER>{}</TIME_ORDER>
+    {}
+    {}
+    {}
+    {}
+</ANNOTATION_DOCUMENT>"#,
+            time_slots.concat(),
+            tiers.join("\n    "),
+            self.types.join("\n    "),
+            self.constraints.join("\n    "),
+            self.other.join("\n    "),
+            v = self.version,
+        )
+    }
+
+    /// Deserializes the document, with derived annotation values.
+    pub(crate) fn eaf(&self) -> Eaf {
+        Eaf::de_str(&self.to_xml(), true).unwrap()
+    }
+
+    /// Deserializes the document without deriving annotation values,
+    /// e.g. for documents with invalid references.
+    pub(crate) fn eaf_underived(&self) -> Eaf {
+        Eaf::de_str(&self.to_xml(), false).unwrap()
+    }
+}
+
+fn linguistic_type(id: &str, stereotype: Option<&str>) -> String {
+    let alignable = !stereotype.is_some_and(|s| s.starts_with("Symbolic"));
+    let constraints = stereotype
+        .map(|s| format!(r#" CONSTRAINTS="{s}""#))
+        .unwrap_or_default();
+    format!(r#"<LINGUISTIC_TYPE{constraints} GRAPHIC_REFERENCES="false" LINGUISTIC_TYPE_ID="{id}" TIME_ALIGNABLE="{alignable}"/>"#)
+}
+
+/// Alignable annotation XML.
+pub(crate) fn aligned(id: &str, ts1: &str, ts2: &str, value: &str) -> String {
+    format!(r#"<ANNOTATION><ALIGNABLE_ANNOTATION ANNOTATION_ID="{id}" TIME_SLOT_REF1="{ts1}" TIME_SLOT_REF2="{ts2}"><ANNOTATION_VALUE>{value}</ANNOTATION_VALUE></ALIGNABLE_ANNOTATION></ANNOTATION>"#)
+}
+
+/// Referred annotation XML.
+pub(crate) fn referred(id: &str, ref_id: &str, value: &str) -> String {
+    format!(r#"<ANNOTATION><REF_ANNOTATION ANNOTATION_ID="{id}" ANNOTATION_REF="{ref_id}"><ANNOTATION_VALUE>{value}</ANNOTATION_VALUE></REF_ANNOTATION></ANNOTATION>"#)
+}
+
+/// Referred annotation XML, following `previous` in a symbolic subdivision.
+pub(crate) fn subdivision(id: &str, ref_id: &str, previous: &str, value: &str) -> String {
+    format!(r#"<ANNOTATION><REF_ANNOTATION ANNOTATION_ID="{id}" ANNOTATION_REF="{ref_id}" PREVIOUS_ANNOTATION="{previous}"><ANNOTATION_VALUE>{value}</ANNOTATION_VALUE></REF_ANNOTATION></ANNOTATION>"#)
+}
+
+/// Asserts that the document passes validation, see `Eaf::validate()`.
+pub(crate) fn assert_valid(eaf: &Eaf) {
+    let report = eaf.validate();
+    assert!(report.is_empty(), "{report}");
+}
+
+/// Asserts that a computed value exists and is equal to `expected`,
+/// within floating point precision.
+pub(crate) fn assert_approx(value: Option<f64>, expected: f64) {
+    let value = value.expect("No value");
+    assert!((value - expected).abs() < 1e-9, "{value} != {expected}");
+}
+
+/// Uniquely named directory in the system temp directory,
+/// removed with its content on drop, also if the test fails.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("eaf-rs-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
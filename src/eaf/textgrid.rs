@@ -0,0 +1,598 @@
+//! Praat TextGrid, for exchanging annotations with [Praat](https://www.fon.hum.uva.nl/praat/).
+//!
+//! Supports reading and writing both the long ("text file") and
+//! the short ("short text file") TextGrid formats. Files may be
+//! encoded as UTF-8 or UTF-16 (Praat's default for non-ASCII content),
+//! where the latter requires a byte order mark.
+//!
+//! Mapping between EAF and TextGrid:
+//! - Main tiers are exported as `IntervalTier`. Gaps between annotations
+//! are filled with empty intervals, since Praat requires intervals to
+//! cover the entire TextGrid time span.
+//! - Zero-length annotations (start time equals end time) are exported
+//! as points in a `TextTier` (a "point tier" in Praat).
+//! - Referred tiers are not exported.
+//! - On import, empty intervals are discarded and each TextGrid tier becomes a main tier.
+
+use std::{fs::File, io::Write, path::Path};
+
+use crate::{Eaf, EafError};
+
+/// Praat TextGrid.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextGrid {
+    /// Start of TextGrid in seconds.
+    pub xmin: f64,
+    /// End of TextGrid in seconds.
+    pub xmax: f64,
+    /// Interval and point tiers.
+    pub tiers: Vec<TextGridTier>,
+}
+
+/// TextGrid tier. Either an interval tier,
+/// or a point tier (`TextTier` in Praat).
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextGridTier {
+    IntervalTier(IntervalTier),
+    TextTier(TextTier),
+}
+
+/// TextGrid interval tier.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntervalTier {
+    pub name: String,
+    pub xmin: f64,
+    pub xmax: f64,
+    pub intervals: Vec<Interval>,
+}
+
+/// TextGrid interval, with boundaries in seconds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Interval {
+    pub xmin: f64,
+    pub xmax: f64,
+    pub text: String,
+}
+
+/// TextGrid point tier (`TextTier` in Praat).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextTier {
+    pub name: String,
+    pub xmin: f64,
+    pub xmax: f64,
+    pub points: Vec<Point>,
+}
+
+/// TextGrid point, with time in seconds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Point {
+    pub number: f64,
+    pub mark: String,
+}
+
+impl TextGridTier {
+    /// Returns tier name.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::IntervalTier(t) => &t.name,
+            Self::TextTier(t) => &t.name,
+        }
+    }
+
+    /// Returns Praat class name.
+    fn class(&self) -> &str {
+        match self {
+            Self::IntervalTier(_) => "IntervalTier",
+            Self::TextTier(_) => "TextTier",
+        }
+    }
+
+    /// Returns tier boundaries in seconds.
+    fn bounds(&self) -> (f64, f64) {
+        match self {
+            Self::IntervalTier(t) => (t.xmin, t.xmax),
+            Self::TextTier(t) => (t.xmin, t.xmax),
+        }
+    }
+
+    /// Returns annotation values as tuples
+    /// `(annotation_value, start_time_ms, end_time_ms)`.
+    /// Empty intervals are discarded.
+    fn values(&self) -> Vec<(String, i64, i64)> {
+        match self {
+            Self::IntervalTier(t) => t.intervals.iter()
+                .filter(|i| !i.text.trim().is_empty())
+                .map(|i| (i.text.to_owned(), sec_to_ms(i.xmin), sec_to_ms(i.xmax)))
+                .collect(),
+            Self::TextTier(t) => t.points.iter()
+                .map(|p| (p.mark.to_owned(), sec_to_ms(p.number), sec_to_ms(p.number)))
+                .collect(),
+        }
+    }
+}
+
+/// Token in a TextGrid file.
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// Quoted string.
+    Text(String),
+    /// Real number.
+    Number(f64),
+    /// Flag such as `<exists>`.
+    Flag(String),
+}
+
+/// Splits TextGrid content into tokens.
+///
+/// Labels, such as `xmin =` and `intervals [1]:` in the long format,
+/// and comments (starting with `!`) in the short format are discarded,
+/// which means both formats result in the same token sequence.
+fn tokenize(content: &str) -> Result<Vec<Token>, EafError> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                // Strings may span multiple lines, and double quotes are escaped as `""`.
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            text.push('"');
+                        },
+                        Some('"') => break,
+                        Some(ch) => text.push(ch),
+                        None => return Err(EafError::TextGridInvalid("Unterminated string".to_owned())),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            },
+            '!' => {
+                // Comment until end of line
+                while chars.next_if(|ch| *ch != '\n').is_some() {}
+            },
+            '[' => {
+                // Index in long format, e.g. "item [1]:"
+                while chars.next_if(|ch| *ch != ']').is_some() {}
+                chars.next();
+            },
+            '<' => {
+                let mut flag = String::new();
+                while let Some(ch) = chars.next_if(|ch| *ch != '>') {
+                    flag.push(ch);
+                }
+                chars.next();
+                tokens.push(Token::Flag(flag));
+            },
+            c if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let mut number = String::from(c);
+                while let Some(ch) = chars.next_if(|ch|
+                    ch.is_ascii_digit() || matches!(ch, '.' | 'e' | 'E' | '-' | '+')
+                ) {
+                    number.push(ch);
+                }
+                tokens.push(Token::Number(number.parse::<f64>()?));
+            },
+            _ => {
+                // Labels, '=', ':' and whitespace.
+                // Labels may contain digits, e.g. "xmin2", so consume the entire word.
+                if c.is_alphabetic() {
+                    while chars.next_if(|ch| ch.is_alphanumeric() || *ch == '_').is_some() {}
+                }
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Cursor over TextGrid tokens.
+struct Tokens {
+    tokens: std::vec::IntoIter<Token>,
+}
+
+impl Tokens {
+    fn text(&mut self) -> Result<String, EafError> {
+        match self.tokens.next() {
+            Some(Token::Text(s)) => Ok(s),
+            t => Err(EafError::TextGridInvalid(format!("Expected string, found {t:?}"))),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, EafError> {
+        match self.tokens.next() {
+            Some(Token::Number(n)) => Ok(n),
+            t => Err(EafError::TextGridInvalid(format!("Expected number, found {t:?}"))),
+        }
+    }
+
+    fn count(&mut self) -> Result<usize, EafError> {
+        let n = self.number()?;
+        if n < 0. || n.fract() != 0. {
+            return Err(EafError::TextGridInvalid(format!("Invalid size {n}")))
+        }
+        Ok(n as usize)
+    }
+}
+
+/// Converts seconds to milliseconds.
+fn sec_to_ms(sec: f64) -> i64 {
+    (sec * 1000.).round() as i64
+}
+
+/// Converts milliseconds to seconds.
+fn ms_to_sec(ms: i64) -> f64 {
+    ms as f64 / 1000.
+}
+
+/// Escapes double quotes for TextGrid strings.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+/// Decodes TextGrid file content. UTF-16 requires a byte order mark,
+/// otherwise UTF-8 is assumed.
+fn decode(bytes: &[u8]) -> Result<String, EafError> {
+    let utf16 = |be: bool| -> Result<String, EafError> {
+        let chunks = bytes[2..].chunks_exact(2);
+        if !chunks.remainder().is_empty() {
+            return Err(EafError::TextGridInvalid("Odd number of bytes in UTF-16 content".to_owned()))
+        }
+        let units: Vec<u16> = chunks
+            .map(|b| match be {
+                true => u16::from_be_bytes([b[0], b[1]]),
+                false => u16::from_le_bytes([b[0], b[1]]),
+            })
+            .collect();
+        Ok(String::from_utf16(&units)?)
+    };
+
+    match bytes {
+        [0xFE, 0xFF, ..] => utf16(true),
+        [0xFF, 0xFE, ..] => utf16(false),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => Ok(std::str::from_utf8(rest)?.to_owned()),
+        _ => Ok(std::str::from_utf8(bytes)?.to_owned()),
+    }
+}
+
+impl TextGrid {
+    /// Read and parse a TextGrid file
+    /// (long or short format, UTF-8 or UTF-16).
+    pub fn read(path: &Path) -> Result<Self, EafError> {
+        Self::parse(&decode(&std::fs::read(path)?)?)
+    }
+
+    /// Parse TextGrid content (long or short format).
+    pub fn parse(content: &str) -> Result<Self, EafError> {
+        let mut tokens = Tokens { tokens: tokenize(content)?.into_iter() };
+
+        if tokens.text()? != "ooTextFile" {
+            return Err(EafError::TextGridInvalid("Not a Praat text file".to_owned()))
+        }
+        if tokens.text()? != "TextGrid" {
+            return Err(EafError::TextGridInvalid("Not a TextGrid".to_owned()))
+        }
+
+        let mut textgrid = Self {
+            xmin: tokens.number()?,
+            xmax: tokens.number()?,
+            tiers: Vec::new(),
+        };
+
+        match tokens.tokens.next() {
+            Some(Token::Flag(f)) if f == "exists" => (),
+            // No tiers
+            Some(Token::Flag(_)) => return Ok(textgrid),
+            t => return Err(EafError::TextGridInvalid(format!("Expected tier flag, found {t:?}"))),
+        }
+
+        let size = tokens.count()?;
+        for _ in 0..size {
+            let class = tokens.text()?;
+            let name = tokens.text()?;
+            let xmin = tokens.number()?;
+            let xmax = tokens.number()?;
+            let len = tokens.count()?;
+            let tier = match class.as_str() {
+                "IntervalTier" => TextGridTier::IntervalTier(IntervalTier {
+                    name,
+                    xmin,
+                    xmax,
+                    intervals: (0..len)
+                        .map(|_| Ok(Interval {
+                            xmin: tokens.number()?,
+                            xmax: tokens.number()?,
+                            text: tokens.text()?,
+                        }))
+                        .collect::<Result<Vec<_>, EafError>>()?
+                }),
+                "TextTier" => TextGridTier::TextTier(TextTier {
+                    name,
+                    xmin,
+                    xmax,
+                    points: (0..len)
+                        .map(|_| Ok(Point {
+                            number: tokens.number()?,
+                            mark: tokens.text()?,
+                        }))
+                        .collect::<Result<Vec<_>, EafError>>()?
+                }),
+                c => return Err(EafError::TextGridInvalid(format!("Unknown tier class '{c}'")))
+            };
+            textgrid.tiers.push(tier);
+        }
+
+        Ok(textgrid)
+    }
+
+    /// Serialize to TextGrid string in either the long
+    /// or the short (`short = true`) format.
+    pub fn to_string(&self, short: bool) -> String {
+        // "label = value" in the long format, only the value in the short format.
+        let field = |indent: usize, label: &str, value: String| match short {
+            true => Some(value),
+            false => Some(format!("{}{label} = {value}", " ".repeat(indent))),
+        };
+        // Index headers, e.g. "item [1]:", are only written in the long format.
+        let header = |indent: usize, label: String| match short {
+            true => None,
+            false => Some(format!("{}{label}", " ".repeat(indent))),
+        };
+
+        let mut lines: Vec<Option<String>> = vec![
+            Some(r#"File type = "ooTextFile""#.to_owned()),
+            Some(r#"Object class = "TextGrid""#.to_owned()),
+            Some(String::new()),
+            field(0, "xmin", self.xmin.to_string()),
+            field(0, "xmax", self.xmax.to_string()),
+        ];
+
+        if self.tiers.is_empty() {
+            lines.push(field(0, "tiers?", "<absent>".to_owned()));
+        } else {
+            lines.push(field(0, "tiers?", "<exists>".to_owned()));
+            lines.push(field(0, "size", self.tiers.len().to_string()));
+            lines.push(header(0, "item []:".to_owned()));
+        }
+
+        for (i, tier) in self.tiers.iter().enumerate() {
+            let (xmin, xmax) = tier.bounds();
+            lines.extend([
+                header(4, format!("item [{}]:", i + 1)),
+                field(8, "class", quote(tier.class())),
+                field(8, "name", quote(tier.name())),
+                field(8, "xmin", xmin.to_string()),
+                field(8, "xmax", xmax.to_string()),
+            ]);
+            match tier {
+                TextGridTier::IntervalTier(t) => {
+                    lines.push(field(8, "intervals: size", t.intervals.len().to_string()));
+                    for (j, interval) in t.intervals.iter().enumerate() {
+                        lines.extend([
+                            header(8, format!("intervals [{}]:", j + 1)),
+                            field(12, "xmin", interval.xmin.to_string()),
+                            field(12, "xmax", interval.xmax.to_string()),
+                            field(12, "text", quote(&interval.text)),
+                        ]);
+                    }
+                },
+                TextGridTier::TextTier(t) => {
+                    lines.push(field(8, "points: size", t.points.len().to_string()));
+                    for (j, point) in t.points.iter().enumerate() {
+                        lines.extend([
+                            header(8, format!("points [{}]:", j + 1)),
+                            field(12, "number", point.number.to_string()),
+                            field(12, "mark", quote(&point.mark)),
+                        ]);
+                    }
+                },
+            }
+        }
+
+        // Praat ends files with a line break.
+        lines.push(Some(String::new()));
+
+        lines.into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Serialize and write TextGrid to disk as UTF-8,
+    /// in either the long or the short (`short = true`) format.
+    pub fn write(&self, path: &Path, short: bool) -> Result<(), EafError> {
+        let mut outfile = File::create(&path)?;
+        outfile.write_all(self.to_string(short).as_bytes()).map_err(|e| EafError::IOError(e))
+    }
+
+    /// Generates a TextGrid from the main tiers in an `Eaf`.
+    ///
+    /// Tiers with only zero-length annotations become point tiers.
+    /// For tiers with a mix of zero-length annotations and
+    /// annotations with a duration, the zero-length annotations
+    /// are exported to an additional point tier named `<TIER_ID>-points`.
+    ///
+    /// Returns an error if annotations in the same tier overlap,
+    /// or if any annotation boundary lacks a time value.
+    pub fn from_eaf(eaf: &Eaf) -> Result<Self, EafError> {
+        let xmin = ms_to_sec(eaf.ts_min_val().unwrap_or(0).min(0));
+        let xmax = ms_to_sec(eaf.ts_max_val().unwrap_or(0));
+
+        let mut tiers: Vec<TextGridTier> = Vec::new();
+
+        for tier in eaf.main_tiers() {
+            // (annotation_value, start_ms, end_ms)
+            let mut values = tier.iter()
+                .map(|a| {
+                    let (ref1, ref2) = a.ts_ref()
+                        .ok_or_else(|| EafError::TimeslotRefMissing(a.id().to_owned()))?;
+                    match (eaf.ts_val(&ref1), eaf.ts_val(&ref2)) {
+                        (Some(t1), Some(t2)) => Ok((a.to_str(), t1, t2)),
+                        _ => Err(EafError::TimeslotValMissing(a.id().to_owned()))
+                    }
+                })
+                .collect::<Result<Vec<_>, EafError>>()?;
+            values.sort_by_key(|(_, t1, t2)| (*t1, *t2));
+
+            let (points, intervals): (Vec<_>, Vec<_>) = values.into_iter()
+                .partition(|(_, t1, t2)| t1 == t2);
+
+            if !intervals.is_empty() || points.is_empty() {
+                let mut tg_intervals: Vec<Interval> = Vec::new();
+                let mut cursor = xmin;
+                for (value, t1, t2) in intervals.iter() {
+                    let (start, end) = (ms_to_sec(*t1), ms_to_sec(*t2));
+                    if start < cursor {
+                        return Err(EafError::AnnotationOverlap)
+                    }
+                    if start > cursor {
+                        tg_intervals.push(Interval { xmin: cursor, xmax: start, text: String::new() });
+                    }
+                    tg_intervals.push(Interval { xmin: start, xmax: end, text: value.to_string() });
+                    cursor = end;
+                }
+                if cursor < xmax || tg_intervals.is_empty() {
+                    tg_intervals.push(Interval { xmin: cursor, xmax, text: String::new() });
+                }
+
+                tiers.push(TextGridTier::IntervalTier(IntervalTier {
+                    name: tier.tier_id.to_owned(),
+                    xmin,
+                    xmax,
+                    intervals: tg_intervals,
+                }));
+            }
+
+            if !points.is_empty() {
+                let name = match intervals.is_empty() {
+                    true => tier.tier_id.to_owned(),
+                    false => format!("{}-points", tier.tier_id),
+                };
+                tiers.push(TextGridTier::TextTier(TextTier {
+                    name,
+                    xmin,
+                    xmax,
+                    points: points.iter()
+                        .map(|(value, t, _)| Point { number: ms_to_sec(*t), mark: value.to_string() })
+                        .collect(),
+                }));
+            }
+        }
+
+        Ok(Self { xmin, xmax, tiers })
+    }
+
+    /// Generates an `Eaf` with one main tier per TextGrid tier.
+    /// Empty intervals are discarded, and points become zero-length annotations.
+    ///
+    /// TextGrid allows duplicate tier names, whereas EAF does not.
+    /// Duplicate names are suffixed with a number, e.g. `words-2`.
+    pub fn to_eaf(&self) -> Result<Eaf, EafError> {
+        let mut tier_values: Vec<(String, Vec<(String, i64, i64)>)> = Vec::new();
+        for tier in self.tiers.iter() {
+            let mut name = tier.name().to_owned();
+            let mut n = 1;
+            while tier_values.iter().any(|(id, _)| id == &name) {
+                n += 1;
+                name = format!("{}-{n}", tier.name());
+            }
+            tier_values.push((name, tier.values()));
+        }
+
+        Eaf::from_tier_values(&tier_values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(eaf: &Eaf, tier_id: &str) -> Vec<(String, i64, i64)> {
+        eaf.get_tier(tier_id).unwrap().iter()
+            .map(|a| {
+                let (t1, t2) = a.ts_val();
+                (a.to_str().to_owned(), t1.unwrap(), t2.unwrap())
+            })
+            .collect()
+    }
+
+    fn sample() -> Eaf {
+        Eaf::from_tier_values(&[
+            ("words".to_owned(), vec![
+                ("hello".to_owned(), 100, 500),
+                ("\"world\"".to_owned(), 800, 1200),
+            ]),
+            ("beats".to_owned(), vec![
+                ("x".to_owned(), 300, 300),
+                ("y".to_owned(), 900, 900),
+            ]),
+        ]).unwrap()
+    }
+
+    #[test]
+    fn round_trip_long_and_short() {
+        let eaf = sample();
+        for short in [false, true] {
+            let content = eaf.to_textgrid(short).unwrap();
+            let eaf2 = TextGrid::parse(&content).unwrap().to_eaf().unwrap();
+            assert_eq!(eaf2.main_tier_ids(), vec!["words", "beats"]);
+            assert_eq!(values(&eaf2, "words"), values(&eaf, "words"));
+            assert_eq!(values(&eaf2, "beats"), values(&eaf, "beats"));
+        }
+    }
+
+    #[test]
+    fn gaps_and_points() {
+        let tg = TextGrid::from_eaf(&sample()).unwrap();
+        assert_eq!(tg.xmax, 1.2);
+        match &tg.tiers[0] {
+            TextGridTier::IntervalTier(t) => {
+                let texts: Vec<&str> = t.intervals.iter().map(|i| i.text.as_str()).collect();
+                assert_eq!(texts, vec!["", "hello", "", "\"world\""]);
+                assert_eq!(t.intervals[0].xmin, 0.);
+                assert_eq!(t.intervals.last().unwrap().xmax, 1.2);
+            },
+            t => panic!("Expected interval tier, found {t:?}"),
+        }
+        match &tg.tiers[1] {
+            TextGridTier::TextTier(t) => assert_eq!(t.points.len(), 2),
+            t => panic!("Expected point tier, found {t:?}"),
+        }
+    }
+
+    #[test]
+    fn short_and_long_parse_equal() {
+        let tg = TextGrid::from_eaf(&sample()).unwrap();
+        assert_eq!(TextGrid::parse(&tg.to_string(true)).unwrap(), tg);
+        assert_eq!(TextGrid::parse(&tg.to_string(false)).unwrap(), tg);
+    }
+
+    #[test]
+    fn decode_utf16() {
+        let content = TextGrid::from_eaf(&sample()).unwrap().to_string(false);
+        let mut le = vec![0xFF, 0xFE];
+        le.extend(content.encode_utf16().flat_map(|u| u.to_le_bytes()));
+        let mut be = vec![0xFE, 0xFF];
+        be.extend(content.encode_utf16().flat_map(|u| u.to_be_bytes()));
+        assert_eq!(decode(&le).unwrap(), content);
+        assert_eq!(decode(&be).unwrap(), content);
+
+        le.push(b'\n');
+        assert!(matches!(decode(&le), Err(EafError::TextGridInvalid(_))));
+    }
+
+    #[test]
+    fn duplicate_tier_names() {
+        let mut tg = TextGrid::from_eaf(&sample()).unwrap();
+        tg.tiers.push(tg.tiers[0].clone());
+        let eaf = tg.to_eaf().unwrap();
+        assert_eq!(eaf.main_tier_ids(), vec!["words", "beats", "words-2"]);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(TextGrid::parse("\"ooTextFile\"\n\"Pitch 1\"\n").is_err());
+    }
+}
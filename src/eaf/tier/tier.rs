@@ -10,7 +10,7 @@ use std::{collections::{HashMap, HashSet}, io::copy};
 
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use rayon::{prelude::{IntoParallelRefIterator, ParallelIterator, IndexedParallelIterator}, slice::ParallelSliceMut};
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator, IndexedParallelIterator};
 
 use crate::{Annotation, EafError, TimeSlot};
 
@@ -644,32 +644,39 @@ impl Tier {
     /// raises an error.
     ///
     /// Make sure to add the corresponding time slot value to `Eaf`.
-    pub(crate) fn add(&mut self, annotation: &Annotation) -> Result<(), EafError> {
+    ///
+    /// Returns the index of the annotation in the tier.
+    pub(crate) fn add(&mut self, annotation: &Annotation) -> Result<usize, EafError> {
         // ensure annotation/tier types match
         if self.is_ref() != annotation.is_ref() {
             return Err(EafError::AnnotationTypeMismatch)
         }
 
-        if self.overlaps(annotation) {
+        // Referred annotations may share time span with their siblings.
+        if !annotation.is_ref() && self.overlaps(annotation) {
             return Err(EafError::AnnotationOverlap);
         }
 
-        // Add annotation as last item
-        self.annotations.push(annotation.to_owned());
-
-        // Then sort by derived time values
-        self.annotations
-            .par_sort_by_cached_key(|a|
-                // a.ts_val().0.ok_or(EafError::MissingTimeslotVal(a.id())));
-                a.ts_val().0.expect("Fatal: Annotation has no time value set."));
+        // Insert annotation by derived time value,
+        // after existing annotations with the same start time.
+        let start = annotation.ts_val().0;
+        let idx = self.annotations.partition_point(|a| a.ts_val().0 <= start);
+        self.annotations.insert(idx, annotation.to_owned());
 
-        Ok(())
+        Ok(idx)
     }
 
     /// Returns `true` if the annotation's timespan overlaps
-    /// with any existing annotation in the tier
+    /// with any existing annotation in the tier.
+    /// Only checks the annotations before and after the annotation's
+    /// position, since annotations are expected to be in time order.
     pub(crate) fn overlaps(&self, annotation: &Annotation) -> bool {
-        self.annotations.iter().any(|a| a.overlaps(annotation))
+        let start = annotation.ts_val().0;
+        let idx = self.annotations.partition_point(|a| a.ts_val().0 <= start);
+        [idx.checked_sub(1), Some(idx)].into_iter()
+            .flatten()
+            .filter_map(|i| self.annotations.get(i))
+            .any(|a| a.overlaps(annotation))
     }
 
     /// Returns tier attributes from other tier
@@ -0,0 +1,604 @@
+//! Time index for annotations, for fast time-based lookups,
+//! e.g. which annotations are active at a specific time,
+//! or which annotations overlap a time span.
+//!
+//! Implemented as an interval tree: a treap, i.e. a randomized balanced binary
+//! search tree, over annotation time spans ordered on start time, where each node
+//! holds the maximum end time in its subtree. Lookups are `O((k + 1) log n)`,
+//! where `k` is the number of hits. Adding and removing a span are `O(log n)`.
+//!
+//! Time spans are half-open, i.e. an annotation spanning `1000-2000`
+//! is active at 1000 ms, but not at 2000 ms. Zero-length annotations,
+//! e.g. TextGrid points, are active at their start time.
+//! Annotations without time values are not indexed.
+//!
+//! Spans are keyed on annotation ID, and lookups return annotation IDs rather than
+//! positions in `Eaf.tiers`, which change as tiers and annotations are edited.
+//!
+//! Built for all tiers and per tier as part of `Eaf::index()`, and kept up to date
+//! when annotations are added or removed, or time slot values are edited.
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// Time range lookup mode for `Eaf::annotations_in()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RangeMode {
+    /// Annotations fully contained within the time span.
+    Contained,
+    /// Annotations that overlap the time span.
+    Overlapping,
+    /// Annotations that start within the time span.
+    StartsWithin,
+}
+
+/// Heap priority for a treap node, derived from the annotation ID.
+/// IDs are unique, so priorities are effectively random, but stable.
+pub(crate) fn priority(id: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Indexed annotation time span.
+#[derive(Debug, Clone, PartialEq)]
+struct Span {
+    start: i64,
+    end: i64,
+    /// Annotation ID.
+    id: String,
+}
+
+impl Span {
+    /// Returns `true` if the span overlaps `start..end`.
+    /// Zero-length spans overlap if they start within `start..end`.
+    fn overlaps(&self, start: i64, end: i64) -> bool {
+        self.start < end && (self.end > start || (self.start == self.end && self.start >= start))
+    }
+
+    /// Sort key: start time, end time, then annotation ID.
+    fn key(&self) -> (i64, i64, &str) {
+        (self.start, self.end, &self.id)
+    }
+}
+
+/// Tree node for a time span.
+#[derive(Debug, Clone)]
+struct Node {
+    span: Span,
+    priority: u64,
+    left: Option<usize>,
+    right: Option<usize>,
+    /// Maximum end time in the subtree rooted at this node.
+    max_end: i64,
+}
+
+/// Interval tree over annotation time spans.
+/// Lookups return annotation IDs, sorted on start time,
+/// then end time, then annotation ID.
+#[derive(Debug, Clone, Default)]
+pub struct TimeIndex {
+    /// Tree nodes. Positions of removed nodes are reused.
+    nodes: Vec<Node>,
+    /// Positions in `nodes` not in use.
+    free: Vec<usize>,
+    root: Option<usize>,
+    /// Key: Annotation ID. Value: Position in `nodes`.
+    slots: HashMap<String, usize>,
+}
+
+impl TimeIndex {
+    /// Builds time index from `(start, end, annotation ID)`.
+    pub(crate) fn new(spans: Vec<(i64, i64, String)>) -> Self {
+        let mut spans: Vec<Span> = spans.into_iter()
+            .map(|(start, end, id)| Span { start, end, id })
+            .collect();
+        spans.sort_by(|a, b| a.key().cmp(&b.key()));
+
+        // Spans are sorted, so the tree can be built in linear time:
+        // the right spine of the tree built so far is kept on a stack.
+        let mut index = Self::default();
+        let mut spine: Vec<usize> = Vec::new();
+        for span in spans {
+            let node = index.alloc(span);
+            let mut left = None;
+            while let Some(last) = spine.last().copied()
+                && index.nodes[last].priority < index.nodes[node].priority
+            {
+                left = spine.pop();
+            }
+            index.nodes[node].left = left;
+            if let Some(last) = spine.last().copied() {
+                index.nodes[last].right = Some(node);
+            }
+            spine.push(node);
+        }
+        index.root = spine.first().copied();
+        index.update_all(index.root);
+
+        index
+    }
+
+    /// Adds a node for a span, not yet linked into the tree, and returns its position.
+    fn alloc(&mut self, span: Span) -> usize {
+        let node = Node {
+            priority: priority(&span.id),
+            max_end: span.end,
+            left: None,
+            right: None,
+            span,
+        };
+        let pos = match self.free.pop() {
+            Some(pos) => {
+                self.nodes[pos] = node;
+                pos
+            },
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        self.slots.insert(self.nodes[pos].span.id.to_owned(), pos);
+        pos
+    }
+
+    /// Sets maximum end time for a node from its span and its children.
+    fn update(&mut self, node: usize) {
+        let Node { span, left, right, .. } = &self.nodes[node];
+        let max_end = [*left, *right].into_iter()
+            .flatten()
+            .fold(span.end, |max, child| max.max(self.nodes[child].max_end));
+        self.nodes[node].max_end = max_end;
+    }
+
+    /// Sets maximum end time for all nodes in a subtree.
+    fn update_all(&mut self, node: Option<usize>) {
+        if let Some(n) = node {
+            self.update_all(self.nodes[n].left);
+            self.update_all(self.nodes[n].right);
+            self.update(n);
+        }
+    }
+
+    /// Splits a subtree into nodes that sort before node `at`, and the remaining nodes.
+    fn split(&mut self, node: Option<usize>, at: usize) -> (Option<usize>, Option<usize>) {
+        let Some(n) = node else {
+            return (None, None)
+        };
+        if self.nodes[n].span.key() < self.nodes[at].span.key() {
+            let (left, right) = self.split(self.nodes[n].right, at);
+            self.nodes[n].right = left;
+            self.update(n);
+            (Some(n), right)
+        } else {
+            let (left, right) = self.split(self.nodes[n].left, at);
+            self.nodes[n].left = right;
+            self.update(n);
+            (left, Some(n))
+        }
+    }
+
+    /// Joins two subtrees, where all nodes in `left` sort before those in `right`.
+    fn merge(&mut self, left: Option<usize>, right: Option<usize>) -> Option<usize> {
+        let (Some(l), Some(r)) = (left, right) else {
+            return left.or(right)
+        };
+        if self.nodes[l].priority > self.nodes[r].priority {
+            let merged = self.merge(self.nodes[l].right, right);
+            self.nodes[l].right = merged;
+            self.update(l);
+            Some(l)
+        } else {
+            let merged = self.merge(left, self.nodes[r].left);
+            self.nodes[r].left = merged;
+            self.update(r);
+            Some(r)
+        }
+    }
+
+    /// Links node `new` into a subtree, and returns the new subtree root.
+    fn insert_node(&mut self, node: Option<usize>, new: usize) -> Option<usize> {
+        let Some(n) = node else {
+            return Some(new)
+        };
+        if self.nodes[new].priority > self.nodes[n].priority {
+            let (left, right) = self.split(node, new);
+            self.nodes[new].left = left;
+            self.nodes[new].right = right;
+            self.update(new);
+            return Some(new)
+        }
+        if self.nodes[new].span.key() < self.nodes[n].span.key() {
+            let left = self.insert_node(self.nodes[n].left, new);
+            self.nodes[n].left = left;
+        } else {
+            let right = self.insert_node(self.nodes[n].right, new);
+            self.nodes[n].right = right;
+        }
+        self.update(n);
+        Some(n)
+    }
+
+    /// Unlinks node `target` from a subtree, and returns the new subtree root.
+    fn remove_node(&mut self, node: Option<usize>, target: usize) -> Option<usize> {
+        let n = node?;
+        if n == target {
+            return self.merge(self.nodes[n].left, self.nodes[n].right)
+        }
+        if self.nodes[target].span.key() < self.nodes[n].span.key() {
+            let left = self.remove_node(self.nodes[n].left, target);
+            self.nodes[n].left = left;
+        } else {
+            let right = self.remove_node(self.nodes[n].right, target);
+            self.nodes[n].right = right;
+        }
+        self.update(n);
+        Some(n)
+    }
+
+    /// Adds a time span for an annotation, replacing any existing span
+    /// for the same annotation ID.
+    pub(crate) fn insert(&mut self, start: i64, end: i64, id: &str) {
+        self.remove(id);
+        let new = self.alloc(Span { start, end, id: id.to_owned() });
+        self.root = self.insert_node(self.root, new);
+    }
+
+    /// Removes the time span for an annotation, if indexed.
+    pub(crate) fn remove(&mut self, id: &str) {
+        if let Some(pos) = self.slots.remove(id) {
+            self.root = self.remove_node(self.root, pos);
+            self.free.push(pos);
+        }
+    }
+
+    /// Shifts all time spans by `shift_ms` milliseconds.
+    pub(crate) fn shift_time(&mut self, shift_ms: i64) {
+        self.nodes.iter_mut()
+            .for_each(|n| {
+                n.span.start += shift_ms;
+                n.span.end += shift_ms;
+                n.max_end += shift_ms;
+            });
+    }
+
+    /// Collects IDs for spans in a subtree that overlap `start..end`.
+    fn overlap<'a>(&'a self, node: Option<usize>, start: i64, end: i64, hits: &mut Vec<&'a str>) {
+        let Some(n) = node else {
+            return
+        };
+        let node = &self.nodes[n];
+        // No span in this subtree ends after start,
+        // or is a zero-length span at start.
+        if node.max_end < start {
+            return
+        }
+        self.overlap(node.left, start, end, hits);
+        // Spans to the right start at or after this one.
+        if node.span.start < end {
+            if node.span.overlaps(start, end) {
+                hits.push(&node.span.id);
+            }
+            self.overlap(node.right, start, end, hits);
+        }
+    }
+
+    /// Collects IDs for spans in a subtree that start within `start..end`,
+    /// and, if `contained`, also end within it.
+    fn starting<'a>(&'a self, node: Option<usize>, start: i64, end: i64, contained: bool, hits: &mut Vec<&'a str>) {
+        let Some(n) = node else {
+            return
+        };
+        let node = &self.nodes[n];
+        if node.span.start >= start {
+            self.starting(node.left, start, end, contained, hits);
+        }
+        if node.span.start >= start
+            && node.span.start < end
+            && (!contained || node.span.end <= end)
+        {
+            hits.push(&node.span.id);
+        }
+        if node.span.start < end {
+            self.starting(node.right, start, end, contained, hits);
+        }
+    }
+
+    /// Returns the node with the latest end time among spans that start
+    /// at or before `ms`. On ties, the first node in sort order is returned.
+    fn latest_end(&self, ms: i64) -> Option<usize> {
+        // Latest end time so far, with either a node or a whole left subtree
+        // (`true`) holding it. These are visited in sort order, so the first
+        // one is kept on ties.
+        let mut latest: Option<(i64, usize, bool)> = None;
+        let mut node = self.root;
+        while let Some(n) = node {
+            let current = &self.nodes[n];
+            if current.span.start > ms {
+                node = current.left;
+                continue
+            }
+            let candidates = current.left
+                .map(|l| (self.nodes[l].max_end, l, true))
+                .into_iter()
+                .chain(std::iter::once((current.span.end, n, false)));
+            for candidate in candidates {
+                if latest.is_none_or(|(end, ..)| candidate.0 > end) {
+                    latest = Some(candidate)
+                }
+            }
+            node = current.right;
+        }
+
+        let (end, mut n, subtree) = latest?;
+        // Find the first node in the subtree with the latest end time.
+        if subtree {
+            loop {
+                let current = &self.nodes[n];
+                match (current.left, current.right) {
+                    (Some(l), _) if self.nodes[l].max_end == end => n = l,
+                    _ if current.span.end == end => break,
+                    (_, Some(r)) => n = r,
+                    _ => break,
+                }
+            }
+        }
+        Some(n)
+    }
+
+    /// Returns the first node with a span that starts after `ms`.
+    fn first_after(&self, ms: i64) -> Option<usize> {
+        let mut first = None;
+        let mut node = self.root;
+        while let Some(n) = node {
+            if self.nodes[n].span.start > ms {
+                first = Some(n);
+                node = self.nodes[n].left;
+            } else {
+                node = self.nodes[n].right;
+            }
+        }
+        first
+    }
+
+    /// Number of indexed annotations.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// IDs for annotations active at `ms` milliseconds,
+    /// including zero-length annotations at `ms`.
+    pub fn at(&self, ms: i64) -> Vec<&str> {
+        self.overlapping(ms, ms + 1)
+    }
+
+    /// IDs for annotations that overlap `start..end` milliseconds,
+    /// including zero-length annotations that start within `start..end`.
+    pub fn overlapping(&self, start: i64, end: i64) -> Vec<&str> {
+        let mut hits: Vec<&str> = Vec::new();
+        self.overlap(self.root, start, end, &mut hits);
+        hits
+    }
+
+    /// IDs for annotations in `start..end` milliseconds, according to `mode`.
+    pub fn range(&self, start: i64, end: i64, mode: RangeMode) -> Vec<&str> {
+        match mode {
+            RangeMode::Overlapping => self.overlapping(start, end),
+            RangeMode::StartsWithin | RangeMode::Contained => {
+                let mut hits: Vec<&str> = Vec::new();
+                self.starting(self.root, start, end, mode == RangeMode::Contained, &mut hits);
+                hits
+            }
+        }
+    }
+
+    /// ID for the annotation nearest to `ms` milliseconds, i.e. an annotation active
+    /// at `ms`, or otherwise the annotation with the closest start or end time.
+    /// On ties, the earlier annotation is returned.
+    pub fn nearest(&self, ms: i64) -> Option<&str> {
+        if let Some(hit) = self.at(ms).first() {
+            return Some(hit)
+        }
+        // No span is active at ms, so all spans starting before ms also end
+        // at or before ms. Closest end before ms, closest start after ms.
+        let before = self.latest_end(ms).map(|n| &self.nodes[n].span);
+        let after = self.first_after(ms).map(|n| &self.nodes[n].span);
+        let nearest = match (before, after) {
+            (Some(b), Some(a)) => if a.start - ms < ms - b.end { a } else { b },
+            (b, a) => b.or(a)?,
+        };
+        Some(&nearest.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Eaf;
+    use crate::eaf::test_util::{TestEaf, aligned, referred};
+
+    /// Deterministic pseudo-random time spans `(start, end, annotation ID)`,
+    /// including nested ones. Every tenth span is zero-length.
+    fn spans(n: usize) -> Vec<(i64, i64, String)> {
+        let mut seed: u64 = 42;
+        let mut next = |max: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((seed >> 33) % max) as i64
+        };
+        (0..n)
+            .map(|i| {
+                let start = next(10_000);
+                let duration = if i % 10 == 0 { 0 } else { next(1500) };
+                (start, start + duration, format!("a{i}"))
+            })
+            .collect()
+    }
+
+    /// Brute force lookups for comparison, sorted as `TimeIndex` results.
+    fn expected(spans: &[(i64, i64, String)], filter: impl Fn(i64, i64) -> bool) -> Vec<&str> {
+        let mut hits: Vec<&(i64, i64, String)> = spans.iter().filter(|(s, e, _)| filter(*s, *e)).collect();
+        hits.sort();
+        hits.into_iter().map(|(.., id)| id.as_str()).collect()
+    }
+
+    #[test]
+    fn lookups() {
+        let spans = spans(500);
+        let index = TimeIndex::new(spans.to_owned());
+        assert_eq!(index.len(), 500);
+
+        for ms in (-100..12_000).step_by(97) {
+            let at = |s: i64, e: i64| (s <= ms && ms < e) || (s == e && s == ms);
+            assert_eq!(index.at(ms), expected(&spans, at), "at {ms}");
+
+            let (start, end) = (ms, ms + 700);
+            let overlaps = |s: i64, e: i64| s < end && (start < e || (s == e && start <= s));
+            assert_eq!(index.overlapping(start, end), expected(&spans, overlaps));
+            assert_eq!(index.range(start, end, RangeMode::Overlapping), index.overlapping(start, end));
+            // Zero-length spans at `end` are not contained in the half-open range.
+            let contained = |s: i64, e: i64| start <= s && s < end && e <= end;
+            assert_eq!(index.range(start, end, RangeMode::Contained), expected(&spans, contained));
+            assert_eq!(index.range(start, end, RangeMode::StartsWithin), expected(&spans, |s, _| start <= s && s < end));
+
+            let distance = |(s, e, _): &(i64, i64, String)| match (ms < *s, ms >= *e) {
+                (true, _) => s - ms,
+                (_, true) => ms - e,
+                _ => 0,
+            };
+            let min = spans.iter().map(distance).min().unwrap();
+            let id = index.nearest(ms).unwrap();
+            let nearest = spans.iter().find(|s| s.2 == id).unwrap();
+            assert_eq!(distance(nearest), min, "nearest {ms}");
+        }
+    }
+
+    fn index(spans: &[(i64, i64, &str)]) -> TimeIndex {
+        TimeIndex::new(spans.iter().map(|(s, e, id)| (*s, *e, id.to_string())).collect())
+    }
+
+    #[test]
+    fn half_open() {
+        let index = index(&[(0, 1000, "a1"), (1000, 2000, "a2"), (3000, 4000, "a3")]);
+        assert_eq!(index.at(1000), vec!["a2"]);
+        assert_eq!(index.at(999), vec!["a1"]);
+        assert!(index.at(2500).is_empty());
+        assert_eq!(index.overlapping(1000, 3000), vec!["a2"]);
+        // Equal distance to previous end and next start
+        assert_eq!(index.nearest(2500), Some("a2"));
+        assert_eq!(index.nearest(2600), Some("a3"));
+        assert_eq!(index.nearest(-50), Some("a1"));
+        assert!(TimeIndex::default().nearest(0).is_none());
+        assert!(TimeIndex::default().at(0).is_empty());
+    }
+
+    #[test]
+    fn zero_length() {
+        let index = index(&[(0, 1000, "a1"), (500, 500, "p1"), (1000, 1000, "p2"), (3000, 3000, "p3")]);
+        assert_eq!(index.at(500), vec!["a1", "p1"]);
+        assert_eq!(index.at(1000), vec!["p2"]);
+        assert_eq!(index.overlapping(500, 1000), vec!["a1", "p1"]);
+        // Zero-length spans at the (exclusive) end of the range are not included
+        assert_eq!(index.overlapping(0, 500), vec!["a1"]);
+        assert_eq!(index.overlapping(1000, 3001), vec!["p2", "p3"]);
+        assert_eq!(index.range(400, 1000, RangeMode::Contained), vec!["p1"]);
+        assert_eq!(index.nearest(1000), Some("p2"));
+        assert_eq!(index.nearest(2900), Some("p3"));
+        // a1 and p2 both end at 1000 ms, a1 starts first
+        assert_eq!(index.nearest(1200), Some("a1"));
+    }
+
+    #[test]
+    fn edits() {
+        let mut spans = spans(300);
+        let mut index = TimeIndex::new(spans.to_owned());
+
+        // Appended last, inserted first, and in between
+        for (i, start) in [(300, 20_000), (301, -500), (302, 5000)] {
+            index.insert(start, start + 100, &format!("a{i}"));
+            spans.push((start, start + 100, format!("a{i}")));
+        }
+        // Removed, and moved, i.e. replaced
+        for i in (0..303).step_by(7) {
+            index.remove(&format!("a{i}"));
+        }
+        spans.retain(|(.., id)| id[1..].parse::<usize>().unwrap() % 7 != 0);
+        for (start, end, id) in spans.iter_mut().step_by(5) {
+            (*start, *end) = (*start / 2, *start / 2 + (*end - *start) * 2);
+            index.insert(*start, *end, id);
+        }
+        index.remove("a0");
+        index.shift_time(250);
+        spans.iter_mut().for_each(|(start, end, _)| {
+            *start += 250;
+            *end += 250;
+        });
+
+        let rebuilt = TimeIndex::new(spans.to_owned());
+        assert_eq!(index.len(), spans.len());
+        assert_eq!(
+            index.range(i64::MIN / 2, i64::MAX / 2, RangeMode::Contained),
+            expected(&spans, |_, _| true)
+        );
+        for ms in (-500..21_000).step_by(113) {
+            assert_eq!(index.at(ms), rebuilt.at(ms));
+            assert_eq!(index.overlapping(ms, ms + 300), rebuilt.overlapping(ms, ms + 300));
+            assert_eq!(index.range(ms, ms + 300, RangeMode::Contained), rebuilt.range(ms, ms + 300, RangeMode::Contained));
+            assert_eq!(index.nearest(ms), rebuilt.nearest(ms));
+        }
+    }
+
+    /// Main tiers `A` and `B`, with a `Symbolic_Association` tier `G` under `A`,
+    /// and main tier `P` with a zero-length annotation.
+    fn eaf() -> Eaf {
+        TestEaf::new()
+            .time_slots(&[0, 1000, 1500, 3000, 1200, 1200])
+            .tier("A", "default-lt", None, &[
+                aligned("a1", "ts1", "ts2", "one"),
+                aligned("a2", "ts3", "ts4", "two"),
+            ])
+            .linguistic_type("gloss", Some("Symbolic_Association"))
+            .tier("G", "gloss", Some("A"), &[referred("a3", "a2", "2")])
+            .tier("B", "default-lt", None, &[aligned("a4", "ts2", "ts4", "three")])
+            .tier("P", "default-lt", None, &[aligned("p1", "ts5", "ts6", "point")])
+            .eaf()
+    }
+
+    fn ids(annotations: Vec<&crate::Annotation>) -> Vec<&str> {
+        annotations.into_iter().map(|a| a.id()).collect()
+    }
+
+    #[test]
+    fn eaf_lookups() {
+        let mut eaf = eaf();
+        for _ in 0..2 {
+            // Referred annotations use the time values of their main annotation.
+            assert_eq!(ids(eaf.annotations_at(2000, None)), vec!["a4", "a2", "a3"]);
+            assert_eq!(ids(eaf.annotations_at(2000, Some("G"))), vec!["a3"]);
+            assert_eq!(ids(eaf.annotations_at(1000, Some("A"))), Vec::<&str>::new());
+            assert!(eaf.annotations_at(0, Some("X")).is_empty());
+
+            assert_eq!(ids(eaf.annotations_in(0, 1500, RangeMode::Overlapping, Some("A"))), vec!["a1"]);
+            assert_eq!(ids(eaf.annotations_in(0, 1500, RangeMode::Overlapping, None)), vec!["a1", "a4", "p1"]);
+            assert_eq!(ids(eaf.annotations_in(0, 1500, RangeMode::Contained, None)), vec!["a1", "p1"]);
+            assert_eq!(ids(eaf.annotations_in(1000, 2000, RangeMode::StartsWithin, Some("A"))), vec!["a2"]);
+
+            assert_eq!(eaf.nearest(1200, Some("A")).map(|a| a.id()), Some("a1"));
+            assert_eq!(eaf.nearest(1300, Some("A")).map(|a| a.id()), Some("a2"));
+            // a2, a3 and a4 all end at 3000 ms, a4 starts first
+            assert_eq!(eaf.nearest(5000, None).map(|a| a.id()), Some("a4"));
+
+            // Zero-length annotations
+            assert_eq!(ids(eaf.annotations_at(1200, Some("P"))), vec!["p1"]);
+            assert_eq!(ids(eaf.annotations_at(1200, None)), vec!["a4", "p1"]);
+            assert_eq!(eaf.nearest(1200, Some("P")).map(|a| a.id()), Some("p1"));
+            assert_eq!(eaf.nearest(0, Some("P")).map(|a| a.id()), Some("p1"));
+
+            // Same results with a temporary time index
+            eaf.indexed = false;
+        }
+    }
+}
@@ -79,6 +79,33 @@ impl TimeOrder {
         self.time_slots.push(time_slot.to_owned())
     }
 
+    /// Inserts time slot before the first time slot
+    /// with a larger time value, or last if there is none.
+    /// 
+    /// Does not check whether the time slot already exists.
+    pub fn insert(&mut self, time_slot: &TimeSlot) {
+        let idx = match time_slot.time_value {
+            Some(val) => self.time_slots.iter()
+                .position(|t| t.time_value.is_some_and(|v| v > val))
+                .unwrap_or(self.time_slots.len()),
+            None => self.time_slots.len(),
+        };
+        self.time_slots.insert(idx, time_slot.to_owned())
+    }
+
+    /// Inserts time slot directly after the time slot with specified ID,
+    /// or last if there is none. For unaligned time slots,
+    /// which can not be ordered on time value.
+    ///
+    /// Does not check whether the time slot already exists.
+    pub fn insert_after(&mut self, time_slot: &TimeSlot, time_slot_id: &str) {
+        let idx = self.time_slots.iter()
+            .position(|t| t.time_slot_id == time_slot_id)
+            .map(|i| i + 1)
+            .unwrap_or(self.time_slots.len());
+        self.time_slots.insert(idx, time_slot.to_owned())
+    }
+
     /// Extends existing time order with time slots.
     /// 
     /// Does not check whether the time slots
@@ -245,6 +272,21 @@ impl TimeOrder {
             .find_any(|t| t.time_slot_id == time_slot_id)
     }
 
+    /// Returns mutable reference to `TimeSlot` with specified ID,
+    /// or `None`if it does not exist.
+    pub fn find_mut(&mut self, time_slot_id: &str) -> Option<&mut TimeSlot> {
+        self.time_slots.iter_mut()
+            .find(|t| t.time_slot_id == time_slot_id)
+    }
+
+    /// Removes and returns `TimeSlot` with specified ID,
+    /// or `None`if it does not exist.
+    pub fn remove(&mut self, time_slot_id: &str) -> Option<TimeSlot> {
+        let idx = self.time_slots.iter()
+            .position(|t| t.time_slot_id == time_slot_id)?;
+        Some(self.time_slots.remove(idx))
+    }
+
     pub fn id_exists(&self, time_slot_id: &str) -> bool {
         self.find(time_slot_id).is_some()
     }
@@ -0,0 +1,665 @@
+//! Tokenized tiers, similar to ELAN's "Tokenize Tier".
+//!
+//! Each annotation in the source tier is split into tokens with a `Tokenizer`,
+//! and the tokens are added as annotations in a new tier referring to the source tier:
+//! - `Symbolic_Subdivision`: Referred annotations, where each token refers
+//!   to the annotation it was split from, and to the previous token
+//!   via `PREVIOUS_ANNOTATION`.
+//! - `Time_Subdivision`: Alignable annotations dividing the time span of
+//!   the annotation they were split from. Time values are interpolated
+//!   according to the character length of each token. The first and last
+//!   token share the source annotation's time slots.
+//!
+//! Existing referred tiers can also be converted between symbolic and
+//! time-aligned stereotypes, e.g. to get time values for each token
+//! in a `Symbolic_Subdivision` tier. Parent annotations are then divided
+//! according to the grapheme count of the annotations referring to them.
+
+use std::collections::HashMap;
+
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{Annotation, Eaf, EafError, LinguisticType, StereoType, Tier, TimeSlot};
+
+use super::{
+    edit::{descendant_tiers, sequences},
+    validate::tier_stereotype,
+};
+
+/// Splits annotation values into tokens.
+#[derive(Debug, Clone)]
+pub enum Tokenizer {
+    /// Splits on whitespace.
+    Whitespace,
+    /// Splits on matches for the regular expression,
+    /// e.g. `[-=]` for morpheme boundaries.
+    Regex(Regex),
+    /// Splits on word boundaries according to
+    /// [Unicode Standard Annex #29](https://www.unicode.org/reports/tr29/),
+    /// using <https://crates.io/crates/unicode-segmentation>.
+    /// Punctuation and whitespace are not included as tokens.
+    Words,
+}
+
+impl Tokenizer {
+    /// Splits `value` into tokens. Empty tokens are ignored.
+    pub fn tokens<'a>(&self, value: &'a str) -> Vec<&'a str> {
+        match self {
+            Self::Whitespace => value.split_whitespace().collect(),
+            Self::Regex(rx) => rx.split(value)
+                .filter(|t| !t.is_empty())
+                .collect(),
+            Self::Words => value.unicode_words().collect(),
+        }
+    }
+}
+
+/// Options for converting a symbolic tier to a time-aligned tier,
+/// see `Eaf::tier_to_aligned()`.
+#[derive(Debug, Clone)]
+pub struct AlignOptions {
+    /// Stereotype for the converted tier.
+    /// Either `Time_Subdivision` (default), or `Included_In`.
+    pub stereotype: StereoType,
+    /// Fixed weights as annotation ID -> weight,
+    /// used in place of grapheme count for the specified annotations.
+    /// E.g. a larger weight for a token followed by a pause.
+    pub weights: HashMap<String, f64>,
+    /// Set interpolated time values for new time slots.
+    /// If `false`, new time slots are unaligned.
+    pub time_values: bool,
+}
+
+impl Default for AlignOptions {
+    fn default() -> Self {
+        Self {
+            stereotype: StereoType::TimeSubdivision,
+            weights: HashMap::new(),
+            time_values: true,
+        }
+    }
+}
+
+/// Returns ID for a linguistic type with specified stereotype.
+/// Adds the linguistic type, named after the stereotype,
+/// and the corresponding constraint, if these do not exist.
+fn linguistic_type(eaf: &mut Eaf, stereotype: &StereoType) -> String {
+    if !eaf.constraints.iter().any(|c| &c.stereotype == stereotype) {
+        eaf.add_constraint(&stereotype.to_constraint())
+    }
+
+    let constraint = stereotype.to_string();
+    if let Some(lt) = eaf.linguistic_types.iter()
+        .find(|lt| lt.constraints.as_deref() == Some(constraint.as_str()))
+    {
+        return lt.linguistic_type_id.to_owned()
+    }
+
+    // Stereotype name may already be in use for another linguistic type
+    let lt_id = (1..)
+        .map(|n| match n {
+            1 => constraint.to_owned(),
+            n => format!("{constraint}-{n}"),
+        })
+        .find(|id| !eaf.linguistic_types.iter().any(|lt| &lt.linguistic_type_id == id))
+        .unwrap_or(constraint);
+    eaf.add_linguistic_type(&LinguisticType::new(&lt_id, Some(stereotype)), false);
+
+    lt_id
+}
+
+/// Token time slot boundaries, interpolated between `start` and `end`
+/// according to character length. Returns the `n - 1` inner boundaries
+/// for `n` tokens.
+fn boundaries(tokens: &[&str], start: i64, end: i64) -> Vec<i64> {
+    let lengths: Vec<i64> = tokens.iter()
+        .map(|t| t.chars().count() as i64)
+        .collect();
+    let total: i64 = lengths.iter().sum::<i64>().max(1);
+
+    lengths.iter()
+        .take(tokens.len().saturating_sub(1))
+        .scan(0, |cumulative, len| {
+            *cumulative += len;
+            Some(start + (end - start) * *cumulative / total)
+        })
+        .collect()
+}
+
+/// See `Eaf::tokenize_tier()`.
+pub(crate) fn tokenize_tier(
+    eaf: &mut Eaf,
+    tier_id: &str,
+    new_id: &str,
+    tokenizer: &Tokenizer,
+    time_subdivision: bool
+) -> Result<(), EafError> {
+    if !eaf.indexed {
+        eaf.index()
+    }
+    if eaf.index.t2idx.contains_key(new_id) {
+        return Err(EafError::TierIdExists(new_id.to_owned()))
+    }
+    if !eaf.derived {
+        eaf.derive()?
+    }
+
+    let tier = eaf.get_tier(tier_id)
+        .ok_or_else(|| EafError::TierIdInvalid(tier_id.to_owned()))?;
+    let stereotype = match time_subdivision {
+        true => StereoType::TimeSubdivision,
+        false => StereoType::SymbolicSubdivision,
+    };
+    // Time subdivisions require a time-aligned parent tier
+    if time_subdivision && tier_stereotype(eaf, tier).is_some_and(|st| !st.time_alignable()) {
+        return Err(EafError::TierParentInvalid((new_id.to_owned(), tier_id.to_owned())))
+    }
+
+    let tokenized: Vec<(&Annotation, Vec<&str>)> = tier.annotations.iter()
+        .map(|a| (a, tokenizer.tokens(a.to_str())))
+        .filter(|(_, tokens)| !tokens.is_empty())
+        .collect();
+
+    let len = tokenized.iter().map(|(_, tokens)| tokens.len()).sum();
+    let mut a_ids = match eaf.generate_a_id_multi(len) {
+        // No numerical annotation ID:s in use
+        ids if ids.len() < len => (1..=len).map(|n| format!("a{n}")).collect(),
+        ids => ids,
+    }.into_iter();
+    let mut ts_ids = match time_subdivision {
+        true => eaf.time_order.gen_id_multi(len - tokenized.len()),
+        false => Vec::new(),
+    }.into_iter();
+
+    let mut time_slots: Vec<TimeSlot> = Vec::new();
+    let mut annotations: Vec<Annotation> = Vec::new();
+    for (annotation, tokens) in tokenized.iter() {
+        let a_ids: Vec<String> = a_ids.by_ref().take(tokens.len()).collect();
+
+        if time_subdivision {
+            let (ts1, ts2) = annotation.ts_ref()
+                .ok_or(EafError::TimeslotRefsMissing)?;
+            let (Some(start), Some(end)) = annotation.ts_val() else {
+                return Err(EafError::TimeslotValMissing(annotation.id().to_owned()))
+            };
+            let mut ts_refs = vec![ts1];
+            for value in boundaries(tokens, start, end) {
+                let ts = TimeSlot::new(&ts_ids.next().unwrap_or_default(), Some(value));
+                ts_refs.push(ts.time_slot_id.to_owned());
+                time_slots.push(ts);
+            }
+            ts_refs.push(ts2);
+
+            for ((token, a_id), ts) in tokens.iter().zip(a_ids.iter()).zip(ts_refs.windows(2)) {
+                annotations.push(Annotation::alignable(token, a_id, &ts[0], &ts[1]));
+            }
+        } else {
+            for (i, (token, a_id)) in tokens.iter().zip(a_ids.iter()).enumerate() {
+                let previous = i.checked_sub(1).map(|p| a_ids[p].as_str());
+                annotations.push(Annotation::referred(token, a_id, annotation.id(), previous));
+            }
+        }
+    }
+
+    let lt_id = linguistic_type(eaf, &stereotype);
+    let tier = Tier::new(new_id, Some(&annotations), Some(&lt_id), Some(tier_id));
+
+    for ts in time_slots.iter() {
+        eaf.time_order.insert(ts);
+        eaf.index.insert_timeslot(&ts.time_slot_id, ts.time_value);
+    }
+
+    let t_idx = eaf.tiers.len();
+    eaf.index.insert_tier(&tier, t_idx);
+    eaf.tiers.push(tier);
+    let tier = &mut eaf.tiers[t_idx];
+    for annotation in tier.annotations.iter_mut() {
+        eaf.index.derive(annotation, &tier.tier_id)?;
+    }
+
+    Ok(())
+}
+
+/// Time values for `weights.len() + 1` boundaries dividing `start`-`end`
+/// proportionally to `weights`. Equal weights are used if all are zero.
+fn divide(weights: &[f64], start: i64, end: i64) -> Vec<i64> {
+    let total: f64 = weights.iter().sum();
+    let mut cumulative = 0.0;
+    let mut values = vec![start];
+    for (i, weight) in weights.iter().enumerate() {
+        cumulative += match total > 0.0 {
+            true => weight / total,
+            false => 1.0 / weights.len() as f64,
+        };
+        values.push(match i + 1 == weights.len() {
+            true => end,
+            false => start + ((end - start) as f64 * cumulative).round() as i64,
+        });
+    }
+    values
+}
+
+/// Re-indexes and derives the tier with specified ID and its dependent tiers,
+/// after annotations have been converted between alignable and referred.
+fn reindex(eaf: &mut Eaf, tier_id: &str) -> Result<(), EafError> {
+    let mut tier_ids = vec![tier_id.to_owned()];
+    tier_ids.extend(descendant_tiers(eaf, tier_id));
+
+    // Parent tiers first, since time spans are resolved via the parent annotation.
+    for id in tier_ids.iter() {
+        let Some(t_idx) = eaf.index.t2idx.get(id).copied() else {
+            continue
+        };
+        eaf.index.remove_tier(id);
+        eaf.index.insert_tier(&eaf.tiers[t_idx], t_idx);
+    }
+    for id in tier_ids.iter() {
+        let Some(t_idx) = eaf.index.t2idx.get(id).copied() else {
+            continue
+        };
+        let tier = &mut eaf.tiers[t_idx];
+        for annotation in tier.annotations.iter_mut() {
+            eaf.index.derive(annotation, &tier.tier_id)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// See `Eaf::tier_to_aligned()`.
+pub(crate) fn tier_to_aligned(eaf: &mut Eaf, tier_id: &str, options: &AlignOptions) -> Result<(), EafError> {
+    if !eaf.derived {
+        eaf.derive()?
+    }
+
+    let tier = eaf.get_tier(tier_id)
+        .ok_or_else(|| EafError::TierIdInvalid(tier_id.to_owned()))?;
+    if !matches!(tier_stereotype(eaf, tier), Some(StereoType::SymbolicSubdivision | StereoType::SymbolicAssociation))
+        || !options.stereotype.time_alignable()
+    {
+        return Err(EafError::TierStereotypeInvalid(tier_id.to_owned()))
+    }
+    let parent_id = tier.parent_ref.to_owned()
+        .ok_or_else(|| EafError::TierRefMissingParent(tier_id.to_owned()))?;
+    let parent = eaf.get_tier(&parent_id)
+        .ok_or_else(|| EafError::TierRefMissingParent(tier_id.to_owned()))?;
+    if tier_stereotype(eaf, parent).is_some_and(|st| !st.time_alignable()) {
+        return Err(EafError::TierParentInvalid((tier_id.to_owned(), parent_id)))
+    }
+
+    let groups = sequences(tier);
+    let included_in = options.stereotype == StereoType::IncludedIn;
+    let len: usize = groups.iter()
+        .map(|(_, group)| if included_in { group.len() + 1 } else { group.len() - 1 })
+        .sum();
+    let mut ts_ids = eaf.time_order.gen_id_multi(len).into_iter();
+
+    // New time slots, with the ID of the time slot to insert unaligned ones after.
+    let mut time_slots: Vec<(TimeSlot, String)> = Vec::new();
+    // Annotation index -> time slot references
+    let mut ts_refs: Vec<(usize, String, String)> = Vec::new();
+    for (ref_id, group) in groups.iter() {
+        let parent = eaf.get_annotation(ref_id)
+            .ok_or_else(|| EafError::AnnotationParentMissing(tier.annotations[group[0]].id().to_owned()))?;
+        let (ts1, ts2) = parent.ts_ref()
+            .ok_or(EafError::TimeslotRefsMissing)?;
+
+        let values: Vec<Option<i64>> = match (options.time_values, parent.ts_val()) {
+            (true, (Some(start), Some(end))) => {
+                let weights: Vec<f64> = group.iter()
+                    .map(|i| {
+                        let annotation = &tier.annotations[*i];
+                        options.weights.get(annotation.id())
+                            .copied()
+                            .unwrap_or_else(|| annotation.value().grapheme_count() as f64)
+                            .max(0.0)
+                    })
+                    .collect();
+                divide(&weights, start, end).into_iter().map(Some).collect()
+            },
+            (true, _) => return Err(EafError::TimeslotValMissing(parent.id().to_owned())),
+            (false, _) => vec![None; group.len() + 1],
+        };
+
+        // Time subdivisions share the parent annotation's boundaries.
+        let mut refs: Vec<String> = Vec::new();
+        for (i, value) in values.into_iter().enumerate() {
+            if !included_in && i == 0 {
+                refs.push(ts1.to_owned());
+            } else if !included_in && i == group.len() {
+                refs.push(ts2.to_owned());
+            } else {
+                let ts = TimeSlot::new(&ts_ids.next().unwrap_or_default(), value);
+                let after = refs.last().unwrap_or(&ts1).to_owned();
+                refs.push(ts.time_slot_id.to_owned());
+                time_slots.push((ts, after));
+            }
+        }
+        ts_refs.extend(group.iter()
+            .zip(refs.windows(2))
+            .map(|(i, refs)| (*i, refs[0].to_owned(), refs[1].to_owned())));
+    }
+
+    let lt_id = linguistic_type(eaf, &options.stereotype);
+
+    for (ts, after) in time_slots.iter() {
+        match ts.time_value {
+            Some(_) => eaf.time_order.insert(ts),
+            None => eaf.time_order.insert_after(ts, after),
+        }
+        eaf.index.insert_timeslot(&ts.time_slot_id, ts.time_value);
+    }
+
+    if let Some(tier) = eaf.get_tier_mut(tier_id) {
+        tier.linguistic_type_ref = lt_id;
+        for (a_idx, ts1, ts2) in ts_refs.iter() {
+            let annotation = &mut tier.annotations[*a_idx];
+            *annotation = annotation.to_alignable(ts1, ts2);
+        }
+    }
+
+    reindex(eaf, tier_id)
+}
+
+/// See `Eaf::tier_to_symbolic()`.
+pub(crate) fn tier_to_symbolic(eaf: &mut Eaf, tier_id: &str) -> Result<(), EafError> {
+    if !eaf.derived {
+        eaf.derive()?
+    }
+
+    let tier = eaf.get_tier(tier_id)
+        .ok_or_else(|| EafError::TierIdInvalid(tier_id.to_owned()))?;
+    if !matches!(tier_stereotype(eaf, tier), Some(StereoType::IncludedIn | StereoType::TimeSubdivision)) {
+        return Err(EafError::TierStereotypeInvalid(tier_id.to_owned()))
+    }
+    let parent_id = tier.parent_ref.to_owned()
+        .ok_or_else(|| EafError::TierRefMissingParent(tier_id.to_owned()))?;
+    // Time-aligned tiers require a time-aligned parent tier.
+    if let Some(child) = eaf.tiers.iter()
+        .filter(|t| t.parent_ref.as_deref() == Some(tier_id))
+        .find(|t| tier_stereotype(eaf, t).is_some_and(|st| st.time_alignable()))
+    {
+        return Err(EafError::TierParentInvalid((child.tier_id.to_owned(), tier_id.to_owned())))
+    }
+
+    // Annotations are matched with the parent annotation active at the midpoint
+    // of their time span. Unaligned annotations are matched using
+    // the aligned boundary, or the preceding annotation in the tier.
+    let mut refs: Vec<String> = Vec::new();
+    for annotation in tier.annotations.iter() {
+        let ms = match annotation.ts_val() {
+            (Some(start), Some(end)) => Some(start + (end - start) / 2),
+            (Some(start), None) => Some(start),
+            (None, Some(end)) => Some(end - 1),
+            (None, None) => None,
+        };
+        let ref_id = match ms {
+            Some(ms) => match eaf.annotations_at(ms, Some(&parent_id)).as_slice() {
+                [candidate] => candidate.id().to_owned(),
+                [] => return Err(EafError::AnnotationParentMissing(annotation.id().to_owned())),
+                _ => return Err(EafError::TierParentInvalid((tier_id.to_owned(), parent_id))),
+            },
+            None => refs.last()
+                .ok_or_else(|| EafError::AnnotationParentMissing(annotation.id().to_owned()))?
+                .to_owned(),
+        };
+        refs.push(ref_id);
+    }
+
+    let ts_ids: Vec<String> = tier.annotations.iter()
+        .filter_map(|a| a.ts_ref())
+        .flat_map(|(ts1, ts2)| [ts1, ts2])
+        .collect();
+
+    // Annotations with the same parent annotation form a sequence, in time order.
+    // Time slot order is used, since inner time slots may be unaligned.
+    let position: HashMap<&str, usize> = eaf.time_order.iter()
+        .enumerate()
+        .map(|(i, ts)| (ts.time_slot_id.as_str(), i))
+        .collect();
+    let mut order: Vec<usize> = (0..tier.annotations.len()).collect();
+    order.sort_by_key(|i| tier.annotations[*i].ts_ref()
+        .map(|(ts1, ts2)| (position.get(ts1.as_str()).copied(), position.get(ts2.as_str()).copied())));
+    let mut previous: Vec<Option<String>> = vec![None; tier.annotations.len()];
+    let mut last: HashMap<&str, &str> = HashMap::new();
+    for i in order.into_iter() {
+        previous[i] = last.insert(&refs[i], tier.annotations[i].id()).map(String::from);
+    }
+
+    let lt_id = linguistic_type(eaf, &StereoType::SymbolicSubdivision);
+
+    if let Some(tier) = eaf.get_tier_mut(tier_id) {
+        tier.linguistic_type_ref = lt_id;
+        for ((annotation, ref_id), previous) in tier.annotations.iter_mut().zip(refs.iter()).zip(previous.iter()) {
+            *annotation = annotation.to_referred(ref_id, previous.as_deref(), None);
+        }
+    }
+
+    reindex(eaf, tier_id)?;
+    super::edit::remove_unused_timeslots(eaf, &ts_ids);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eaf::test_util::{self, TestEaf, assert_valid};
+
+    fn eaf() -> Eaf {
+        TestEaf::new()
+            .time_slots(&[0, 700, 1000, 2000, 2600])
+            .tier("A", "default-lt", None, &[
+                test_util::aligned("a1", "ts1", "ts2", "the cat-s"),
+                test_util::aligned("a2", "ts2", "ts3", " "),
+                test_util::aligned("a3", "ts4", "ts5", "dog"),
+            ])
+            .eaf()
+    }
+
+    /// `(value, referred annotation ID, previous annotation ID)` for a symbolic tier.
+    fn referred<'a>(eaf: &'a Eaf, tier_id: &str) -> Vec<(&'a str, Option<&'a str>, Option<&'a str>)> {
+        eaf.get_tier(tier_id).unwrap().annotations.iter()
+            .map(|a| (a.to_str(), a.ref_id(), a.previous()))
+            .collect()
+    }
+
+    /// `(value, start, end)` for a time-aligned tier.
+    fn aligned<'a>(eaf: &'a Eaf, tier_id: &str) -> Vec<(&'a str, Option<i64>, Option<i64>)> {
+        eaf.get_tier(tier_id).unwrap().annotations.iter()
+            .map(|a| {
+                let (start, end) = a.ts_val();
+                (a.to_str(), start, end)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(Tokenizer::Whitespace.tokens(" a  b\tc\n"), ["a", "b", "c"]);
+        let rx = Tokenizer::Regex(Regex::new("[-=]").unwrap());
+        assert_eq!(rx.tokens("cat-s=PL"), ["cat", "s", "PL"]);
+        assert_eq!(rx.tokens("-a--b-"), ["a", "b"]);
+        assert_eq!(Tokenizer::Words.tokens("Hello, wörld! It's"), ["Hello", "wörld", "It's"]);
+        assert!(Tokenizer::Words.tokens(" ... ").is_empty());
+    }
+
+    #[test]
+    fn boundaries_by_length() {
+        assert_eq!(boundaries(&["ab", "c", "def"], 0, 600), [200, 300]);
+        assert_eq!(boundaries(&["ab"], 100, 600), Vec::<i64>::new());
+        assert!(boundaries(&[], 0, 600).is_empty());
+    }
+
+    #[test]
+    fn symbolic_subdivision() {
+        let mut eaf = eaf();
+        eaf.tokenize_tier("A", "W", &Tokenizer::Whitespace, false).unwrap();
+
+        let w = eaf.get_tier("W").unwrap();
+        assert_eq!(w.parent_ref.as_deref(), Some("A"));
+        let ids: Vec<&str> = w.annotations.iter().map(|a| a.id()).collect();
+        assert_eq!(ids, ["a4", "a5", "a6"]);
+        // Annotations without tokens are skipped.
+        assert_eq!(referred(&eaf, "W"), [
+            ("the", Some("a1"), None),
+            ("cat-s", Some("a1"), Some("a4")),
+            ("dog", Some("a3"), None),
+        ]);
+        assert_eq!(eaf.get_annotation("a5").unwrap().ts_val(), (Some(0), Some(700)));
+        assert!(eaf.linguistic_types.iter().any(|lt| lt.linguistic_type_id == "Symbolic_Subdivision"));
+        assert!(eaf.constraints.iter().any(|c| c.stereotype == StereoType::SymbolicSubdivision));
+        assert_valid(&eaf);
+
+        // Existing linguistic type is reused.
+        eaf.tokenize_tier("W", "M", &Tokenizer::Regex(Regex::new("-").unwrap()), false).unwrap();
+        assert_eq!(referred(&eaf, "M"), [
+            ("the", Some("a4"), None),
+            ("cat", Some("a5"), None),
+            ("s", Some("a5"), Some("a8")),
+            ("dog", Some("a6"), None),
+        ]);
+        assert_eq!(eaf.linguistic_types.len(), 2);
+        assert_valid(&eaf);
+    }
+
+    #[test]
+    fn time_subdivision() {
+        let mut eaf = eaf();
+        eaf.tokenize_tier("A", "T", &Tokenizer::Regex(Regex::new(r"[-\s]").unwrap()), true).unwrap();
+
+        // Time span divided according to character length.
+        assert_eq!(aligned(&eaf, "T"), [
+            ("the", Some(0), Some(300)),
+            ("cat", Some(300), Some(600)),
+            ("s", Some(600), Some(700)),
+            ("dog", Some(2000), Some(2600)),
+        ]);
+        // First and last token share the time slots of the tokenized annotation.
+        let t = eaf.get_tier("T").unwrap();
+        assert_eq!(t.annotations[0].ts_ref().unwrap().0, "ts1");
+        assert_eq!(t.annotations[2].ts_ref().unwrap().1, "ts2");
+        assert_eq!(t.annotations[3].ts_ref(), Some(("ts4".to_owned(), "ts5".to_owned())));
+        assert_eq!(eaf.time_order.len(), 7);
+        assert_eq!(eaf.annotations_at(400, Some("T"))[0].to_str(), "cat");
+        assert_valid(&eaf);
+    }
+
+    #[test]
+    fn invalid() {
+        let mut eaf = eaf();
+        assert!(matches!(eaf.tokenize_tier("B", "W", &Tokenizer::Words, false), Err(EafError::TierIdInvalid(_))));
+        assert!(matches!(eaf.tokenize_tier("A", "A", &Tokenizer::Words, false), Err(EafError::TierIdExists(_))));
+
+        eaf.tokenize_tier("A", "W", &Tokenizer::Words, false).unwrap();
+        // Time subdivisions of a symbolic tier.
+        assert!(matches!(eaf.tokenize_tier("W", "T", &Tokenizer::Words, true), Err(EafError::TierParentInvalid(_))));
+        assert_eq!(eaf.tiers.len(), 2);
+    }
+
+    #[test]
+    fn divide_weights() {
+        assert_eq!(divide(&[1.0, 3.0], 0, 100), [0, 25, 100]);
+        assert_eq!(divide(&[0.0, 0.0], 100, 200), [100, 150, 200]);
+        assert_eq!(divide(&[1.0, 1.0, 1.0], 0, 100), [0, 33, 67, 100]);
+    }
+
+    #[test]
+    fn aligned_round_trip() {
+        let mut eaf = eaf();
+        eaf.tokenize_tier("A", "W", &Tokenizer::Whitespace, false).unwrap();
+        eaf.tokenize_tier("W", "M", &Tokenizer::Regex(Regex::new("-").unwrap()), false).unwrap();
+        let symbolic = eaf.get_tier("W").unwrap().annotations.to_owned();
+
+        eaf.tier_to_aligned("W", &AlignOptions::default()).unwrap();
+        // Divided by grapheme count.
+        assert_eq!(aligned(&eaf, "W"), [
+            ("the", Some(0), Some(263)),
+            ("cat-s", Some(263), Some(700)),
+            ("dog", Some(2000), Some(2600)),
+        ]);
+        assert!(eaf.get_tier("W").unwrap().annotations.iter().all(|a| !a.is_ref()));
+        assert_eq!(eaf.time_order.len(), 6);
+        assert_eq!(eaf.annotations_at(300, Some("W"))[0].id(), "a5");
+        // Dependent annotations follow.
+        assert_eq!(eaf.get_annotation("a9").unwrap().ts_val(), (Some(263), Some(700)));
+        assert_valid(&eaf);
+
+        eaf.tier_to_symbolic("W").unwrap();
+        assert_eq!(referred(&eaf, "W"), [
+            ("the", Some("a1"), None),
+            ("cat-s", Some("a1"), Some("a4")),
+            ("dog", Some("a3"), None),
+        ]);
+        assert_eq!(eaf.time_order.len(), 5);
+        assert_valid(&eaf);
+        assert_eq!(eaf.get_tier("W").unwrap().annotations, symbolic);
+    }
+
+    #[test]
+    fn aligned_options() {
+        let mut eaf = eaf();
+        eaf.tokenize_tier("A", "W", &Tokenizer::Whitespace, false).unwrap();
+
+        let options = AlignOptions {
+            stereotype: StereoType::IncludedIn,
+            weights: HashMap::from([("a4".to_owned(), 5.0)]),
+            time_values: true,
+        };
+        eaf.tier_to_aligned("W", &options).unwrap();
+        assert_eq!(aligned(&eaf, "W"), [
+            ("the", Some(0), Some(350)),
+            ("cat-s", Some(350), Some(700)),
+            ("dog", Some(2000), Some(2600)),
+        ]);
+        // Included in annotations get time slots of their own.
+        let w = eaf.get_tier("W").unwrap();
+        assert_ne!(w.annotations[0].ts_ref().unwrap().0, "ts1");
+        assert_eq!(eaf.time_order.len(), 10);
+        assert_valid(&eaf);
+
+        // Unaligned time slots are positioned after the preceding boundary.
+        let mut eaf = self::eaf();
+        eaf.tokenize_tier("A", "W", &Tokenizer::Whitespace, false).unwrap();
+        let options = AlignOptions { time_values: false, ..AlignOptions::default() };
+        eaf.tier_to_aligned("W", &options).unwrap();
+        assert_eq!(aligned(&eaf, "W"), [
+            ("the", Some(0), None),
+            ("cat-s", None, Some(700)),
+            ("dog", Some(2000), Some(2600)),
+        ]);
+        let position = eaf.time_order.iter()
+            .position(|ts| ts.time_value.is_none())
+            .unwrap();
+        assert_eq!(eaf.time_order.iter().nth(position - 1).unwrap().time_slot_id, "ts1");
+
+        eaf.tier_to_symbolic("W").unwrap();
+        assert_eq!(referred(&eaf, "W"), [
+            ("the", Some("a1"), None),
+            ("cat-s", Some("a1"), Some("a4")),
+            ("dog", Some("a3"), None),
+        ]);
+        assert_eq!(eaf.time_order.len(), 5);
+        assert_valid(&eaf);
+    }
+
+    #[test]
+    fn convert_invalid() {
+        let mut eaf = eaf();
+        eaf.tokenize_tier("A", "W", &Tokenizer::Whitespace, false).unwrap();
+        eaf.tokenize_tier("A", "T", &Tokenizer::Whitespace, true).unwrap();
+
+        assert!(matches!(eaf.tier_to_aligned("A", &AlignOptions::default()), Err(EafError::TierStereotypeInvalid(_))));
+        assert!(matches!(eaf.tier_to_aligned("T", &AlignOptions::default()), Err(EafError::TierStereotypeInvalid(_))));
+        let options = AlignOptions { stereotype: StereoType::SymbolicAssociation, ..AlignOptions::default() };
+        assert!(matches!(eaf.tier_to_aligned("W", &options), Err(EafError::TierStereotypeInvalid(_))));
+        assert!(matches!(eaf.tier_to_symbolic("W"), Err(EafError::TierStereotypeInvalid(_))));
+        assert!(matches!(eaf.tier_to_symbolic("X"), Err(EafError::TierIdInvalid(_))));
+
+        // Time-aligned dependent tier.
+        eaf.tokenize_tier("T", "T2", &Tokenizer::Words, true).unwrap();
+        assert!(matches!(eaf.tier_to_symbolic("T"), Err(EafError::TierParentInvalid(_))));
+        assert_valid(&eaf);
+    }
+}
@@ -1,11 +1,21 @@
-//! A few simple validation checks for various sections of an EAF-file.
+//! Validation checks for various sections of an EAF-file.
 //! Does not substitue validating against the corresponding XML-schema.
+//!
+//! `Eaf::validate()` runs all checks and collects every issue found
+//! into a `ValidationReport`, rather than stopping at the first one.
+//! Issues either make the EAF invalid (`Severity::Error`),
+//! e.g. references to time slots that do not exist,
+//! or are likely unintended (`Severity::Warning`), e.g. unused time slots.
 
-use std::{ops::Range, collections::HashSet};
+use std::{ops::Range, collections::{HashMap, HashSet}, fmt::Display};
 
-use crate::{Annotation, TimeSlot, Tier, Eaf};
+use serde::Serialize;
+
+use crate::{Annotation, TimeSlot, Tier, Eaf, EafError, StereoType, CvType};
 
 /// Returns `true` if any annotation timespans overlap.
+/// Annotations without time values, e.g. those referring to
+/// unaligned time slots, are skipped.
 pub(crate) fn overlap(annotations: &[Annotation]) -> bool {
     let mut ranges: Vec<Range<i64>> = annotations.iter()
         .filter_map(|a| if let (Some(start), Some(end)) = a.ts_val() {
@@ -15,22 +25,11 @@ pub(crate) fn overlap(annotations: &[Annotation]) -> bool {
         })
         .collect();
 
-    // filter_map silently discards annotations
-    // with no explicit timestamps set (was possible on old EAF formats versions)
-    assert_eq!(ranges.len(), annotations.len(), "Failed to compile annotation timestamps for overlap check");
-
     // Sort ranges on start value to be able to check overlaps,
     // return true on first overlap between end of one and start of next
     // ranges.sort_by(|a, b| a.start.cmp(&b.start));
     ranges.sort_by_key(|r| r.start);
-    ranges.windows(2).any(|w| {
-        let overlaps = w[0].end > w[1].start;
-        if overlaps {
-            panic!("OVERLAP {w:?}");
-
-        }
-        overlaps
-    })
+    ranges.windows(2).any(|w| w[0].end > w[1].start)
 }
 
 
@@ -58,8 +57,1331 @@ pub(crate) fn tier_type_match(tier: &Tier) -> bool {
     true
 }
 
-/// Returns `false` is any timeslot
-/// referred to by an annotation does not exist.
-pub(crate) fn ts_exists(eaf: &Eaf) {
-    // let to =
+/// Issue severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub enum Severity {
+    /// Likely unintended, but does not make the EAF invalid.
+    Warning,
+    /// Invalid EAF. ELAN may fail to open the file,
+    /// or silently discard content.
+    Error,
+}
+
+/// Where in the EAF an issue was found.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub enum Location {
+    /// Document level, e.g. for issues with header or
+    /// top-level elements.
+    Document,
+    /// Tier ID.
+    Tier(String),
+    /// Annotation ID.
+    Annotation(String),
+    /// Time slot ID.
+    TimeSlot(String),
+    /// Linguistic type ID.
+    LinguisticType(String),
+    /// Controlled vocabulary ID.
+    ControlledVocabulary(String),
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::Document => write!(f, "document"),
+            Location::Tier(id) => write!(f, "tier '{id}'"),
+            Location::Annotation(id) => write!(f, "annotation '{id}'"),
+            Location::TimeSlot(id) => write!(f, "time slot '{id}'"),
+            Location::LinguisticType(id) => write!(f, "linguistic type '{id}'"),
+            Location::ControlledVocabulary(id) => write!(f, "controlled vocabulary '{id}'"),
+        }
+    }
+}
+
+/// Machine-readable issue code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueCode {
+    /// Annotation refers to a time slot that does not exist.
+    TimeslotRefMissing,
+    /// Time slot is not referred to by any annotation.
+    TimeslotUnused,
+    /// Annotation start time is larger than its end time.
+    TimeslotOrder,
+    /// Time slot ID occurs more than once.
+    DuplicateTimeslotId,
+    /// Tier ID occurs more than once.
+    DuplicateTierId,
+    /// Annotation ID occurs more than once.
+    DuplicateAnnotationId,
+    /// Linguistic type ID occurs more than once.
+    DuplicateLinguisticTypeId,
+    /// Tier refers to a linguistic type that does not exist.
+    LinguisticTypeMissing,
+    /// Linguistic type refers to an unknown stereotype.
+    StereotypeInvalid,
+    /// Linguistic type refers to a stereotype without
+    /// a corresponding constraint.
+    ConstraintMissing,
+    /// Linguistic type refers to a controlled vocabulary that does not exist.
+    ControlledVocabularyMissing,
+    /// Annotation refers to a controlled vocabulary entry that does not exist.
+    CvEntryMissing,
+    /// Tier refers to a parent tier that does not exist.
+    ParentTierMissing,
+    /// Tier is its own ancestor.
+    ParentCycle,
+    /// Main tier has a linguistic type with a constraint,
+    /// or referred tier has a linguistic type without one.
+    TierTypeMismatch,
+    /// Annotation type does not match that of its tier,
+    /// e.g. a referred annotation in a main tier.
+    AnnotationTypeMismatch,
+    /// Referred annotation refers to an annotation that does not exist.
+    AnnotationRefMissing,
+    /// Referred annotation refers to an annotation that is
+    /// not in the parent tier.
+    AnnotationRefInvalid,
+    /// Referred annotation refers to a previous annotation that does not exist.
+    PreviousAnnotationMissing,
+    /// Annotations in the same tier overlap.
+    AnnotationOverlap,
+    /// Time aligned annotation in a referred tier is not
+    /// within the time span of any annotation in the parent tier.
+    ParentAnnotationMissing,
+    /// `Time_Subdivision` annotations do not cover
+    /// the parent annotation's time span without gaps.
+    TimeSubdivisionGap,
+    /// `Included_In` annotation extends beyond the parent annotation's time span.
+    IncludedInBounds,
+    /// More than one `Symbolic_Association` annotation
+    /// refers to the same parent annotation.
+    SymbolicAssociationMultiple,
+    /// `Symbolic_Subdivision` annotations referring to the same parent
+    /// do not form a single `PREVIOUS_ANNOTATION` chain.
+    SymbolicSubdivisionChain,
+}
+
+impl IssueCode {
+    /// Returns issue code as a string, e.g. `timeslot_ref_missing`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueCode::TimeslotRefMissing => "timeslot_ref_missing",
+            IssueCode::TimeslotUnused => "timeslot_unused",
+            IssueCode::TimeslotOrder => "timeslot_order",
+            IssueCode::DuplicateTimeslotId => "duplicate_timeslot_id",
+            IssueCode::DuplicateTierId => "duplicate_tier_id",
+            IssueCode::DuplicateAnnotationId => "duplicate_annotation_id",
+            IssueCode::DuplicateLinguisticTypeId => "duplicate_linguistic_type_id",
+            IssueCode::LinguisticTypeMissing => "linguistic_type_missing",
+            IssueCode::StereotypeInvalid => "stereotype_invalid",
+            IssueCode::ConstraintMissing => "constraint_missing",
+            IssueCode::ControlledVocabularyMissing => "controlled_vocabulary_missing",
+            IssueCode::CvEntryMissing => "cv_entry_missing",
+            IssueCode::ParentTierMissing => "parent_tier_missing",
+            IssueCode::ParentCycle => "parent_cycle",
+            IssueCode::TierTypeMismatch => "tier_type_mismatch",
+            IssueCode::AnnotationTypeMismatch => "annotation_type_mismatch",
+            IssueCode::AnnotationRefMissing => "annotation_ref_missing",
+            IssueCode::AnnotationRefInvalid => "annotation_ref_invalid",
+            IssueCode::PreviousAnnotationMissing => "previous_annotation_missing",
+            IssueCode::AnnotationOverlap => "annotation_overlap",
+            IssueCode::ParentAnnotationMissing => "parent_annotation_missing",
+            IssueCode::TimeSubdivisionGap => "time_subdivision_gap",
+            IssueCode::IncludedInBounds => "included_in_bounds",
+            IssueCode::SymbolicAssociationMultiple => "symbolic_association_multiple",
+            IssueCode::SymbolicSubdivisionChain => "symbolic_subdivision_chain",
+        }
+    }
+}
+
+impl Display for IssueCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A single validation issue.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    pub code: IssueCode,
+    pub location: Location,
+    /// Human-readable description.
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn error(code: IssueCode, location: Location, message: String) -> Self {
+        Self { severity: Severity::Error, code, location, message }
+    }
+
+    fn warning(code: IssueCode, location: Location, message: String) -> Self {
+        Self { severity: Severity::Warning, code, location, message }
+    }
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{severity}[{}] {}: {}", self.code, self.location, self.message)
+    }
+}
+
+/// All issues found when validating an EAF. See `Eaf::validate()`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for issue in self.issues.iter() {
+            writeln!(f, "{issue}")?;
+        }
+        Ok(())
+    }
+}
+
+impl ValidationReport {
+    /// Runs all checks on `eaf`.
+    pub fn new(eaf: &Eaf) -> Self {
+        let mut issues: Vec<ValidationIssue> = Vec::new();
+
+        issues.extend(duplicate_ids(eaf));
+        issues.extend(ts_exists(eaf));
+        issues.extend(ts_unused(eaf));
+        issues.extend(ts_order(eaf));
+        issues.extend(lt_exists(eaf));
+        issues.extend(constraints_exist(eaf));
+        issues.extend(cv_exists(eaf));
+        issues.extend(parent_tiers(eaf));
+        issues.extend(annotation_types(eaf));
+        issues.extend(ref_exists(eaf));
+        issues.extend(overlaps(eaf));
+        issues.extend(stereotype_violations(eaf));
+
+        Self { issues }
+    }
+
+    /// Returns `true` if no errors were found.
+    /// Warnings are ignored.
+    pub fn is_valid(&self) -> bool {
+        !self.issues.iter().any(|i| i.severity == Severity::Error)
+    }
+
+    /// Returns `true` if no issues were found.
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Returns number of issues.
+    pub fn len(&self) -> usize {
+        self.issues.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter()
+    }
+
+    /// Returns issues with severity `Severity::Error`.
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(|i| i.severity == Severity::Error)
+    }
+
+    /// Returns issues with severity `Severity::Warning`.
+    pub fn warnings(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(|i| i.severity == Severity::Warning)
+    }
+
+    /// Returns issues with the specified code.
+    pub fn filter(&self, code: IssueCode) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(move |i| i.code == code)
+    }
+}
+
+/// Returns IDs that occur more than once.
+fn duplicates<'a>(ids: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut dupes: Vec<&str> = Vec::new();
+    for id in ids {
+        if !seen.insert(id) && !dupes.contains(&id) {
+            dupes.push(id);
+        }
+    }
+    dupes
+}
+
+/// Duplicate time slot, tier, annotation, and linguistic type IDs.
+fn duplicate_ids(eaf: &Eaf) -> Vec<ValidationIssue> {
+    let mut issues: Vec<ValidationIssue> = Vec::new();
+
+    for id in duplicates(eaf.time_order.iter().map(|ts| ts.time_slot_id.as_str())) {
+        issues.push(ValidationIssue::error(
+            IssueCode::DuplicateTimeslotId,
+            Location::TimeSlot(id.to_owned()),
+            "Time slot ID occurs more than once".to_owned()
+        ));
+    }
+    for id in duplicates(eaf.tiers.iter().map(|t| t.tier_id.as_str())) {
+        issues.push(ValidationIssue::error(
+            IssueCode::DuplicateTierId,
+            Location::Tier(id.to_owned()),
+            "Tier ID occurs more than once".to_owned()
+        ));
+    }
+    for id in duplicates(eaf.tiers.iter().flat_map(|t| t.iter().map(|a| a.id()))) {
+        issues.push(ValidationIssue::error(
+            IssueCode::DuplicateAnnotationId,
+            Location::Annotation(id.to_owned()),
+            "Annotation ID occurs more than once".to_owned()
+        ));
+    }
+    for id in duplicates(eaf.linguistic_types.iter().map(|lt| lt.linguistic_type_id.as_str())) {
+        issues.push(ValidationIssue::error(
+            IssueCode::DuplicateLinguisticTypeId,
+            Location::LinguisticType(id.to_owned()),
+            "Linguistic type ID occurs more than once".to_owned()
+        ));
+    }
+
+    issues
+}
+
+/// Annotations referring to time slots that do not exist.
+fn ts_exists(eaf: &Eaf) -> Vec<ValidationIssue> {
+    let ts_ids: HashSet<&str> = eaf.time_order.iter()
+        .map(|ts| ts.time_slot_id.as_str())
+        .collect();
+
+    eaf.tiers.iter()
+        .flat_map(|t| t.iter())
+        .filter_map(|a| a.ts_ref().map(|refs| (a, refs)))
+        .flat_map(|(a, (ref1, ref2))| [ref1, ref2].into_iter()
+            .filter(|r| !ts_ids.contains(r.as_str()))
+            .map(|r| ValidationIssue::error(
+                IssueCode::TimeslotRefMissing,
+                Location::Annotation(a.id().to_owned()),
+                format!("Time slot '{r}' does not exist")
+            ))
+            .collect::<Vec<_>>()
+        )
+        .collect()
+}
+
+/// Time slots that are not referred to by any annotation.
+fn ts_unused(eaf: &Eaf) -> Vec<ValidationIssue> {
+    let ts_refs: HashSet<String> = eaf.tiers.iter()
+        .flat_map(|t| t.iter())
+        .filter_map(|a| a.ts_ref())
+        .flat_map(|(ref1, ref2)| [ref1, ref2])
+        .collect();
+
+    eaf.time_order.iter()
+        .filter(|ts| !ts_refs.contains(&ts.time_slot_id))
+        .map(|ts| ValidationIssue::warning(
+            IssueCode::TimeslotUnused,
+            Location::TimeSlot(ts.time_slot_id.to_owned()),
+            "Time slot is not used by any annotation".to_owned()
+        ))
+        .collect()
+}
+
+/// Annotations with a start time larger than the end time.
+fn ts_order(eaf: &Eaf) -> Vec<ValidationIssue> {
+    let ts_vals = ts_values(eaf);
+
+    eaf.tiers.iter()
+        .flat_map(|t| t.iter())
+        .filter_map(|a| {
+            let (ref1, ref2) = a.ts_ref()?;
+            let (t1, t2) = (ts_vals.get(ref1.as_str())?, ts_vals.get(ref2.as_str())?);
+            (t1 > t2).then(|| ValidationIssue::error(
+                IssueCode::TimeslotOrder,
+                Location::Annotation(a.id().to_owned()),
+                format!("Start time {t1} is larger than end time {t2}")
+            ))
+        })
+        .collect()
+}
+
+/// Returns time slot values as `HashMap<time_slot_ID, time_value>`.
+/// Time slots without a time value are not included.
+fn ts_values(eaf: &Eaf) -> HashMap<&str, i64> {
+    eaf.time_order.iter()
+        .filter_map(|ts| ts.time_value.map(|v| (ts.time_slot_id.as_str(), v)))
+        .collect()
+}
+
+/// Tiers referring to linguistic types that do not exist.
+fn lt_exists(eaf: &Eaf) -> Vec<ValidationIssue> {
+    let lt_ids: HashSet<&str> = eaf.linguistic_types.iter()
+        .map(|lt| lt.linguistic_type_id.as_str())
+        .collect();
+
+    eaf.tiers.iter()
+        .filter(|t| !lt_ids.contains(t.linguistic_type_ref.as_str()))
+        .map(|t| ValidationIssue::error(
+            IssueCode::LinguisticTypeMissing,
+            Location::Tier(t.tier_id.to_owned()),
+            format!("Linguistic type '{}' does not exist", t.linguistic_type_ref)
+        ))
+        .collect()
+}
+
+/// Returns stereotype for a constraint string,
+/// e.g. `Symbolic_Association`, or `None` if not recognized.
+fn stereotype(constraint: &str) -> Option<StereoType> {
+    [
+        StereoType::IncludedIn,
+        StereoType::SymbolicAssociation,
+        StereoType::SymbolicSubdivision,
+        StereoType::TimeSubdivision,
+    ].into_iter()
+        .find(|s| s.to_string() == constraint)
+}
+
+/// Linguistic types referring to unknown stereotypes,
+/// or stereotypes without a corresponding constraint.
+fn constraints_exist(eaf: &Eaf) -> Vec<ValidationIssue> {
+    let mut issues: Vec<ValidationIssue> = Vec::new();
+
+    for lt in eaf.linguistic_types.iter() {
+        let Some(constraint) = lt.constraints.as_deref() else {
+            continue
+        };
+        match stereotype(constraint) {
+            Some(st) => if !eaf.constraints.iter().any(|c| c.stereotype == st) {
+                issues.push(ValidationIssue::error(
+                    IssueCode::ConstraintMissing,
+                    Location::LinguisticType(lt.linguistic_type_id.to_owned()),
+                    format!("No constraint for stereotype '{constraint}'")
+                ))
+            },
+            None => issues.push(ValidationIssue::error(
+                IssueCode::StereotypeInvalid,
+                Location::LinguisticType(lt.linguistic_type_id.to_owned()),
+                format!("Unknown stereotype '{constraint}'")
+            )),
+        }
+    }
+
+    issues
+}
+
+/// Linguistic types referring to controlled vocabularies that do not exist,
+/// and annotations referring to controlled vocabulary entries that do not exist.
+///
+/// Entry references are only checked for controlled vocabularies
+/// that are defined in the EAF, not for external ones (`EXT_REF`).
+fn cv_exists(eaf: &Eaf) -> Vec<ValidationIssue> {
+    let mut issues: Vec<ValidationIssue> = Vec::new();
+
+    for lt in eaf.linguistic_types.iter() {
+        if let Some(cv_id) = &lt.controlled_vocabulary
+            && !eaf.controlled_vocabularies.iter().any(|cv| &cv.cv_id == cv_id)
+        {
+            issues.push(ValidationIssue::error(
+                IssueCode::ControlledVocabularyMissing,
+                Location::LinguisticType(lt.linguistic_type_id.to_owned()),
+                format!("Controlled vocabulary '{cv_id}' does not exist")
+            ))
+        }
+    }
+
+    // Only check entry references if all vocabularies are local.
+    if eaf.controlled_vocabularies.iter().any(|cv| cv.ext_ref.is_some()) {
+        return issues
+    }
+    let cve_ids: HashSet<&str> = eaf.controlled_vocabularies.iter()
+        .flat_map(|cv| cv.iter())
+        .filter_map(|cv_type| match cv_type {
+            CvType::CvEntryMl(entry) => Some(entry.cve_id.as_str()),
+            _ => None
+        })
+        .collect();
+
+    for annotation in eaf.tiers.iter().flat_map(|t| t.iter()) {
+        if let Some(cve_ref) = annotation.cve_ref()
+            && !cve_ids.contains(cve_ref.as_str())
+        {
+            issues.push(ValidationIssue::error(
+                IssueCode::CvEntryMissing,
+                Location::Annotation(annotation.id().to_owned()),
+                format!("Controlled vocabulary entry '{cve_ref}' does not exist")
+            ))
+        }
+    }
+
+    issues
+}
+
+/// Tiers referring to parent tiers that do not exist,
+/// tiers that are their own ancestor, and tiers with a linguistic type
+/// that does not match their position in the tier hierarchy.
+fn parent_tiers(eaf: &Eaf) -> Vec<ValidationIssue> {
+    let mut issues: Vec<ValidationIssue> = Vec::new();
+
+    let parents: HashMap<&str, Option<&str>> = eaf.tiers.iter()
+        .map(|t| (t.tier_id.as_str(), t.parent_ref.as_deref()))
+        .collect();
+
+    for tier in eaf.tiers.iter() {
+        let Some(parent) = tier.parent_ref.as_deref() else {
+            continue
+        };
+
+        if !parents.contains_key(parent) {
+            issues.push(ValidationIssue::error(
+                IssueCode::ParentTierMissing,
+                Location::Tier(tier.tier_id.to_owned()),
+                format!("Parent tier '{parent}' does not exist")
+            ));
+            continue
+        }
+
+        // Walk up the hierarchy. A cycle is reported for every tier in it.
+        let mut visited: HashSet<&str> = HashSet::from([tier.tier_id.as_str()]);
+        let mut current = Some(parent);
+        while let Some(id) = current {
+            if !visited.insert(id) {
+                if id == tier.tier_id {
+                    issues.push(ValidationIssue::error(
+                        IssueCode::ParentCycle,
+                        Location::Tier(tier.tier_id.to_owned()),
+                        "Tier is its own ancestor".to_owned()
+                    ));
+                }
+                break
+            }
+            current = parents.get(id).copied().flatten();
+        }
+    }
+
+    // Main tiers should have a linguistic type without constraints,
+    // referred tiers one with.
+    for tier in eaf.tiers.iter() {
+        let Some(lt) = eaf.linguistic_types.iter()
+            .find(|lt| lt.linguistic_type_id == tier.linguistic_type_ref) else {
+            continue
+        };
+        let message = match (tier.is_ref(), &lt.constraints) {
+            (false, Some(c)) => format!("Main tier has linguistic type with constraint '{c}'"),
+            (true, None) => "Referred tier has linguistic type without constraint".to_owned(),
+            _ => continue
+        };
+        issues.push(ValidationIssue::error(
+            IssueCode::TierTypeMismatch,
+            Location::Tier(tier.tier_id.to_owned()),
+            message
+        ));
+    }
+
+    issues
+}
+
+/// Returns `true` if tier is expected to contain referred annotations,
+/// i.e. it has a linguistic type with a symbolic stereotype.
+fn symbolic(eaf: &Eaf, tier: &Tier) -> bool {
+    eaf.linguistic_types.iter()
+        .find(|lt| lt.linguistic_type_id == tier.linguistic_type_ref)
+        .and_then(|lt| lt.constraints.as_deref())
+        .and_then(stereotype)
+        .map(|st| !st.time_alignable())
+        .unwrap_or(false)
+}
+
+/// Annotations whose type does not match that of their tier,
+/// i.e. referred annotations in main tiers or time aligned referred tiers,
+/// or alignable annotations in symbolic tiers.
+fn annotation_types(eaf: &Eaf) -> Vec<ValidationIssue> {
+    let mut issues: Vec<ValidationIssue> = Vec::new();
+
+    for tier in eaf.tiers.iter() {
+        let symbolic = tier.is_ref() && symbolic(eaf, tier);
+        for annotation in tier.iter().filter(|a| a.is_ref() != symbolic) {
+            let message = match annotation.is_ref() {
+                true => format!("Referred annotation in time alignable tier '{}'", tier.tier_id),
+                false => format!("Alignable annotation in symbolic tier '{}'", tier.tier_id),
+            };
+            issues.push(ValidationIssue::error(
+                IssueCode::AnnotationTypeMismatch,
+                Location::Annotation(annotation.id().to_owned()),
+                message
+            ));
+        }
+    }
+
+    issues
+}
+
+/// Referred annotations whose parent annotation, or previous annotation,
+/// does not exist, or whose parent annotation is not in the parent tier.
+fn ref_exists(eaf: &Eaf) -> Vec<ValidationIssue> {
+    let mut issues: Vec<ValidationIssue> = Vec::new();
+
+    // Annotation ID -> tier ID
+    let a2t: HashMap<&str, &str> = eaf.tiers.iter()
+        .flat_map(|t| t.iter().map(|a| (a.id(), t.tier_id.as_str())))
+        .collect();
+
+    for tier in eaf.tiers.iter() {
+        for annotation in tier.iter() {
+            if let Some(ref_id) = annotation.ref_id() {
+                match a2t.get(ref_id) {
+                    None => issues.push(ValidationIssue::error(
+                        IssueCode::AnnotationRefMissing,
+                        Location::Annotation(annotation.id().to_owned()),
+                        format!("Parent annotation '{ref_id}' does not exist")
+                    )),
+                    Some(t_id) if tier.parent_ref.as_deref() != Some(t_id) => issues.push(ValidationIssue::error(
+                        IssueCode::AnnotationRefInvalid,
+                        Location::Annotation(annotation.id().to_owned()),
+                        format!("Parent annotation '{ref_id}' is in tier '{t_id}', not in the parent tier")
+                    )),
+                    _ => ()
+                }
+            }
+            if let Some(prev_id) = annotation.previous()
+                && !a2t.contains_key(prev_id)
+            {
+                issues.push(ValidationIssue::error(
+                    IssueCode::PreviousAnnotationMissing,
+                    Location::Annotation(annotation.id().to_owned()),
+                    format!("Previous annotation '{prev_id}' does not exist")
+                ))
+            }
+        }
+    }
+
+    issues
+}
+
+/// Overlapping alignable annotations in the same tier.
+/// Annotations with unaligned time slots (no time value) are ignored.
+fn overlaps(eaf: &Eaf) -> Vec<ValidationIssue> {
+    let ts_vals = ts_values(eaf);
+    let mut issues: Vec<ValidationIssue> = Vec::new();
+
+    for tier in eaf.tiers.iter() {
+        // (annotation_id, start, end)
+        let mut spans: Vec<(&str, i64, i64)> = tier.iter()
+            .filter_map(|a| {
+                let (ref1, ref2) = a.ts_ref()?;
+                Some((a.id(), *ts_vals.get(ref1.as_str())?, *ts_vals.get(ref2.as_str())?))
+            })
+            .collect();
+        spans.sort_by_key(|(_, start, end)| (*start, *end));
+
+        // Compare with the latest end so far, since an annotation
+        // may overlap more than the one immediately before it.
+        let mut latest: Option<(&str, i64)> = None;
+        for (id, start, end) in spans {
+            if let Some((prev_id, prev_end)) = latest
+                && prev_end > start
+            {
+                issues.push(ValidationIssue::error(
+                    IssueCode::AnnotationOverlap,
+                    Location::Annotation(id.to_owned()),
+                    format!("Overlaps annotation '{prev_id}' in tier '{}'", tier.tier_id)
+                ))
+            }
+            if latest.map(|(_, e)| end > e).unwrap_or(true) {
+                latest = Some((id, end));
+            }
+        }
+    }
+
+    issues
+}
+
+
+/// Returns the stereotype for the tier's linguistic type,
+/// or `None` for main tiers and unknown linguistic types.
+pub(crate) fn tier_stereotype(eaf: &Eaf, tier: &Tier) -> Option<StereoType> {
+    tier.parent_ref.as_ref()?;
+    eaf.linguistic_types.iter()
+        .find(|lt| lt.linguistic_type_id == tier.linguistic_type_ref)
+        .and_then(|lt| lt.constraints.as_deref())
+        .and_then(stereotype)
+}
+
+/// Returns indices for tiers with the specified stereotype,
+/// and their parent tier, as `(tier_index, parent_tier_index)`.
+fn stereotype_tiers(eaf: &Eaf, st: StereoType) -> Vec<(usize, usize)> {
+    eaf.tiers.iter()
+        .enumerate()
+        .filter(|(_, t)| tier_stereotype(eaf, t).as_ref() == Some(&st))
+        .filter_map(|(i, t)| {
+            let parent = t.parent_ref.as_deref()?;
+            let p = eaf.tiers.iter().position(|pt| pt.tier_id == parent)?;
+            Some((i, p))
+        })
+        .collect()
+}
+
+/// Alignable annotation time span, with time slot references.
+#[derive(Debug, Clone)]
+struct Span {
+    /// Annotation index in tier.
+    idx: usize,
+    ts_ref1: String,
+    ts_ref2: String,
+    start: Option<i64>,
+    end: Option<i64>,
+}
+
+/// Returns time spans for alignable annotations in a tier.
+fn spans(tier: &Tier, ts_vals: &HashMap<&str, i64>) -> Vec<Span> {
+    tier.iter()
+        .enumerate()
+        .filter_map(|(idx, a)| {
+            let (ts_ref1, ts_ref2) = a.ts_ref()?;
+            Some(Span {
+                idx,
+                start: ts_vals.get(ts_ref1.as_str()).copied(),
+                end: ts_vals.get(ts_ref2.as_str()).copied(),
+                ts_ref1,
+                ts_ref2,
+            })
+        })
+        .collect()
+}
+
+/// Returns the index of the span in `parents` that contains `start`, `end`.
+fn containing(parents: &[Span], start: i64, end: i64) -> Option<usize> {
+    parents.iter().position(|p| match (p.start, p.end) {
+        (Some(p_start), Some(p_end)) => p_start <= start && end <= p_end,
+        _ => false
+    })
+}
+
+/// Time subdivision chain, i.e. annotations where one annotation's
+/// end time slot is the next annotation's start time slot.
+#[derive(Debug)]
+struct Chain {
+    /// Spans in chronological order.
+    spans: Vec<Span>,
+}
+
+impl Chain {
+    fn first(&self) -> &Span {
+        &self.spans[0]
+    }
+
+    fn last(&self) -> &Span {
+        &self.spans[self.spans.len() - 1]
+    }
+}
+
+/// Returns time subdivision chains for alignable annotations in `tier`.
+/// Inner time slots in a chain may be unaligned (no time value).
+/// Chains are split at `breaks`, e.g. time slots where adjacent
+/// parent annotations meet.
+fn chains(tier: &Tier, ts_vals: &HashMap<&str, i64>, breaks: &HashSet<&str>) -> Vec<Chain> {
+    let spans = spans(tier, ts_vals);
+    let ends: HashSet<&str> = spans.iter()
+        .map(|s| s.ts_ref2.as_str())
+        .filter(|ts| !breaks.contains(ts))
+        .collect();
+    // Start time slot ID -> span index
+    let mut starts: HashMap<&str, usize> = HashMap::new();
+    for (i, span) in spans.iter().enumerate() {
+        starts.entry(span.ts_ref1.as_str()).or_insert(i);
+    }
+
+    let mut visited: HashSet<usize> = HashSet::new();
+    let mut chains: Vec<Chain> = Vec::new();
+    for (i, span) in spans.iter().enumerate() {
+        if ends.contains(span.ts_ref1.as_str()) {
+            continue
+        }
+        let mut chain = Chain { spans: Vec::new() };
+        let mut next = Some(i);
+        while let Some(n) = next {
+            if !visited.insert(n) {
+                break
+            }
+            chain.spans.push(spans[n].to_owned());
+            next = starts.get(spans[n].ts_ref2.as_str())
+                .filter(|_| !breaks.contains(spans[n].ts_ref2.as_str()))
+                .copied();
+        }
+        chains.push(chain);
+    }
+
+    chains
+}
+
+/// Edits for repairing time subdivisions:
+/// `(tier_index, annotation_index, new_ts_ref1, new_ts_ref2)`.
+type TsRefEdit = (usize, usize, Option<String>, Option<String>);
+
+/// Time subdivision violations and the edits that repair these.
+/// Annotations in a `Time_Subdivision` tier must cover
+/// the parent annotation's time span without gaps.
+fn time_subdivision(eaf: &Eaf) -> (Vec<ValidationIssue>, Vec<TsRefEdit>) {
+    let ts_vals = ts_values(eaf);
+    let mut issues: Vec<ValidationIssue> = Vec::new();
+    let mut edits: Vec<TsRefEdit> = Vec::new();
+
+    for (t_idx, p_idx) in stereotype_tiers(eaf, StereoType::TimeSubdivision) {
+        let tier = &eaf.tiers[t_idx];
+        let parents = spans(&eaf.tiers[p_idx], &ts_vals);
+        let breaks: HashSet<&str> = parents.iter()
+            .map(|p| p.ts_ref1.as_str())
+            .collect();
+
+        // Parent span index -> chains within that span
+        let mut grouped: HashMap<usize, Vec<Chain>> = HashMap::new();
+        for chain in chains(tier, &ts_vals, &breaks) {
+            let (Some(start), Some(end)) = (chain.first().start, chain.last().end) else {
+                continue
+            };
+            match containing(&parents, start, end) {
+                Some(p) => grouped.entry(p).or_default().push(chain),
+                None => issues.push(ValidationIssue::error(
+                    IssueCode::ParentAnnotationMissing,
+                    Location::Annotation(tier.annotations[chain.first().idx].id().to_owned()),
+                    format!("No annotation in parent tier '{}' contains {start}-{end}ms", eaf.tiers[p_idx].tier_id)
+                )),
+            }
+        }
+
+        for (p, mut group) in grouped.into_iter() {
+            let parent = &parents[p];
+            let parent_id = eaf.tiers[p_idx].annotations[parent.idx].id();
+            group.sort_by_key(|c| c.first().start);
+            let gap = |a_idx: usize, message: String| ValidationIssue::error(
+                IssueCode::TimeSubdivisionGap,
+                Location::Annotation(tier.annotations[a_idx].id().to_owned()),
+                message
+            );
+
+            let first = group[0].first();
+            if first.ts_ref1 != parent.ts_ref1 && first.start != parent.start {
+                issues.push(gap(first.idx, format!("Gap between start of parent annotation '{parent_id}' and first subdivision")));
+                edits.push((t_idx, first.idx, Some(parent.ts_ref1.to_owned()), None));
+            }
+            for pair in group.windows(2) {
+                let (last, next) = (pair[0].last(), pair[1].first());
+                if last.end == next.start {
+                    continue
+                }
+                issues.push(gap(last.idx, format!("Gap between subdivisions within parent annotation '{parent_id}'")));
+                edits.push((t_idx, last.idx, None, Some(next.ts_ref1.to_owned())));
+            }
+            let last = group[group.len() - 1].last();
+            if last.ts_ref2 != parent.ts_ref2 && last.end != parent.end {
+                issues.push(gap(last.idx, format!("Gap between last subdivision and end of parent annotation '{parent_id}'")));
+                edits.push((t_idx, last.idx, None, Some(parent.ts_ref2.to_owned())));
+            }
+        }
+    }
+
+    (issues, edits)
+}
+
+/// Edits for repairing included in annotations:
+/// `(tier_index, annotation_index, new_start, new_end)`.
+type BoundsEdit = (usize, usize, i64, i64);
+
+/// Included in violations and the edits that repair these.
+/// Annotations in an `Included_In` tier must be
+/// within the parent annotation's time span.
+/// Annotations that partially overlap a parent annotation can be repaired
+/// by trimming these to the parent annotation with which the overlap is the largest.
+fn included_in(eaf: &Eaf) -> (Vec<ValidationIssue>, Vec<BoundsEdit>) {
+    let ts_vals = ts_values(eaf);
+    let mut issues: Vec<ValidationIssue> = Vec::new();
+    let mut edits: Vec<BoundsEdit> = Vec::new();
+
+    for (t_idx, p_idx) in stereotype_tiers(eaf, StereoType::IncludedIn) {
+        let tier = &eaf.tiers[t_idx];
+        let parents = spans(&eaf.tiers[p_idx], &ts_vals);
+
+        for span in spans(tier, &ts_vals) {
+            let (Some(start), Some(end)) = (span.start, span.end) else {
+                continue
+            };
+            if containing(&parents, start, end).is_some() {
+                continue
+            }
+
+            let id = tier.annotations[span.idx].id().to_owned();
+            let best = parents.iter()
+                .filter_map(|p| {
+                    let (p_start, p_end) = (p.start?, p.end?);
+                    let overlap = end.min(p_end) - start.max(p_start);
+                    (overlap > 0).then_some((overlap, p_start, p_end))
+                })
+                .max_by_key(|(overlap, ..)| *overlap);
+
+            match best {
+                Some((_, p_start, p_end)) => {
+                    issues.push(ValidationIssue::error(
+                        IssueCode::IncludedInBounds,
+                        Location::Annotation(id),
+                        format!("{start}-{end}ms extends beyond parent annotation {p_start}-{p_end}ms")
+                    ));
+                    edits.push((t_idx, span.idx, start.max(p_start), end.min(p_end)));
+                },
+                None => issues.push(ValidationIssue::error(
+                    IssueCode::ParentAnnotationMissing,
+                    Location::Annotation(id),
+                    format!("No annotation in parent tier '{}' contains {start}-{end}ms", eaf.tiers[p_idx].tier_id)
+                )),
+            }
+        }
+    }
+
+    (issues, edits)
+}
+
+/// Returns annotation indices grouped on referred annotation ID,
+/// in the order these appear in the tier.
+fn ref_groups(tier: &Tier) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_idx: HashMap<&str, usize> = HashMap::new();
+    for (i, annotation) in tier.iter().enumerate() {
+        let Some(ref_id) = annotation.ref_id() else {
+            continue
+        };
+        let g = *group_idx.entry(ref_id).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[g].push(i);
+    }
+    groups
+}
+
+/// Symbolic association violations, as `(issue, tier_index, annotation_indices)`
+/// where `annotation_indices` are those referring to the same parent annotation.
+/// A `Symbolic_Association` tier may only have a single annotation per parent annotation.
+fn symbolic_association(eaf: &Eaf) -> Vec<(ValidationIssue, usize, Vec<usize>)> {
+    let mut violations = Vec::new();
+
+    for (t_idx, _) in stereotype_tiers(eaf, StereoType::SymbolicAssociation) {
+        let tier = &eaf.tiers[t_idx];
+        for group in ref_groups(tier).into_iter().filter(|g| g.len() > 1) {
+            let first = &tier.annotations[group[0]];
+            let issue = ValidationIssue::error(
+                IssueCode::SymbolicAssociationMultiple,
+                Location::Annotation(first.id().to_owned()),
+                format!(
+                    "{} annotations refer to parent annotation '{}'",
+                    group.len(),
+                    first.ref_id().unwrap_or_default()
+                )
+            );
+            violations.push((issue, t_idx, group));
+        }
+    }
+
+    violations
+}
+
+/// Symbolic subdivision violations, as `(issue, tier_index, annotation_indices)`
+/// where `annotation_indices` are those referring to the same parent annotation.
+/// Annotations in a `Symbolic_Subdivision` tier referring to the same parent
+/// must form a single chain, where the first one has no previous annotation,
+/// and every other one refers to the one before it.
+fn symbolic_subdivision(eaf: &Eaf) -> Vec<(ValidationIssue, usize, Vec<usize>)> {
+    let mut violations = Vec::new();
+
+    for (t_idx, _) in stereotype_tiers(eaf, StereoType::SymbolicSubdivision) {
+        let tier = &eaf.tiers[t_idx];
+        for group in ref_groups(tier) {
+            let ids: HashSet<&str> = group.iter().map(|i| tier.annotations[*i].id()).collect();
+            // Previous annotation ID -> annotation ID
+            let mut next: HashMap<&str, &str> = HashMap::new();
+            let mut heads: Vec<&str> = Vec::new();
+            let mut valid = true;
+            for annotation in group.iter().map(|i| &tier.annotations[*i]) {
+                match annotation.previous() {
+                    None => heads.push(annotation.id()),
+                    Some(prev) if ids.contains(prev) && !next.contains_key(prev) => {
+                        next.insert(prev, annotation.id());
+                    },
+                    Some(_) => valid = false,
+                }
+            }
+            // Walk from the single head, which must reach every annotation.
+            if valid && heads.len() == 1 {
+                let mut len = 1;
+                let mut current = heads[0];
+                while let Some(n) = next.get(current) {
+                    len += 1;
+                    current = n;
+                    if len > group.len() { break }
+                }
+                valid = len == group.len();
+            } else {
+                valid = false;
+            }
+
+            if !valid {
+                let first = &tier.annotations[group[0]];
+                let issue = ValidationIssue::error(
+                    IssueCode::SymbolicSubdivisionChain,
+                    Location::Annotation(first.id().to_owned()),
+                    format!(
+                        "Annotations referring to parent annotation '{}' do not form a single chain",
+                        first.ref_id().unwrap_or_default()
+                    )
+                );
+                violations.push((issue, t_idx, group));
+            }
+        }
+    }
+
+    violations
+}
+
+/// `Time_Subdivision` annotations must cover the parent annotation
+/// without gaps.
+pub fn check_time_subdivision(eaf: &Eaf) -> Vec<ValidationIssue> {
+    time_subdivision(eaf).0
+}
+
+/// `Included_In` annotations must be within the time span of
+/// a parent annotation.
+pub fn check_included_in(eaf: &Eaf) -> Vec<ValidationIssue> {
+    included_in(eaf).0
+}
+
+/// `Symbolic_Association` annotations must be
+/// the only annotation referring to their parent annotation.
+pub fn check_symbolic_association(eaf: &Eaf) -> Vec<ValidationIssue> {
+    symbolic_association(eaf).into_iter().map(|(issue, ..)| issue).collect()
+}
+
+/// `Symbolic_Subdivision` annotations referring to the same parent annotation
+/// must form a single `PREVIOUS_ANNOTATION` chain.
+pub fn check_symbolic_subdivision(eaf: &Eaf) -> Vec<ValidationIssue> {
+    symbolic_subdivision(eaf).into_iter().map(|(issue, ..)| issue).collect()
+}
+
+/// Runs all stereotype checks.
+pub fn stereotype_violations(eaf: &Eaf) -> Vec<ValidationIssue> {
+    let mut issues = check_time_subdivision(eaf);
+    issues.extend(check_included_in(eaf));
+    issues.extend(check_symbolic_association(eaf));
+    issues.extend(check_symbolic_subdivision(eaf));
+    issues
+}
+
+/// Repairs stereotype violations where possible:
+/// - `Time_Subdivision`: Gaps are closed by moving the boundary
+/// of the annotation before the gap to the next annotation's (or the parent's)
+/// time slot. A gap at the start of the parent annotation is closed by
+/// moving the first annotation's start.
+/// - `Included_In`: Annotations partially outside the parent annotation
+/// are trimmed to the parent annotation's time span.
+/// - `Symbolic_Association`: Annotations referring to the same parent
+/// annotation are joined into the first one, values separated by a space.
+/// Annotations referring to the removed annotations are moved to the first one.
+/// - `Symbolic_Subdivision`: Previous annotation references are re-set
+/// according to the order annotations appear in the tier.
+///
+/// Annotations outside of any parent annotation can not be repaired.
+/// Requires re-indexing and deriving afterwards.
+pub(crate) fn repair_stereotypes(eaf: &mut Eaf) -> Result<(), EafError> {
+    // Time slots that are no longer referred to are removed afterwards.
+    let mut replaced: HashSet<String> = HashSet::new();
+
+    let (_, ts_edits) = time_subdivision(eaf);
+    for (t_idx, a_idx, ts_ref1, ts_ref2) in ts_edits {
+        let annotation = &mut eaf.tiers[t_idx].annotations[a_idx];
+        let Some((old_ref1, old_ref2)) = annotation.ts_ref() else {
+            continue
+        };
+        annotation.set_ts_ref(
+            ts_ref1.as_deref().unwrap_or(&old_ref1),
+            ts_ref2.as_deref().unwrap_or(&old_ref2)
+        );
+        replaced.extend([old_ref1, old_ref2]);
+    }
+
+    let (_, bounds_edits) = included_in(eaf);
+    for (t_idx, a_idx, start, end) in bounds_edits {
+        // New time slots, since existing ones may be shared with other annotations.
+        let ts_ref1 = eaf.time_order.add(None, Some(start))?;
+        let ts_ref2 = eaf.time_order.add(None, Some(end))?;
+        let annotation = &mut eaf.tiers[t_idx].annotations[a_idx];
+        replaced.extend(annotation.ts_ref().map(|(r1, r2)| [r1, r2]).into_iter().flatten());
+        annotation.set_ts_ref(&ts_ref1, &ts_ref2);
+    }
+
+    if !replaced.is_empty() {
+        let used: HashSet<String> = eaf.tiers.iter()
+            .flat_map(|t| t.iter())
+            .filter_map(|a| a.ts_ref())
+            .flat_map(|(r1, r2)| [r1, r2])
+            .collect();
+        eaf.time_order.time_slots
+            .retain(|ts| !replaced.contains(&ts.time_slot_id) || used.contains(&ts.time_slot_id));
+    }
+
+    // Joined annotation ID -> ID for annotation it was joined into
+    let mut joined: HashMap<String, String> = HashMap::new();
+    // Tier index -> indices for joined annotations. Removed once all groups
+    // are joined, since the indices refer to the tier before any edits.
+    let mut removed: HashMap<usize, HashSet<usize>> = HashMap::new();
+    for (_, t_idx, group) in symbolic_association(eaf) {
+        let tier = &mut eaf.tiers[t_idx];
+        let value = group.iter()
+            .map(|i| tier.annotations[*i].to_str())
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let kept_id = tier.annotations[group[0]].id().to_owned();
+        tier.annotations[group[0]].set_value(&value);
+        for i in group[1..].iter() {
+            joined.insert(tier.annotations[*i].id().to_owned(), kept_id.to_owned());
+        }
+        removed.entry(t_idx).or_default().extend(group[1..].iter().copied());
+    }
+    for (t_idx, indices) in removed.into_iter() {
+        let tier = &mut eaf.tiers[t_idx];
+        tier.annotations = tier.annotations.drain(..)
+            .enumerate()
+            .filter_map(|(i, a)| (!indices.contains(&i)).then_some(a))
+            .collect();
+    }
+    if !joined.is_empty() {
+        for annotation in eaf.tiers.iter_mut().flat_map(|t| t.annotations.iter_mut()) {
+            if let Some(new_ref) = annotation.ref_id().and_then(|r| joined.get(r)) {
+                annotation.set_ref_id(&new_ref.to_owned());
+            }
+        }
+    }
+
+    for (_, t_idx, group) in symbolic_subdivision(eaf) {
+        let tier = &mut eaf.tiers[t_idx];
+        let mut prev: Option<String> = None;
+        for i in group {
+            let annotation = &mut tier.annotations[i];
+            match &prev {
+                Some(p) => annotation.set_previous(p),
+                None => annotation.unset_previous(),
+            }
+            prev = Some(annotation.id().to_owned());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eaf::test_util::{TestEaf, aligned, referred, subdivision};
+
+    fn codes(issues: &[ValidationIssue]) -> Vec<IssueCode> {
+        let mut codes: Vec<IssueCode> = issues.iter().map(|i| i.code).collect();
+        codes.sort_by_key(|c| c.as_str());
+        codes
+    }
+
+    #[test]
+    fn valid() {
+        let eaf = Eaf::from_values(&[
+            ("a".to_owned(), 0, 100),
+            ("b".to_owned(), 100, 200),
+        ], None).unwrap();
+        let report = eaf.validate();
+        assert!(report.is_empty(), "{report}");
+        assert!(report.is_valid());
+    }
+
+    #[test]
+    fn collects_all_issues() {
+        let eaf = TestEaf::new()
+            .time_slots(&[500, 100, 900])
+            .tier("A", "default-lt", None, &[
+                aligned("a1", "ts1", "ts2", "x"),
+                aligned("a1", "ts2", "ts9", "y"),
+            ])
+            .tier("B", "missing-lt", Some("C"), &[referred("a3", "a99", "z")])
+            .tier("C", "assoc", Some("B"), &[])
+            // Symbolic_Association without the corresponding constraint
+            .xml(r#"<LINGUISTIC_TYPE CONSTRAINTS="Symbolic_Association" LINGUISTIC_TYPE_ID="assoc" TIME_ALIGNABLE="false"/>"#)
+            .eaf_underived();
+
+        let report = eaf.validate();
+        assert_eq!(codes(&report.issues), vec![
+            IssueCode::AnnotationRefMissing,
+            // Tier B has no (known) symbolic linguistic type
+            IssueCode::AnnotationTypeMismatch,
+            IssueCode::ConstraintMissing,
+            IssueCode::DuplicateAnnotationId,
+            IssueCode::LinguisticTypeMissing,
+            IssueCode::ParentCycle,
+            IssueCode::ParentCycle,
+            IssueCode::TimeslotOrder,
+            IssueCode::TimeslotRefMissing,
+            IssueCode::TimeslotUnused,
+        ]);
+        assert!(!report.is_valid());
+        assert_eq!(report.warnings().count(), 1);
+        assert_eq!(
+            report.filter(IssueCode::TimeslotRefMissing).next().unwrap().location,
+            Location::Annotation("a1".to_owned())
+        );
+    }
+
+    #[test]
+    fn overlaps() {
+        let eaf = TestEaf::new()
+            .time_slots(&[0, 1000, 200, 300])
+            .tier("A", "default-lt", None, &[
+                aligned("a1", "ts1", "ts2", "x"),
+                aligned("a2", "ts3", "ts4", "y"),
+            ])
+            .eaf_underived();
+        let report = eaf.validate();
+        assert_eq!(codes(&report.issues), vec![IssueCode::AnnotationOverlap]);
+        assert_eq!(report.issues[0].location, Location::Annotation("a2".to_owned()));
+    }
+
+    #[test]
+    fn overlap_unaligned() {
+        // a2 ends on an unaligned time slot
+        let eaf = TestEaf::new()
+            .time_slots(&[0, 1000])
+            .time_slot(None)
+            .time_slots(&[2000])
+            .tier("A", "default-lt", None, &[
+                aligned("a1", "ts1", "ts2", "x"),
+                aligned("a2", "ts2", "ts3", "y"),
+                aligned("a3", "ts3", "ts4", "z"),
+            ])
+            .eaf();
+        assert!(!overlap(&eaf.tiers[0].annotations));
+        assert!(eaf.validate().is_valid());
+
+        let mut tier = eaf.tiers[0].to_owned();
+        tier.annotations.push(eaf.tiers[0].annotations[0].to_owned());
+        assert!(overlap(&tier.annotations));
+    }
+
+    /// Main tier `A` with two annotations, and a `Symbolic_Association` tier `G`.
+    fn association(annotations: &[String]) -> Eaf {
+        TestEaf::new()
+            .time_slots(&[0, 1000, 2000])
+            .tier("A", "default-lt", None, &[
+                aligned("a1", "ts1", "ts2", "x"),
+                aligned("a2", "ts2", "ts3", "y"),
+            ])
+            .linguistic_type("assoc", Some("Symbolic_Association"))
+            .tier("G", "assoc", Some("A"), annotations)
+            .eaf_underived()
+    }
+
+    #[test]
+    fn repair_symbolic_association_groups() {
+        let mut eaf = association(&[
+            referred("g1", "a1", "p"),
+            referred("g2", "a1", "q"),
+            referred("g3", "a2", "r"),
+            referred("g4", "a2", "s"),
+            referred("g5", "a2", "t"),
+        ]);
+        assert_eq!(check_symbolic_association(&eaf).len(), 2);
+
+        assert!(eaf.check_stereotypes(true).unwrap().is_empty());
+        let tier = eaf.get_tier("G").unwrap();
+        let annotations: Vec<(&str, &str, &str)> = tier.iter()
+            .map(|a| (a.id(), a.ref_id().unwrap(), a.to_str()))
+            .collect();
+        assert_eq!(annotations, vec![("g1", "a1", "p q"), ("g3", "a2", "r s t")]);
+    }
+
+    /// Main tier `A` with a single annotation `a1` (0-1000ms),
+    /// and a referred tier `R` with the specified stereotype.
+    /// Additional time slots are numbered from `ts3`.
+    fn stereotyped(stereotype: &str, time_slots: &[i64], annotations: &[String]) -> Eaf {
+        TestEaf::new()
+            .time_slots(&[0, 1000])
+            .time_slots(time_slots)
+            .tier("A", "default-lt", None, &[aligned("a1", "ts1", "ts2", "x")])
+            .linguistic_type("st", Some(stereotype))
+            .tier("R", "st", Some("A"), annotations)
+            .eaf_underived()
+    }
+
+    fn spans(eaf: &Eaf, tier_id: &str) -> Vec<(i64, i64)> {
+        eaf.get_tier(tier_id).unwrap().iter()
+            .map(|a| match a.ts_val() {
+                (Some(t1), Some(t2)) => (t1, t2),
+                _ => panic!("Time values not set for {}", a.id())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn repair_time_subdivision() {
+        let mut eaf = stereotyped(
+            "Time_Subdivision",
+            &[400, 500, 900],
+            &[
+                aligned("s1", "ts1", "ts3", "s1"),
+                aligned("s2", "ts4", "ts5", "s2"),
+            ]
+        );
+        let issues = check_time_subdivision(&eaf);
+        assert_eq!(codes(&issues), vec![IssueCode::TimeSubdivisionGap; 2]);
+
+        assert!(eaf.check_stereotypes(true).unwrap().is_empty());
+        assert_eq!(spans(&eaf, "R"), vec![(0, 500), (500, 1000)]);
+        // Time slots no longer in use are removed.
+        assert!(eaf.ts_val("ts3").is_none());
+        assert!(eaf.ts_val("ts5").is_none());
+    }
+
+    #[test]
+    fn repair_included_in() {
+        let mut eaf = stereotyped(
+            "Included_In",
+            &[200, 800, 1200, 1500, 2000],
+            &[
+                aligned("i1", "ts3", "ts4", "i1"),
+                aligned("i2", "ts4", "ts5", "i2"),
+                aligned("i3", "ts6", "ts7", "i3"),
+            ]
+        );
+        let issues = check_included_in(&eaf);
+        assert_eq!(codes(&issues), vec![IssueCode::IncludedInBounds, IssueCode::ParentAnnotationMissing]);
+
+        // Annotations outside of any parent annotation can not be repaired.
+        let issues = eaf.check_stereotypes(true).unwrap();
+        assert_eq!(codes(&issues), vec![IssueCode::ParentAnnotationMissing]);
+        assert_eq!(issues[0].location, Location::Annotation("i3".to_owned()));
+        assert_eq!(spans(&eaf, "R"), vec![(200, 800), (800, 1000), (1500, 2000)]);
+    }
+
+    #[test]
+    fn repair_symbolic_subdivision() {
+        let mut eaf = stereotyped(
+            "Symbolic_Subdivision",
+            &[],
+            &[
+                referred("w1", "a1", "w1"),
+                referred("w2", "a1", "w2"),
+                subdivision("w3", "a1", "w1", "w3"),
+            ]
+        );
+        assert_eq!(codes(&check_symbolic_subdivision(&eaf)), vec![IssueCode::SymbolicSubdivisionChain]);
+
+        assert!(eaf.check_stereotypes(true).unwrap().is_empty());
+        let previous: Vec<Option<&str>> = eaf.get_tier("R").unwrap().iter()
+            .map(|a| a.previous())
+            .collect();
+        assert_eq!(previous, vec![None, Some("w1"), Some("w2")]);
+    }
 }
@@ -0,0 +1,374 @@
+//! Conversion between EAF versions 2.7, 2.8, and 3.0.
+//!
+//! Breaking changes were introduced in EAF v2.8:
+//! - Controlled vocabularies are multilingual. `CV_ENTRY` (v2.7) was replaced
+//!   by `CV_ENTRY_ML` with one `CVE_VALUE` per language, and the CV `DESCRIPTION`
+//!   attribute was replaced by `DESCRIPTION` elements, one per language.
+//! - Languages are specified via top-level `LANGUAGE` elements,
+//!   referred to by CV values, tiers (`LANG_REF`), and annotations (`LANG_REF`).
+//! - Annotations may refer to a CV entry (`CVE_REF`).
+//! - `LICENSE` was added.
+//!
+//! EAF v3.0 adds reference links (`REF_LINK_SET`), which are not part of `Eaf`.
+//! For an `Eaf` read via `Eaf::read_lossless()` these are kept as is,
+//! and must be removed manually before downgrading to v2.8.
+
+use std::{collections::HashMap, fmt::Display};
+
+use super::{CvEntry, CvEntryMl, CvType, CveValue, Description, Eaf, Language, Location};
+
+/// EAF version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum EafVersion {
+    V2_7,
+    V2_8,
+    V3_0,
+}
+
+impl Display for EafVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl EafVersion {
+    /// Version as used in the `VERSION` and `FORMAT` attributes, e.g. "3.0".
+    pub fn as_str(&self) -> &str {
+        match self {
+            EafVersion::V2_7 => "2.7",
+            EafVersion::V2_8 => "2.8",
+            EafVersion::V3_0 => "3.0",
+        }
+    }
+
+    /// Parses version string, e.g. "2.8". Returns `None`
+    /// for unsupported versions.
+    pub fn parse(version: &str) -> Option<Self> {
+        match version.trim() {
+            "2.7" => Some(EafVersion::V2_7),
+            "2.8" => Some(EafVersion::V2_8),
+            "3.0" => Some(EafVersion::V3_0),
+            _ => None
+        }
+    }
+
+    /// Multilingual controlled vocabularies and languages (EAF v2.8+).
+    pub fn is_multilingual(&self) -> bool {
+        *self >= EafVersion::V2_8
+    }
+}
+
+/// Information lost when converting to an earlier EAF version,
+/// or to another format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionLoss {
+    pub location: Location,
+    pub message: String,
+}
+
+impl Display for ConversionLoss {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+impl ConversionLoss {
+    pub(crate) fn new(location: Location, message: &str) -> Self {
+        Self {
+            location,
+            message: message.to_owned(),
+        }
+    }
+}
+
+/// Language used for v2.7 controlled vocabularies on upgrade,
+/// if the EAF has no languages: "und" (undetermined, ISO 639-3).
+fn undetermined() -> Language {
+    Language {
+        lang_id: "und".to_owned(),
+        lang_def: None,
+        lang_label: Some("undetermined (und)".to_owned()),
+    }
+}
+
+/// Converts `eaf` to `target` version. Returns information lost
+/// when downgrading, which is empty when upgrading.
+pub(crate) fn convert(eaf: &mut Eaf, target: EafVersion) -> Vec<ConversionLoss> {
+    let losses = match target.is_multilingual() {
+        true => {
+            to_multilingual(eaf);
+            Vec::new()
+        },
+        false => to_monolingual(eaf),
+    };
+
+    eaf.version = target.as_str().to_owned();
+    eaf.format = target.as_str().to_owned();
+    eaf.set_ns();
+
+    losses
+}
+
+/// Upgrades v2.7 controlled vocabularies to the multilingual (v2.8+) form.
+/// Annotations with a value that matches an entry in the controlled vocabulary
+/// for the tier's linguistic type are set to refer to that entry.
+fn to_multilingual(eaf: &mut Eaf) {
+    let has_monolingual = eaf.controlled_vocabularies.iter()
+        .any(|cv| cv.description_attr.is_some() || cv.iter().any(|e| matches!(e, CvType::CvEntry(_))));
+    if !has_monolingual {
+        return
+    }
+
+    let lang_ref = match eaf.languages.first() {
+        Some(lang) => lang.lang_id.to_owned(),
+        None => {
+            let lang = undetermined();
+            let lang_id = lang.lang_id.to_owned();
+            eaf.languages.push(lang);
+            lang_id
+        }
+    };
+
+    // (CV ID, value) -> CVE ID for converted entries
+    let mut cve_ids: HashMap<(String, String), String> = HashMap::new();
+
+    for cv in eaf.controlled_vocabularies.iter_mut() {
+        if let Some(value) = cv.description_attr.take() && cv.descriptions.is_empty() {
+            cv.descriptions.push(Description {
+                lang_ref: Some(lang_ref.to_owned()),
+                value: Some(value),
+            });
+        }
+        let cv_id = cv.cv_id.to_owned();
+        if let Some(entries) = cv.entries.as_mut() {
+            for entry in entries.iter_mut() {
+                if let CvType::CvEntry(e) = entry {
+                    let ml = CvEntryMl {
+                        ext_ref: e.ext_ref.to_owned(),
+                        cve_values: vec![CveValue {
+                            description: e.description.to_owned(),
+                            lang_ref: lang_ref.to_owned(),
+                            value: e.value.to_owned(),
+                        }],
+                        ..CvEntryMl::default()
+                    };
+                    cve_ids.entry((cv_id.to_owned(), e.value.to_owned()))
+                        .or_insert(ml.cve_id.to_owned());
+                    *entry = CvType::CvEntryMl(ml);
+                }
+            }
+        }
+    }
+
+    let lt2cv: HashMap<String, String> = eaf.linguistic_types.iter()
+        .filter_map(|lt| lt.controlled_vocabulary.as_ref()
+            .map(|cv| (lt.linguistic_type_id.to_owned(), cv.to_owned())))
+        .collect();
+    for tier in eaf.tiers.iter_mut() {
+        let Some(cv_id) = lt2cv.get(&tier.linguistic_type_ref) else {
+            continue;
+        };
+        for annotation in tier.annotations.iter_mut() {
+            if annotation.cve_ref().is_none()
+                && let Some(cve_id) = cve_ids.get(&(cv_id.to_owned(), annotation.to_str().to_owned()))
+            {
+                annotation.set_cve_ref(Some(cve_id));
+            }
+        }
+    }
+}
+
+/// Downgrades controlled vocabularies to the v2.7 form,
+/// using the first value for each entry, and removes
+/// languages, language and CV entry references, and license.
+fn to_monolingual(eaf: &mut Eaf) -> Vec<ConversionLoss> {
+    let mut losses: Vec<ConversionLoss> = Vec::new();
+
+    for cv in eaf.controlled_vocabularies.iter_mut() {
+        let location = Location::ControlledVocabulary(cv.cv_id.to_owned());
+
+        // Descriptions, either as element or in entries
+        let mut descriptions = std::mem::take(&mut cv.descriptions);
+        if let Some(entries) = cv.entries.as_mut() {
+            entries.retain(|e| match e {
+                CvType::Description(d) => {
+                    descriptions.push(d.to_owned());
+                    false
+                },
+                _ => true
+            });
+        }
+        let mut values = descriptions.into_iter().filter_map(|d| d.value);
+        if cv.description_attr.is_none() {
+            cv.description_attr = values.next();
+        }
+        let dropped = values.count();
+        if dropped > 0 {
+            losses.push(ConversionLoss::new(location.to_owned(),
+                &format!("{dropped} description/s in other languages removed")));
+        }
+
+        let Some(entries) = cv.entries.as_mut() else {
+            continue;
+        };
+        for entry in entries.iter_mut() {
+            let CvType::CvEntryMl(ml) = entry else {
+                continue;
+            };
+            let Some(value) = ml.cve_values.first() else {
+                losses.push(ConversionLoss::new(location.to_owned(),
+                    &format!("Entry '{}' has no values and was removed", ml.cve_id)));
+                continue;
+            };
+            if ml.cve_values.len() > 1 {
+                losses.push(ConversionLoss::new(location.to_owned(), &format!(
+                    "Entry '{}': {} value/s in other languages than '{}' removed",
+                    ml.cve_id,
+                    ml.cve_values.len() - 1,
+                    value.lang_ref
+                )));
+            }
+            *entry = CvType::CvEntry(CvEntry {
+                description: value.description.to_owned(),
+                ext_ref: ml.ext_ref.to_owned(),
+                value: value.value.to_owned(),
+            });
+        }
+        entries.retain(|e| !matches!(e, CvType::CvEntryMl(_)));
+    }
+
+    for tier in eaf.tiers.iter_mut() {
+        let location = Location::Tier(tier.tier_id.to_owned());
+        if let Some(lang_ref) = tier.lang_ref.take() {
+            losses.push(ConversionLoss::new(location.to_owned(),
+                &format!("Language reference '{lang_ref}' removed")));
+        }
+        let (mut cve_refs, mut lang_refs) = (0, 0);
+        for annotation in tier.annotations.iter_mut() {
+            if annotation.cve_ref().is_some() {
+                annotation.set_cve_ref(None);
+                cve_refs += 1;
+            }
+            if annotation.lang_ref().is_some() {
+                annotation.set_lang_ref(None);
+                lang_refs += 1;
+            }
+        }
+        if cve_refs > 0 {
+            losses.push(ConversionLoss::new(location.to_owned(),
+                &format!("CV entry references removed for {cve_refs} annotation/s")));
+        }
+        if lang_refs > 0 {
+            losses.push(ConversionLoss::new(location,
+                &format!("Language references removed for {lang_refs} annotation/s")));
+        }
+    }
+
+    for lang in eaf.languages.drain(..) {
+        losses.push(ConversionLoss::new(Location::Document,
+            &format!("Language '{}' removed", lang.lang_id)));
+    }
+
+    if eaf.license.take().is_some() {
+        losses.push(ConversionLoss::new(Location::Document, "License removed"));
+    }
+
+    losses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eaf::test_util::{TestEaf, aligned};
+
+    /// EAF v2.7 with a monolingual controlled vocabulary
+    /// used by the linguistic type for tier `A`.
+    fn v2_7() -> Eaf {
+        TestEaf::new()
+            .version("2.7")
+            .time_slots(&[0, 1000])
+            .tier("A", "colour-lt", None, &[aligned("a1", "ts1", "ts2", "red")])
+            .xml(r#"<LINGUISTIC_TYPE CONTROLLED_VOCABULARY_REF="colours" GRAPHIC_REFERENCES="false" LINGUISTIC_TYPE_ID="colour-lt" TIME_ALIGNABLE="true"/>
+    <CONTROLLED_VOCABULARY CV_ID="colours" DESCRIPTION="Basic colours">
+        <CV_ENTRY DESCRIPTION="warm">red</CV_ENTRY>
+        <CV_ENTRY>blue</CV_ENTRY>
+    </CONTROLLED_VOCABULARY>"#)
+            .eaf()
+    }
+
+    fn entries(eaf: &Eaf) -> Vec<&CvType> {
+        eaf.controlled_vocabularies[0].iter().collect()
+    }
+
+    #[test]
+    fn upgrade() {
+        let mut eaf = v2_7();
+        assert_eq!(eaf.eaf_version(), Some(EafVersion::V2_7));
+
+        assert!(eaf.convert_version(EafVersion::V3_0).is_empty());
+        assert_eq!((eaf.version.as_str(), eaf.format.as_str()), ("3.0", "3.0"));
+        assert_eq!(eaf.xsi_nonamespaceschemalocation, "http://www.mpi.nl/tools/elan/EAFv3.0.xsd");
+        assert_eq!(eaf.languages.len(), 1);
+        assert_eq!(eaf.languages[0].lang_id, "und");
+
+        let cv = &eaf.controlled_vocabularies[0];
+        assert_eq!(cv.description_attr, None);
+        assert_eq!(cv.descriptions[0].value(), Some("Basic colours"));
+        let values: Vec<(&str, Option<&str>, &str)> = entries(&eaf).into_iter()
+            .map(|e| match e {
+                CvType::CvEntryMl(ml) => (
+                    ml.cve_values[0].value.as_str(),
+                    ml.cve_values[0].description.as_deref(),
+                    ml.cve_values[0].lang_ref.as_str()
+                ),
+                e => panic!("Expected CV_ENTRY_ML, found {e:?}")
+            })
+            .collect();
+        assert_eq!(values, vec![("red", Some("warm"), "und"), ("blue", None, "und")]);
+
+        // Annotation values matching an entry refer to it.
+        let CvType::CvEntryMl(red) = entries(&eaf)[0] else { unreachable!() };
+        assert_eq!(eaf.get_annotation("a1").unwrap().cve_ref(), Some(red.cve_id.to_owned()));
+
+        // Serializes and deserializes as v3.0.
+        let xml = eaf.to_string(None).unwrap();
+        assert!(xml.contains("<CV_ENTRY_ML"));
+        assert!(!xml.contains("<CV_ENTRY "));
+        assert_eq!(Eaf::de_str(&xml, true).unwrap().controlled_vocabularies, eaf.controlled_vocabularies);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut eaf = v2_7();
+        let original = eaf.controlled_vocabularies.to_owned();
+        eaf.convert_version(EafVersion::V2_8);
+        let losses = eaf.convert_version(EafVersion::V2_7);
+
+        assert_eq!(eaf.controlled_vocabularies, original);
+        assert_eq!(eaf.eaf_version(), Some(EafVersion::V2_7));
+        assert!(eaf.languages.is_empty());
+        assert!(eaf.get_annotation("a1").unwrap().cve_ref().is_none());
+        let mut locations: Vec<String> = losses.iter().map(|l| l.location.to_string()).collect();
+        locations.sort();
+        assert_eq!(locations, vec!["document", "tier 'A'"]);
+    }
+
+    #[test]
+    fn downgrade_losses() {
+        let mut eaf = v2_7();
+        eaf.convert_version(EafVersion::V3_0);
+        let cv = &mut eaf.controlled_vocabularies[0];
+        cv.descriptions.push(Description { lang_ref: Some("swe".to_owned()), value: Some("Färger".to_owned()) });
+        if let Some(CvType::CvEntryMl(ml)) = cv.entries.as_mut().and_then(|e| e.first_mut()) {
+            ml.cve_values.push(CveValue { description: None, lang_ref: "swe".to_owned(), value: "röd".to_owned() });
+        }
+
+        let losses = eaf.convert_version(EafVersion::V2_7);
+        let cv_losses: Vec<&ConversionLoss> = losses.iter()
+            .filter(|l| l.location == Location::ControlledVocabulary("colours".to_owned()))
+            .collect();
+        assert_eq!(cv_losses.len(), 2);
+        assert_eq!(eaf.controlled_vocabularies[0].description_attr.as_deref(), Some("Basic colours"));
+        assert!(matches!(entries(&eaf)[0], CvType::CvEntry(e) if e.value == "red"));
+    }
+}
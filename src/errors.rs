@@ -36,6 +36,13 @@ pub enum EafError {
     AnnotationTypeMismatch,
     /// Annotation timespan overlaps with another annotation in the same tier
     AnnotationOverlap,
+    /// No adjacent annotation in the same tier, e.g. when merging annotations.
+    /// Value: Annotation ID.
+    AnnotationAdjacentMissing(String),
+    /// No annotation in the parent tier contains the annotation's time span,
+    /// e.g. after merging or moving annotations in a time-aligned referred tier.
+    /// Value: Annotation ID.
+    AnnotationParentMissing(String),
 
     /// Missing file name (when e.g. trying to extract section from media file path)
     FileNameMissing(String),
@@ -92,6 +99,21 @@ pub enum EafError {
     TierRefMissingParent(String),
     /// Encounterd referred tier, expected main tier.
     TierMainExpected(String),
+    /// Tier ID already exists (e.g. when copying tiers).
+    TierIdExists(String),
+    /// Tier has referred tiers, e.g. when removing
+    /// a tier non-recursively.
+    /// Value: Tier ID.
+    TierDependentsExist(String),
+    /// Tier can not have the specified parent tier, e.g. if the parent
+    /// is one of its dependents, or if the parent does not fit
+    /// the tier's stereotype.
+    /// Value: `(tier_id, parent_tier_id)`
+    TierParentInvalid((String, String)),
+    /// Tier stereotype does not support the operation, e.g. when
+    /// converting a tier that is already time-aligned to a time-aligned stereotype.
+    /// Value: Tier ID.
+    TierStereotypeInvalid(String),
 
     // Time slot errors
 
@@ -131,6 +153,41 @@ pub enum EafError {
     PathInvalid(String),
     /// Error decoding string as UTF-8.
     Utf8Error(std::str::Utf8Error),
+    /// Error decoding string as UTF-16.
+    Utf16Error(std::string::FromUtf16Error),
+    /// Invalid or unsupported Praat TextGrid.
+    /// Value: Description of the problem.
+    TextGridInvalid(String),
+    /// Invalid or unsupported SRT/WebVTT subtitles.
+    /// Value: Description of the problem.
+    SubtitleInvalid(String),
+    /// Invalid or unsupported CHAT transcription.
+    /// Value: Description of the problem.
+    ChatInvalid(String),
+    /// Invalid or unsupported FLEx interlinear text.
+    /// Value: Description of the problem.
+    FlexTextInvalid(String),
+    /// Invalid or unsupported EXMARaLDA basic transcription.
+    /// Value: Description of the problem.
+    ExmaraldaInvalid(String),
+    /// Invalid structured search, e.g. a relation
+    /// refers to a layer that does not exist.
+    /// Value: Description of the problem.
+    SearchInvalid(String),
+    /// Invalid regular expression.
+    RegexError(regex::Error),
+    /// CSV error.
+    CsvError(csv::Error),
+    /// CSV content does not match the column mapping,
+    /// e.g. a mapped column is missing.
+    /// Value: Description of the problem.
+    CsvInvalid(String),
+    /// Invalid or unsupported XML.
+    /// Value: Description of the problem.
+    XmlInvalid(String),
+    /// Lossless serialization for an `Eaf`
+    /// that was not read in lossless mode.
+    LosslessSourceMissing,
     /// Value is too small to be used in this context.
     /// E.g. negative time slot values.
     ValueTooSmall(i64),
@@ -161,6 +218,18 @@ impl fmt::Display for EafError {
             EafError::XmlNoNameSpaceMissing => write!(f, "Missing XML no namespace locatation"),
             EafError::TierTypeMismatch((id1, id2)) => write!(f, "The tiers '{id1}' and '{id2}' do not have compatible type."),
             EafError::Utf8Error(err) => write!(f, "Error parsing bytes to string: {}", err),
+            EafError::Utf16Error(err) => write!(f, "Error parsing UTF-16 bytes to string: {}", err),
+            EafError::TextGridInvalid(msg) => write!(f, "Invalid TextGrid: {}", msg),
+            EafError::SubtitleInvalid(msg) => write!(f, "Invalid subtitles: {}", msg),
+            EafError::ChatInvalid(msg) => write!(f, "Invalid CHAT: {}", msg),
+            EafError::FlexTextInvalid(msg) => write!(f, "Invalid FLEx interlinear text: {}", msg),
+            EafError::ExmaraldaInvalid(msg) => write!(f, "Invalid EXMARaLDA transcription: {}", msg),
+            EafError::SearchInvalid(msg) => write!(f, "Invalid search: {}", msg),
+            EafError::RegexError(err) => write!(f, "Invalid regular expression: {}", err),
+            EafError::CsvError(err) => write!(f, "CSV error: {}", err),
+            EafError::CsvInvalid(msg) => write!(f, "Invalid CSV: {}", msg),
+            EafError::XmlInvalid(msg) => write!(f, "Invalid XML: {}", msg),
+            EafError::LosslessSourceMissing => write!(f, "No original XML. EAF must be read in lossless mode"),
             EafError::QuickXMLError(err) => write!(f, "QuickXML error parsing EAF: {}", err),
             EafError::QuickXMLDeError(err) => write!(f, "QuickXML error deserialising EAF: {}", err),
             EafError::QuickXMLSeError(err) => write!(f, "QuickXML error serialising EAF: {}", err),
@@ -195,10 +264,16 @@ impl fmt::Display for EafError {
             EafError::TimeSlotIdExists(id) => write!(f, "Timeslot with ID '{}' already exists", id),
             EafError::AnnotationIdExists(id) => write!(f, "Annotation with ID '{}' already exists", id),
             EafError::TierMainExpected(tier_id) => write!(f, "Expected main tier. '{}' is referred.", tier_id),
+            EafError::TierIdExists(tier_id) => write!(f, "Tier with ID '{}' already exists", tier_id),
+            EafError::TierDependentsExist(tier_id) => write!(f, "Tier '{}' has referred tiers", tier_id),
+            EafError::TierParentInvalid((tier_id, parent_id)) => write!(f, "Tier '{tier_id}' can not have '{parent_id}' as parent tier"),
+            EafError::TierStereotypeInvalid(tier_id) => write!(f, "Stereotype for tier '{tier_id}' is not supported for this operation"),
             EafError::AnnotationMainExpected(annotation_id) => write!(f,
                 "Expected annotation on main tier. '{}' is referred", annotation_id),
             EafError::AnnotationTypeMismatch => write!(f, "Annotation types do not match or annotation type is incompatible with tier type"),
             EafError::AnnotationOverlap => write!(f, "Annotation timespans overlap in the same tier"),
+            EafError::AnnotationAdjacentMissing(id) => write!(f, "No adjacent annotation for annotation with ID '{}'", id),
+            EafError::AnnotationParentMissing(id) => write!(f, "No parent annotation contains annotation with ID '{}'", id),
             EafError::FileNameMissing(path) => write!(f, "No file name in path '{}'", path),
             EafError::FileExtensionMissing(path) => write!(f, "No file extion in path '{}'", path),
             EafError::UrlError(path) => write!(f, "Failed to convert path to UNC for {}", path),
@@ -216,6 +291,27 @@ impl From<std::str::Utf8Error> for EafError {
     }
 }
 
+/// Converts std::string::FromUtf16Error to EafError
+impl From<std::string::FromUtf16Error> for EafError {
+    fn from(err: std::string::FromUtf16Error) -> EafError {
+        EafError::Utf16Error(err)
+    }
+}
+
+/// Converts regex::Error to EafError
+impl From<regex::Error> for EafError {
+    fn from(err: regex::Error) -> EafError {
+        EafError::RegexError(err)
+    }
+}
+
+/// Converts csv::Error to EafError
+impl From<csv::Error> for EafError {
+    fn from(err: csv::Error) -> EafError {
+        EafError::CsvError(err)
+    }
+}
+
 /// Converts EafError to std::io::Error
 impl From<EafError> for std::io::Error {
     fn from(err: EafError) -> std::io::Error {
@@ -65,7 +65,46 @@ pub use eaf::{
     JsonAnnotation,
     JsonEaf,
     JsonTier,
-    OverlapStrategy
+    OverlapStrategy,
+    TextGrid,
+    Subtitles,
+    SubtitleFormat,
+    SubtitleOptions,
+    ValidationReport,
+    ValidationIssue,
+    IssueCode,
+    Severity,
+    Search,
+    SearchHit,
+    TierSelector,
+    ValueMatch,
+    Relation,
+    AllenRelation,
+    QueryResult,
+    Kwic,
+    Corpus,
+    CorpusHit,
+    EafVersion,
+    ConversionLoss,
+    Chat,
+    FlexText,
+    Exmaralda,
+    Table,
+    TabColumn,
+    TabOptions,
+    TimeFormat,
+    CsvMapping,
+    RowError,
+    RangeMode,
+    Overlap,
+    ValueRule,
+    Tokenizer,
+    AlignOptions,
+    Agreement,
+    AgreementOptions,
+    ConversationStats,
+    ConversationOptions,
+    EafDiff,
 };
 pub use pfsx::Pfsx;
 pub use timeseries::TimeSeries;